@@ -13,6 +13,7 @@ import Cookies from 'js-cookie';
 import { Icon } from '@iconify/react';
 
 import { useRouter } from 'src/routes/hooks';
+import { api } from 'src/services/axios';
 
 export type AccountPopoverProps = IconButtonProps & {
     data?: {
@@ -37,8 +38,11 @@ export function AccountPopover({ data = [], sx, ...other }: AccountPopoverProps)
         setOpenPopover(null);
     }, []);
 
-    const handleLogout = () => {
-        Cookies.remove('access_token');
+    const handleLogout = async () => {
+        /** Revoke the token pair server-side; clear cookies even if that fails */
+        await api.post('/api/v1/logout').catch(() => undefined);
+        Cookies.remove('access_token', { path: '/' });
+        Cookies.remove('refresh_token', { path: '/' });
         handleClosePopover();
         router.push('/sign-in');
     };
@@ -54,7 +54,7 @@ export function AdminView() {
         setFormError(null);
         const success = await create({
             username: newUsername,
-            password_hash: newPassword,
+            password: newPassword,
             role: newRole,
         });
         setCreating(false);
@@ -14,6 +14,7 @@ import { useRouter } from 'src/routes/hooks';
 import { Iconify } from 'src/components/iconify';
 import { SignInRequest, Token } from 'src/types/login';
 import { signIn } from 'src/services/sigin_in';
+import { changePassword } from 'src/services/users';
 import { ApiResponse } from 'src/types/api_response';
 
 const inputSx = {
@@ -39,6 +40,9 @@ export function SignInView() {
     const [form, setForm] = useState<SignInRequest>({ username: '', password: '' });
     const [loading, setLoading] = useState(false);
     const [error, setError] = useState<string | null>(null);
+    /** Set when the account still uses a default password and must change it */
+    const [mustChange, setMustChange] = useState(false);
+    const [newPassword, setNewPassword] = useState('');
 
     const handleChange = (e: React.ChangeEvent<HTMLInputElement>) => {
         setForm((prev) => ({ ...prev, [e.target.name]: e.target.value }));
@@ -58,15 +62,38 @@ export function SignInView() {
 
             Cookies.set('access_token', response.data!.access_token, { expires: 1, path: '/' });
             Cookies.set('refresh_token', response.data!.refresh_token, { expires: 7, path: '/' });
+
+            if (response.data!.password_change_required) {
+                setMustChange(true);
+                return;
+            }
             router.push('/');
-        } catch (err) {
-            setError('Unexpected error occurred.');
+        } catch (err: any) {
+            setError(err?.response?.data?.message ?? 'Unexpected error occurred.');
             console.error(err);
         } finally {
             setLoading(false);
         }
     }, [form, router]);
 
+    const handleChangePassword = useCallback(async () => {
+        setLoading(true);
+        setError(null);
+        try {
+            await changePassword({ current_password: form.password, new_password: newPassword });
+
+            /* The change revokes the current tokens, so sign in again with the new password. */
+            const raw = await signIn({ username: form.username, password: newPassword });
+            Cookies.set('access_token', raw.data!.access_token, { expires: 1, path: '/' });
+            Cookies.set('refresh_token', raw.data!.refresh_token, { expires: 7, path: '/' });
+            router.push('/');
+        } catch (err: any) {
+            setError(err?.response?.data?.message ?? 'Could not change the password.');
+        } finally {
+            setLoading(false);
+        }
+    }, [form.username, form.password, newPassword, router]);
+
     return (
         <>
             <Box
@@ -143,6 +170,27 @@ export function SignInView() {
                     sx={{ ...inputSx, mb: 3 }}
                 />
 
+                {mustChange && (
+                    <>
+                        <Alert severity="warning" sx={{ mb: 2 }}>
+                            This account uses the default password. Choose a new one to continue.
+                        </Alert>
+                        <TextField
+                            fullWidth
+                            name="new_password"
+                            label="New password"
+                            type="password"
+                            value={newPassword}
+                            onChange={(e) => setNewPassword(e.target.value)}
+                            onKeyDown={(e) => {
+                                if (e.key === 'Enter') handleChangePassword();
+                            }}
+                            slotProps={{ inputLabel: { shrink: true } }}
+                            sx={{ ...inputSx, mb: 3 }}
+                        />
+                    </>
+                )}
+
                 {error && (
                     <Alert severity="error" sx={{ mb: 2 }}>
                         {error}
@@ -155,11 +203,13 @@ export function SignInView() {
                     type="button"
                     color="primary"
                     variant="contained"
-                    onClick={handleSignIn}
-                    disabled={loading}
+                    onClick={mustChange ? handleChangePassword : handleSignIn}
+                    disabled={loading || (mustChange && !newPassword)}
                     sx={{ py: 1.4 }}
                 >
-                    {loading ? 'Signing in...' : 'Sign in'}
+                    {mustChange
+                        ? loading ? 'Saving...' : 'Change password'
+                        : loading ? 'Signing in...' : 'Sign in'}
                 </Button>
             </Box>
         </>
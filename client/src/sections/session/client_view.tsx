@@ -1,4 +1,4 @@
-import { useEffect, useState } from 'react';
+import { useEffect, useRef, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 
 import Box from '@mui/material/Box';
@@ -30,6 +30,7 @@ import Snackbar from '@mui/material/Snackbar';
 import { Iconify } from 'src/components/iconify';
 import type { Session } from 'src/types/sessions';
 import { useSessionStore } from 'src/stores/session-store';
+import { subscribeEvents } from 'src/services/events';
 
 export function SessionView() {
     const {
@@ -56,6 +57,25 @@ export function SessionView() {
         fetchSessions(0, size, '');
     }, []);
 
+    /** Reload the current page when clients connect or disconnect, at most twice a second */
+    const searchRef = useRef(search);
+    searchRef.current = search;
+    useEffect(() => {
+        let timer: ReturnType<typeof setTimeout> | undefined;
+        const close = subscribeEvents(['client_connected', 'client_disconnected'], () => {
+            if (timer) return;
+            timer = setTimeout(() => {
+                timer = undefined;
+                const state = useSessionStore.getState();
+                state.fetch(state.page, state.size, searchRef.current);
+            }, 500);
+        });
+        return () => {
+            clearTimeout(timer);
+            close();
+        };
+    }, []);
+
     const handleDisconnect = async (clientId: string) => {
         if (!window.confirm(`Disconnect client "${clientId}"?`)) return;
         setDisconnecting(clientId);
@@ -54,13 +54,13 @@ export default function PublishDrawer({ open, topic, onClose, t }: Props) {
         setSuccess(null);
         setError(null);
         try {
-            await publishMessage({
+            const res = await publishMessage({
                 topic: topicValue.trim(),
                 payload,
                 qos,
                 retain,
             });
-            setSuccess(`Message published to "${topicValue.trim()}"`);
+            setSuccess(`Message published to "${topicValue.trim()}" (${res.data.matched} matched)`);
             setPayload('');
         } catch (err: any) {
             setError(err?.message || 'Failed to publish message');
@@ -11,7 +11,7 @@ const TOKEN_KEYS = {
 const COOKIE_OPTIONS = { path: '/' } as const;
 
 /** API base URL derived from current hostname */
-const API_BASE = `http://${window.location.hostname}:18083`;
+export const API_BASE = `http://${window.location.hostname}:18083`;
 
 /** Axios instance for all API calls */
 export const api = axios.create({ baseURL: API_BASE });
@@ -42,6 +42,11 @@ function getToken(key: string): string | undefined {
     return Cookies.get(key);
 }
 
+/** Current access token, for requests that can't go through axios */
+export function getAccessToken(): string | undefined {
+    return getToken(TOKEN_KEYS.ACCESS);
+}
+
 /** Persist a token to cookies with expiry */
 function setToken(key: string, value: string, expiresDays: number) {
     Cookies.set(key, value, { ...COOKIE_OPTIONS, expires: expiresDays });
@@ -63,9 +68,8 @@ async function refreshAccessToken(): Promise<string> {
     }
 
     const { data } = await axios.post(
-        `${API_BASE}/api/v1/auth/refresh-token`,
+        `${API_BASE}/api/v1/public/refresh`,
         { refresh_token: refreshToken },
-        { headers: { Authorization: `Bearer ${refreshToken}` } },
     );
 
     const newAccessToken: string = data?.data?.access_token;
@@ -94,7 +98,9 @@ api.interceptors.response.use(
     async (error: AxiosError) => {
         const originalRequest = error.config as InternalAxiosRequestConfig & { _retry?: boolean };
 
-        if (!error.response || error.response.status !== 401 || originalRequest._retry) {
+        /** Login and refresh failures are final; there is nothing to refresh */
+        const isPublic = originalRequest.url?.startsWith('/api/v1/public/');
+        if (!error.response || error.response.status !== 401 || originalRequest._retry || isPublic) {
             return Promise.reject(error);
         }
 
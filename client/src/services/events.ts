@@ -0,0 +1,19 @@
+import type { BrokerEvent, BrokerEventType } from 'src/types/events';
+import { API_BASE, getAccessToken } from './axios';
+
+/**
+ * Follows the broker event stream. EventSource can't send headers, so the
+ * token goes in the query string. Returns a function that closes the stream.
+ */
+export function subscribeEvents(types: BrokerEventType[], onEvent: (event: BrokerEvent) => void): () => void {
+    const params = new URLSearchParams();
+    if (types.length > 0) params.set('types', types.join(','));
+    const token = getAccessToken();
+    if (token) params.set('access_token', token);
+
+    const source = new EventSource(`${API_BASE}/api/v1/events?${params}`);
+    const handle = (message: MessageEvent) => onEvent(JSON.parse(message.data) as BrokerEvent);
+    [...types, 'lagged'].forEach((type) => source.addEventListener(type, handle));
+
+    return () => source.close();
+}
@@ -1,13 +1,16 @@
 import { ApiResponse } from 'src/types/api_response';
-import { TopicInfo, PublishRequest } from 'src/types/topics';
+import { Pagination } from 'src/types/pagination';
+import { TopicInfo, PublishRequest, PublishResult } from 'src/types/topics';
 import { api } from './axios';
 
-export async function fetchTopics(): Promise<ApiResponse<TopicInfo[]>> {
-    const res = await api.get<ApiResponse<TopicInfo[]>>('/api/v1/topics');
+export async function fetchTopics(page = 0, size = 1000): Promise<ApiResponse<Pagination<TopicInfo>>> {
+    const res = await api.get<ApiResponse<Pagination<TopicInfo>>>('/api/v1/topics', {
+        params: { page, size },
+    });
     return res.data;
 }
 
-export async function publishMessage(request: PublishRequest): Promise<ApiResponse<string>> {
-    const res = await api.post<ApiResponse<string>>('/api/v1/publish', request);
+export async function publishMessage(request: PublishRequest): Promise<ApiResponse<PublishResult>> {
+    const res = await api.post<ApiResponse<PublishResult>>('/api/v1/publish', request);
     return res.data;
 }
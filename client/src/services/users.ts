@@ -1,4 +1,4 @@
-import type { User, CreateUserRequest } from 'src/types/users';
+import type { User, CreateUserRequest, ChangePasswordRequest } from 'src/types/users';
 import type { ApiResponse } from 'src/types/api_response';
 import { api } from './axios';
 
@@ -11,3 +11,18 @@ export async function createUser(data: CreateUserRequest): Promise<ApiResponse<U
     const res = await api.post<ApiResponse<User>>('/api/v1/users', data);
     return res.data;
 }
+
+export async function updateUserRole(username: string, role: string): Promise<ApiResponse<User>> {
+    const res = await api.put<ApiResponse<User>>(`/api/v1/users/${encodeURIComponent(username)}/role`, { role });
+    return res.data;
+}
+
+export async function deleteUser(username: string): Promise<ApiResponse<null>> {
+    const res = await api.delete<ApiResponse<null>>(`/api/v1/users/${encodeURIComponent(username)}`);
+    return res.data;
+}
+
+export async function changePassword(data: ChangePasswordRequest): Promise<ApiResponse<User>> {
+    const res = await api.post<ApiResponse<User>>('/api/v1/account/password', data);
+    return res.data;
+}
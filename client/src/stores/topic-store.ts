@@ -29,7 +29,7 @@ export const useTopicStore = create<TopicState & TopicActions>((set) => ({
         set({ loading: true, error: null });
         try {
             const res = await fetchTopics();
-            const list = res?.data ?? [];
+            const list = res?.data?.content ?? [];
             const total = list.reduce((sum, t) => sum + t.subscriber_count, 0);
             set({ topics: list, totalSubscriptions: total, loading: false });
         } catch (err: any) {
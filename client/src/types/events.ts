@@ -0,0 +1,30 @@
+export type DisconnectReason =
+    | 'client_disconnect'
+    | 'connection_closed'
+    | 'keep_alive_timeout'
+    | 'protocol_error'
+    | 'taken_over'
+    | 'kicked'
+    | 'banned'
+    | 'listener_stopped'
+    | 'shutdown';
+
+export type BrokerEvent = { at: string } & (
+    | {
+          type: 'client_connected';
+          client_id: string;
+          username: string | null;
+          remote_addr: string;
+          port: number;
+          protocol_version: number;
+      }
+    | { type: 'client_disconnected'; client_id: string; reason: DisconnectReason }
+    | { type: 'subscribed'; client_id: string; topic: string; qos: number }
+    | { type: 'unsubscribed'; client_id: string; topic: string }
+    | { type: 'listener_started'; name: string; port: number; protocol: string }
+    | { type: 'listener_stopped'; name: string; port: number }
+    | { type: 'auth_failed'; username: string; ip: string; locked: boolean }
+    | { type: 'lagged'; missed: number }
+);
+
+export type BrokerEventType = BrokerEvent['type'];
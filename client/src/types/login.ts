@@ -6,4 +6,5 @@ export type SignInRequest = {
 export type Token = {
     access_token: string;
     refresh_token: string;
+    password_change_required: boolean;
 };
@@ -1,7 +1,17 @@
+export type SessionWill = {
+    topic: string;
+    qos: number;
+    retain: boolean;
+    payload_size: number;
+};
+
 export type Session = {
     client_id: string;
     username: string;
     clean_session: boolean;
+    protocol_version: number;
+    keep_alive: number;
+    will: SessionWill | null;
     remote_addr: string;
     connected_port: number;
     connected_at: string;
@@ -1,11 +1,26 @@
 export type TopicInfo = {
     topic: string;
     subscriber_count: number;
+    messages: number;
+    bytes: number;
+    last_publish_at: string | null;
+    retained: boolean;
 };
 
+export type PayloadEncoding = 'plain' | 'base64' | 'hex';
+
 export type PublishRequest = {
     topic: string;
     payload: string;
+    encoding?: PayloadEncoding;
     qos: number;
     retain: boolean;
 };
+
+export type PublishResult = {
+    topic: string;
+    matched: number;
+    delivered: number;
+    acks_expected: number | null;
+    acknowledged: number | null;
+};
@@ -1,11 +1,16 @@
 export type User = {
     username: string;
-    password_hash: string;
     role: string;
+    must_change_password: boolean;
 };
 
 export type CreateUserRequest = {
     username: string;
-    password_hash: string;
+    password: string;
     role: string;
 };
+
+export type ChangePasswordRequest = {
+    current_password: string;
+    new_password: string;
+};
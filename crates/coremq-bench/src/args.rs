@@ -0,0 +1,108 @@
+use clap::{Parser, ValueEnum};
+
+/*
+  `-h` is the broker host, as in coremq-cli; help is `--help`.
+*/
+#[derive(Parser)]
+#[command(name = "coremq-bench", version, about = "Load-test a CoreMQ broker and report throughput and latency", disable_help_flag = true)]
+pub struct Cli {
+    #[arg(value_enum)]
+    pub scenario: Scenario,
+
+    /// Broker host
+    #[arg(short = 'h', long, default_value = "localhost")]
+    pub host: String,
+
+    /// Broker port [default: 1883, 8083 with --ws]
+    #[arg(short = 'p', long)]
+    pub port: Option<u16>,
+
+    #[arg(short = 'u', long)]
+    pub username: Option<String>,
+
+    #[arg(short = 'P', long, requires = "username")]
+    pub password: Option<String>,
+
+    /// Connect over WebSocket
+    #[arg(long)]
+    pub ws: bool,
+
+    /// WebSocket path
+    #[arg(long, default_value = "/mqtt")]
+    pub ws_path: String,
+
+    /// Publishing connections [default: 1000 fan-in, 1 fan-out, 500 p2p]
+    #[arg(long)]
+    pub publishers: Option<usize>,
+
+    /// Subscribing connections [default: 1 fan-in, 1000 fan-out; always equal to --publishers for p2p]
+    #[arg(long)]
+    pub subscribers: Option<usize>,
+
+    /// Seconds to publish for
+    #[arg(short = 'd', long, default_value_t = 10)]
+    pub duration: u64,
+
+    /// Messages per second per publisher, 0 publishes as fast as acks allow
+    #[arg(short = 'r', long, default_value_t = 10.0)]
+    pub rate: f64,
+
+    /// Unacknowledged publishes allowed per publisher
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    pub inflight: u64,
+
+    /// Publish QoS levels, cycled per message (e.g. 0,1,2)
+    #[arg(short = 'q', long, value_delimiter = ',', default_value = "0", value_parser = clap::value_parser!(u8).range(0..=2))]
+    pub qos: Vec<u8>,
+
+    /// QoS requested by subscribers
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(0..=2))]
+    pub sub_qos: u8,
+
+    /// Payload sizes in bytes, cycled per message (e.g. 16,1024,65536); at least 8
+    #[arg(short = 's', long, value_delimiter = ',', default_value = "64")]
+    pub payload: Vec<usize>,
+
+    /// Connections opened concurrently during setup
+    #[arg(long, default_value_t = 100)]
+    pub connect_concurrency: usize,
+
+    /// Seconds to wait for in-flight messages after publishing stops
+    #[arg(long, default_value_t = 5)]
+    pub drain: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Exit with status 1 if the p99 end-to-end latency exceeds this many milliseconds
+    #[arg(long)]
+    pub max_p99_ms: Option<f64>,
+
+    /// Exit with status 1 if less than this fraction of expected messages arrive (0.0-1.0)
+    #[arg(long)]
+    pub min_delivery: Option<f64>,
+
+    #[arg(long, action = clap::ArgAction::Help, help = "Print help")]
+    pub help: Option<bool>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Scenario {
+    /// Many publishers, each on its own topic, read by a few wildcard subscribers
+    FanIn,
+    /// A few publishers on one topic read by many subscribers
+    FanOut,
+    /// Publisher i sends to subscriber i on a private topic
+    P2p,
+}
+
+impl Scenario {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scenario::FanIn => "fan-in",
+            Scenario::FanOut => "fan-out",
+            Scenario::P2p => "p2p",
+        }
+    }
+}
@@ -0,0 +1,47 @@
+/*
+  coremq-bench: open many MQTT connections against a broker, publish for
+  a fixed time and report throughput and end-to-end latency percentiles.
+
+    coremq-bench fan-in --publishers 2000 --rate 5
+    coremq-bench fan-out --subscribers 5000 -q 0,1,2 -s 64,4096
+    coremq-bench p2p --publishers 1000 --rate 0 --json --max-p99-ms 50
+*/
+use clap::Parser;
+
+mod args;
+mod plan;
+mod run;
+mod stats;
+
+use args::Cli;
+use plan::Plan;
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(e) = bench(cli).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn bench(cli: Cli) -> anyhow::Result<()> {
+    let plan = Plan::new(&cli)?;
+    let report = run::run(plan).await?;
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+    } else {
+        report.print();
+    }
+
+    if let Some(max) = cli.max_p99_ms {
+        anyhow::ensure!(report.p99_ms() <= max, "p99 latency {:.2}ms exceeds {:.2}ms", report.p99_ms(), max);
+    }
+    if let Some(min) = cli.min_delivery {
+        anyhow::ensure!(report.delivery() >= min, "delivered {:.4} of expected messages, below {:.4}", report.delivery(), min);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::{process, time::Duration};
+
+use coremq_client::{ClientOptions, Transport};
+
+use crate::args::{Cli, Scenario};
+
+/*
+  Every payload starts with the publish time (microseconds since the run
+  started, big endian) and the QoS it was published with, so subscribers
+  can measure end-to-end latency per QoS. Requested sizes below this are
+  rounded up.
+*/
+pub const HEADER_LEN: usize = 9;
+
+/*
+  What one run does: which topic each publisher writes to, which filter
+  each subscriber reads, and how many subscribers receive each message.
+*/
+pub struct Plan {
+    pub scenario: Scenario,
+    pub publish_topics: Vec<String>,
+    pub subscribe_filters: Vec<String>,
+    pub fanout: u64,
+    pub qos: Vec<u8>,
+    pub sub_qos: u8,
+    pub payload_sizes: Vec<usize>,
+    pub rate: Option<Duration>,
+    pub inflight: usize,
+    pub duration: Duration,
+    pub drain: Duration,
+    pub connect_concurrency: usize,
+    run_id: String,
+    host: String,
+    port: u16,
+    transport: Transport,
+    credentials: Option<(String, String)>,
+}
+
+impl Plan {
+    pub fn new(cli: &Cli) -> anyhow::Result<Self> {
+        /* Topics are namespaced per run so that concurrent runs and retained leftovers don't mix. */
+        let run_id = format!("{}-{}", process::id(), std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default());
+        let prefix = format!("bench/{}", run_id);
+
+        let (publishers, subscribers) = match cli.scenario {
+            Scenario::FanIn => (cli.publishers.unwrap_or(1000), cli.subscribers.unwrap_or(1)),
+            Scenario::FanOut => (cli.publishers.unwrap_or(1), cli.subscribers.unwrap_or(1000)),
+            Scenario::P2p => {
+                let pairs = cli.publishers.or(cli.subscribers).unwrap_or(500);
+                (pairs, pairs)
+            }
+        };
+        anyhow::ensure!(publishers > 0 && subscribers > 0, "at least one publisher and one subscriber are needed");
+        anyhow::ensure!(cli.rate >= 0.0 && cli.rate.is_finite(), "--rate must be a non-negative number");
+        anyhow::ensure!(!cli.payload.is_empty() && !cli.qos.is_empty(), "--payload and --qos need at least one value");
+
+        let (publish_topics, subscribe_filters, fanout) = match cli.scenario {
+            Scenario::FanIn => (
+                (0..publishers).map(|i| format!("{}/in/{}", prefix, i)).collect(),
+                vec![format!("{}/in/+", prefix); subscribers],
+                subscribers as u64,
+            ),
+            Scenario::FanOut => (
+                vec![format!("{}/out", prefix); publishers],
+                vec![format!("{}/out", prefix); subscribers],
+                subscribers as u64,
+            ),
+            Scenario::P2p => {
+                let topics: Vec<String> = (0..publishers).map(|i| format!("{}/p2p/{}", prefix, i)).collect();
+                (topics.clone(), topics, 1)
+            }
+        };
+
+        let transport = if cli.ws {
+            Transport::WebSocket { path: cli.ws_path.clone() }
+        } else {
+            Transport::Tcp
+        };
+
+        Ok(Self {
+            scenario: cli.scenario,
+            publish_topics,
+            subscribe_filters,
+            fanout,
+            qos: cli.qos.clone(),
+            sub_qos: cli.sub_qos,
+            payload_sizes: cli.payload.iter().map(|&size| size.max(HEADER_LEN)).collect(),
+            rate: (cli.rate > 0.0).then(|| Duration::from_secs_f64(1.0 / cli.rate)),
+            inflight: cli.inflight as usize,
+            duration: Duration::from_secs(cli.duration),
+            drain: Duration::from_secs(cli.drain),
+            connect_concurrency: cli.connect_concurrency.max(1),
+            run_id,
+            host: cli.host.clone(),
+            port: cli.port.unwrap_or(if cli.ws { 8083 } else { 1883 }),
+            transport,
+            credentials: cli.username.clone().map(|u| (u, cli.password.clone().unwrap_or_default())),
+        })
+    }
+
+    /*
+      Options for one benchmark connection. Reconnecting would hide broker
+      failures, so a dropped connection simply stops counting.
+    */
+    pub fn options(&self, role: &str, index: usize) -> ClientOptions {
+        let client_id = format!("bench-{}-{}-{}", self.run_id, role, index);
+        let mut options = ClientOptions::new(client_id, self.host.clone(), self.port)
+            .transport(self.transport.clone())
+            .reconnect(false)
+            .connect_timeout(Duration::from_secs(30))
+            .event_capacity(4096);
+
+        if let Some((username, password)) = &self.credentials {
+            options = options.credentials(username.clone(), password.clone().into_bytes());
+        }
+
+        options
+    }
+
+    /*
+      QoS and payload size of the n-th message of a publisher. The two lists
+      are cycled independently so every combination gets exercised.
+    */
+    pub fn message(&self, n: usize) -> (u8, usize) {
+        let qos = self.qos[n % self.qos.len()];
+        let size = self.payload_sizes[(n / self.qos.len()) % self.payload_sizes.len()];
+        (qos, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn plan(args: &[&str]) -> anyhow::Result<Plan> {
+        let cli = Cli::try_parse_from(std::iter::once("coremq-bench").chain(args.iter().copied()))?;
+        Plan::new(&cli)
+    }
+
+    #[test]
+    fn fan_in_gives_every_publisher_its_own_topic() {
+        let plan = plan(&["fan-in", "--publishers", "3", "--subscribers", "2"]).unwrap();
+        let prefix = format!("bench/{}", plan.run_id);
+        assert_eq!(plan.publish_topics, [0, 1, 2].map(|i| format!("{}/in/{}", prefix, i)));
+        assert_eq!(plan.subscribe_filters, vec![format!("{}/in/+", prefix); 2]);
+        assert_eq!(plan.fanout, 2);
+    }
+
+    #[test]
+    fn fan_out_shares_one_topic() {
+        let plan = plan(&["fan-out", "--subscribers", "4"]).unwrap();
+        assert_eq!(plan.publish_topics.len(), 1);
+        assert_eq!(plan.subscribe_filters.len(), 4);
+        assert!(plan.subscribe_filters.iter().all(|filter| *filter == plan.publish_topics[0]));
+        assert_eq!(plan.fanout, 4);
+    }
+
+    #[test]
+    fn p2p_pairs_publishers_with_subscribers() {
+        let plan = plan(&["p2p", "--subscribers", "5"]).unwrap();
+        assert_eq!(plan.publish_topics.len(), 5);
+        assert_eq!(plan.publish_topics, plan.subscribe_filters);
+        assert_eq!(plan.fanout, 1);
+    }
+
+    #[test]
+    fn defaults_follow_the_transport_and_rate() {
+        let tcp = plan(&["fan-out"]).unwrap();
+        assert_eq!(tcp.port, 1883);
+        assert_eq!(tcp.rate, Some(Duration::from_millis(100)));
+        assert_eq!(tcp.payload_sizes, [64]);
+
+        let ws = plan(&["fan-out", "--ws", "--rate", "0", "-s", "1,512"]).unwrap();
+        assert_eq!(ws.port, 8083);
+        assert_eq!(ws.rate, None);
+        assert_eq!(ws.payload_sizes, [HEADER_LEN, 512]);
+    }
+
+    #[test]
+    fn messages_cycle_qos_and_sizes_independently() {
+        let plan = plan(&["fan-out", "-q", "0,1", "-s", "16,32,64"]).unwrap();
+        let messages: Vec<(u8, usize)> = (0..7).map(|n| plan.message(n)).collect();
+        assert_eq!(messages, [(0, 16), (1, 16), (0, 32), (1, 32), (0, 64), (1, 64), (0, 16)]);
+    }
+
+    #[test]
+    fn invalid_plans_are_refused() {
+        assert!(plan(&["fan-in", "--publishers", "0"]).is_err());
+        assert!(plan(&["fan-in", "--rate=-1"]).is_err());
+        assert!(plan(&["fan-in", "-q", "3"]).is_err());
+    }
+}
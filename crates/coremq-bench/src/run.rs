@@ -0,0 +1,309 @@
+use std::{
+    sync::{Arc, atomic::Ordering},
+    time::{Duration, Instant},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use coremq_client::{Client, EventStream};
+use futures_util::{StreamExt, stream::FuturesUnordered};
+use hdrhistogram::Histogram;
+use tokio::{
+    sync::{Semaphore, watch},
+    task::JoinSet,
+    time::{Interval, MissedTickBehavior},
+};
+
+use crate::{
+    plan::{HEADER_LEN, Plan},
+    stats::{Counters, Latency, Report, Setup},
+};
+
+pub async fn run(plan: Plan) -> anyhow::Result<Report> {
+    /* Subscribers connect first so that nothing published is missed. */
+    let setup_started = Instant::now();
+    let (subscribers, sub_setup) = connect_all(&plan, "sub", plan.subscribe_filters.len()).await;
+    anyhow::ensure!(!subscribers.is_empty(), "no subscriber could connect");
+
+    let mut subscribed = JoinSet::new();
+    for ((client, _), filter) in subscribers.iter().zip(&plan.subscribe_filters) {
+        let (client, filter, qos) = (client.clone(), filter.clone(), plan.sub_qos);
+        subscribed.spawn(async move { client.subscribe(filter, qos).await });
+    }
+    while let Some(result) = subscribed.join_next().await {
+        match result? {
+            Ok(code) if code <= 2 => {}
+            Ok(code) => anyhow::bail!("subscription rejected by the broker (code {:#04x})", code),
+            Err(e) => anyhow::bail!("subscribe failed: {}", e),
+        }
+    }
+
+    let (publishers, pub_setup) = connect_all(&plan, "pub", plan.publish_topics.len()).await;
+    anyhow::ensure!(!publishers.is_empty(), "no publisher could connect");
+    let setup = pub_setup.merge(sub_setup, setup_started.elapsed());
+
+    let base = Instant::now();
+    let counters = Arc::new(Counters::default());
+    let plan = Arc::new(plan);
+
+    let (stop_subscribers, subscribers_stopped) = watch::channel(false);
+    let mut subscriber_tasks = JoinSet::new();
+    let mut subscriber_clients = Vec::with_capacity(subscribers.len());
+    for (client, events) in subscribers {
+        subscriber_clients.push(client);
+        subscriber_tasks.spawn(subscriber(events, base, counters.clone(), subscribers_stopped.clone()));
+    }
+
+    let (stop_publishers, publishers_stopped) = watch::channel(false);
+    let mut publisher_tasks = JoinSet::new();
+    let mut publisher_clients = Vec::with_capacity(publishers.len());
+    for (index, (client, events)) in publishers.into_iter().enumerate() {
+        /* Publishers only get connection events, which nobody reads. */
+        drop(events);
+        publisher_clients.push(client.clone());
+        publisher_tasks.spawn(publisher(client, index, plan.clone(), base, counters.clone(), publishers_stopped.clone()));
+    }
+
+    tokio::time::sleep(plan.duration).await;
+    let _ = stop_publishers.send(true);
+    while publisher_tasks.join_next().await.is_some() {}
+    let publish_elapsed = base.elapsed();
+
+    /* Wait for the broker to deliver what was accepted, up to the drain limit. */
+    let sent_ok = counters.sent_total() - counters.failed.load(Ordering::Relaxed);
+    let expected = sent_ok * plan.fanout;
+    let drain_deadline = Instant::now() + plan.drain;
+    while counters.received.load(Ordering::Relaxed) < expected && Instant::now() < drain_deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let _ = stop_subscribers.send(true);
+    let mut latency = Latency::new();
+    while let Some(result) = subscriber_tasks.join_next().await {
+        latency.merge(&result?);
+    }
+
+    for client in publisher_clients.iter().chain(&subscriber_clients) {
+        let _ = client.disconnect().await;
+    }
+
+    Ok(Report {
+        scenario: plan.scenario.name(),
+        publishers: plan.publish_topics.len(),
+        subscribers: plan.subscribe_filters.len(),
+        setup,
+        elapsed: publish_elapsed,
+        sent: [0, 1, 2].map(|qos| counters.sent[qos].load(Ordering::Relaxed)),
+        failed: counters.failed.load(Ordering::Relaxed),
+        expected,
+        received: counters.received.load(Ordering::Relaxed),
+        sent_bytes: counters.sent_bytes.load(Ordering::Relaxed),
+        received_bytes: counters.received_bytes.load(Ordering::Relaxed),
+        latency,
+    })
+}
+
+/*
+  Opens `count` connections with at most `connect_concurrency` handshakes
+  in flight. Failed connections are counted and left out.
+*/
+async fn connect_all(plan: &Plan, role: &str, count: usize) -> (Vec<(Client, EventStream)>, Setup) {
+    let started = Instant::now();
+    let limit = Arc::new(Semaphore::new(plan.connect_concurrency));
+    let mut tasks = JoinSet::new();
+
+    for index in 0..count {
+        let options = plan.options(role, index);
+        let limit = limit.clone();
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let started = Instant::now();
+            let result = Client::connect(options).await;
+            (index, started.elapsed(), result)
+        });
+    }
+
+    let mut connect_micros = Histogram::new_with_bounds(1, 3_600_000_000, 3).expect("valid histogram bounds");
+    let mut connected = Vec::with_capacity(count);
+    let mut failed = 0;
+    let mut first_error = None;
+
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, elapsed, Ok(connection))) => {
+                connect_micros.saturating_record(elapsed.as_micros() as u64);
+                connected.push((index, connection));
+            }
+            Ok((_, _, Err(e))) => {
+                failed += 1;
+                first_error.get_or_insert(e.to_string());
+            }
+            Err(_) => failed += 1,
+        }
+    }
+
+    if let Some(error) = first_error {
+        eprintln!("{} of {} {} connections failed, first error: {}", failed, count, role, error);
+    }
+
+    /* Keep the plan's order so publisher/subscriber i still map to topic i. */
+    connected.sort_by_key(|(index, _)| *index);
+
+    let setup = Setup {
+        connected: connected.len(),
+        failed,
+        elapsed: started.elapsed(),
+        connect_micros,
+    };
+    (connected.into_iter().map(|(_, connection)| connection).collect(), setup)
+}
+
+async fn publisher(
+    client: Client,
+    index: usize,
+    plan: Arc<Plan>,
+    base: Instant,
+    counters: Arc<Counters>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let topic = plan.publish_topics[index].clone();
+    let mut ticker = plan.rate.map(|period| {
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+    let mut inflight = FuturesUnordered::new();
+    let mut n = 0;
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = stop.changed() => break,
+            Some(result) = inflight.next(), if !inflight.is_empty() => {
+                if let Err(()) = result {
+                    counters.failed.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            _ = tick(&mut ticker), if inflight.len() < plan.inflight => {
+                let (qos, size) = plan.message(n);
+                n += 1;
+
+                counters.sent[qos as usize].fetch_add(1, Ordering::Relaxed);
+                counters.sent_bytes.fetch_add(size as u64, Ordering::Relaxed);
+
+                let client = client.clone();
+                let payload = stamped_payload(base, qos, size);
+                let topic = topic.clone();
+                inflight.push(async move { client.publish(topic, payload, qos, false).await.map_err(|_| ()) });
+            }
+        }
+    }
+
+    while let Some(result) = inflight.next().await {
+        if result.is_err() {
+            counters.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn tick(ticker: &mut Option<Interval>) {
+    if let Some(ticker) = ticker {
+        ticker.tick().await;
+    }
+}
+
+async fn subscriber(
+    mut events: EventStream,
+    base: Instant,
+    counters: Arc<Counters>,
+    mut stop: watch::Receiver<bool>,
+) -> Latency {
+    let mut latency = Latency::new();
+
+    loop {
+        tokio::select! {
+            _ = stop.changed() => break,
+            message = events.next_message() => {
+                let Some(message) = message else { break };
+                let now = base.elapsed().as_micros() as u64;
+
+                counters.received.fetch_add(1, Ordering::Relaxed);
+                counters.received_bytes.fetch_add(message.payload.len() as u64, Ordering::Relaxed);
+
+                if let Some((qos, sent_at)) = read_stamp(&message.payload) {
+                    latency.record(qos, now.saturating_sub(sent_at));
+                }
+            }
+        }
+    }
+
+    latency
+}
+
+fn stamped_payload(base: Instant, qos: u8, size: usize) -> Bytes {
+    let mut payload = BytesMut::with_capacity(size);
+    payload.put_u64(base.elapsed().as_micros() as u64);
+    payload.put_u8(qos);
+    payload.resize(size, 0);
+    payload.freeze()
+}
+
+/*
+  The QoS and publish time written by `stamped_payload`, `None` for a
+  payload too short to carry them.
+*/
+fn read_stamp(payload: &[u8]) -> Option<(u8, u64)> {
+    let header = payload.get(..HEADER_LEN)?;
+    let sent_at = u64::from_be_bytes(header[..8].try_into().ok()?);
+    Some((header[8], sent_at))
+}
+
+impl Setup {
+    fn merge(mut self, other: Setup, elapsed: Duration) -> Setup {
+        let _ = self.connect_micros.add(&other.connect_micros);
+        Setup {
+            connected: self.connected + other.connected,
+            failed: self.failed + other.failed,
+            elapsed,
+            connect_micros: self.connect_micros,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamped_payloads_carry_qos_and_publish_time() {
+        let base = Instant::now() - Duration::from_millis(5);
+        let payload = stamped_payload(base, 2, 64);
+        assert_eq!(payload.len(), 64);
+
+        let (qos, sent_at) = read_stamp(&payload).unwrap();
+        assert_eq!(qos, 2);
+        assert!(sent_at >= 5_000);
+        assert!(payload[HEADER_LEN..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn short_payloads_have_no_stamp() {
+        assert_eq!(read_stamp(&[0; HEADER_LEN - 1]), None);
+        assert_eq!(read_stamp(&[0, 0, 0, 0, 0, 0, 0, 7, 1]), Some((1, 7)));
+    }
+
+    #[test]
+    fn setups_merge_counts_and_histograms() {
+        let setup = |connected, failed, micros: &[u64]| {
+            let mut connect_micros = Histogram::new(3).unwrap();
+            for &m in micros {
+                connect_micros.record(m).unwrap();
+            }
+            Setup { connected, failed, elapsed: Duration::ZERO, connect_micros }
+        };
+
+        let merged = setup(3, 1, &[100, 200]).merge(setup(2, 0, &[300]), Duration::from_secs(2));
+        assert_eq!((merged.connected, merged.failed), (5, 1));
+        assert_eq!(merged.elapsed, Duration::from_secs(2));
+        assert_eq!(merged.connect_micros.len(), 3);
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use hdrhistogram::Histogram;
+use serde_json::{Value, json};
+
+/*
+  Counters shared by all publisher and subscriber tasks.
+*/
+#[derive(Default)]
+pub struct Counters {
+    pub sent: [AtomicU64; 3],
+    pub failed: AtomicU64,
+    pub received: AtomicU64,
+    pub received_bytes: AtomicU64,
+    pub sent_bytes: AtomicU64,
+}
+
+impl Counters {
+    pub fn sent_total(&self) -> u64 {
+        self.sent.iter().map(|c| c.load(Ordering::Relaxed)).sum()
+    }
+}
+
+/*
+  End-to-end latency in microseconds, one histogram per publish QoS.
+  Each subscriber keeps its own and they are merged at the end.
+*/
+pub struct Latency {
+    pub by_qos: [Histogram<u64>; 3],
+}
+
+impl Latency {
+    pub fn new() -> Self {
+        let histogram = || Histogram::new_with_bounds(1, 3_600_000_000, 3).expect("valid histogram bounds");
+        Self { by_qos: [histogram(), histogram(), histogram()] }
+    }
+
+    pub fn record(&mut self, qos: u8, micros: u64) {
+        if let Some(histogram) = self.by_qos.get_mut(qos as usize) {
+            histogram.saturating_record(micros.max(1));
+        }
+    }
+
+    pub fn merge(&mut self, other: &Latency) {
+        for (mine, theirs) in self.by_qos.iter_mut().zip(&other.by_qos) {
+            let _ = mine.add(theirs);
+        }
+    }
+
+    fn total(&self) -> Histogram<u64> {
+        let mut total = self.by_qos[0].clone();
+        let _ = total.add(&self.by_qos[1]);
+        let _ = total.add(&self.by_qos[2]);
+        total
+    }
+}
+
+pub struct Setup {
+    pub connected: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+    pub connect_micros: Histogram<u64>,
+}
+
+pub struct Report {
+    pub scenario: &'static str,
+    pub publishers: usize,
+    pub subscribers: usize,
+    pub setup: Setup,
+    pub elapsed: Duration,
+    pub sent: [u64; 3],
+    pub failed: u64,
+    pub expected: u64,
+    pub received: u64,
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
+    pub latency: Latency,
+}
+
+impl Report {
+    pub fn delivery(&self) -> f64 {
+        if self.expected == 0 { 1.0 } else { self.received as f64 / self.expected as f64 }
+    }
+
+    pub fn p99_ms(&self) -> f64 {
+        ms(self.latency.total().value_at_quantile(0.99))
+    }
+
+    pub fn to_json(&self) -> Value {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        let per_qos: Vec<Value> = (0..3)
+            .filter(|&qos| self.sent[qos] > 0)
+            .map(|qos| {
+                json!({
+                    "qos": qos,
+                    "sent": self.sent[qos],
+                    "received": self.latency.by_qos[qos].len(),
+                    "latency_ms": percentiles(&self.latency.by_qos[qos]),
+                })
+            })
+            .collect();
+
+        json!({
+            "scenario": self.scenario,
+            "publishers": self.publishers,
+            "subscribers": self.subscribers,
+            "connections": {
+                "connected": self.setup.connected,
+                "failed": self.setup.failed,
+                "setup_secs": self.setup.elapsed.as_secs_f64(),
+                "connect_ms": percentiles(&self.setup.connect_micros),
+            },
+            "duration_secs": secs,
+            "sent": self.sent.iter().sum::<u64>(),
+            "publish_errors": self.failed,
+            "expected": self.expected,
+            "received": self.received,
+            "delivery": self.delivery(),
+            "throughput": {
+                "sent_msgs_per_sec": self.sent.iter().sum::<u64>() as f64 / secs,
+                "received_msgs_per_sec": self.received as f64 / secs,
+                "sent_mb_per_sec": self.sent_bytes as f64 / secs / 1_000_000.0,
+                "received_mb_per_sec": self.received_bytes as f64 / secs / 1_000_000.0,
+            },
+            "latency_ms": percentiles(&self.latency.total()),
+            "by_qos": per_qos,
+        })
+    }
+
+    pub fn print(&self) {
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        let sent: u64 = self.sent.iter().sum();
+
+        println!("scenario      {} ({} publishers, {} subscribers)", self.scenario, self.publishers, self.subscribers);
+        println!(
+            "connections   {} connected, {} failed in {:.2}s (connect p50 {:.2}ms, p99 {:.2}ms)",
+            self.setup.connected,
+            self.setup.failed,
+            self.setup.elapsed.as_secs_f64(),
+            ms(self.setup.connect_micros.value_at_quantile(0.5)),
+            ms(self.setup.connect_micros.value_at_quantile(0.99)),
+        );
+        println!("duration      {:.2}s", secs);
+        println!(
+            "sent          {} msgs, {:.0} msg/s, {:.2} MB/s, {} publish errors",
+            sent,
+            sent as f64 / secs,
+            self.sent_bytes as f64 / secs / 1_000_000.0,
+            self.failed,
+        );
+        println!(
+            "received      {} of {} expected ({:.2}%), {:.0} msg/s, {:.2} MB/s",
+            self.received,
+            self.expected,
+            self.delivery() * 100.0,
+            self.received as f64 / secs,
+            self.received_bytes as f64 / secs / 1_000_000.0,
+        );
+        println!();
+        println!("{:<6} {:>10} {:>10} {:>9} {:>9} {:>9} {:>9} {:>9}", "QOS", "SENT", "RECEIVED", "P50 ms", "P90 ms", "P99 ms", "P99.9 ms", "MAX ms");
+        for qos in 0..3 {
+            if self.sent[qos] > 0 {
+                print_latency_row(&qos.to_string(), self.sent[qos], &self.latency.by_qos[qos]);
+            }
+        }
+        print_latency_row("all", sent, &self.latency.total());
+    }
+}
+
+fn print_latency_row(label: &str, sent: u64, histogram: &Histogram<u64>) {
+    println!(
+        "{:<6} {:>10} {:>10} {:>9.2} {:>9.2} {:>9.2} {:>9.2} {:>9.2}",
+        label,
+        sent,
+        histogram.len(),
+        ms(histogram.value_at_quantile(0.5)),
+        ms(histogram.value_at_quantile(0.9)),
+        ms(histogram.value_at_quantile(0.99)),
+        ms(histogram.value_at_quantile(0.999)),
+        ms(histogram.max()),
+    );
+}
+
+fn percentiles(histogram: &Histogram<u64>) -> Value {
+    json!({
+        "p50": ms(histogram.value_at_quantile(0.5)),
+        "p90": ms(histogram.value_at_quantile(0.9)),
+        "p99": ms(histogram.value_at_quantile(0.99)),
+        "p999": ms(histogram.value_at_quantile(0.999)),
+        "max": ms(histogram.max()),
+        "mean": histogram.mean() / 1000.0,
+    })
+}
+
+fn ms(micros: u64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(sent: [u64; 3], expected: u64, received: u64, latency: Latency) -> Report {
+        Report {
+            scenario: "fan-out",
+            publishers: 1,
+            subscribers: 2,
+            setup: Setup {
+                connected: 3,
+                failed: 0,
+                elapsed: Duration::from_millis(500),
+                connect_micros: Histogram::new(3).unwrap(),
+            },
+            elapsed: Duration::from_secs(2),
+            sent,
+            failed: 0,
+            expected,
+            received,
+            sent_bytes: 4_000_000,
+            received_bytes: 8_000_000,
+            latency,
+        }
+    }
+
+    #[test]
+    fn latency_is_kept_per_qos_and_merged() {
+        let mut first = Latency::new();
+        first.record(0, 0);
+        first.record(1, 2_000);
+        first.record(7, 1_000);
+
+        let mut second = Latency::new();
+        second.record(1, 4_000);
+        first.merge(&second);
+
+        assert_eq!(first.by_qos[0].len(), 1);
+        assert_eq!(first.by_qos[0].min(), 1);
+        assert_eq!(first.by_qos[1].len(), 2);
+        assert_eq!(first.by_qos[2].len(), 0);
+        assert_eq!(first.total().len(), 3);
+    }
+
+    #[test]
+    fn delivery_is_complete_when_nothing_was_expected() {
+        assert_eq!(report([0; 3], 0, 0, Latency::new()).delivery(), 1.0);
+        assert_eq!(report([10, 0, 0], 20, 15, Latency::new()).delivery(), 0.75);
+    }
+
+    #[test]
+    fn json_report_lists_only_the_qos_levels_sent() {
+        let mut latency = Latency::new();
+        latency.record(1, 10_000);
+        let json = report([0, 10, 0], 20, 20, latency).to_json();
+
+        assert_eq!(json["sent"], 10);
+        assert_eq!(json["delivery"], 1.0);
+        assert_eq!(json["throughput"]["sent_msgs_per_sec"], 5.0);
+        assert_eq!(json["throughput"]["received_mb_per_sec"], 4.0);
+
+        let by_qos = json["by_qos"].as_array().unwrap();
+        assert_eq!(by_qos.len(), 1);
+        assert_eq!(by_qos[0]["qos"], 1);
+        assert_eq!(by_qos[0]["received"], 1);
+        assert_eq!(by_qos[0]["latency_ms"]["max"].as_f64().unwrap().round(), 10.0);
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+/*
+  `-h` is the broker host, as in the mosquitto tools; help is `--help`.
+*/
+#[derive(Parser)]
+#[command(name = "coremq-cli", version, about = "Publish and subscribe to a CoreMQ broker", disable_help_flag = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[arg(long, action = clap::ArgAction::Help, global = true, help = "Print help")]
+    pub help: Option<bool>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Publish a message
+    Pub(PubArgs),
+    /// Subscribe and print incoming messages
+    Sub(SubArgs),
+}
+
+#[derive(Args)]
+pub struct ConnectArgs {
+    /// Broker host
+    #[arg(short = 'h', long, default_value = "localhost")]
+    pub host: String,
+
+    /// Broker port [default: 1883, 8883 with TLS, 8083 with --ws]
+    #[arg(short = 'p', long)]
+    pub port: Option<u16>,
+
+    /// Client id [default: coremq-cli-<pid>]
+    #[arg(short = 'i', long = "id")]
+    pub client_id: Option<String>,
+
+    #[arg(short = 'u', long)]
+    pub username: Option<String>,
+
+    #[arg(short = 'P', long, requires = "username")]
+    pub password: Option<String>,
+
+    /// Keep-alive in seconds, 0 disables it
+    #[arg(short = 'k', long = "keepalive", default_value_t = 60)]
+    pub keep_alive: u16,
+
+    /// Ask the broker to keep the session after disconnecting
+    #[arg(short = 'c', long = "disable-clean-session")]
+    pub disable_clean_session: bool,
+
+    /// Connect over WebSocket
+    #[arg(long)]
+    pub ws: bool,
+
+    /// WebSocket path
+    #[arg(long, default_value = "/mqtt")]
+    pub ws_path: String,
+
+    #[command(flatten)]
+    pub tls: TlsArgs,
+}
+
+#[derive(Args)]
+pub struct TlsArgs {
+    /// Connect with TLS
+    #[arg(long)]
+    pub tls: bool,
+
+    /// CA certificate (PEM) to verify the broker with; implies --tls
+    #[arg(long)]
+    pub cafile: Option<PathBuf>,
+
+    /// Client certificate (PEM) for mutual TLS; implies --tls
+    #[arg(long, requires = "key")]
+    pub cert: Option<PathBuf>,
+
+    /// Client private key (PEM)
+    #[arg(long, requires = "cert")]
+    pub key: Option<PathBuf>,
+
+    /// Name to verify the broker certificate against
+    #[arg(long)]
+    pub tls_server_name: Option<String>,
+
+    /// Do not verify the broker certificate; implies --tls
+    #[arg(long)]
+    pub insecure: bool,
+}
+
+#[derive(Args)]
+#[command(group(clap::ArgGroup::new("payload").required(true).args(["message", "file", "stdin_file", "stdin_line", "null_message"])))]
+pub struct PubArgs {
+    #[command(flatten)]
+    pub connect: ConnectArgs,
+
+    #[arg(short = 't', long)]
+    pub topic: String,
+
+    /// Message payload
+    #[arg(short = 'm', long)]
+    pub message: Option<String>,
+
+    /// Send the contents of a file as the payload
+    #[arg(short = 'f', long)]
+    pub file: Option<PathBuf>,
+
+    /// Read stdin to the end and send it as one message
+    #[arg(short = 's', long = "stdin-file")]
+    pub stdin_file: bool,
+
+    /// Send each line of stdin as a separate message
+    #[arg(short = 'l', long = "stdin-line")]
+    pub stdin_line: bool,
+
+    /// Send an empty payload
+    #[arg(short = 'n', long = "null-message")]
+    pub null_message: bool,
+
+    #[arg(short = 'q', long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+    pub qos: u8,
+
+    #[arg(short = 'r', long)]
+    pub retain: bool,
+}
+
+#[derive(Args)]
+pub struct SubArgs {
+    #[command(flatten)]
+    pub connect: ConnectArgs,
+
+    /// Topic filter, may be repeated
+    #[arg(short = 't', long = "topic", required = true)]
+    pub topics: Vec<String>,
+
+    #[arg(short = 'q', long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+    pub qos: u8,
+
+    /// Exit after this many messages
+    #[arg(short = 'C', long)]
+    pub count: Option<usize>,
+
+    /// Exit after this many seconds; an error if --count was not reached
+    #[arg(short = 'W', long)]
+    pub timeout: Option<u64>,
+
+    #[arg(short = 'F', long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Same as --format verbose
+    #[arg(short = 'v', long)]
+    pub verbose: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Payload only
+    Text,
+    /// Topic followed by payload
+    Verbose,
+    /// One JSON object per message with timestamp, topic, QoS and payload
+    Json,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("coremq-cli").chain(args.iter().copied()))
+    }
+
+    fn publish(args: &[&str]) -> PubArgs {
+        match parse(&[&["pub"], args].concat()).unwrap().command {
+            Command::Pub(args) => args,
+            Command::Sub(_) => unreachable!(),
+        }
+    }
+
+    fn error(args: &[&str]) -> ErrorKind {
+        parse(args).err().expect("arguments should be rejected").kind()
+    }
+
+    #[test]
+    fn short_h_is_the_host_like_mosquitto() {
+        let args = publish(&["-h", "broker", "-t", "a", "-m", "x"]);
+        assert_eq!(args.connect.host, "broker");
+        assert_eq!(args.connect.keep_alive, 60);
+        assert_eq!((args.qos, args.retain), (0, false));
+        assert_eq!(error(&["pub", "--help"]), ErrorKind::DisplayHelp);
+    }
+
+    #[test]
+    fn publish_takes_exactly_one_payload_source() {
+        assert!(publish(&["-t", "a", "-n"]).null_message);
+        assert!(publish(&["-t", "a", "-l"]).stdin_line);
+        assert_eq!(error(&["pub", "-t", "a"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error(&["pub", "-t", "a", "-m", "x", "-f", "payload.bin"]), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn qos_is_limited_to_0_2() {
+        assert_eq!(publish(&["-t", "a", "-m", "x", "-q", "2"]).qos, 2);
+        assert_eq!(error(&["pub", "-t", "a", "-m", "x", "-q", "3"]), ErrorKind::ValueValidation);
+        assert_eq!(error(&["sub", "-t", "a", "-q", "3"]), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn dependent_options_are_checked() {
+        assert_eq!(error(&["pub", "-t", "a", "-m", "x", "-P", "secret"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error(&["sub", "-t", "a", "--cert", "c.pem"]), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error(&["sub"]), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn subscribe_accepts_repeated_topics() {
+        let Command::Sub(args) = parse(&["sub", "-t", "a/#", "-t", "b/+", "-C", "3", "-W", "5", "-F", "json"]).unwrap().command else {
+            panic!("expected sub");
+        };
+        assert_eq!(args.topics, ["a/#", "b/+"]);
+        assert_eq!((args.count, args.timeout), (Some(3), Some(5)));
+        assert!(args.format == Format::Json);
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use coremq_client::{ClientOptions, TlsOptions};
+
+use crate::args::ConnectArgs;
+
+impl ConnectArgs {
+    pub fn uses_tls(&self) -> bool {
+        self.tls.tls || self.tls.cafile.is_some() || self.tls.cert.is_some() || self.tls.insecure
+    }
+
+    /*
+      The given port, or the broker's default for the transport.
+    */
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(match (self.ws, self.uses_tls()) {
+            (false, false) => 1883,
+            (false, true) => 8883,
+            (true, false) => 8083,
+            (true, true) => 8084,
+        })
+    }
+
+    pub fn options(&self, command: &str) -> ClientOptions {
+        let tls = self.uses_tls();
+        let port = self.port();
+        let client_id = self
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("coremq-cli-{}-{}", command, std::process::id()));
+
+        let mut options = ClientOptions::new(client_id, self.host.clone(), port)
+            .keep_alive(Duration::from_secs(self.keep_alive as u64))
+            .clean_session(!self.disable_clean_session);
+
+        if let Some(username) = &self.username {
+            options = options.credentials(username.clone(), self.password.clone().unwrap_or_default());
+        }
+
+        if self.ws {
+            options = options.websocket(self.ws_path.clone());
+        }
+
+        if tls {
+            let mut tls_options = TlsOptions::new().insecure(self.tls.insecure);
+            if let Some(ca) = &self.tls.cafile {
+                tls_options = tls_options.ca_file(ca);
+            }
+            if let (Some(cert), Some(key)) = (&self.tls.cert, &self.tls.key) {
+                tls_options = tls_options.client_cert(cert, key);
+            }
+            if let Some(name) = &self.tls.tls_server_name {
+                tls_options = tls_options.server_name(name.clone());
+            }
+            options = options.tls(tls_options);
+        }
+
+        options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::args::{Cli, Command};
+
+    use super::*;
+
+    fn connect_args(args: &[&str]) -> ConnectArgs {
+        let cli = Cli::try_parse_from(["coremq-cli", "sub", "-t", "a/#"].into_iter().chain(args.iter().copied())).unwrap();
+        match cli.command {
+            Command::Sub(sub) => sub.connect,
+            Command::Pub(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn port_defaults_follow_the_transport() {
+        assert_eq!(connect_args(&[]).port(), 1883);
+        assert_eq!(connect_args(&["--tls"]).port(), 8883);
+        assert_eq!(connect_args(&["--ws"]).port(), 8083);
+        assert_eq!(connect_args(&["--ws", "--tls"]).port(), 8084);
+        assert_eq!(connect_args(&["--ws", "-p", "9001"]).port(), 9001);
+    }
+
+    #[test]
+    fn certificate_options_imply_tls() {
+        assert!(!connect_args(&[]).uses_tls());
+        assert!(connect_args(&["--cafile", "ca.pem"]).uses_tls());
+        assert!(connect_args(&["--cert", "c.pem", "--key", "k.pem"]).uses_tls());
+        assert!(connect_args(&["--insecure"]).uses_tls());
+        assert!(!connect_args(&["--tls-server-name", "broker"]).uses_tls());
+    }
+
+    #[test]
+    fn client_id_defaults_to_the_command_and_pid() {
+        let generated = connect_args(&[]).options("sub");
+        assert_eq!(generated.client_id(), format!("coremq-cli-sub-{}", std::process::id()));
+        assert_eq!(connect_args(&["-i", "sensor-1"]).options("sub").client_id(), "sensor-1");
+    }
+}
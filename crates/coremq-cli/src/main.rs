@@ -0,0 +1,30 @@
+/*
+  coremq-cli: publish and subscribe from the command line.
+
+    coremq-cli sub -t 'sensors/#' -v
+    coremq-cli pub -t sensors/1/temp -m 23.5 -q 1
+*/
+use clap::Parser;
+
+mod args;
+mod connect;
+mod output;
+mod publish;
+mod subscribe;
+
+use args::{Cli, Command};
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Pub(args) => publish::run(args).await,
+        Command::Sub(args) => subscribe::run(args).await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
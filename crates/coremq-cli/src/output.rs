@@ -0,0 +1,92 @@
+use std::io::Write;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{Local, SecondsFormat};
+use coremq_client::PublishPacket;
+use serde_json::json;
+
+use crate::args::Format;
+
+/*
+  Writes one received message to `out` in the selected format. JSON
+  payloads are strings when valid UTF-8, otherwise base64 under
+  `payload_base64`.
+*/
+pub fn write_message(out: &mut impl Write, format: Format, message: &PublishPacket) -> std::io::Result<()> {
+    match format {
+        Format::Text => {
+            out.write_all(&message.payload)?;
+            out.write_all(b"\n")?;
+        }
+
+        Format::Verbose => {
+            write!(out, "{} ", message.topic)?;
+            out.write_all(&message.payload)?;
+            out.write_all(b"\n")?;
+        }
+
+        Format::Json => {
+            let mut line = json!({
+                "timestamp": Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+                "topic": message.topic,
+                "qos": message.qos,
+                "retain": message.retain,
+            });
+            match std::str::from_utf8(&message.payload) {
+                Ok(text) => line["payload"] = json!(text),
+                Err(_) => line["payload_base64"] = json!(STANDARD.encode(&message.payload)),
+            }
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use serde_json::Value;
+
+    use super::*;
+
+    fn render(format: Format, payload: &'static [u8]) -> String {
+        let message = PublishPacket {
+            packet_id: Some(3),
+            topic: "sensors/1/temp".to_string(),
+            payload: Bytes::from_static(payload),
+            qos: 1,
+            retain: true,
+            dup: false,
+        };
+        let mut out = Vec::new();
+        write_message(&mut out, format, &message).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn text_and_verbose_print_the_raw_payload() {
+        assert_eq!(render(Format::Text, b"23.5"), "23.5\n");
+        assert_eq!(render(Format::Verbose, b"23.5"), "sensors/1/temp 23.5\n");
+    }
+
+    #[test]
+    fn json_has_one_object_per_line() {
+        let line = render(Format::Json, b"23.5");
+        assert!(line.ends_with('\n'));
+        let value: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["topic"], "sensors/1/temp");
+        assert_eq!(value["qos"], 1);
+        assert_eq!(value["retain"], true);
+        assert_eq!(value["payload"], "23.5");
+        assert!(value.get("payload_base64").is_none());
+        assert!(chrono::DateTime::parse_from_rfc3339(value["timestamp"].as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn json_encodes_binary_payloads_as_base64() {
+        let value: Value = serde_json::from_str(&render(Format::Json, &[0xff, 0x00])).unwrap();
+        assert_eq!(value["payload_base64"], "/wA=");
+        assert!(value.get("payload").is_none());
+    }
+}
@@ -0,0 +1,44 @@
+use bytes::Bytes;
+use coremq_client::Client;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::args::PubArgs;
+
+pub async fn run(args: PubArgs) -> anyhow::Result<()> {
+    let options = args.connect.options("pub").reconnect(false);
+    let (client, _events) = Client::connect(options).await?;
+
+    if args.stdin_line {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Some(line) = lines.next_line().await? {
+            client.publish(args.topic.clone(), line, args.qos, args.retain).await?;
+        }
+    } else {
+        let payload = read_payload(&args).await?;
+        client.publish(args.topic.clone(), payload, args.qos, args.retain).await?;
+    }
+
+    client.disconnect().await?;
+    Ok(())
+}
+
+async fn read_payload(args: &PubArgs) -> anyhow::Result<Bytes> {
+    if let Some(message) = &args.message {
+        return Ok(Bytes::from(message.clone()));
+    }
+
+    if let Some(path) = &args.file {
+        let data = tokio::fs::read(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("cannot read {}: {}", path.display(), e))?;
+        return Ok(Bytes::from(data));
+    }
+
+    if args.stdin_file {
+        let mut data = Vec::new();
+        tokio::io::stdin().read_to_end(&mut data).await?;
+        return Ok(Bytes::from(data));
+    }
+
+    Ok(Bytes::new())
+}
@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use coremq_client::{Client, Event, SubscribeFilter, codec::packets::SUBACK_FAILURE};
+use tokio::time::{self, Instant};
+
+use crate::{
+    args::{Format, SubArgs},
+    output::write_message,
+};
+
+pub async fn run(args: SubArgs) -> anyhow::Result<()> {
+    let format = if args.verbose { Format::Verbose } else { args.format };
+    let (client, mut events) = Client::connect(args.connect.options("sub")).await?;
+
+    let filters = args
+        .topics
+        .iter()
+        .map(|topic| SubscribeFilter { topic: topic.clone(), qos: args.qos })
+        .collect();
+    let codes = client.subscribe_many(filters).await?;
+    for (topic, code) in args.topics.iter().zip(&codes) {
+        if *code == SUBACK_FAILURE {
+            anyhow::bail!("subscription to '{}' was rejected", topic);
+        }
+    }
+
+    let deadline = args.timeout.map(|secs| Instant::now() + Duration::from_secs(secs));
+    let mut received = 0;
+    let mut stdout = std::io::stdout().lock();
+
+    while args.count.is_none_or(|count| received < count) {
+        let event = match deadline {
+            Some(deadline) => match time::timeout_at(deadline, events.recv()).await {
+                Ok(event) => event,
+                Err(_) if args.count.is_some() => {
+                    anyhow::bail!("timed out after {} of {} messages", received, args.count.unwrap_or_default());
+                }
+                Err(_) => break,
+            },
+            None => events.recv().await,
+        };
+
+        match event {
+            Some(Event::Message(message)) => {
+                write_message(&mut stdout, format, &message)?;
+                received += 1;
+            }
+            Some(Event::Disconnected(e)) => eprintln!("Connection lost: {}, reconnecting", e),
+            Some(Event::Connected { .. }) => {}
+            None => anyhow::bail!("connection closed"),
+        }
+    }
+
+    client.disconnect().await?;
+    Ok(())
+}
@@ -0,0 +1,41 @@
+/*
+  Subscribes to `demo/#`, publishes one message at each QoS and prints what
+  comes back. Expects a broker on 127.0.0.1:1883 (or pass host and port).
+
+  cargo run -p coremq-client --example pubsub -- 127.0.0.1 1883
+*/
+use std::time::Duration;
+
+use coremq_client::{Client, ClientOptions, Event};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let host = args.next().unwrap_or_else(|| "127.0.0.1".to_string());
+    let port = args.next().map(|p| p.parse()).transpose()?.unwrap_or(1883);
+
+    let options = ClientOptions::new("coremq-client-example", host, port).keep_alive(Duration::from_secs(30));
+    let (client, mut events) = Client::connect(options).await?;
+
+    let granted = client.subscribe("demo/#", 2).await?;
+    println!("subscribed to demo/# with QoS {}", granted);
+
+    for qos in 0..=2 {
+        client.publish(format!("demo/qos{}", qos), format!("hello at QoS {}", qos), qos, false).await?;
+    }
+
+    let mut received = 0;
+    while received < 3 {
+        match events.recv().await {
+            Some(Event::Message(message)) => {
+                received += 1;
+                println!("{} (QoS {}): {}", message.topic, message.qos, String::from_utf8_lossy(&message.payload));
+            }
+            Some(event) => println!("{:?}", event),
+            None => break,
+        }
+    }
+
+    client.disconnect().await?;
+    Ok(())
+}
@@ -0,0 +1,169 @@
+use bytes::Bytes;
+use coremq_codec::packets::{PublishPacket, SUBACK_FAILURE, SubscribeFilter};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    error::ClientError,
+    eventloop::EventLoop,
+    options::ClientOptions,
+    state::Reply,
+};
+
+pub(crate) enum Request {
+    Publish(PublishPacket, Reply<()>),
+    Subscribe(Vec<SubscribeFilter>, Reply<Vec<u8>>),
+    Unsubscribe(Vec<String>, Reply<()>),
+    Disconnect(oneshot::Sender<()>),
+}
+
+#[derive(Debug)]
+pub enum Event {
+    /*
+      Emitted after every successful (re)connect.
+    */
+    Connected { session_present: bool },
+    Message(PublishPacket),
+
+    /*
+      The connection was lost. With reconnect enabled the client keeps
+      retrying in the background and emits `Connected` once it is back.
+    */
+    Disconnected(ClientError),
+}
+
+/*
+  Incoming messages and connection state changes, in order. Ends once the
+  client has disconnected for good.
+*/
+pub struct EventStream {
+    rx: mpsc::Receiver<Event>,
+}
+
+impl EventStream {
+    pub async fn recv(&mut self) -> Option<Event> {
+        self.rx.recv().await
+    }
+
+    /*
+      Waits for the next message, skipping connection events.
+    */
+    pub async fn next_message(&mut self) -> Option<PublishPacket> {
+        loop {
+            if let Event::Message(message) = self.rx.recv().await? {
+                return Some(message);
+            }
+        }
+    }
+}
+
+/*
+  Handle to a running client. Cloning is cheap; the connection is closed
+  when `disconnect` is called or the last handle is dropped.
+*/
+#[derive(Clone)]
+pub struct Client {
+    requests: mpsc::Sender<Request>,
+}
+
+impl Client {
+    /*
+      Connects to the broker and spawns the event loop. The initial
+      connection is not retried; reconnects only apply once connected.
+    */
+    pub async fn connect(options: ClientOptions) -> Result<(Client, EventStream), ClientError> {
+        let (conn, session_present) = EventLoop::open(&options).await?;
+
+        let (requests_tx, requests_rx) = mpsc::channel(1024);
+        let (events_tx, events_rx) = mpsc::channel(options.event_capacity);
+        let _ = events_tx.try_send(Event::Connected { session_present });
+
+        tokio::spawn(EventLoop::new(options, requests_rx, events_tx).run(conn, session_present));
+
+        Ok((Client { requests: requests_tx }, EventStream { rx: events_rx }))
+    }
+
+    /*
+      Publishes a message. Resolves once it is written for QoS 0, on PUBACK
+      for QoS 1 and on PUBCOMP for QoS 2.
+    */
+    pub async fn publish(
+        &self,
+        topic: impl Into<String>,
+        payload: impl Into<Bytes>,
+        qos: u8,
+        retain: bool,
+    ) -> Result<(), ClientError> {
+        check_qos(qos)?;
+
+        let packet = PublishPacket {
+            packet_id: None,
+            topic: topic.into(),
+            payload: payload.into(),
+            qos,
+            retain,
+            dup: false,
+        };
+
+        self.request(|reply| Request::Publish(packet, reply)).await
+    }
+
+    /*
+      Subscribes to one filter and returns the QoS granted by the broker.
+    */
+    pub async fn subscribe(&self, filter: impl Into<String>, qos: u8) -> Result<u8, ClientError> {
+        let codes = self
+            .subscribe_many(vec![SubscribeFilter { topic: filter.into(), qos }])
+            .await?;
+
+        match codes.first() {
+            Some(&code) if code != SUBACK_FAILURE => Ok(code),
+            _ => Err(ClientError::Protocol("subscription rejected by the broker".into())),
+        }
+    }
+
+    /*
+      Subscribes to several filters in one SUBSCRIBE and returns the SUBACK
+      return codes, `SUBACK_FAILURE` for rejected filters.
+    */
+    pub async fn subscribe_many(&self, filters: Vec<SubscribeFilter>) -> Result<Vec<u8>, ClientError> {
+        for filter in &filters {
+            check_qos(filter.qos)?;
+        }
+
+        self.request(|reply| Request::Subscribe(filters, reply)).await
+    }
+
+    pub async fn unsubscribe(&self, filter: impl Into<String>) -> Result<(), ClientError> {
+        let topics = vec![filter.into()];
+        self.request(|reply| Request::Unsubscribe(topics, reply)).await
+    }
+
+    /*
+      Sends DISCONNECT and stops the event loop. Requests still waiting for
+      the broker fail with `ClientError::Disconnected`.
+    */
+    pub async fn disconnect(&self) -> Result<(), ClientError> {
+        let (done_tx, done_rx) = oneshot::channel();
+        self.requests
+            .send(Request::Disconnect(done_tx))
+            .await
+            .map_err(|_| ClientError::Disconnected)?;
+        done_rx.await.map_err(|_| ClientError::Disconnected)
+    }
+
+    async fn request<T>(&self, build: impl FnOnce(Reply<T>) -> Request) -> Result<T, ClientError> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.requests
+            .send(build(reply_tx))
+            .await
+            .map_err(|_| ClientError::Disconnected)?;
+        reply_rx.await.map_err(|_| ClientError::Disconnected)?
+    }
+}
+
+fn check_qos(qos: u8) -> Result<(), ClientError> {
+    if qos > 2 {
+        return Err(ClientError::InvalidQoS(qos));
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use std::{fmt, io};
+
+use coremq_codec::{CodecError, packets::ConnectReturnCode};
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    Codec(CodecError),
+    WebSocket(String),
+    Tls(String),
+
+    /*
+      The broker answered CONNECT with a non-zero return code.
+    */
+    ConnectionRefused(ConnectReturnCode),
+
+    /*
+      The broker did not answer CONNECT or PINGREQ in time.
+    */
+    Timeout,
+
+    /*
+      The broker closed the connection.
+    */
+    ConnectionClosed,
+
+    /*
+      The broker sent a packet that is not valid at this point.
+    */
+    Protocol(String),
+
+    InvalidQoS(u8),
+
+    /*
+      The client was disconnected and its event loop has stopped.
+    */
+    Disconnected,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "io error: {}", e),
+            ClientError::Codec(e) => write!(f, "{}", e),
+            ClientError::WebSocket(e) => write!(f, "websocket error: {}", e),
+            ClientError::Tls(e) => write!(f, "tls error: {}", e),
+            ClientError::ConnectionRefused(code) => write!(f, "connection refused: {:?}", code),
+            ClientError::Timeout => f.write_str("timed out waiting for the broker"),
+            ClientError::ConnectionClosed => f.write_str("connection closed by the broker"),
+            ClientError::Protocol(e) => write!(f, "protocol error: {}", e),
+            ClientError::InvalidQoS(qos) => write!(f, "invalid QoS {}", qos),
+            ClientError::Disconnected => f.write_str("client is disconnected"),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+impl From<CodecError> for ClientError {
+    fn from(e: CodecError) -> Self {
+        ClientError::Codec(e)
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for ClientError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        ClientError::WebSocket(e.to_string())
+    }
+}
@@ -0,0 +1,286 @@
+use std::time::Duration;
+
+use coremq_codec::packets::{ConnectReturnCode, Packet};
+use tokio::{
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use crate::{
+    client::{Event, Request},
+    error::ClientError,
+    options::ClientOptions,
+    state::{Outgoing, Session},
+    transport::Connection,
+};
+
+/*
+  Why a connection ended: the application asked to disconnect (or dropped
+  every `Client`), or the connection was lost.
+*/
+enum Exit {
+    Closed,
+    Lost(ClientError),
+}
+
+/*
+  Owns the connection and the session state. Runs until the client
+  disconnects, or until the connection is lost with reconnect disabled.
+*/
+pub(crate) struct EventLoop {
+    options: ClientOptions,
+    requests: mpsc::Receiver<Request>,
+    events: mpsc::Sender<Event>,
+    session: Session,
+    queued: Vec<Request>,
+}
+
+impl EventLoop {
+    pub fn new(options: ClientOptions, requests: mpsc::Receiver<Request>, events: mpsc::Sender<Event>) -> Self {
+        Self {
+            options,
+            requests,
+            events,
+            session: Session::new(),
+            queued: Vec::new(),
+        }
+    }
+
+    /*
+      Opens the transport, sends CONNECT and waits for CONNACK. Returns the
+      connection and the broker's session-present flag.
+    */
+    pub async fn open(options: &ClientOptions) -> Result<(Connection, bool), ClientError> {
+        let handshake = async {
+            let mut conn = Connection::open(options).await?;
+            conn.send(&Packet::Connect(options.connect_packet())).await?;
+
+            match conn.recv().await? {
+                Some(Packet::ConnAck(ack)) if ack.return_code == ConnectReturnCode::Accepted => {
+                    Ok((conn, ack.session_present))
+                }
+                Some(Packet::ConnAck(ack)) => Err(ClientError::ConnectionRefused(ack.return_code)),
+                Some(other) => Err(ClientError::Protocol(format!("expected CONNACK, got {:?}", other.packet_type()))),
+                None => Err(ClientError::ConnectionClosed),
+            }
+        };
+
+        time::timeout(options.connect_timeout, handshake)
+            .await
+            .map_err(|_| ClientError::Timeout)?
+    }
+
+    pub async fn run(mut self, mut conn: Connection, mut session_present: bool) {
+        loop {
+            let exit = match self.resume(&mut conn, session_present).await {
+                Ok(()) => self.run_connection(&mut conn).await,
+                Err(e) => Exit::Lost(e),
+            };
+
+            let error = match exit {
+                Exit::Closed => break,
+                Exit::Lost(error) => error,
+            };
+
+            let _ = self.events.send(Event::Disconnected(error)).await;
+
+            if !self.options.reconnect {
+                break;
+            }
+
+            match self.reconnect().await {
+                Some((new_conn, present)) => {
+                    conn = new_conn;
+                    session_present = present;
+                    let _ = self.events.send(Event::Connected { session_present }).await;
+                }
+                None => break,
+            }
+        }
+
+        self.shutdown();
+    }
+
+    /*
+      Retransmits in-flight packets and sends requests queued while offline.
+    */
+    async fn resume(&mut self, conn: &mut Connection, session_present: bool) -> Result<(), ClientError> {
+        for packet in self.session.resume(session_present)? {
+            conn.send(&packet).await?;
+        }
+
+        for request in std::mem::take(&mut self.queued) {
+            if let Some(out) = self.apply(request) {
+                write(conn, out).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_connection(&mut self, conn: &mut Connection) -> Exit {
+        let keep_alive = self.options.keep_alive;
+        let mut ping_at = Instant::now() + keep_alive;
+        let mut ping_outstanding = false;
+
+        loop {
+            let ping = async {
+                if keep_alive.is_zero() {
+                    std::future::pending::<()>().await;
+                } else {
+                    time::sleep_until(ping_at).await;
+                }
+            };
+
+            tokio::select! {
+                request = self.requests.recv() => {
+                    let request = match request {
+                        Some(Request::Disconnect(done)) => {
+                            let _ = conn.send(&Packet::Disconnect).await;
+                            conn.close().await;
+                            let _ = done.send(());
+                            return Exit::Closed;
+                        }
+                        Some(request) => request,
+                        None => {
+                            let _ = conn.send(&Packet::Disconnect).await;
+                            conn.close().await;
+                            return Exit::Closed;
+                        }
+                    };
+
+                    if let Some(out) = self.apply(request) {
+                        if let Err(e) = write(conn, out).await {
+                            return Exit::Lost(e);
+                        }
+                        if !ping_outstanding {
+                            ping_at = Instant::now() + keep_alive;
+                        }
+                    }
+                }
+
+                packet = conn.recv() => {
+                    let packet = match packet {
+                        Ok(Some(packet)) => packet,
+                        Ok(None) => return Exit::Lost(ClientError::ConnectionClosed),
+                        Err(e) => return Exit::Lost(e),
+                    };
+
+                    if matches!(packet, Packet::PingResp) {
+                        ping_outstanding = false;
+                        ping_at = Instant::now() + keep_alive;
+                    }
+
+                    let (reply, message) = match self.session.handle(packet) {
+                        Ok(result) => result,
+                        Err(e) => return Exit::Lost(e),
+                    };
+
+                    if let Some(reply) = reply
+                        && let Err(e) = conn.send(&reply).await
+                    {
+                        return Exit::Lost(e);
+                    }
+
+                    if let Some(message) = message {
+                        let _ = self.events.send(Event::Message(message)).await;
+                    }
+                }
+
+                _ = ping => {
+                    if ping_outstanding {
+                        return Exit::Lost(ClientError::Timeout);
+                    }
+                    if let Err(e) = conn.send(&Packet::PingReq).await {
+                        return Exit::Lost(e);
+                    }
+                    ping_outstanding = true;
+                    ping_at = Instant::now() + keep_alive;
+                }
+            }
+        }
+    }
+
+    /*
+      Registers a request with the session and returns the packet to send.
+      A request the session cannot take is answered with the error.
+    */
+    fn apply(&mut self, request: Request) -> Option<Outgoing> {
+        match request {
+            Request::Publish(packet, reply) => self.session.publish(packet, reply),
+            Request::Subscribe(filters, reply) => self.session.subscribe(filters, reply).map(Outgoing::from),
+            Request::Unsubscribe(topics, reply) => self.session.unsubscribe(topics, reply).map(Outgoing::from),
+            Request::Disconnect(done) => {
+                let _ = done.send(());
+                None
+            }
+        }
+    }
+
+    /*
+      Retries with exponential backoff until connected. Requests made in
+      the meantime are queued; returns `None` if the client disconnects.
+    */
+    async fn reconnect(&mut self) -> Option<(Connection, bool)> {
+        let mut delay = self.options.reconnect_min_delay;
+
+        loop {
+            let wake_at = Instant::now() + delay;
+            loop {
+                tokio::select! {
+                    _ = time::sleep_until(wake_at) => break,
+                    request = self.requests.recv() => match request {
+                        Some(Request::Disconnect(done)) => {
+                            let _ = done.send(());
+                            return None;
+                        }
+                        Some(request) => self.queued.push(request),
+                        None => return None,
+                    }
+                }
+            }
+
+            if let Ok(connected) = Self::open(&self.options).await {
+                return Some(connected);
+            }
+
+            delay = (delay * 2).min(self.options.reconnect_max_delay).max(Duration::from_millis(1));
+        }
+    }
+
+    fn shutdown(&mut self) {
+        self.session.fail_all();
+        self.requests.close();
+        while let Ok(request) = self.requests.try_recv() {
+            self.queued.push(request);
+        }
+        for request in self.queued.drain(..) {
+            match request {
+                Request::Publish(_, reply) | Request::Unsubscribe(_, reply) => {
+                    let _ = reply.send(Err(ClientError::Disconnected));
+                }
+                Request::Subscribe(_, reply) => {
+                    let _ = reply.send(Err(ClientError::Disconnected));
+                }
+                Request::Disconnect(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+}
+
+/*
+  Writes a packet and answers a QoS 0 publisher with the outcome. A failed
+  write loses the connection, so the message is not retried.
+*/
+async fn write(conn: &mut Connection, out: Outgoing) -> Result<(), ClientError> {
+    let result = conn.send(&out.packet).await;
+    if let Some(reply) = out.written {
+        let _ = reply.send(match result {
+            Ok(()) => Ok(()),
+            Err(_) => Err(ClientError::ConnectionClosed),
+        });
+    }
+    result
+}
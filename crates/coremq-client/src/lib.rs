@@ -0,0 +1,30 @@
+/*
+  Async MQTT 3.1.1 client for CoreMQ.
+
+  `Client::connect` opens the connection and spawns an event loop that
+  owns it. The returned `Client` is a cheap handle for publishing and
+  (un)subscribing; `EventStream` yields incoming messages and connection
+  state changes. The wire format is shared with the broker through
+  `coremq-codec`.
+
+    let (client, mut events) = Client::connect(ClientOptions::new("sensor-1", "127.0.0.1", 1883)).await?;
+    client.subscribe("sensors/#", 1).await?;
+    client.publish("sensors/1/temp", "23.5", 1, false).await?;
+    while let Some(event) = events.recv().await { ... }
+*/
+pub mod client;
+pub mod error;
+pub mod options;
+
+mod eventloop;
+mod state;
+mod tls;
+mod transport;
+
+pub use client::{Client, Event, EventStream};
+pub use coremq_codec::{
+    self as codec,
+    packets::{ConnectReturnCode, PublishPacket, SubscribeFilter, Will},
+};
+pub use error::ClientError;
+pub use options::{ClientOptions, TlsOptions, Transport};
@@ -0,0 +1,196 @@
+use std::{path::PathBuf, time::Duration};
+
+use bytes::Bytes;
+use coremq_codec::packets::{ConnectPacket, PROTOCOL_LEVEL_3_1_1, Will};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transport {
+    Tcp,
+
+    /*
+      MQTT over WebSocket, e.g. `path: "/mqtt"` for the broker's WS listener.
+    */
+    WebSocket { path: String },
+}
+
+/*
+  TLS settings for `mqtts` and `wss` connections. Without a CA file the
+  bundled Mozilla root certificates are trusted.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    pub(crate) ca_file: Option<PathBuf>,
+    pub(crate) cert_file: Option<PathBuf>,
+    pub(crate) key_file: Option<PathBuf>,
+    pub(crate) server_name: Option<String>,
+    pub(crate) insecure: bool,
+}
+
+impl TlsOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ca_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_file = Some(path.into());
+        self
+    }
+
+    /*
+      Client certificate and private key (PEM) for mutual TLS.
+    */
+    pub fn client_cert(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.cert_file = Some(cert.into());
+        self.key_file = Some(key.into());
+        self
+    }
+
+    /*
+      Name to verify the certificate against, when it differs from the host.
+    */
+    pub fn server_name(mut self, name: impl Into<String>) -> Self {
+        self.server_name = Some(name.into());
+        self
+    }
+
+    /*
+      Skips certificate verification entirely.
+    */
+    pub fn insecure(mut self, insecure: bool) -> Self {
+        self.insecure = insecure;
+        self
+    }
+}
+
+/*
+  Connection settings. Defaults: TCP, 60s keep-alive, clean session,
+  automatic reconnect with 1s..30s exponential backoff.
+*/
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    pub(crate) client_id: String,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) transport: Transport,
+    pub(crate) tls: Option<TlsOptions>,
+    pub(crate) keep_alive: Duration,
+    pub(crate) clean_session: bool,
+    pub(crate) username: Option<String>,
+    pub(crate) password: Option<Bytes>,
+    pub(crate) will: Option<Will>,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) reconnect: bool,
+    pub(crate) reconnect_min_delay: Duration,
+    pub(crate) reconnect_max_delay: Duration,
+    pub(crate) event_capacity: usize,
+}
+
+impl ClientOptions {
+    pub fn new(client_id: impl Into<String>, host: impl Into<String>, port: u16) -> Self {
+        Self {
+            client_id: client_id.into(),
+            host: host.into(),
+            port,
+            transport: Transport::Tcp,
+            tls: None,
+            keep_alive: Duration::from_secs(60),
+            clean_session: true,
+            username: None,
+            password: None,
+            will: None,
+            connect_timeout: Duration::from_secs(10),
+            reconnect: true,
+            reconnect_min_delay: Duration::from_secs(1),
+            reconnect_max_delay: Duration::from_secs(30),
+            event_capacity: 1024,
+        }
+    }
+
+    pub fn transport(mut self, transport: Transport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    pub fn websocket(self, path: impl Into<String>) -> Self {
+        self.transport(Transport::WebSocket { path: path.into() })
+    }
+
+    /*
+      Wraps the TCP or WebSocket transport in TLS.
+    */
+    pub fn tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /*
+      Zero disables keep-alive. Whole seconds only; sub-second parts are
+      dropped when sent to the broker.
+    */
+    pub fn keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = Duration::from_secs(keep_alive.as_secs().min(u16::MAX as u64));
+        self
+    }
+
+    /*
+      With `false` the broker is asked to keep the session across
+      reconnects. Either way the client retransmits unacknowledged
+      messages and restores its subscriptions after reconnecting.
+    */
+    pub fn clean_session(mut self, clean_session: bool) -> Self {
+        self.clean_session = clean_session;
+        self
+    }
+
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<Bytes>) -> Self {
+        self.username = Some(username.into());
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn will(mut self, will: Will) -> Self {
+        self.will = Some(will);
+        self
+    }
+
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    pub fn reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    pub fn reconnect_delay(mut self, min: Duration, max: Duration) -> Self {
+        self.reconnect_min_delay = min;
+        self.reconnect_max_delay = max.max(min);
+        self
+    }
+
+    /*
+      Number of events buffered for `EventStream`. When it is full the
+      client stops reading from the broker until events are consumed.
+    */
+    pub fn event_capacity(mut self, capacity: usize) -> Self {
+        self.event_capacity = capacity.max(1);
+        self
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub(crate) fn connect_packet(&self) -> ConnectPacket {
+        ConnectPacket {
+            protocol_level: PROTOCOL_LEVEL_3_1_1,
+            client_id: self.client_id.clone(),
+            keep_alive: self.keep_alive.as_secs() as u16,
+            clean_session: self.clean_session,
+            username: self.username.clone(),
+            password: self.password.clone(),
+            will: self.will.clone(),
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use std::collections::{HashMap, HashSet};
+
+use coremq_codec::packets::{
+    Packet, PublishPacket, SUBACK_FAILURE, SubscribeFilter, SubscribePacket, UnsubscribePacket,
+};
+use tokio::sync::oneshot;
+
+use crate::error::ClientError;
+
+pub(crate) type Reply<T> = oneshot::Sender<Result<T, ClientError>>;
+
+/*
+  A packet to write. A QoS 0 publish has no acknowledgement, so its
+  requester travels with the packet and is answered once it is written.
+*/
+pub(crate) struct Outgoing {
+    pub packet: Packet,
+    pub written: Option<Reply<()>>,
+}
+
+impl From<Packet> for Outgoing {
+    fn from(packet: Packet) -> Self {
+        Self { packet, written: None }
+    }
+}
+
+/*
+  The acknowledgement an outgoing QoS 1/2 publish is waiting for:
+  PUBACK, PUBREC or PUBCOMP.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Awaiting {
+    Ack,
+    Rec,
+    Comp,
+}
+
+enum Pending {
+    Publish {
+        packet: PublishPacket,
+        stage: Awaiting,
+        reply: Option<Reply<()>>,
+    },
+    Subscribe {
+        packet: SubscribePacket,
+        reply: Option<Reply<Vec<u8>>>,
+    },
+    Unsubscribe {
+        packet: UnsubscribePacket,
+        reply: Option<Reply<()>>,
+    },
+}
+
+/*
+  Client-side session state that survives reconnects: packets waiting
+  for acknowledgement, granted subscriptions and inbound QoS 2 ids that
+  have been delivered but not yet released.
+*/
+pub(crate) struct Session {
+    next_packet_id: u16,
+    next_seq: u64,
+    pending: HashMap<u16, (u64, Pending)>,
+    subscriptions: HashMap<String, u8>,
+    incoming_qos2: HashSet<u16>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            next_packet_id: 0,
+            next_seq: 0,
+            pending: HashMap::new(),
+            subscriptions: HashMap::new(),
+            incoming_qos2: HashSet::new(),
+        }
+    }
+
+    /*
+      The request methods return the packet to send. When no packet id is
+      free the requester gets the error instead and nothing is sent.
+    */
+    pub fn publish(&mut self, mut packet: PublishPacket, reply: Reply<()>) -> Option<Outgoing> {
+        let stage = match packet.qos {
+            0 => {
+                packet.packet_id = None;
+                return Some(Outgoing { packet: Packet::Publish(packet), written: Some(reply) });
+            }
+            1 => Awaiting::Ack,
+            _ => Awaiting::Rec,
+        };
+
+        let (packet_id, reply) = self.reserve(reply)?;
+        packet.packet_id = Some(packet_id);
+        let out = Packet::Publish(packet.clone());
+        self.insert(packet_id, Pending::Publish { packet, stage, reply: Some(reply) });
+        Some(out.into())
+    }
+
+    pub fn subscribe(&mut self, filters: Vec<SubscribeFilter>, reply: Reply<Vec<u8>>) -> Option<Packet> {
+        let (packet_id, reply) = self.reserve(reply)?;
+        let packet = SubscribePacket { packet_id, filters };
+        let out = Packet::Subscribe(packet.clone());
+        self.insert(packet_id, Pending::Subscribe { packet, reply: Some(reply) });
+        Some(out)
+    }
+
+    pub fn unsubscribe(&mut self, topics: Vec<String>, reply: Reply<()>) -> Option<Packet> {
+        let (packet_id, reply) = self.reserve(reply)?;
+        let packet = UnsubscribePacket { packet_id, topics };
+        let out = Packet::Unsubscribe(packet.clone());
+        self.insert(packet_id, Pending::Unsubscribe { packet, reply: Some(reply) });
+        Some(out)
+    }
+
+    /*
+      Applies a packet from the broker. Returns the packets to send back
+      and, for PUBLISH, the message to hand to the application.
+    */
+    pub fn handle(&mut self, packet: Packet) -> Result<(Option<Packet>, Option<PublishPacket>), ClientError> {
+        match packet {
+            Packet::Publish(p) => match (p.qos, p.packet_id) {
+                (0, _) => Ok((None, Some(p))),
+                (1, Some(id)) => Ok((Some(Packet::PubAck(id)), Some(p))),
+                (_, Some(id)) => {
+                    /*
+                      QoS 2 is delivered on first receipt; redeliveries are
+                      dropped until the broker releases the id with PUBREL.
+                    */
+                    let first = self.incoming_qos2.insert(id);
+                    Ok((Some(Packet::PubRec(id)), first.then_some(p)))
+                }
+                _ => Err(ClientError::Protocol("PUBLISH without packet id".into())),
+            },
+
+            Packet::PubRel(id) => {
+                self.incoming_qos2.remove(&id);
+                Ok((Some(Packet::PubComp(id)), None))
+            }
+
+            Packet::PubAck(id) => {
+                if let Some(Pending::Publish { reply: Some(reply), .. }) = self.take(id, |p| {
+                    matches!(p, Pending::Publish { stage: Awaiting::Ack, .. })
+                }) {
+                    let _ = reply.send(Ok(()));
+                }
+                Ok((None, None))
+            }
+
+            Packet::PubRec(id) => {
+                if let Some((_, Pending::Publish { stage, .. })) = self.pending.get_mut(&id)
+                    && *stage != Awaiting::Ack
+                {
+                    *stage = Awaiting::Comp;
+                    return Ok((Some(Packet::PubRel(id)), None));
+                }
+                Ok((None, None))
+            }
+
+            Packet::PubComp(id) => {
+                if let Some(Pending::Publish { reply: Some(reply), .. }) = self.take(id, |p| {
+                    matches!(p, Pending::Publish { stage: Awaiting::Comp, .. })
+                }) {
+                    let _ = reply.send(Ok(()));
+                }
+                Ok((None, None))
+            }
+
+            Packet::SubAck(ack) => {
+                if let Some(Pending::Subscribe { packet, reply }) =
+                    self.take(ack.packet_id, |p| matches!(p, Pending::Subscribe { .. }))
+                {
+                    for (filter, code) in packet.filters.iter().zip(&ack.return_codes) {
+                        if *code != SUBACK_FAILURE {
+                            self.subscriptions.insert(filter.topic.clone(), filter.qos);
+                        }
+                    }
+                    if let Some(reply) = reply {
+                        let _ = reply.send(Ok(ack.return_codes));
+                    }
+                }
+                Ok((None, None))
+            }
+
+            Packet::UnsubAck(id) => {
+                if let Some(Pending::Unsubscribe { packet, reply }) =
+                    self.take(id, |p| matches!(p, Pending::Unsubscribe { .. }))
+                {
+                    for topic in &packet.topics {
+                        self.subscriptions.remove(topic);
+                    }
+                    if let Some(reply) = reply {
+                        let _ = reply.send(Ok(()));
+                    }
+                }
+                Ok((None, None))
+            }
+
+            Packet::PingResp => Ok((None, None)),
+
+            other => Err(ClientError::Protocol(format!("unexpected {:?} from broker", other.packet_type()))),
+        }
+    }
+
+    /*
+      Packets to send after a reconnect, in their original order: a
+      SUBSCRIBE restoring every granted filter when the broker did not keep
+      the session, then every unacknowledged PUBLISH (flagged DUP), PUBREL,
+      SUBSCRIBE and UNSUBSCRIBE.
+    */
+    pub fn resume(&mut self, session_present: bool) -> Result<Vec<Packet>, ClientError> {
+        let mut entries: Vec<(u64, Packet)> = self
+            .pending
+            .values()
+            .map(|(seq, pending)| {
+                let packet = match pending {
+                    Pending::Publish { packet, stage: Awaiting::Comp, .. } => {
+                        Packet::PubRel(packet.packet_id.unwrap_or_default())
+                    }
+                    Pending::Publish { packet, .. } => Packet::Publish(PublishPacket { dup: true, ..packet.clone() }),
+                    Pending::Subscribe { packet, .. } => Packet::Subscribe(packet.clone()),
+                    Pending::Unsubscribe { packet, .. } => Packet::Unsubscribe(packet.clone()),
+                };
+                (*seq, packet)
+            })
+            .collect();
+        entries.sort_by_key(|(seq, _)| *seq);
+
+        let mut packets = Vec::with_capacity(entries.len() + 1);
+
+        if !session_present {
+            self.incoming_qos2.clear();
+
+            if !self.subscriptions.is_empty() {
+                let filters = self
+                    .subscriptions
+                    .iter()
+                    .map(|(topic, qos)| SubscribeFilter { topic: topic.clone(), qos: *qos })
+                    .collect();
+                let packet = SubscribePacket { packet_id: self.next_packet_id()?, filters };
+                packets.push(Packet::Subscribe(packet.clone()));
+                self.insert(packet.packet_id, Pending::Subscribe { packet, reply: None });
+            }
+        }
+
+        packets.extend(entries.into_iter().map(|(_, packet)| packet));
+        Ok(packets)
+    }
+
+    /*
+      Fails every request still waiting for the broker.
+    */
+    pub fn fail_all(&mut self) {
+        for (_, (_, pending)) in self.pending.drain() {
+            match pending {
+                Pending::Publish { reply: Some(reply), .. } | Pending::Unsubscribe { reply: Some(reply), .. } => {
+                    let _ = reply.send(Err(ClientError::Disconnected));
+                }
+                Pending::Subscribe { reply: Some(reply), .. } => {
+                    let _ = reply.send(Err(ClientError::Disconnected));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn insert(&mut self, packet_id: u16, pending: Pending) {
+        self.next_seq += 1;
+        self.pending.insert(packet_id, (self.next_seq, pending));
+    }
+
+    fn take(&mut self, packet_id: u16, expected: impl Fn(&Pending) -> bool) -> Option<Pending> {
+        if !self.pending.get(&packet_id).is_some_and(|(_, p)| expected(p)) {
+            return None;
+        }
+        self.pending.remove(&packet_id).map(|(_, p)| p)
+    }
+
+    /*
+      Takes a packet id for a request, or fails the request.
+    */
+    fn reserve<T>(&mut self, reply: Reply<T>) -> Option<(u16, Reply<T>)> {
+        match self.next_packet_id() {
+            Ok(packet_id) => Some((packet_id, reply)),
+            Err(e) => {
+                let _ = reply.send(Err(e));
+                None
+            }
+        }
+    }
+
+    /*
+      Next free non-zero packet id.
+    */
+    fn next_packet_id(&mut self) -> Result<u16, ClientError> {
+        for _ in 0..u16::MAX {
+            self.next_packet_id = self.next_packet_id.wrapping_add(1).max(1);
+            if !self.pending.contains_key(&self.next_packet_id) {
+                return Ok(self.next_packet_id);
+            }
+        }
+        Err(ClientError::Protocol("no free packet id".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use coremq_codec::packets::SubAckPacket;
+    use tokio::sync::oneshot::{self, Receiver};
+
+    use super::*;
+
+    fn message(topic: &str, qos: u8) -> PublishPacket {
+        PublishPacket {
+            packet_id: None,
+            topic: topic.to_string(),
+            payload: Bytes::from_static(b"23.5"),
+            qos,
+            retain: false,
+            dup: false,
+        }
+    }
+
+    fn publish(session: &mut Session, topic: &str, qos: u8) -> (Option<Packet>, Receiver<Result<(), ClientError>>) {
+        let (reply, rx) = oneshot::channel();
+        (session.publish(message(topic, qos), reply).map(|out| out.packet), rx)
+    }
+
+    fn packet_id(packet: &Option<Packet>) -> u16 {
+        match packet {
+            Some(Packet::Publish(p)) => p.packet_id.unwrap(),
+            Some(Packet::Subscribe(p)) => p.packet_id,
+            other => panic!("expected PUBLISH or SUBSCRIBE, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn qos_0_is_answered_by_the_writer_without_a_packet_id() {
+        let mut session = Session::new();
+        let (reply, mut rx) = oneshot::channel();
+        let out = session.publish(message("a", 0), reply).unwrap();
+        assert!(matches!(out.packet, Packet::Publish(PublishPacket { packet_id: None, .. })));
+        assert!(rx.try_recv().is_err());
+        assert!(session.pending.is_empty());
+
+        out.written.expect("requester travels with the packet").send(Ok(())).unwrap();
+        assert!(rx.try_recv().unwrap().is_ok());
+    }
+
+    #[test]
+    fn qos_1_resolves_on_puback() {
+        let mut session = Session::new();
+        let (packet, mut rx) = publish(&mut session, "a", 1);
+        let id = packet_id(&packet);
+
+        /* A PUBCOMP is not the acknowledgement a QoS 1 publish waits for. */
+        session.handle(Packet::PubComp(id)).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        assert_eq!(session.handle(Packet::PubAck(id)).unwrap(), (None, None));
+        assert!(rx.try_recv().unwrap().is_ok());
+        assert!(session.pending.is_empty());
+    }
+
+    #[test]
+    fn qos_2_resolves_on_pubcomp_after_pubrel() {
+        let mut session = Session::new();
+        let (packet, mut rx) = publish(&mut session, "a", 2);
+        let id = packet_id(&packet);
+
+        session.handle(Packet::PubAck(id)).unwrap();
+        session.handle(Packet::PubComp(id)).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        assert_eq!(session.handle(Packet::PubRec(id)).unwrap(), (Some(Packet::PubRel(id)), None));
+        assert!(rx.try_recv().is_err());
+        session.handle(Packet::PubComp(id)).unwrap();
+        assert!(rx.try_recv().unwrap().is_ok());
+        assert!(session.pending.is_empty());
+    }
+
+    #[test]
+    fn incoming_qos_2_is_delivered_once_until_released() {
+        let mut session = Session::new();
+        let incoming = PublishPacket { packet_id: Some(7), ..message("a", 2) };
+
+        let (reply, delivered) = session.handle(Packet::Publish(incoming.clone())).unwrap();
+        assert_eq!(reply, Some(Packet::PubRec(7)));
+        assert!(delivered.is_some());
+
+        /* The broker retransmits: acknowledged again, not delivered again. */
+        let (reply, delivered) = session.handle(Packet::Publish(PublishPacket { dup: true, ..incoming.clone() })).unwrap();
+        assert_eq!(reply, Some(Packet::PubRec(7)));
+        assert!(delivered.is_none());
+
+        assert_eq!(session.handle(Packet::PubRel(7)).unwrap(), (Some(Packet::PubComp(7)), None));
+        assert!(session.handle(Packet::Publish(incoming)).unwrap().1.is_some());
+    }
+
+    /*
+      A session with a granted subscription, a QoS 2 publish past PUBREC
+      and an unacknowledged QoS 1 publish. Returns the two publish ids.
+    */
+    fn in_flight() -> (Session, u16, u16) {
+        let mut session = Session::new();
+        let (reply, _) = oneshot::channel();
+        let id = packet_id(&session.subscribe(vec![SubscribeFilter { topic: "sensors/#".into(), qos: 1 }], reply));
+        session.handle(Packet::SubAck(SubAckPacket { packet_id: id, return_codes: vec![1] })).unwrap();
+
+        let first = packet_id(&publish(&mut session, "a", 2).0);
+        let second = packet_id(&publish(&mut session, "b", 1).0);
+        session.handle(Packet::PubRec(first)).unwrap();
+        (session, first, second)
+    }
+
+    fn assert_retransmitted(packets: &[Packet], first: u16, second: u16) {
+        assert_eq!(packets[0], Packet::PubRel(first));
+        match &packets[1] {
+            Packet::Publish(p) => assert_eq!((p.packet_id, p.dup), (Some(second), true)),
+            other => panic!("expected PUBLISH, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn resume_without_a_session_restores_subscriptions_first() {
+        let (mut session, first, second) = in_flight();
+
+        let packets = session.resume(false).unwrap();
+        assert_eq!(packets.len(), 3);
+        match &packets[0] {
+            Packet::Subscribe(p) => assert_eq!(p.filters, [SubscribeFilter { topic: "sensors/#".into(), qos: 1 }]),
+            other => panic!("expected SUBSCRIBE, got {:?}", other),
+        }
+        assert_retransmitted(&packets[1..], first, second);
+    }
+
+    #[test]
+    fn resume_with_a_session_only_retransmits() {
+        let (mut session, first, second) = in_flight();
+
+        let packets = session.resume(true).unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_retransmitted(&packets, first, second);
+    }
+
+    #[test]
+    fn requests_fail_when_no_packet_id_is_free() {
+        let mut session = Session::new();
+        let mut replies = Vec::new();
+        for _ in 0..u16::MAX {
+            let (packet, rx) = publish(&mut session, "a", 1);
+            assert!(packet.is_some());
+            replies.push(rx);
+        }
+
+        let (packet, mut rx) = publish(&mut session, "a", 1);
+        assert!(packet.is_none());
+        assert!(matches!(rx.try_recv(), Ok(Err(ClientError::Protocol(_)))));
+
+        session.handle(Packet::PubAck(300)).unwrap();
+        assert_eq!(packet_id(&publish(&mut session, "a", 1).0), 300);
+    }
+}
@@ -0,0 +1,108 @@
+use bytes::BytesMut;
+use coremq_codec::{CodecError, MqttCodec, packets::Packet};
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{Message, client::IntoClientRequest, http::HeaderValue},
+};
+use tokio_util::codec::Framed;
+
+use crate::{
+    error::ClientError,
+    options::{ClientOptions, Transport},
+    tls,
+};
+
+/*
+  A plain TCP or TLS byte stream.
+*/
+pub(crate) trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+type Stream = Box<dyn Io>;
+
+/*
+  One network connection to the broker, exchanging whole packets.
+*/
+pub(crate) enum Connection {
+    Mqtt(Framed<Stream, MqttCodec>),
+    WebSocket(Box<WebSocketStream<Stream>>, BytesMut),
+}
+
+impl Connection {
+    pub async fn open(options: &ClientOptions) -> Result<Self, ClientError> {
+        let tcp = TcpStream::connect((options.host.as_str(), options.port)).await?;
+        tcp.set_nodelay(true)?;
+
+        let stream: Stream = match &options.tls {
+            Some(tls_options) => Box::new(tls::connect(tcp, &options.host, tls_options).await?),
+            None => Box::new(tcp),
+        };
+
+        match &options.transport {
+            Transport::Tcp => Ok(Connection::Mqtt(Framed::new(stream, MqttCodec::new()))),
+
+            Transport::WebSocket { path } => {
+                let scheme = if options.tls.is_some() { "wss" } else { "ws" };
+                let path = if path.starts_with('/') { path.clone() } else { format!("/{}", path) };
+                let url = format!("{}://{}:{}{}", scheme, options.host, options.port, path);
+                let mut request = url.into_client_request()?;
+                request
+                    .headers_mut()
+                    .insert("Sec-WebSocket-Protocol", HeaderValue::from_static("mqtt"));
+
+                let (ws, _) = tokio_tungstenite::client_async(request, stream).await?;
+                Ok(Connection::WebSocket(Box::new(ws), BytesMut::new()))
+            }
+        }
+    }
+
+    pub async fn send(&mut self, packet: &Packet) -> Result<(), ClientError> {
+        match self {
+            Connection::Mqtt(framed) => framed.send(packet).await?,
+            Connection::WebSocket(ws, _) => {
+                let bytes = packet.to_bytes().map_err(CodecError::from)?;
+                ws.send(Message::Binary(bytes.to_vec())).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /*
+      Returns the next packet, or `None` once the broker has closed the
+      connection. Cancel safe: partially received data stays buffered.
+    */
+    pub async fn recv(&mut self) -> Result<Option<Packet>, ClientError> {
+        match self {
+            Connection::Mqtt(framed) => framed.next().await.transpose().map_err(ClientError::from),
+            Connection::WebSocket(ws, buffer) => loop {
+                if let Some(packet) = Packet::decode(buffer).map_err(CodecError::from)? {
+                    return Ok(Some(packet));
+                }
+
+                match ws.next().await {
+                    Some(Ok(Message::Binary(data))) => buffer.extend_from_slice(&data),
+                    Some(Ok(Message::Close(_))) | None => return Ok(None),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.into()),
+                }
+            },
+        }
+    }
+
+    pub async fn close(&mut self) {
+        match self {
+            Connection::Mqtt(framed) => {
+                let _ = SinkExt::<&Packet>::close(framed).await;
+            }
+            Connection::WebSocket(ws, _) => {
+                let _ = SinkExt::close(ws.as_mut()).await;
+            }
+        }
+    }
+}
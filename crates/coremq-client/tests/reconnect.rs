@@ -0,0 +1,186 @@
+/*
+  Drives the client against a scripted broker on a local socket, to check
+  what goes over the wire when the connection drops mid-flight.
+*/
+use std::time::Duration;
+
+use coremq_client::{
+    Client, ClientError, ClientOptions, Event, EventStream,
+    codec::{
+        MqttCodec,
+        packets::{ConnAckPacket, ConnectReturnCode, Packet, PublishPacket, SubAckPacket},
+    },
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+use tokio_util::codec::Framed;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+type Conn = Framed<TcpStream, MqttCodec>;
+
+async fn listener() -> (TcpListener, ClientOptions) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let options = ClientOptions::new("sensor-1", "127.0.0.1", port)
+        .reconnect_delay(Duration::from_millis(10), Duration::from_millis(50));
+    (listener, options)
+}
+
+/*
+  Accepts the next connection and answers its CONNECT.
+*/
+async fn accept(listener: &TcpListener, session_present: bool) -> Conn {
+    let (stream, _) = timeout(TIMEOUT, listener.accept()).await.expect("client connects").unwrap();
+    let mut conn = Framed::new(stream, MqttCodec::new());
+    assert!(matches!(recv(&mut conn).await, Packet::Connect(_)));
+    send(&mut conn, Packet::ConnAck(ConnAckPacket { session_present, return_code: ConnectReturnCode::Accepted })).await;
+    conn
+}
+
+async fn connect(listener: &TcpListener, options: ClientOptions) -> (Client, EventStream, Conn) {
+    let connecting = tokio::spawn(Client::connect(options));
+    let conn = accept(listener, false).await;
+    let (client, mut events) = connecting.await.unwrap().unwrap();
+    assert!(matches!(next_event(&mut events).await, Event::Connected { session_present: false }));
+    (client, events, conn)
+}
+
+async fn send(conn: &mut Conn, packet: Packet) {
+    conn.send(&packet).await.unwrap();
+}
+
+async fn recv(conn: &mut Conn) -> Packet {
+    timeout(TIMEOUT, conn.next()).await.expect("packet from client").expect("open connection").unwrap()
+}
+
+async fn recv_publish(conn: &mut Conn) -> PublishPacket {
+    match recv(conn).await {
+        Packet::Publish(p) => p,
+        other => panic!("expected PUBLISH, got {:?}", other),
+    }
+}
+
+async fn next_event(events: &mut EventStream) -> Event {
+    timeout(TIMEOUT, events.recv()).await.expect("client event").expect("event stream open")
+}
+
+/*
+  Subscribes through the client and grants the filter.
+*/
+async fn subscribe(client: &Client, conn: &mut Conn, filter: &str) {
+    let subscribing = tokio::spawn({
+        let client = client.clone();
+        let filter = filter.to_string();
+        async move { client.subscribe(filter, 1).await }
+    });
+    let Packet::Subscribe(packet) = recv(conn).await else { panic!("expected SUBSCRIBE") };
+    send(conn, Packet::SubAck(SubAckPacket { packet_id: packet.packet_id, return_codes: vec![1] })).await;
+    assert_eq!(subscribing.await.unwrap().unwrap(), 1);
+}
+
+fn publish(client: &Client, topic: &str, qos: u8) -> tokio::task::JoinHandle<Result<(), ClientError>> {
+    let client = client.clone();
+    let topic = topic.to_string();
+    tokio::spawn(async move { client.publish(topic, "23.5", qos, false).await })
+}
+
+#[tokio::test]
+async fn reconnects_restores_subscriptions_and_retransmits_qos_1() {
+    let (listener, options) = listener().await;
+    let (client, mut events, mut conn) = connect(&listener, options).await;
+    subscribe(&client, &mut conn, "sensors/#").await;
+
+    let in_flight = publish(&client, "sensors/1/temp", 1);
+    let sent = recv_publish(&mut conn).await;
+    assert!(!sent.dup);
+    drop(conn);
+    assert!(matches!(next_event(&mut events).await, Event::Disconnected(_)));
+
+    /* Made while offline: queued and sent once reconnected. */
+    let queued = publish(&client, "sensors/2/temp", 1);
+
+    let mut conn = accept(&listener, false).await;
+    let Packet::Subscribe(restore) = recv(&mut conn).await else { panic!("expected SUBSCRIBE") };
+    assert_eq!(restore.filters[0].topic, "sensors/#");
+    let retry = recv_publish(&mut conn).await;
+    assert_eq!((retry.packet_id, retry.dup, retry.topic.as_str()), (sent.packet_id, true, "sensors/1/temp"));
+    let fresh = recv_publish(&mut conn).await;
+    assert_eq!((fresh.dup, fresh.topic.as_str()), (false, "sensors/2/temp"));
+    assert_ne!(fresh.packet_id, sent.packet_id);
+
+    send(&mut conn, Packet::SubAck(SubAckPacket { packet_id: restore.packet_id, return_codes: vec![1] })).await;
+    send(&mut conn, Packet::PubAck(retry.packet_id.unwrap())).await;
+    send(&mut conn, Packet::PubAck(fresh.packet_id.unwrap())).await;
+    timeout(TIMEOUT, in_flight).await.unwrap().unwrap().unwrap();
+    timeout(TIMEOUT, queued).await.unwrap().unwrap().unwrap();
+    assert!(matches!(next_event(&mut events).await, Event::Connected { session_present: false }));
+
+    client.disconnect().await.unwrap();
+    assert_eq!(recv(&mut conn).await, Packet::Disconnect);
+}
+
+#[tokio::test]
+async fn resumed_session_continues_qos_2_with_pubrel() {
+    let (listener, options) = listener().await;
+    let (client, mut events, mut conn) = connect(&listener, options.clean_session(false)).await;
+    subscribe(&client, &mut conn, "sensors/#").await;
+
+    let in_flight = publish(&client, "sensors/1/temp", 2);
+    let id = recv_publish(&mut conn).await.packet_id.unwrap();
+    send(&mut conn, Packet::PubRec(id)).await;
+    assert_eq!(recv(&mut conn).await, Packet::PubRel(id));
+    drop(conn);
+    assert!(matches!(next_event(&mut events).await, Event::Disconnected(_)));
+
+    /* The broker kept the session: no SUBSCRIBE, and the PUBLISH is not sent again. */
+    let mut conn = accept(&listener, true).await;
+    assert_eq!(recv(&mut conn).await, Packet::PubRel(id));
+    assert!(!in_flight.is_finished());
+    send(&mut conn, Packet::PubComp(id)).await;
+    timeout(TIMEOUT, in_flight).await.unwrap().unwrap().unwrap();
+    assert!(matches!(next_event(&mut events).await, Event::Connected { session_present: true }));
+}
+
+#[tokio::test]
+async fn incoming_qos_2_is_delivered_once() {
+    let (listener, options) = listener().await;
+    let (_client, mut events, mut conn) = connect(&listener, options).await;
+
+    let message = PublishPacket {
+        packet_id: Some(9),
+        topic: "sensors/1/temp".to_string(),
+        payload: "23.5".into(),
+        qos: 2,
+        retain: false,
+        dup: false,
+    };
+    send(&mut conn, Packet::Publish(message.clone())).await;
+    assert_eq!(recv(&mut conn).await, Packet::PubRec(9));
+    send(&mut conn, Packet::Publish(PublishPacket { dup: true, ..message.clone() })).await;
+    assert_eq!(recv(&mut conn).await, Packet::PubRec(9));
+    send(&mut conn, Packet::PubRel(9)).await;
+    assert_eq!(recv(&mut conn).await, Packet::PubComp(9));
+
+    let Event::Message(delivered) = next_event(&mut events).await else { panic!("expected a message") };
+    assert_eq!(delivered.topic, "sensors/1/temp");
+    assert!(timeout(Duration::from_millis(100), events.recv()).await.is_err());
+}
+
+#[tokio::test]
+async fn without_reconnect_pending_requests_fail() {
+    let (listener, options) = listener().await;
+    let (client, mut events, mut conn) = connect(&listener, options.reconnect(false)).await;
+
+    let in_flight = publish(&client, "sensors/1/temp", 1);
+    recv_publish(&mut conn).await;
+    drop(conn);
+
+    assert!(matches!(next_event(&mut events).await, Event::Disconnected(ClientError::ConnectionClosed)));
+    assert!(matches!(timeout(TIMEOUT, in_flight).await.unwrap().unwrap(), Err(ClientError::Disconnected)));
+    assert!(timeout(TIMEOUT, events.recv()).await.unwrap().is_none());
+    assert!(matches!(client.publish("sensors/1/temp", "23.5", 0, false).await, Err(ClientError::Disconnected)));
+}
@@ -0,0 +1,47 @@
+/*
+  Feeds the same bytes to `MqttCodec` in arbitrary chunks and in one piece.
+  Chunking must not change which packets come out or where decoding fails.
+  The first input byte picks the chunk size.
+*/
+#![no_main]
+
+use bytes::BytesMut;
+use coremq_codec::{MqttCodec, Packet};
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::Decoder;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&chunk, data)) = data.split_first() else {
+        return;
+    };
+    let chunk = (chunk as usize % 64) + 1;
+
+    let whole = decode_all(&[data]);
+    let chunks: Vec<&[u8]> = data.chunks(chunk).collect();
+    let pieces = decode_all(&chunks);
+
+    assert_eq!(whole, pieces);
+});
+
+/*
+  Packets decoded before the first error (or the end of input), and
+  whether an error stopped decoding.
+*/
+fn decode_all(chunks: &[&[u8]]) -> (Vec<Packet>, bool) {
+    let mut codec = MqttCodec::new();
+    let mut buf = BytesMut::new();
+    let mut packets = Vec::new();
+
+    for chunk in chunks {
+        buf.extend_from_slice(chunk);
+        loop {
+            match codec.decode(&mut buf) {
+                Ok(Some(packet)) => packets.push(packet),
+                Ok(None) => break,
+                Err(_) => return (packets, true),
+            }
+        }
+    }
+
+    (packets, false)
+}
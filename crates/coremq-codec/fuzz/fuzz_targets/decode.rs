@@ -0,0 +1,26 @@
+/*
+  Decodes arbitrary bytes as a stream of packets. Decoding must never
+  panic, and every packet it accepts must encode and decode back to the
+  same value.
+*/
+#![no_main]
+
+use bytes::BytesMut;
+use coremq_codec::Packet;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut buf = BytesMut::from(data);
+
+    while let Ok(Some(packet)) = Packet::decode(&mut buf) {
+        let encoded = packet.to_bytes().expect("a decoded packet must encode");
+
+        let mut reencoded = BytesMut::from(&encoded[..]);
+        let decoded = Packet::decode(&mut reencoded)
+            .expect("an encoded packet must decode")
+            .expect("an encoded packet is complete");
+
+        assert_eq!(decoded, packet);
+        assert!(reencoded.is_empty());
+    }
+});
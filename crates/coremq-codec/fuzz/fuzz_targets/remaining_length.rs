@@ -0,0 +1,27 @@
+/*
+  Fixed header parsing: the variable length field never reads more than
+  four bytes, never exceeds the protocol maximum and survives a write/parse
+  roundtrip.
+*/
+#![no_main]
+
+use bytes::BytesMut;
+use coremq_codec::{Header, MAX_REMAINING_LENGTH};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(Some((length, used))) = Header::read_remaining_length(data) {
+        assert!((1..=4).contains(&used));
+        assert!(used <= data.len());
+        assert!(length <= MAX_REMAINING_LENGTH);
+
+        let mut written = BytesMut::new();
+        Header::write(0x30, length, &mut written).expect("a parsed length must be writable");
+        assert_eq!(Header::read_remaining_length(&written[1..]), Ok(Some((length, written.len() - 1))));
+    }
+
+    if let Ok(Some((header, header_len))) = Header::parse(data) {
+        assert!((2..=5).contains(&header_len));
+        assert!(header.remaining_length <= MAX_REMAINING_LENGTH);
+    }
+});
@@ -0,0 +1,61 @@
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{MAX_REMAINING_LENGTH, error::CodecError, packets::Packet};
+
+/*
+  `tokio_util` adapter around `Packet::decode`/`Packet::encode`, for use
+  with `Framed` streams.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct MqttCodec {
+    max_packet_size: usize,
+}
+
+impl MqttCodec {
+    pub fn new() -> Self {
+        Self { max_packet_size: MAX_REMAINING_LENGTH }
+    }
+
+    /*
+      Rejects incoming packets whose remaining length exceeds `max`.
+    */
+    pub fn with_max_packet_size(max: usize) -> Self {
+        Self { max_packet_size: max.min(MAX_REMAINING_LENGTH) }
+    }
+
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+}
+
+impl Default for MqttCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for MqttCodec {
+    type Item = Packet;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, CodecError> {
+        Ok(Packet::decode_with_limit(src, self.max_packet_size)?)
+    }
+}
+
+impl Encoder<Packet> for MqttCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Packet, dst: &mut BytesMut) -> Result<(), CodecError> {
+        Ok(item.encode(dst)?)
+    }
+}
+
+impl Encoder<&Packet> for MqttCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: &Packet, dst: &mut BytesMut) -> Result<(), CodecError> {
+        Ok(item.encode(dst)?)
+    }
+}
@@ -0,0 +1,257 @@
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::{
+    MAX_REMAINING_LENGTH,
+    error::DecodeError,
+    header::Header,
+    packet_type::MqttPacketType,
+    packets::*,
+};
+
+impl Packet {
+    /*
+      Decodes one packet from the front of `buf`. Returns `Ok(None)` and
+      leaves `buf` untouched until a complete packet is buffered.
+    */
+    pub fn decode(buf: &mut BytesMut) -> Result<Option<Packet>, DecodeError> {
+        Self::decode_with_limit(buf, MAX_REMAINING_LENGTH)
+    }
+
+    /*
+      Same as `decode`, but rejects packets whose remaining length exceeds
+      `max_remaining_length` as soon as the fixed header is readable.
+    */
+    pub fn decode_with_limit(buf: &mut BytesMut, max_remaining_length: usize) -> Result<Option<Packet>, DecodeError> {
+        let Some((header, header_len)) = Header::parse(buf)? else {
+            return Ok(None);
+        };
+
+        if header.remaining_length > max_remaining_length {
+            return Err(DecodeError::PacketTooLarge(header.remaining_length));
+        }
+
+        if buf.len() < header_len + header.remaining_length {
+            return Ok(None);
+        }
+
+        buf.advance(header_len);
+        let body = buf.split_to(header.remaining_length).freeze();
+
+        Self::decode_body(header, body).map(Some)
+    }
+
+    /*
+      Decodes a packet body whose fixed header has already been parsed.
+    */
+    pub fn decode_body(header: Header, mut body: Bytes) -> Result<Packet, DecodeError> {
+        let packet = match header.packet_type {
+            MqttPacketType::Connect => Packet::Connect(decode_connect(&mut body)?),
+            MqttPacketType::ConnAck => Packet::ConnAck(decode_connack(&mut body)?),
+            MqttPacketType::Publish => Packet::Publish(decode_publish(header.flags, &mut body)?),
+            MqttPacketType::PubAck => Packet::PubAck(read_packet_id(&mut body)?),
+            MqttPacketType::PubRec => Packet::PubRec(read_packet_id(&mut body)?),
+            MqttPacketType::PubRel => Packet::PubRel(read_packet_id(&mut body)?),
+            MqttPacketType::PubComp => Packet::PubComp(read_packet_id(&mut body)?),
+            MqttPacketType::Subscribe => Packet::Subscribe(decode_subscribe(&mut body)?),
+            MqttPacketType::SubAck => Packet::SubAck(decode_suback(&mut body)?),
+            MqttPacketType::Unsubscribe => Packet::Unsubscribe(decode_unsubscribe(&mut body)?),
+            MqttPacketType::UnsubAck => Packet::UnsubAck(read_packet_id(&mut body)?),
+            MqttPacketType::PingReq => Packet::PingReq,
+            MqttPacketType::PingResp => Packet::PingResp,
+            MqttPacketType::Disconnect => Packet::Disconnect,
+        };
+
+        if body.has_remaining() {
+            return Err(DecodeError::TrailingBytes(body.remaining()));
+        }
+
+        Ok(packet)
+    }
+}
+
+fn decode_connect(buf: &mut Bytes) -> Result<ConnectPacket, DecodeError> {
+    let protocol_name = read_string(buf)?;
+    let protocol_level = read_u8(buf)?;
+
+    match (protocol_name.as_str(), protocol_level) {
+        ("MQTT", PROTOCOL_LEVEL_3_1_1) | ("MQIsdp", PROTOCOL_LEVEL_3_1) => {}
+        _ => {
+            return Err(DecodeError::UnsupportedProtocol { name: protocol_name, level: protocol_level });
+        }
+    }
+
+    let connect_flags = read_u8(buf)?;
+    let keep_alive = read_u16(buf)?;
+
+    let clean_session = (connect_flags & 0b0000_0010) != 0;
+    let will_flag     = (connect_flags & 0b0000_0100) != 0;
+    let will_qos      = (connect_flags & 0b0001_1000) >> 3;
+    let will_retain   = (connect_flags & 0b0010_0000) != 0;
+    let password_flag = (connect_flags & 0b0100_0000) != 0;
+    let username_flag = (connect_flags & 0b1000_0000) != 0;
+
+    /*
+      Reserved bit must be zero, will QoS/retain are only meaningful with
+      the will flag, and a password requires a username.
+    */
+    if connect_flags & 0b0000_0001 != 0
+        || will_qos > 2
+        || (!will_flag && (will_qos != 0 || will_retain))
+        || (password_flag && !username_flag)
+    {
+        return Err(DecodeError::InvalidConnectFlags(connect_flags));
+    }
+
+    let client_id = read_string(buf)?;
+
+    let will = if will_flag {
+        let topic = read_string(buf)?;
+        let message = read_binary(buf)?;
+        Some(Will { topic, message, qos: will_qos, retain: will_retain })
+    } else {
+        None
+    };
+
+    let username = if username_flag { Some(read_string(buf)?) } else { None };
+    let password = if password_flag { Some(read_binary(buf)?) } else { None };
+
+    Ok(ConnectPacket {
+        protocol_level,
+        client_id,
+        keep_alive,
+        clean_session,
+        username,
+        password,
+        will,
+    })
+}
+
+fn decode_connack(buf: &mut Bytes) -> Result<ConnAckPacket, DecodeError> {
+    let ack_flags = read_u8(buf)?;
+    if ack_flags & 0b1111_1110 != 0 {
+        return Err(DecodeError::InvalidConnectFlags(ack_flags));
+    }
+
+    let code = read_u8(buf)?;
+    let return_code = ConnectReturnCode::from_u8(code).ok_or(DecodeError::InvalidConnectReturnCode(code))?;
+
+    Ok(ConnAckPacket {
+        session_present: ack_flags & 1 != 0,
+        return_code,
+    })
+}
+
+fn decode_publish(flags: u8, buf: &mut Bytes) -> Result<PublishPacket, DecodeError> {
+    let dup = (flags & 0b1000) != 0;
+    let qos = (flags & 0b0110) >> 1;
+    let retain = (flags & 0b0001) != 0;
+
+    if qos > 2 {
+        return Err(DecodeError::InvalidQoS(qos));
+    }
+
+    if qos == 0 && dup {
+        return Err(DecodeError::InvalidFlags(MqttPacketType::Publish, flags));
+    }
+
+    let topic = read_string(buf)?;
+    let packet_id = if qos > 0 { Some(read_packet_id(buf)?) } else { None };
+    let payload = buf.split_off(0);
+
+    Ok(PublishPacket {
+        packet_id,
+        topic,
+        payload,
+        qos,
+        retain,
+        dup,
+    })
+}
+
+fn decode_subscribe(buf: &mut Bytes) -> Result<SubscribePacket, DecodeError> {
+    let packet_id = read_packet_id(buf)?;
+    let mut filters = Vec::new();
+
+    while buf.has_remaining() {
+        let topic = read_string(buf)?;
+        let qos = read_u8(buf)?;
+        if qos > 2 {
+            return Err(DecodeError::InvalidQoS(qos));
+        }
+        filters.push(SubscribeFilter { topic, qos });
+    }
+
+    if filters.is_empty() {
+        return Err(DecodeError::EmptyPayload);
+    }
+
+    Ok(SubscribePacket { packet_id, filters })
+}
+
+fn decode_suback(buf: &mut Bytes) -> Result<SubAckPacket, DecodeError> {
+    let packet_id = read_packet_id(buf)?;
+    let mut return_codes = Vec::with_capacity(buf.remaining());
+
+    while buf.has_remaining() {
+        let code = buf.get_u8();
+        if code > 2 && code != SUBACK_FAILURE {
+            return Err(DecodeError::InvalidQoS(code));
+        }
+        return_codes.push(code);
+    }
+
+    if return_codes.is_empty() {
+        return Err(DecodeError::EmptyPayload);
+    }
+
+    Ok(SubAckPacket { packet_id, return_codes })
+}
+
+fn decode_unsubscribe(buf: &mut Bytes) -> Result<UnsubscribePacket, DecodeError> {
+    let packet_id = read_packet_id(buf)?;
+    let mut topics = Vec::new();
+
+    while buf.has_remaining() {
+        topics.push(read_string(buf)?);
+    }
+
+    if topics.is_empty() {
+        return Err(DecodeError::EmptyPayload);
+    }
+
+    Ok(UnsubscribePacket { packet_id, topics })
+}
+
+fn read_u8(buf: &mut Bytes) -> Result<u8, DecodeError> {
+    if buf.remaining() < 1 {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    Ok(buf.get_u8())
+}
+
+fn read_u16(buf: &mut Bytes) -> Result<u16, DecodeError> {
+    if buf.remaining() < 2 {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    Ok(buf.get_u16())
+}
+
+fn read_packet_id(buf: &mut Bytes) -> Result<u16, DecodeError> {
+    match read_u16(buf)? {
+        0 => Err(DecodeError::MissingPacketId),
+        id => Ok(id),
+    }
+}
+
+fn read_binary(buf: &mut Bytes) -> Result<Bytes, DecodeError> {
+    let len = read_u16(buf)? as usize;
+    if buf.remaining() < len {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    Ok(buf.split_to(len))
+}
+
+fn read_string(buf: &mut Bytes) -> Result<String, DecodeError> {
+    let bytes = read_binary(buf)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8)
+}
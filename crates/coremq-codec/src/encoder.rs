@@ -0,0 +1,261 @@
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{error::EncodeError, header::Header, packet_type::MqttPacketType, packets::*};
+
+impl Packet {
+    pub fn packet_type(&self) -> MqttPacketType {
+        match self {
+            Packet::Connect(_) => MqttPacketType::Connect,
+            Packet::ConnAck(_) => MqttPacketType::ConnAck,
+            Packet::Publish(_) => MqttPacketType::Publish,
+            Packet::PubAck(_) => MqttPacketType::PubAck,
+            Packet::PubRec(_) => MqttPacketType::PubRec,
+            Packet::PubRel(_) => MqttPacketType::PubRel,
+            Packet::PubComp(_) => MqttPacketType::PubComp,
+            Packet::Subscribe(_) => MqttPacketType::Subscribe,
+            Packet::SubAck(_) => MqttPacketType::SubAck,
+            Packet::Unsubscribe(_) => MqttPacketType::Unsubscribe,
+            Packet::UnsubAck(_) => MqttPacketType::UnsubAck,
+            Packet::PingReq => MqttPacketType::PingReq,
+            Packet::PingResp => MqttPacketType::PingResp,
+            Packet::Disconnect => MqttPacketType::Disconnect,
+        }
+    }
+
+    /*
+      Appends the wire form of the packet to `out`. Nothing is written if
+      the packet cannot be encoded.
+    */
+    pub fn encode(&self, out: &mut BytesMut) -> Result<(), EncodeError> {
+        let remaining_length = self.remaining_length()?;
+        Header::write(self.first_byte(), remaining_length, out)?;
+
+        match self {
+            Packet::Connect(p) => write_connect(p, out),
+            Packet::ConnAck(p) => {
+                out.put_u8(p.session_present as u8);
+                out.put_u8(p.return_code as u8);
+            }
+            Packet::Publish(p) => write_publish_body(p, out),
+            Packet::PubAck(id)
+            | Packet::PubRec(id)
+            | Packet::PubRel(id)
+            | Packet::PubComp(id)
+            | Packet::UnsubAck(id) => out.put_u16(*id),
+            Packet::Subscribe(p) => {
+                out.put_u16(p.packet_id);
+                for filter in &p.filters {
+                    write_string(&filter.topic, out);
+                    out.put_u8(filter.qos);
+                }
+            }
+            Packet::SubAck(p) => {
+                out.put_u16(p.packet_id);
+                out.put_slice(&p.return_codes);
+            }
+            Packet::Unsubscribe(p) => {
+                out.put_u16(p.packet_id);
+                for topic in &p.topics {
+                    write_string(topic, out);
+                }
+            }
+            Packet::PingReq | Packet::PingResp | Packet::Disconnect => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Bytes, EncodeError> {
+        let mut out = BytesMut::new();
+        self.encode(&mut out)?;
+        Ok(out.freeze())
+    }
+
+    fn first_byte(&self) -> u8 {
+        let flags = match self {
+            Packet::Publish(p) => {
+                ((p.dup as u8) << 3) | ((p.qos & 0x03) << 1) | p.retain as u8
+            }
+            other => other.packet_type().required_flags().unwrap_or(0),
+        };
+
+        ((self.packet_type() as u8) << 4) | flags
+    }
+
+    /*
+      Validates the packet and computes the length of everything after the
+      fixed header.
+    */
+    fn remaining_length(&self) -> Result<usize, EncodeError> {
+        let len = match self {
+            Packet::Connect(p) => {
+                let mut len = 10 + string_len(&p.client_id)?;
+                if let Some(will) = &p.will {
+                    check_qos(will.qos)?;
+                    len += string_len(&will.topic)? + binary_len(&will.message)?;
+                }
+                if let Some(username) = &p.username {
+                    len += string_len(username)?;
+                }
+                if let Some(password) = &p.password {
+                    len += binary_len(password)?;
+                }
+                if p.protocol_level == PROTOCOL_LEVEL_3_1 {
+                    /* "MQIsdp" is two bytes longer than "MQTT". */
+                    len += 2;
+                }
+                len
+            }
+            Packet::ConnAck(_) => 2,
+            Packet::Publish(p) => publish_remaining_length(p)?,
+            Packet::PubAck(_)
+            | Packet::PubRec(_)
+            | Packet::PubRel(_)
+            | Packet::PubComp(_)
+            | Packet::UnsubAck(_) => 2,
+            Packet::Subscribe(p) => {
+                if p.filters.is_empty() {
+                    return Err(EncodeError::EmptyPayload);
+                }
+                let mut len = 2;
+                for filter in &p.filters {
+                    check_qos(filter.qos)?;
+                    len += string_len(&filter.topic)? + 1;
+                }
+                len
+            }
+            Packet::SubAck(p) => {
+                if p.return_codes.is_empty() {
+                    return Err(EncodeError::EmptyPayload);
+                }
+                if let Some(&code) = p.return_codes.iter().find(|&&code| code > 2 && code != SUBACK_FAILURE) {
+                    return Err(EncodeError::InvalidReturnCode(code));
+                }
+                2 + p.return_codes.len()
+            }
+            Packet::Unsubscribe(p) => {
+                if p.topics.is_empty() {
+                    return Err(EncodeError::EmptyPayload);
+                }
+                let mut len = 2;
+                for topic in &p.topics {
+                    len += string_len(topic)?;
+                }
+                len
+            }
+            Packet::PingReq | Packet::PingResp | Packet::Disconnect => 0,
+        };
+
+        Ok(len)
+    }
+}
+
+/*
+  Encodes a PUBLISH packet into a standalone frame, ready to be shared
+  between subscribers.
+*/
+pub fn encode_publish(msg: &PublishPacket) -> Result<Bytes, EncodeError> {
+    let remaining_length = publish_remaining_length(msg)?;
+    let first_byte = ((MqttPacketType::Publish as u8) << 4)
+        | ((msg.dup as u8) << 3)
+        | (msg.qos << 1)
+        | msg.retain as u8;
+
+    let mut out = BytesMut::new();
+    Header::write(first_byte, remaining_length, &mut out)?;
+    write_publish_body(msg, &mut out);
+
+    Ok(out.freeze())
+}
+
+fn publish_remaining_length(msg: &PublishPacket) -> Result<usize, EncodeError> {
+    check_qos(msg.qos)?;
+    if msg.qos == 0 && msg.dup {
+        return Err(EncodeError::DupWithoutQoS);
+    }
+
+    let mut len = string_len(&msg.topic)? + msg.payload.len();
+    if msg.qos > 0 {
+        if msg.packet_id.unwrap_or(0) == 0 {
+            return Err(EncodeError::MissingPacketId);
+        }
+        len += 2;
+    }
+
+    Ok(len)
+}
+
+fn write_publish_body(msg: &PublishPacket, out: &mut BytesMut) {
+    write_string(&msg.topic, out);
+    if msg.qos > 0 && let Some(packet_id) = msg.packet_id {
+        out.put_u16(packet_id);
+    }
+    out.put_slice(&msg.payload);
+}
+
+fn write_connect(p: &ConnectPacket, out: &mut BytesMut) {
+    if p.protocol_level == PROTOCOL_LEVEL_3_1 {
+        write_string("MQIsdp", out);
+    } else {
+        write_string("MQTT", out);
+    }
+    out.put_u8(p.protocol_level);
+
+    let mut flags = 0u8;
+    if p.clean_session {
+        flags |= 0b0000_0010;
+    }
+    if let Some(will) = &p.will {
+        flags |= 0b0000_0100 | (will.qos << 3);
+        if will.retain {
+            flags |= 0b0010_0000;
+        }
+    }
+    if p.password.is_some() {
+        flags |= 0b0100_0000;
+    }
+    if p.username.is_some() {
+        flags |= 0b1000_0000;
+    }
+    out.put_u8(flags);
+    out.put_u16(p.keep_alive);
+
+    write_string(&p.client_id, out);
+    if let Some(will) = &p.will {
+        write_string(&will.topic, out);
+        write_binary(&will.message, out);
+    }
+    if let Some(username) = &p.username {
+        write_string(username, out);
+    }
+    if let Some(password) = &p.password {
+        write_binary(password, out);
+    }
+}
+
+fn check_qos(qos: u8) -> Result<(), EncodeError> {
+    if qos > 2 {
+        return Err(EncodeError::InvalidQoS(qos));
+    }
+    Ok(())
+}
+
+fn string_len(s: &str) -> Result<usize, EncodeError> {
+    binary_len(s.as_bytes())
+}
+
+fn binary_len(b: &[u8]) -> Result<usize, EncodeError> {
+    if b.len() > u16::MAX as usize {
+        return Err(EncodeError::StringTooLong(b.len()));
+    }
+    Ok(2 + b.len())
+}
+
+fn write_string(s: &str, out: &mut BytesMut) {
+    write_binary(s.as_bytes(), out);
+}
+
+fn write_binary(b: &[u8], out: &mut BytesMut) {
+    out.put_u16(b.len() as u16);
+    out.put_slice(b);
+}
@@ -0,0 +1,116 @@
+use std::{fmt, io};
+
+use crate::packet_type::MqttPacketType;
+
+/*
+  Malformed input. Per the spec the connection should be closed; the
+  buffer contents are unspecified after an error.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    InvalidPacketType(u8),
+    InvalidFlags(MqttPacketType, u8),
+    MalformedRemainingLength,
+    PacketTooLarge(usize),
+    UnexpectedEof,
+    InvalidUtf8,
+    InvalidQoS(u8),
+    UnsupportedProtocol { name: String, level: u8 },
+    InvalidConnectFlags(u8),
+    InvalidConnectReturnCode(u8),
+    MissingPacketId,
+    EmptyPayload,
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidPacketType(t) => write!(f, "invalid packet type {}", t),
+            DecodeError::InvalidFlags(t, flags) => write!(f, "invalid fixed header flags {:#06b} for {:?}", flags, t),
+            DecodeError::MalformedRemainingLength => f.write_str("malformed remaining length"),
+            DecodeError::PacketTooLarge(len) => write!(f, "packet of {} bytes exceeds the maximum size", len),
+            DecodeError::UnexpectedEof => f.write_str("packet ended before all fields were read"),
+            DecodeError::InvalidUtf8 => f.write_str("string is not valid UTF-8"),
+            DecodeError::InvalidQoS(qos) => write!(f, "invalid QoS {}", qos),
+            DecodeError::UnsupportedProtocol { name, level } => write!(f, "unsupported protocol {} level {}", name, level),
+            DecodeError::InvalidConnectFlags(flags) => write!(f, "invalid CONNECT flags {:#010b}", flags),
+            DecodeError::InvalidConnectReturnCode(code) => write!(f, "invalid CONNACK return code {}", code),
+            DecodeError::MissingPacketId => f.write_str("packet identifier must be non-zero"),
+            DecodeError::EmptyPayload => f.write_str("SUBSCRIBE/UNSUBSCRIBE must carry at least one topic"),
+            DecodeError::TrailingBytes(n) => write!(f, "{} unexpected bytes after packet", n),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/*
+  A packet that cannot be represented on the wire.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    StringTooLong(usize),
+    PacketTooLarge(usize),
+    InvalidQoS(u8),
+    MissingPacketId,
+    EmptyPayload,
+    InvalidReturnCode(u8),
+    DupWithoutQoS,
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::StringTooLong(len) => write!(f, "field of {} bytes exceeds 65535 bytes", len),
+            EncodeError::PacketTooLarge(len) => write!(f, "remaining length {} exceeds the protocol maximum", len),
+            EncodeError::InvalidQoS(qos) => write!(f, "invalid QoS {}", qos),
+            EncodeError::MissingPacketId => f.write_str("QoS > 0 requires a packet identifier"),
+            EncodeError::EmptyPayload => f.write_str("SUBSCRIBE/UNSUBSCRIBE must carry at least one topic"),
+            EncodeError::InvalidReturnCode(code) => write!(f, "invalid SUBACK return code {}", code),
+            EncodeError::DupWithoutQoS => f.write_str("DUP must be zero for QoS 0"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/*
+  Error type of the `tokio_util` codec adapter.
+*/
+#[derive(Debug)]
+pub enum CodecError {
+    Decode(DecodeError),
+    Encode(EncodeError),
+    Io(io::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Decode(e) => write!(f, "decode error: {}", e),
+            CodecError::Encode(e) => write!(f, "encode error: {}", e),
+            CodecError::Io(e) => write!(f, "io error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<DecodeError> for CodecError {
+    fn from(e: DecodeError) -> Self {
+        CodecError::Decode(e)
+    }
+}
+
+impl From<EncodeError> for CodecError {
+    fn from(e: EncodeError) -> Self {
+        CodecError::Encode(e)
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
@@ -0,0 +1,102 @@
+use bytes::{BufMut, BytesMut};
+
+use crate::{MAX_REMAINING_LENGTH, error::{DecodeError, EncodeError}, packet_type::MqttPacketType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub packet_type: MqttPacketType,
+    pub flags: u8,
+    pub remaining_length: usize,
+}
+
+impl Header {
+    /*
+      Parses the fixed header at the start of `buf` without consuming it.
+      Returns the header and its encoded size, or `None` if more bytes are
+      needed to finish the header.
+    */
+    pub fn parse(buf: &[u8]) -> Result<Option<(Header, usize)>, DecodeError> {
+        let Some(&byte1) = buf.first() else {
+            return Ok(None);
+        };
+
+        let packet_type = MqttPacketType::from_u8(byte1 >> 4)
+            .ok_or(DecodeError::InvalidPacketType(byte1 >> 4))?;
+        let flags = byte1 & 0x0F;
+
+        if let Some(required) = packet_type.required_flags()
+            && flags != required
+        {
+            return Err(DecodeError::InvalidFlags(packet_type, flags));
+        }
+
+        let Some((remaining_length, len_bytes)) = Self::read_remaining_length(&buf[1..])? else {
+            return Ok(None);
+        };
+
+        Ok(Some((Header { packet_type, flags, remaining_length }, 1 + len_bytes)))
+    }
+
+    /*
+      Decodes the variable length "remaining length" field. Returns the value
+      and the number of bytes it occupied, or `None` if the field is not
+      complete yet. At most four bytes are accepted.
+    */
+    pub fn read_remaining_length(buf: &[u8]) -> Result<Option<(usize, usize)>, DecodeError> {
+        let mut multiplier = 1;
+        let mut value = 0;
+
+        for i in 0..4 {
+            let Some(&byte) = buf.get(i) else {
+                return Ok(None);
+            };
+            value += ((byte & 127) as usize) * multiplier;
+            if byte & 128 == 0 {
+                return Ok(Some((value, i + 1)));
+            }
+            multiplier *= 128;
+        }
+
+        Err(DecodeError::MalformedRemainingLength)
+    }
+
+    /*
+      Writes the fixed header for a packet body of `remaining_length` bytes.
+    */
+    pub fn write(first_byte: u8, remaining_length: usize, out: &mut BytesMut) -> Result<(), EncodeError> {
+        if remaining_length > MAX_REMAINING_LENGTH {
+            return Err(EncodeError::PacketTooLarge(remaining_length));
+        }
+
+        out.reserve(1 + remaining_length_size(remaining_length) + remaining_length);
+        out.put_u8(first_byte);
+
+        let mut len = remaining_length;
+        loop {
+            let mut byte = (len % 128) as u8;
+            len /= 128;
+
+            if len > 0 {
+                byte |= 0x80;
+            }
+
+            out.put_u8(byte);
+
+            if len == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/*
+  Number of bytes needed to encode `len` as a remaining length.
+*/
+pub fn remaining_length_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16_383 => 2,
+        16_384..=2_097_151 => 3,
+        _ => 4,
+    }
+}
@@ -0,0 +1,28 @@
+/*
+  Sans-IO MQTT 3.1.1 codec.
+
+  `Packet::decode` and `Packet::encode` work on plain byte buffers and cover
+  every packet type in both directions, so the broker, clients and test
+  tools share one wire implementation. `MqttCodec` adapts them to
+  `tokio_util::codec` for framed streams.
+*/
+pub mod codec;
+pub mod decoder;
+pub mod encoder;
+pub mod error;
+pub mod header;
+pub mod packet_type;
+pub mod packets;
+
+pub use codec::MqttCodec;
+pub use encoder::encode_publish;
+pub use error::{CodecError, DecodeError, EncodeError};
+pub use header::Header;
+pub use packet_type::MqttPacketType;
+pub use packets::*;
+
+/*
+  Largest remaining length representable by the 4-byte variable length
+  encoding.
+*/
+pub const MAX_REMAINING_LENGTH: usize = 268_435_455;
@@ -37,4 +37,16 @@ impl MqttPacketType {
             _ => None,
         }
     }
+
+    /*
+      Fixed header flags required by the spec for every type except PUBLISH,
+      whose flags carry DUP/QoS/RETAIN.
+    */
+    pub fn required_flags(&self) -> Option<u8> {
+        match self {
+            MqttPacketType::Publish => None,
+            MqttPacketType::PubRel | MqttPacketType::Subscribe | MqttPacketType::Unsubscribe => Some(0b0010),
+            _ => Some(0),
+        }
+    }
 }
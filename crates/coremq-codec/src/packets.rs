@@ -0,0 +1,118 @@
+use bytes::Bytes;
+
+/*
+  Every MQTT 3.1.1 control packet. Acknowledgements that only carry a
+  packet identifier are represented by that identifier.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Connect(ConnectPacket),
+    ConnAck(ConnAckPacket),
+    Publish(PublishPacket),
+    PubAck(u16),
+    PubRec(u16),
+    PubRel(u16),
+    PubComp(u16),
+    Subscribe(SubscribePacket),
+    SubAck(SubAckPacket),
+    Unsubscribe(UnsubscribePacket),
+    UnsubAck(u16),
+    PingReq,
+    PingResp,
+    Disconnect,
+}
+
+/*
+  Protocol level 4 is MQTT 3.1.1 ("MQTT"); level 3 is MQTT 3.1 ("MQIsdp").
+*/
+pub const PROTOCOL_LEVEL_3_1: u8 = 3;
+pub const PROTOCOL_LEVEL_3_1_1: u8 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectPacket {
+    pub protocol_level: u8,
+    pub client_id: String,
+    pub keep_alive: u16,
+    pub clean_session: bool,
+    pub username: Option<String>,
+    pub password: Option<Bytes>,
+    pub will: Option<Will>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Will {
+    pub topic: String,
+    pub message: Bytes,
+    pub qos: u8,
+    pub retain: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectReturnCode {
+    Accepted = 0,
+    UnacceptableProtocolVersion = 1,
+    IdentifierRejected = 2,
+    ServerUnavailable = 3,
+    BadUsernameOrPassword = 4,
+    NotAuthorized = 5,
+}
+
+impl ConnectReturnCode {
+    pub fn from_u8(val: u8) -> Option<Self> {
+        match val {
+            0 => Some(ConnectReturnCode::Accepted),
+            1 => Some(ConnectReturnCode::UnacceptableProtocolVersion),
+            2 => Some(ConnectReturnCode::IdentifierRejected),
+            3 => Some(ConnectReturnCode::ServerUnavailable),
+            4 => Some(ConnectReturnCode::BadUsernameOrPassword),
+            5 => Some(ConnectReturnCode::NotAuthorized),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnAckPacket {
+    pub session_present: bool,
+    pub return_code: ConnectReturnCode,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishPacket {
+    pub packet_id: Option<u16>,
+    pub topic: String,
+    pub payload: Bytes,
+    pub qos: u8,
+    pub retain: bool,
+    pub dup: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscribeFilter {
+    pub topic: String,
+    pub qos: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscribePacket {
+    pub packet_id: u16,
+    pub filters: Vec<SubscribeFilter>,
+}
+
+/*
+  SUBACK return code for a rejected subscription. Accepted subscriptions
+  return the granted QoS (0, 1 or 2).
+*/
+pub const SUBACK_FAILURE: u8 = 0x80;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubAckPacket {
+    pub packet_id: u16,
+    pub return_codes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsubscribePacket {
+    pub packet_id: u16,
+    pub topics: Vec<String>,
+}
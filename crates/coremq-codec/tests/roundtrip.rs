@@ -0,0 +1,224 @@
+/*
+  Property tests between the encoder and the decoder: every valid packet
+  survives encode -> decode unchanged, whatever way the bytes are split,
+  and arbitrary input never panics the decoder.
+*/
+use bytes::{Bytes, BytesMut};
+use coremq_codec::*;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use tokio_util::codec::{Decoder, Encoder};
+
+fn text() -> impl Strategy<Value = String> {
+    /* Any Unicode, including multi-byte characters and empty strings. */
+    ".{0,40}"
+}
+
+fn binary(max: usize) -> impl Strategy<Value = Bytes> {
+    vec(any::<u8>(), 0..max).prop_map(Bytes::from)
+}
+
+fn packet_id() -> impl Strategy<Value = u16> {
+    1..=u16::MAX
+}
+
+fn qos() -> impl Strategy<Value = u8> {
+    0..=2u8
+}
+
+fn will() -> impl Strategy<Value = Will> {
+    (text(), binary(64), qos(), any::<bool>()).prop_map(|(topic, message, qos, retain)| Will { topic, message, qos, retain })
+}
+
+fn connect() -> impl Strategy<Value = ConnectPacket> {
+    (
+        prop_oneof![Just(PROTOCOL_LEVEL_3_1), Just(PROTOCOL_LEVEL_3_1_1)],
+        text(),
+        any::<u16>(),
+        any::<bool>(),
+        proptest::option::of((text(), proptest::option::of(binary(32)))),
+        proptest::option::of(will()),
+    )
+        .prop_map(|(protocol_level, client_id, keep_alive, clean_session, credentials, will)| {
+            /* MQTT 3.1.1 only allows a password together with a username. */
+            let (username, password) = match credentials {
+                Some((username, password)) => (Some(username), password),
+                None => (None, None),
+            };
+            ConnectPacket { protocol_level, client_id, keep_alive, clean_session, username, password, will }
+        })
+}
+
+fn publish() -> impl Strategy<Value = PublishPacket> {
+    (text(), binary(512), qos(), any::<bool>(), any::<bool>(), packet_id()).prop_map(
+        |(topic, payload, qos, retain, dup, id)| PublishPacket {
+            packet_id: (qos > 0).then_some(id),
+            topic,
+            payload,
+            qos,
+            retain,
+            /* DUP must be zero for QoS 0. */
+            dup: dup && qos > 0,
+        },
+    )
+}
+
+fn return_code() -> impl Strategy<Value = ConnectReturnCode> {
+    (0..=5u8).prop_map(|code| ConnectReturnCode::from_u8(code).unwrap())
+}
+
+fn packet() -> impl Strategy<Value = Packet> {
+    prop_oneof![
+        connect().prop_map(Packet::Connect),
+        (any::<bool>(), return_code())
+            .prop_map(|(session_present, return_code)| Packet::ConnAck(ConnAckPacket { session_present, return_code })),
+        publish().prop_map(Packet::Publish),
+        packet_id().prop_map(Packet::PubAck),
+        packet_id().prop_map(Packet::PubRec),
+        packet_id().prop_map(Packet::PubRel),
+        packet_id().prop_map(Packet::PubComp),
+        (packet_id(), vec((text(), qos()).prop_map(|(topic, qos)| SubscribeFilter { topic, qos }), 1..8))
+            .prop_map(|(packet_id, filters)| Packet::Subscribe(SubscribePacket { packet_id, filters })),
+        (packet_id(), vec(prop_oneof![qos(), Just(SUBACK_FAILURE)], 1..8))
+            .prop_map(|(packet_id, return_codes)| Packet::SubAck(SubAckPacket { packet_id, return_codes })),
+        (packet_id(), vec(text(), 1..8))
+            .prop_map(|(packet_id, topics)| Packet::Unsubscribe(UnsubscribePacket { packet_id, topics })),
+        packet_id().prop_map(Packet::UnsubAck),
+        Just(Packet::PingReq),
+        Just(Packet::PingResp),
+        Just(Packet::Disconnect),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn encoded_packets_decode_to_themselves(packet in packet()) {
+        let mut buf = BytesMut::new();
+        packet.encode(&mut buf).unwrap();
+
+        let decoded = Packet::decode(&mut buf).unwrap();
+        prop_assert_eq!(decoded, Some(packet));
+        prop_assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn encode_publish_matches_packet_encoding(publish in publish()) {
+        let frame = encode_publish(&publish).unwrap();
+        prop_assert_eq!(&frame, &Packet::Publish(publish.clone()).to_bytes().unwrap());
+
+        let mut buf = BytesMut::from(&frame[..]);
+        prop_assert_eq!(Packet::decode(&mut buf).unwrap(), Some(Packet::Publish(publish)));
+    }
+
+    /*
+      A stream of packets cut at arbitrary points comes out of the codec as
+      the same packets, in order.
+    */
+    #[test]
+    fn codec_reassembles_split_streams(packets in vec(packet(), 1..8), cuts in vec(any::<prop::sample::Index>(), 0..8)) {
+        let mut codec = MqttCodec::new();
+        let mut stream = BytesMut::new();
+        for packet in &packets {
+            codec.encode(packet, &mut stream).unwrap();
+        }
+
+        let mut offsets: Vec<usize> = cuts.iter().map(|cut| cut.index(stream.len())).collect();
+        offsets.push(stream.len());
+        offsets.sort_unstable();
+
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        let mut start = 0;
+        for end in offsets {
+            buf.extend_from_slice(&stream[start..end]);
+            start = end;
+            while let Some(packet) = codec.decode(&mut buf).unwrap() {
+                decoded.push(packet);
+            }
+        }
+
+        prop_assert_eq!(decoded, packets);
+        prop_assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn remaining_length_roundtrips(length in 0..=MAX_REMAINING_LENGTH) {
+        let mut buf = BytesMut::new();
+        Header::write(0x30, length, &mut buf).unwrap();
+        prop_assert_eq!(Header::read_remaining_length(&buf[1..]).unwrap(), Some((length, buf.len() - 1)));
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic(data in vec(any::<u8>(), 0..512)) {
+        let mut buf = BytesMut::from(&data[..]);
+        while let Ok(Some(packet)) = Packet::decode(&mut buf) {
+            /* Whatever the decoder accepts, the encoder can write back. */
+            prop_assert!(packet.to_bytes().is_ok());
+        }
+    }
+
+    /*
+      Flipping a byte of a valid packet either still decodes or is reported
+      as an error; it never panics.
+    */
+    #[test]
+    fn corrupted_packets_never_panic(packet in packet(), index in any::<prop::sample::Index>(), byte in any::<u8>()) {
+        let mut bytes = packet.to_bytes().unwrap().to_vec();
+        let i = index.index(bytes.len());
+        bytes[i] = byte;
+
+        let mut buf = BytesMut::from(&bytes[..]);
+        let _ = Packet::decode(&mut buf);
+    }
+}
+
+#[test]
+fn oversized_remaining_length_is_rejected() {
+    let mut buf = BytesMut::new();
+    assert_eq!(
+        Header::write(0x30, MAX_REMAINING_LENGTH + 1, &mut buf),
+        Err(EncodeError::PacketTooLarge(MAX_REMAINING_LENGTH + 1))
+    );
+    assert_eq!(Header::read_remaining_length(&[0xFF, 0xFF, 0xFF, 0xFF, 0x01]), Err(DecodeError::MalformedRemainingLength));
+}
+
+#[test]
+fn packets_the_decoder_would_reject_are_not_encoded() {
+    let cases = [
+        (Packet::Subscribe(SubscribePacket { packet_id: 1, filters: vec![] }), EncodeError::EmptyPayload),
+        (Packet::Unsubscribe(UnsubscribePacket { packet_id: 1, topics: vec![] }), EncodeError::EmptyPayload),
+        (Packet::SubAck(SubAckPacket { packet_id: 1, return_codes: vec![] }), EncodeError::EmptyPayload),
+        (Packet::SubAck(SubAckPacket { packet_id: 1, return_codes: vec![1, 3] }), EncodeError::InvalidReturnCode(3)),
+        (
+            Packet::Publish(PublishPacket {
+                packet_id: None,
+                topic: "a/b".to_string(),
+                payload: Bytes::from_static(b"x"),
+                qos: 0,
+                retain: false,
+                dup: true,
+            }),
+            EncodeError::DupWithoutQoS,
+        ),
+    ];
+
+    for (packet, error) in cases {
+        let mut out = BytesMut::new();
+        assert_eq!(packet.encode(&mut out), Err(error), "{:?}", packet);
+        assert!(out.is_empty());
+    }
+
+    let dup = PublishPacket {
+        packet_id: None,
+        topic: "a/b".to_string(),
+        payload: Bytes::new(),
+        qos: 0,
+        retain: false,
+        dup: true,
+    };
+    assert_eq!(encode_publish(&dup), Err(EncodeError::DupWithoutQoS));
+
+    /* The failure code is a valid SUBACK entry. */
+    let suback = Packet::SubAck(SubAckPacket { packet_id: 1, return_codes: vec![0, SUBACK_FAILURE] });
+    assert!(suback.to_bytes().is_ok());
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use reqwest::{Method, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+
+/*
+  A non-success answer from the admin API. The exit code is derived from
+  the HTTP status.
+*/
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.status)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+pub struct ApiClient {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    pub fn new(base_url: &str, token: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> anyhow::Result<Value> {
+        self.send(Method::GET, path, None::<&()>).await
+    }
+
+    pub async fn delete(&self, path: &str) -> anyhow::Result<Value> {
+        self.send(Method::DELETE, path, None::<&()>).await
+    }
+
+    pub async fn post(&self, path: &str, body: &impl Serialize) -> anyhow::Result<Value> {
+        self.send(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn put(&self, path: &str, body: &impl Serialize) -> anyhow::Result<Value> {
+        self.send(Method::PUT, path, Some(body)).await
+    }
+
+    /*
+      Sends a request and unwraps the `ApiResponse` envelope when there is
+      one; endpoints that answer with bare JSON are returned as is.
+    */
+    async fn send(&self, method: Method, path: &str, body: Option<&impl Serialize>) -> anyhow::Result<Value> {
+        let mut request = self.request(method, path);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = self.connect(request).await?;
+        let text = response.text().await?;
+        let body: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+
+        if is_envelope(&body) {
+            Ok(body.get("data").cloned().unwrap_or(Value::Null))
+        } else {
+            Ok(body)
+        }
+    }
+
+    /*
+      Follows a Server-Sent Events endpoint, calling `on_event` with each
+      event's JSON until the server ends the stream.
+    */
+    pub async fn follow(&self, path: &str, mut on_event: impl FnMut(Value)) -> anyhow::Result<()> {
+        let mut response = self.connect(self.request(Method::GET, path)).await?;
+
+        let mut buffer: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            take_events(&mut buffer).into_iter().for_each(&mut on_event);
+        }
+        Ok(())
+    }
+
+    fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /*
+      Sends the request and turns a non-success status into an `ApiError`.
+    */
+    async fn connect(&self, request: reqwest::RequestBuilder) -> anyhow::Result<reqwest::Response> {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("cannot reach {}: {}", self.base_url, e))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body: Value = serde_json::from_str(&response.text().await?).unwrap_or(Value::Null);
+        let message = match body.get("message").and_then(Value::as_str) {
+            Some(message) if is_envelope(&body) => message.to_string(),
+            _ => status.canonical_reason().unwrap_or("request failed").to_string(),
+        };
+        Err(ApiError { status, message }.into())
+    }
+}
+
+fn is_envelope(body: &Value) -> bool {
+    body.get("status_code").is_some() && body.get("message").is_some()
+}
+
+/*
+  Removes every complete Server-Sent Events frame from `buffer` and
+  returns the JSON of those with a `data:` line. A partial frame stays
+  buffered until the rest arrives.
+*/
+fn take_events(buffer: &mut Vec<u8>) -> Vec<Value> {
+    let mut events = Vec::new();
+    while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+        let frame: Vec<u8> = buffer.drain(..end + 2).collect();
+        let frame = String::from_utf8_lossy(&frame);
+        if let Some(data) = frame.lines().find_map(|line| line.strip_prefix("data:")) {
+            events.push(serde_json::from_str(data.trim_start()).unwrap_or(Value::Null));
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn envelopes_are_recognised_by_status_code_and_message() {
+        assert!(is_envelope(&json!({ "status_code": 200, "message": "ok", "data": [] })));
+        assert!(is_envelope(&json!({ "status_code": 404, "message": "not found" })));
+        assert!(!is_envelope(&json!({ "message": "bare" })));
+        assert!(!is_envelope(&json!([{ "status_code": 200, "message": "ok" }])));
+        assert!(!is_envelope(&Value::Null));
+    }
+
+    #[test]
+    fn events_are_split_on_blank_lines() {
+        let mut buffer = b"event: client.connected\ndata: {\"client_id\":\"a\"}\n\ndata: {\"client_id\":\"b\"}\n\n".to_vec();
+        assert_eq!(take_events(&mut buffer), [json!({ "client_id": "a" }), json!({ "client_id": "b" })]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn partial_frames_wait_for_the_rest() {
+        let mut buffer = b"data: {\"seq\":".to_vec();
+        assert!(take_events(&mut buffer).is_empty());
+
+        buffer.extend_from_slice(b"1}\n");
+        assert!(take_events(&mut buffer).is_empty());
+
+        buffer.extend_from_slice(b"\ndata: {\"seq\":2}");
+        assert_eq!(take_events(&mut buffer), [json!({ "seq": 1 })]);
+        assert_eq!(buffer, b"data: {\"seq\":2}");
+    }
+
+    #[test]
+    fn frames_without_data_are_skipped() {
+        let mut buffer = b": keep-alive\n\ndata:not json\n\n".to_vec();
+        assert_eq!(take_events(&mut buffer), [Value::Null]);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,328 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(name = "coremqctl", version, about = "Manage a CoreMQ broker through its admin API")]
+pub struct Cli {
+    /// Admin API base URL
+    #[arg(long, env = "COREMQ_URL", default_value = "http://localhost:18083", global = true)]
+    pub url: String,
+
+    /// Use this token or API key instead of the cached login
+    #[arg(long, env = "COREMQ_TOKEN", hide_env_values = true, global = true)]
+    pub token: Option<String>,
+
+    #[arg(short = 'o', long, value_enum, default_value_t = Output::Table, global = true)]
+    pub output: Output,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Table,
+    Json,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Log in and cache the access token for --url
+    Login(LoginArgs),
+    /// Revoke and forget the cached token for --url
+    Logout,
+    /// Change the password of the logged-in user
+    Passwd(PasswdArgs),
+    /// Connected MQTT sessions
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+    /// MQTT listeners
+    #[command(subcommand)]
+    Listeners(ListenersCommand),
+    /// Admin API users
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Subscribed and published topics with their counters
+    #[command(subcommand)]
+    Topics(TopicsCommand),
+    /// Casbin access rules for the admin API
+    #[command(subcommand)]
+    Policies(PoliciesCommand),
+    /// Casbin role inheritance (subject inherits role)
+    #[command(subcommand)]
+    Roles(RolesCommand),
+    /// API keys for scripts and CI
+    #[command(subcommand)]
+    ApiKeys(ApiKeysCommand),
+    /// Failed logins and lockouts by username or IP
+    #[command(subcommand)]
+    Lockouts(LockoutsCommand),
+    /// Banned client ids, usernames, IPs and networks
+    #[command(subcommand)]
+    Bans(BansCommand),
+    /// Publish a message through the REST API
+    Publish(PublishArgs),
+    /// Follow broker events as they happen, until interrupted
+    Events(EventsArgs),
+    /// Packet traces by client id, topic filter or IP
+    #[command(subcommand)]
+    Traces(TracesCommand),
+    /// Engine queue depths
+    Metrics,
+}
+
+#[derive(Args)]
+pub struct LoginArgs {
+    #[arg(short = 'u', long, env = "COREMQ_USERNAME")]
+    pub username: String,
+
+    #[arg(short = 'p', long, env = "COREMQ_PASSWORD", hide_env_values = true, conflicts_with = "password_stdin")]
+    pub password: Option<String>,
+
+    /// Read the password from the first line of stdin
+    #[arg(long)]
+    pub password_stdin: bool,
+}
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    List {
+        /// Zero-based page number
+        #[arg(long, default_value_t = 0)]
+        page: usize,
+        #[arg(long, default_value_t = 50)]
+        size: usize,
+        /// Client id or username substring, case-insensitive
+        #[arg(short = 's', long)]
+        search: Option<String>,
+        /// Remote address or CIDR network
+        #[arg(long)]
+        ip: Option<String>,
+        /// Listener port
+        #[arg(long)]
+        port: Option<u16>,
+        /// CONNECT protocol level (3 = MQTT 3.1, 4 = 3.1.1)
+        #[arg(long)]
+        protocol_version: Option<u8>,
+        /// Connected at or after this time (RFC 3339 or unix seconds)
+        #[arg(long)]
+        since: Option<String>,
+        #[arg(long, value_parser = ["client_id", "username", "connected_at", "remote_addr", "port"])]
+        sort: Option<String>,
+        /// Sort in descending order
+        #[arg(long)]
+        desc: bool,
+    },
+    /// Show one session with its subscriptions and connection counters
+    Get { client_id: String },
+    /// Disconnect a client
+    Kick { client_id: String },
+    /// Subscribe a connected client to a topic filter on its behalf
+    Subscribe {
+        client_id: String,
+        topic: String,
+        #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+        qos: u8,
+    },
+    /// Remove one of a connected client's subscriptions
+    Unsubscribe { client_id: String, topic: String },
+}
+
+#[derive(Subcommand)]
+pub enum ListenersCommand {
+    List,
+    /// Stop the listener on a port and disconnect its clients
+    Stop { port: u16 },
+}
+
+#[derive(Subcommand)]
+pub enum UsersCommand {
+    List,
+    Get { username: String },
+    Create {
+        username: String,
+        #[arg(short = 'p', long)]
+        password: String,
+        #[arg(short = 'r', long, default_value = "user")]
+        role: String,
+    },
+    /// Change a user's role (admin or user); their current tokens stop working
+    SetRole { username: String, role: String },
+    /// Reset a user's password and/or change their role
+    Update {
+        username: String,
+        #[arg(short = 'p', long)]
+        password: Option<String>,
+        #[arg(short = 'r', long)]
+        role: Option<String>,
+    },
+    Delete { username: String },
+}
+
+#[derive(Subcommand)]
+pub enum PoliciesCommand {
+    List,
+    /// Allow SUBJECT to call ACTION (HTTP method or *) on OBJECT (path pattern)
+    Add { subject: String, object: String, action: String },
+    Remove { subject: String, object: String, action: String },
+}
+
+#[derive(Subcommand)]
+pub enum RolesCommand {
+    List,
+    /// Let SUBJECT inherit every permission of ROLE
+    Add { subject: String, role: String },
+    Remove { subject: String, role: String },
+}
+
+#[derive(Subcommand)]
+pub enum ApiKeysCommand {
+    List,
+    /// Create a key acting as ROLE; the key is printed once
+    Create {
+        name: String,
+        #[arg(short = 'r', long, default_value = "user")]
+        role: String,
+        /// Expire the key after this many days
+        #[arg(long)]
+        days: Option<u32>,
+    },
+    Revoke { id: String },
+}
+
+#[derive(Subcommand)]
+pub enum LockoutsCommand {
+    List,
+    /// Forget the failures of a username or IP, lifting its lockout
+    Clear {
+        #[arg(value_parser = ["username", "ip"])]
+        kind: String,
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BansCommand {
+    List,
+    /// Ban a client and disconnect matching clients now
+    Add {
+        #[arg(value_parser = ["client_id", "username", "ip", "cidr"])]
+        kind: String,
+        value: String,
+        #[arg(long)]
+        reason: Option<String>,
+        /// Lift the ban after this many seconds
+        #[arg(long)]
+        expires_in: Option<u64>,
+    },
+    Remove {
+        #[arg(value_parser = ["client_id", "username", "ip", "cidr"])]
+        kind: String,
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TracesCommand {
+    List,
+    /// Capture the packets of a client id, topic filter or IP/CIDR
+    Start {
+        #[arg(value_parser = ["client_id", "topic", "ip"])]
+        kind: String,
+        value: String,
+        /// Stop capturing after this many seconds
+        #[arg(long, default_value_t = 60)]
+        duration: u64,
+        /// Records kept; older ones are dropped
+        #[arg(long, default_value_t = 1000)]
+        capacity: usize,
+        /// Payload bytes kept per PUBLISH, 0 for none
+        #[arg(long, default_value_t = 64)]
+        payload_bytes: usize,
+    },
+    /// Print a trace's records
+    Show {
+        id: String,
+        /// Only records after this sequence number
+        #[arg(long, default_value_t = 0)]
+        after: u64,
+        /// Keep printing new records until the trace stops
+        #[arg(short = 'f', long)]
+        follow: bool,
+    },
+    /// Stop capturing, keeping the records
+    Stop { id: String },
+    Delete { id: String },
+}
+
+#[derive(Subcommand)]
+pub enum TopicsCommand {
+    List {
+        /// Zero-based page number
+        #[arg(long, default_value_t = 0)]
+        page: usize,
+        #[arg(long, default_value_t = 50)]
+        size: usize,
+        /// Only topics starting with this prefix
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Only topics matching this MQTT filter, e.g. 'sensors/+/temp'
+        #[arg(short = 'f', long)]
+        filter: Option<String>,
+        #[arg(long, value_parser = ["topic", "subscribers", "messages", "bytes", "last_publish_at"])]
+        sort: Option<String>,
+        /// Sort in descending order
+        #[arg(long)]
+        desc: bool,
+    },
+    /// Clients subscribed with a filter, or reached by a topic with --topic
+    Subscribers {
+        filter: String,
+        /// Treat the argument as a topic name and list every client a publish would reach
+        #[arg(long)]
+        topic: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct PasswdArgs {
+    #[arg(short = 'c', long, env = "COREMQ_PASSWORD", hide_env_values = true)]
+    pub current: String,
+
+    #[arg(short = 'n', long)]
+    pub new: String,
+}
+
+#[derive(Args)]
+pub struct PublishArgs {
+    pub topic: String,
+    pub payload: String,
+    #[arg(short = 'q', long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+    pub qos: u8,
+    #[arg(short = 'r', long)]
+    pub retain: bool,
+    /// How the payload argument is encoded
+    #[arg(short = 'e', long, default_value = "plain", value_parser = ["plain", "base64", "hex"])]
+    pub encoding: String,
+    /// Wait until QoS 1/2 subscribers acknowledge the message
+    #[arg(short = 'w', long)]
+    pub wait: bool,
+    /// How long --wait waits, in milliseconds
+    #[arg(long, default_value_t = 5000)]
+    pub timeout_ms: u64,
+}
+
+#[derive(Args)]
+pub struct EventsArgs {
+    /// Only these event types; repeat for several
+    #[arg(short = 't', long = "type", value_parser = [
+        "client_connected", "client_disconnected", "subscribed", "unsubscribed",
+        "listener_started", "listener_stopped", "auth_failed",
+    ])]
+    pub types: Vec<String>,
+    /// Only events about this client
+    #[arg(long)]
+    pub client_id: Option<String>,
+    /// Only (un)subscriptions to filters under this MQTT filter
+    #[arg(long)]
+    pub topic: Option<String>,
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/*
+  Tokens from `coremqctl login`, keyed by API base URL so that several
+  brokers can be managed from one machine.
+
+  Stored at $COREMQ_CTL_CACHE, or $XDG_CONFIG_HOME/coremq/ctl.json, or
+  ~/.config/coremq/ctl.json.
+*/
+#[derive(Default, Serialize, Deserialize)]
+pub struct TokenCache {
+    #[serde(default)]
+    tokens: BTreeMap<String, CachedToken>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CachedToken {
+    pub username: String,
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+impl TokenCache {
+    pub fn path() -> anyhow::Result<PathBuf> {
+        if let Some(path) = std::env::var_os("COREMQ_CTL_CACHE") {
+            return Ok(PathBuf::from(path));
+        }
+
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let home = std::env::var_os("HOME")
+                    .context("HOME is not set; use COREMQ_CTL_CACHE to choose a token cache file")?;
+                PathBuf::from(home).join(".config")
+            }
+        };
+
+        Ok(config_dir.join("coremq").join("ctl.json"))
+    }
+
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(&Self::path()?)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    fn load_from(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("corrupt token cache {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("cannot read {}", path.display())),
+        }
+    }
+
+    fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+        }
+
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("cannot write {}", path.display()))?;
+
+        /* The file holds bearer tokens, keep it private to the user. */
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, url: &str) -> Option<&CachedToken> {
+        self.tokens.get(url)
+    }
+
+    pub fn insert(&mut self, url: &str, token: CachedToken) {
+        self.tokens.insert(url.to_string(), token);
+    }
+
+    pub fn remove(&mut self, url: &str) -> bool {
+        self.tokens.remove(url).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(username: &str) -> CachedToken {
+        CachedToken {
+            username: username.to_string(),
+            access_token: format!("{}-access", username),
+            refresh_token: format!("{}-refresh", username),
+        }
+    }
+
+    #[test]
+    fn tokens_survive_a_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("coremq").join("ctl.json");
+
+        assert!(TokenCache::load_from(&path).unwrap().get("http://localhost:18083").is_none());
+
+        let mut cache = TokenCache::default();
+        cache.insert("http://localhost:18083", token("admin"));
+        cache.insert("http://broker-2:18083", token("operator"));
+        cache.save_to(&path).unwrap();
+
+        let mut loaded = TokenCache::load_from(&path).unwrap();
+        let admin = loaded.get("http://localhost:18083").unwrap();
+        assert_eq!((admin.username.as_str(), admin.access_token.as_str()), ("admin", "admin-access"));
+        assert_eq!(loaded.get("http://broker-2:18083").unwrap().refresh_token, "operator-refresh");
+
+        assert!(loaded.remove("http://broker-2:18083"));
+        assert!(!loaded.remove("http://broker-2:18083"));
+        loaded.save_to(&path).unwrap();
+        assert!(TokenCache::load_from(&path).unwrap().get("http://broker-2:18083").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_cache_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ctl.json");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        TokenCache::default().save_to(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn a_corrupt_cache_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ctl.json");
+        fs::write(&path, "not json").unwrap();
+        assert!(TokenCache::load_from(&path).is_err());
+    }
+}
@@ -0,0 +1,660 @@
+use std::io::BufRead;
+
+use anyhow::Context;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+use crate::{
+    api::{ApiClient, ApiError},
+    args::{ApiKeysCommand, BansCommand, Cli, Command, EventsArgs, ListenersCommand, LockoutsCommand, LoginArgs, Output, PasswdArgs, PoliciesCommand, PublishArgs, RolesCommand, SessionsCommand, TopicsCommand, TracesCommand, UsersCommand},
+    cache::{CachedToken, TokenCache},
+    output,
+};
+
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+    let url = cli.url.trim_end_matches('/').to_string();
+    let out = cli.output;
+
+    match cli.command {
+        Command::Login(args) => return login(&url, args).await,
+        Command::Logout => return logout(&url).await,
+        _ => {}
+    }
+
+    /* An explicit token is used as is; a cached one is refreshed once on 401. */
+    if let Some(token) = cli.token {
+        return execute(&ApiClient::new(&url, Some(token)), &cli.command, out).await;
+    }
+
+    let cached = cached_token(&url)?;
+    let username = cached.username.clone();
+    match execute(&ApiClient::new(&url, Some(cached.access_token.clone())), &cli.command, out).await {
+        Err(e) if is_unauthorized(&e) && !cached.refresh_token.is_empty() => {
+            let access_token = refresh(&url, cached).await.map_err(|_| e)?;
+            execute(&ApiClient::new(&url, Some(access_token)), &cli.command, out).await?;
+        }
+        result => result?,
+    }
+
+    /* A password change revokes the cached pair; log in again with the new password. */
+    if let Command::Passwd(PasswdArgs { new, .. }) = &cli.command {
+        return login(&url, LoginArgs { username, password: Some(new.clone()), password_stdin: false }).await;
+    }
+    Ok(())
+}
+
+async fn execute(api: &ApiClient, command: &Command, out: Output) -> anyhow::Result<()> {
+    match command {
+        Command::Login(_) | Command::Logout => unreachable!(),
+
+        Command::Sessions(SessionsCommand::List { page, size, search, ip, port, protocol_version, since, sort, desc }) => {
+            let mut path = format!("/api/v1/sessions?page={}&size={}", page, size);
+            let filters = [
+                ("search", search.clone()),
+                ("ip", ip.clone()),
+                ("port", port.map(|port| port.to_string())),
+                ("protocol_version", protocol_version.map(|version| version.to_string())),
+                ("connected_since", since.clone()),
+                ("sort", sort.clone()),
+                ("order", desc.then(|| "desc".to_string())),
+            ];
+            for (name, value) in filters {
+                if let Some(value) = value {
+                    path.push_str(&format!("&{}={}", name, encode_segment(&value)));
+                }
+            }
+            let data = api.get(&path).await?;
+            if out == Output::Json {
+                output::print_json(&data);
+                return Ok(());
+            }
+            output::print(out, &data["content"], &[
+                ("CLIENT ID", "client_id"),
+                ("USERNAME", "username"),
+                ("REMOTE ADDR", "remote_addr"),
+                ("PORT", "connected_port"),
+                ("CLEAN", "clean_session"),
+                ("SUBS", "subscriptions"),
+                ("CONNECTED AT", "connected_at"),
+            ]);
+            println!(
+                "page {} of {} ({} sessions)",
+                data["page"].as_u64().unwrap_or(0) + 1,
+                data["total_pages"].as_u64().unwrap_or(0).max(1),
+                data["total_elements"].as_u64().unwrap_or(0),
+            );
+        }
+
+        Command::Sessions(SessionsCommand::Get { client_id }) => {
+            let path = format!("/api/v1/sessions/{}", encode_segment(client_id));
+            let data = api.get(&path).await.map_err(|e| not_found_as(e, format!("no session for client '{}'", client_id)))?;
+            if out == Output::Json {
+                output::print_json(&data);
+                return Ok(());
+            }
+            output::print(out, &Value::Array(vec![data.clone()]), &[
+                ("CLIENT ID", "client_id"),
+                ("USERNAME", "username"),
+                ("REMOTE ADDR", "remote_addr"),
+                ("KEEP ALIVE", "keep_alive"),
+                ("WILL", "will.topic"),
+                ("INFLIGHT IN", "inflight_incoming"),
+                ("INFLIGHT OUT", "inflight_outgoing"),
+                ("QUEUED", "queued"),
+                ("BYTES IN", "bytes_in"),
+                ("BYTES OUT", "bytes_out"),
+                ("LAST ACTIVITY", "last_activity_at"),
+            ]);
+            if let Some(subscriptions) = data["subscriptions"].as_object()
+                && !subscriptions.is_empty()
+            {
+                println!();
+                let subscriptions: Vec<Value> = subscriptions.values().cloned().collect();
+                output::print(out, &Value::Array(subscriptions), &[("TOPIC", "topic"), ("QOS", "qos"), ("SUBSCRIBED AT", "subscribed_at")]);
+            }
+        }
+
+        Command::Sessions(SessionsCommand::Subscribe { client_id, topic, qos }) => {
+            let path = format!("/api/v1/sessions/{}/subscriptions", encode_segment(client_id));
+            let data = api.post(&path, &json!({ "topic": topic, "qos": qos })).await?;
+            let granted = data["qos"].as_u64().unwrap_or(0);
+            done(out, data, &format!("subscribed {} to {} (qos {})", client_id, topic, granted));
+        }
+
+        Command::Sessions(SessionsCommand::Unsubscribe { client_id, topic }) => {
+            let path = format!("/api/v1/sessions/{}/subscriptions?topic={}", encode_segment(client_id), encode_segment(topic));
+            api.delete(&path).await?;
+            done(out, json!({ "client_id": client_id, "topic": topic, "unsubscribed": true }), &format!("unsubscribed {} from {}", client_id, topic));
+        }
+
+        Command::Sessions(SessionsCommand::Kick { client_id }) => {
+            let path = format!("/api/v1/sessions/{}", encode_segment(client_id));
+            api.delete(&path).await.map_err(|e| not_found_as(e, format!("no session for client '{}'", client_id)))?;
+            done(out, json!({ "client_id": client_id, "disconnected": true }), &format!("disconnected {}", client_id));
+        }
+
+        Command::Listeners(ListenersCommand::List) => {
+            let mut data = api.get("/api/v1/listeners").await?;
+            /* `tls` is an object when configured; the table only says whether it is on. */
+            if out == Output::Table
+                && let Value::Array(listeners) = &mut data
+            {
+                for listener in listeners {
+                    listener["tls"] = json!(!listener["tls"].is_null());
+                }
+            }
+            output::print(out, &data, &[
+                ("NAME", "name"),
+                ("PROTOCOL", "protocol"),
+                ("HOST", "host"),
+                ("PORT", "port"),
+                ("TLS", "tls"),
+            ]);
+        }
+
+        Command::Listeners(ListenersCommand::Stop { port }) => {
+            api.delete(&format!("/api/v1/listeners/{}", port)).await?;
+            done(out, json!({ "port": port, "stopped": true }), &format!("stopped listener on port {}", port));
+        }
+
+        Command::Passwd(PasswdArgs { current, new }) => {
+            let body = json!({ "current_password": current, "new_password": new });
+            api.post("/api/v1/account/password", &body).await?;
+            done(out, json!({ "password_changed": true }), "password changed");
+        }
+
+        Command::Users(UsersCommand::List) => {
+            let mut data = api.get("/api/v1/users").await?;
+            strip_password_hashes(&mut data);
+            output::print(out, &data, &[("USERNAME", "username"), ("ROLE", "role"), ("MUST CHANGE PASSWORD", "must_change_password")]);
+        }
+
+        Command::Users(UsersCommand::Get { username }) => {
+            let path = format!("/api/v1/users/{}", encode_segment(username));
+            let mut data = api.get(&path).await.map_err(|e| not_found_as(e, format!("no user '{}'", username)))?;
+            strip_password_hashes(&mut data);
+            if out == Output::Json {
+                output::print_json(&data);
+                return Ok(());
+            }
+            output::print(out, &Value::Array(vec![data]), &[("USERNAME", "username"), ("ROLE", "role"), ("MUST CHANGE PASSWORD", "must_change_password")]);
+        }
+
+        Command::Users(UsersCommand::Update { username, password, role }) => {
+            if password.is_none() && role.is_none() {
+                anyhow::bail!("nothing to update: pass --password and/or --role");
+            }
+            let path = format!("/api/v1/users/{}", encode_segment(username));
+            let mut data = api
+                .put(&path, &json!({ "password": password, "role": role }))
+                .await
+                .map_err(|e| not_found_as(e, format!("no user '{}'", username)))?;
+            strip_password_hashes(&mut data);
+            done(out, data, &format!("updated user {}", username));
+        }
+
+        Command::Users(UsersCommand::Delete { username }) => {
+            let path = format!("/api/v1/users/{}", encode_segment(username));
+            api.delete(&path).await.map_err(|e| not_found_as(e, format!("no user '{}'", username)))?;
+            done(out, json!({ "username": username, "deleted": true }), &format!("deleted user {}", username));
+        }
+
+        Command::Users(UsersCommand::Create { username, password, role }) => {
+            let body = json!({ "username": username, "password": password, "role": role });
+            let mut data = api.post("/api/v1/users", &body).await?;
+            strip_password_hashes(&mut data);
+            done(out, data, &format!("created user {} ({})", username, role));
+        }
+
+        Command::Users(UsersCommand::SetRole { username, role }) => {
+            let path = format!("/api/v1/users/{}/role", encode_segment(username));
+            let mut data = api
+                .put(&path, &json!({ "role": role }))
+                .await
+                .map_err(|e| not_found_as(e, format!("no user '{}'", username)))?;
+            strip_password_hashes(&mut data);
+            done(out, data, &format!("{} is now {}", username, role));
+        }
+
+        Command::Policies(PoliciesCommand::List) => {
+            let data = api.get("/api/v1/policies").await?;
+            output::print(out, &data, &[("SUBJECT", "subject"), ("OBJECT", "object"), ("ACTION", "action")]);
+        }
+
+        Command::Policies(PoliciesCommand::Add { subject, object, action }) => {
+            let body = json!({ "subject": subject, "object": object, "action": action });
+            let data = api.post("/api/v1/policies", &body).await?;
+            done(out, data, &format!("allowed {} to {} {}", subject, action.to_uppercase(), object));
+        }
+
+        Command::Policies(PoliciesCommand::Remove { subject, object, action }) => {
+            let path = format!(
+                "/api/v1/policies?subject={}&object={}&action={}",
+                encode_segment(subject),
+                encode_segment(object),
+                encode_segment(action),
+            );
+            let data = api.delete(&path).await.map_err(|e| not_found_as(e, "no such policy".to_string()))?;
+            done(out, data, &format!("removed {} {} {}", subject, object, action.to_uppercase()));
+        }
+
+        Command::Roles(RolesCommand::List) => {
+            let data = api.get("/api/v1/role-assignments").await?;
+            output::print(out, &data, &[("SUBJECT", "subject"), ("ROLE", "role")]);
+        }
+
+        Command::Roles(RolesCommand::Add { subject, role }) => {
+            let data = api.post("/api/v1/role-assignments", &json!({ "subject": subject, "role": role })).await?;
+            done(out, data, &format!("{} now inherits {}", subject, role));
+        }
+
+        Command::Roles(RolesCommand::Remove { subject, role }) => {
+            let path = format!("/api/v1/role-assignments?subject={}&role={}", encode_segment(subject), encode_segment(role));
+            let data = api.delete(&path).await.map_err(|e| not_found_as(e, "no such role assignment".to_string()))?;
+            done(out, data, &format!("{} no longer inherits {}", subject, role));
+        }
+
+        Command::ApiKeys(ApiKeysCommand::List) => {
+            let data = api.get("/api/v1/api-keys").await?;
+            output::print(
+                out,
+                &data,
+                &[("ID", "id"), ("NAME", "name"), ("ROLE", "role"), ("EXPIRES", "expires_at"), ("LAST USED", "last_used_at"), ("EXPIRED", "expired")],
+            );
+        }
+
+        Command::ApiKeys(ApiKeysCommand::Create { name, role, days }) => {
+            let body = json!({ "name": name, "role": role, "expires_in_days": days });
+            let data = api.post("/api/v1/api-keys", &body).await?;
+            if out == Output::Json {
+                output::print_json(&data);
+                return Ok(());
+            }
+            println!("{}", data["key"].as_str().unwrap_or_default());
+            eprintln!("created API key {} ({}); store it now, it cannot be shown again", name, role);
+        }
+
+        Command::ApiKeys(ApiKeysCommand::Revoke { id }) => {
+            let path = format!("/api/v1/api-keys/{}", encode_segment(id));
+            api.delete(&path).await.map_err(|e| not_found_as(e, format!("no API key '{}'", id)))?;
+            done(out, json!({ "id": id, "revoked": true }), &format!("revoked API key {}", id));
+        }
+
+        Command::Lockouts(LockoutsCommand::List) => {
+            let data = api.get("/api/v1/lockouts").await?;
+            output::print(
+                out,
+                &data,
+                &[("KIND", "kind"), ("VALUE", "value"), ("FAILURES", "failures"), ("LAST FAILURE", "last_failure_at"), ("LOCKED", "locked"), ("UNTIL", "locked_until")],
+            );
+        }
+
+        Command::Lockouts(LockoutsCommand::Clear { kind, value }) => {
+            let path = format!("/api/v1/lockouts/{}/{}", kind, encode_segment(value));
+            api.delete(&path).await.map_err(|e| not_found_as(e, format!("no failed logins for {} '{}'", kind, value)))?;
+            done(out, json!({ "kind": kind, "value": value, "cleared": true }), &format!("cleared lockout for {} {}", kind, value));
+        }
+
+        Command::Bans(BansCommand::List) => {
+            let data = api.get("/api/v1/bans").await?;
+            output::print(
+                out,
+                &data,
+                &[("KIND", "kind"), ("VALUE", "value"), ("REASON", "reason"), ("CREATED BY", "created_by"), ("EXPIRES", "expires_at"), ("EXPIRED", "expired")],
+            );
+        }
+
+        Command::Bans(BansCommand::Add { kind, value, reason, expires_in }) => {
+            let body = json!({ "kind": kind, "value": value, "reason": reason, "expires_in_secs": expires_in });
+            let data = api.post("/api/v1/bans", &body).await?;
+            let disconnected = data["disconnected"].as_array().map_or(0, |ids| ids.len());
+            let message = format!("banned {} {}; disconnected {} client(s)", kind, data["ban"]["value"].as_str().unwrap_or(value), disconnected);
+            done(out, data, &message);
+        }
+
+        Command::Bans(BansCommand::Remove { kind, value }) => {
+            let path = format!("/api/v1/bans?kind={}&value={}", kind, encode_segment(value));
+            api.delete(&path).await.map_err(|e| not_found_as(e, format!("no ban on {} '{}'", kind, value)))?;
+            done(out, json!({ "kind": kind, "value": value, "removed": true }), &format!("removed ban on {} {}", kind, value));
+        }
+
+        Command::Topics(TopicsCommand::List { page, size, prefix, filter, sort, desc }) => {
+            let mut path = format!("/api/v1/topics?page={}&size={}", page, size);
+            let filters = [
+                ("prefix", prefix.clone()),
+                ("filter", filter.clone()),
+                ("sort", sort.clone()),
+                ("order", desc.then(|| "desc".to_string())),
+            ];
+            for (name, value) in filters {
+                if let Some(value) = value {
+                    path.push_str(&format!("&{}={}", name, encode_segment(&value)));
+                }
+            }
+            let data = api.get(&path).await?;
+            if out == Output::Json {
+                output::print_json(&data);
+                return Ok(());
+            }
+            output::print(out, &data["content"], &[
+                ("TOPIC", "topic"),
+                ("SUBSCRIBERS", "subscriber_count"),
+                ("MESSAGES", "messages"),
+                ("BYTES", "bytes"),
+                ("RETAINED", "retained"),
+                ("LAST PUBLISH", "last_publish_at"),
+            ]);
+            println!(
+                "page {} of {} ({} topics)",
+                data["page"].as_u64().unwrap_or(0) + 1,
+                data["total_pages"].as_u64().unwrap_or(0).max(1),
+                data["total_elements"].as_u64().unwrap_or(0),
+            );
+        }
+
+        Command::Topics(TopicsCommand::Subscribers { filter, topic }) => {
+            let param = if *topic { "topic" } else { "filter" };
+            let data = api.get(&format!("/api/v1/topics/subscribers?{}={}", param, encode_segment(filter))).await?;
+            output::print(out, &data, &[("CLIENT ID", "client_id"), ("QOS", "qos")]);
+        }
+
+        Command::Publish(PublishArgs { topic, payload, qos, retain, encoding, wait, timeout_ms }) => {
+            let body = json!({ "topic": topic, "payload": payload, "encoding": encoding, "qos": qos, "retain": retain });
+            let path = match wait {
+                true => format!("/api/v1/publish?wait_for_ack=true&timeout_ms={}", timeout_ms),
+                false => "/api/v1/publish".to_string(),
+            };
+            let data = api.post(&path, &body).await?;
+            let mut message = format!(
+                "published to {} ({} matched, {} delivered)",
+                topic,
+                data["matched"].as_u64().unwrap_or(0),
+                data["delivered"].as_u64().unwrap_or(0),
+            );
+            if *wait {
+                message.push_str(&format!(
+                    ", {} of {} acknowledged",
+                    data["acknowledged"].as_u64().unwrap_or(0),
+                    data["acks_expected"].as_u64().unwrap_or(0),
+                ));
+            }
+            done(out, data, &message);
+        }
+
+        Command::Events(EventsArgs { types, client_id, topic }) => {
+            let mut params = Vec::new();
+            if !types.is_empty() {
+                params.push(format!("types={}", types.join(",")));
+            }
+            if let Some(client_id) = client_id {
+                params.push(format!("client_id={}", encode_segment(client_id)));
+            }
+            if let Some(topic) = topic {
+                params.push(format!("topic={}", encode_segment(topic)));
+            }
+            let path = format!("/api/v1/events?{}", params.join("&"));
+            api.follow(&path, |event| match out {
+                Output::Json => println!("{}", event),
+                Output::Table => println!("{}", event_line(&event)),
+            })
+            .await?;
+        }
+
+        Command::Traces(TracesCommand::List) => {
+            let data = api.get("/api/v1/traces").await?;
+            output::print(
+                out,
+                &data,
+                &[("ID", "id"), ("KIND", "kind"), ("VALUE", "value"), ("ACTIVE", "active"), ("ENDS", "ends_at"), ("CAPTURED", "captured"), ("DROPPED", "dropped")],
+            );
+        }
+
+        Command::Traces(TracesCommand::Start { kind, value, duration, capacity, payload_bytes }) => {
+            let body = json!({ "kind": kind, "value": value, "duration_secs": duration, "capacity": capacity, "payload_bytes": payload_bytes });
+            let data = api.post("/api/v1/traces", &body).await?;
+            let message = format!("started trace {} on {} {} until {}", text(&data["id"]), kind, text(&data["value"]), text(&data["ends_at"]));
+            done(out, data, &message);
+        }
+
+        Command::Traces(TracesCommand::Show { id, after, follow }) => {
+            let path = format!("/api/v1/traces/{}", encode_segment(id));
+            let not_found = || format!("no trace '{}'", id);
+            if !*follow {
+                let data = api.get(&format!("{}?after={}", path, after)).await.map_err(|e| not_found_as(e, not_found()))?;
+                if out == Output::Json {
+                    output::print_json(&data);
+                    return Ok(());
+                }
+                output::print(out, &data["records"], &[
+                    ("SEQ", "seq"),
+                    ("TIME", "at"),
+                    ("DIR", "direction"),
+                    ("CLIENT ID", "client_id"),
+                    ("TYPE", "packet_type"),
+                    ("ID", "packet_id"),
+                    ("TOPIC", "topic"),
+                    ("QOS", "qos"),
+                    ("SIZE", "payload_size"),
+                    ("PAYLOAD", "payload_preview"),
+                    ("DETAIL", "detail"),
+                ]);
+                let trace = &data["trace"];
+                println!("{} captured, {} dropped, active: {}", trace["captured"], trace["dropped"], trace["active"]);
+                return Ok(());
+            }
+
+            /* Polls once a second; records pushed out of the buffer in between are lost. */
+            let mut after = *after;
+            loop {
+                let data = api.get(&format!("{}?after={}", path, after)).await.map_err(|e| not_found_as(e, not_found()))?;
+                for record in data["records"].as_array().into_iter().flatten() {
+                    after = after.max(record["seq"].as_u64().unwrap_or(0));
+                    match out {
+                        Output::Json => println!("{}", record),
+                        Output::Table => println!("{}", record_line(record)),
+                    }
+                }
+                if data["trace"]["active"] != true {
+                    return Ok(());
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        }
+
+        Command::Traces(TracesCommand::Stop { id }) => {
+            let path = format!("/api/v1/traces/{}/stop", encode_segment(id));
+            let data = api.post(&path, &json!({})).await.map_err(|e| not_found_as(e, format!("no trace '{}'", id)))?;
+            let message = format!("stopped trace {} ({} captured)", id, data["captured"]);
+            done(out, data, &message);
+        }
+
+        Command::Traces(TracesCommand::Delete { id }) => {
+            let path = format!("/api/v1/traces/{}", encode_segment(id));
+            api.delete(&path).await.map_err(|e| not_found_as(e, format!("no trace '{}'", id)))?;
+            done(out, json!({ "id": id, "deleted": true }), &format!("deleted trace {}", id));
+        }
+
+        Command::Metrics => {
+            let data = api.get("/api/v1/metrics").await?;
+            if out == Output::Json {
+                output::print_json(&data);
+                return Ok(());
+            }
+            let rows: Vec<Value> = ["connect_queue", "pubsub_queue", "admin_queue"]
+                .iter()
+                .map(|queue| json!({ "queue": queue, "depth": data[queue]["depth"], "capacity": data[queue]["capacity"] }))
+                .collect();
+            output::print(out, &Value::Array(rows), &[("QUEUE", "queue"), ("DEPTH", "depth"), ("CAPACITY", "capacity")]);
+            println!("\npublishers waiting on full mailboxes: {}", text(&data["publishers_waiting"]));
+        }
+    }
+
+    Ok(())
+}
+
+async fn login(url: &str, args: LoginArgs) -> anyhow::Result<()> {
+    let password = match (args.password, args.password_stdin) {
+        (Some(password), _) => password,
+        (None, true) => {
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line).context("cannot read password from stdin")?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+        (None, false) => anyhow::bail!("a password is required: use -p, --password-stdin or COREMQ_PASSWORD"),
+    };
+
+    let api = ApiClient::new(url, None);
+    let data = api
+        .post("/api/v1/public/login", &json!({ "username": args.username, "password": password }))
+        .await?;
+
+    let token = CachedToken {
+        username: args.username.clone(),
+        access_token: data["access_token"].as_str().context("login response has no access_token")?.to_string(),
+        refresh_token: data["refresh_token"].as_str().unwrap_or_default().to_string(),
+    };
+
+    let mut cache = TokenCache::load()?;
+    cache.insert(url, token);
+    cache.save()?;
+
+    eprintln!("logged in to {} as {}", url, args.username);
+    if data["password_change_required"].as_bool() == Some(true) {
+        eprintln!("this account must change its password first: run `coremqctl passwd -c <current> -n <new>`");
+    }
+    Ok(())
+}
+
+/*
+  Revokes the cached token pair on the server, then forgets it. The local
+  entry is removed even if the server can't be reached.
+*/
+async fn logout(url: &str) -> anyhow::Result<()> {
+    let mut cache = TokenCache::load()?;
+    let Some(cached) = cache.get(url).cloned() else {
+        return Ok(());
+    };
+
+    if let Err(e) = ApiClient::new(url, Some(cached.access_token)).post("/api/v1/logout", &json!({})).await {
+        eprintln!("warning: could not revoke the token on {}: {}", url, e);
+    }
+
+    cache.remove(url);
+    cache.save()?;
+    eprintln!("logged out of {}", url);
+    Ok(())
+}
+
+/*
+  Exchanges the cached refresh token for a new pair and stores it.
+*/
+async fn refresh(url: &str, cached: CachedToken) -> anyhow::Result<String> {
+    let data = ApiClient::new(url, None)
+        .post("/api/v1/public/refresh", &json!({ "refresh_token": cached.refresh_token }))
+        .await?;
+
+    let token = CachedToken {
+        username: cached.username,
+        access_token: data["access_token"].as_str().context("refresh response has no access_token")?.to_string(),
+        refresh_token: data["refresh_token"].as_str().unwrap_or_default().to_string(),
+    };
+    let access_token = token.access_token.clone();
+
+    let mut cache = TokenCache::load()?;
+    cache.insert(url, token);
+    cache.save()?;
+
+    Ok(access_token)
+}
+
+fn cached_token(url: &str) -> anyhow::Result<CachedToken> {
+    match TokenCache::load()?.get(url) {
+        Some(cached) => Ok(cached.clone()),
+        None => Err(ApiError {
+            status: StatusCode::UNAUTHORIZED,
+            message: format!("not logged in to {}; run `coremqctl login`", url),
+        }
+        .into()),
+    }
+}
+
+fn is_unauthorized(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<ApiError>().is_some_and(|err| err.status == StatusCode::UNAUTHORIZED)
+}
+
+fn done(out: Output, data: Value, message: &str) {
+    match out {
+        Output::Json => output::print_json(&data),
+        Output::Table => println!("{}", message),
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/*
+  One event per line: time, type, then the remaining fields as key=value.
+*/
+fn event_line(event: &Value) -> String {
+    let mut line = format!("{}  {:<19}", text(&event["at"]), text(&event["type"]));
+    if let Some(fields) = event.as_object() {
+        for (key, value) in fields.iter().filter(|(key, _)| *key != "at" && *key != "type") {
+            line.push_str(&format!("  {}={}", key, text(value)));
+        }
+    }
+    line
+}
+
+/*
+  One traced packet per line: time, direction, type, client, then the
+  fields that are set as key=value.
+*/
+fn record_line(record: &Value) -> String {
+    let mut line = format!(
+        "{}  {:<3}  {:<11}  {}",
+        text(&record["at"]),
+        text(&record["direction"]),
+        text(&record["packet_type"]),
+        record["client_id"].as_str().unwrap_or("-"),
+    );
+    for key in ["packet_id", "topic", "qos", "retain", "payload_size", "payload_preview", "detail"] {
+        if !record[key].is_null() {
+            line.push_str(&format!("  {}={}", key, text(&record[key])));
+        }
+    }
+    line
+}
+
+fn strip_password_hashes(data: &mut Value) {
+    match data {
+        Value::Array(items) => items.iter_mut().for_each(strip_password_hashes),
+        Value::Object(map) => {
+            map.remove("password_hash");
+        }
+        _ => {}
+    }
+}
+
+fn not_found_as(e: anyhow::Error, message: String) -> anyhow::Error {
+    match e.downcast::<ApiError>() {
+        Ok(err) if err.status == StatusCode::NOT_FOUND => ApiError { status: err.status, message }.into(),
+        Ok(err) => err.into(),
+        Err(e) => e,
+    }
+}
+
+/* Client ids may contain '/', '#', spaces etc. which must not leak into the path. */
+fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
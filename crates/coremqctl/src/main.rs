@@ -0,0 +1,66 @@
+/*
+  coremqctl: manage a running broker through the admin REST API.
+
+    coremqctl login -u admin -p public
+    coremqctl sessions list
+    coremqctl sessions kick sensor-17
+    coremqctl listeners list -o json
+
+  Exit codes: 0 success, 1 error, 2 usage, 3 not logged in or token
+  rejected, 4 permission denied, 5 not found.
+*/
+use clap::Parser;
+use reqwest::StatusCode;
+
+mod api;
+mod args;
+mod cache;
+mod commands;
+mod output;
+
+use api::ApiError;
+use args::Cli;
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    if let Err(e) = commands::run(cli).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(exit_code(&e));
+    }
+}
+
+fn exit_code(e: &anyhow::Error) -> i32 {
+    match e.downcast_ref::<ApiError>().map(|err| err.status) {
+        Some(StatusCode::UNAUTHORIZED | StatusCode::TOO_MANY_REQUESTS) => 3,
+        Some(StatusCode::FORBIDDEN) => 4,
+        Some(StatusCode::NOT_FOUND) => 5,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(status: StatusCode) -> anyhow::Error {
+        ApiError { status, message: "failed".to_string() }.into()
+    }
+
+    #[test]
+    fn exit_codes_follow_the_http_status() {
+        assert_eq!(exit_code(&api_error(StatusCode::UNAUTHORIZED)), 3);
+        assert_eq!(exit_code(&api_error(StatusCode::TOO_MANY_REQUESTS)), 3);
+        assert_eq!(exit_code(&api_error(StatusCode::FORBIDDEN)), 4);
+        assert_eq!(exit_code(&api_error(StatusCode::NOT_FOUND)), 5);
+        assert_eq!(exit_code(&api_error(StatusCode::BAD_REQUEST)), 1);
+        assert_eq!(exit_code(&api_error(StatusCode::INTERNAL_SERVER_ERROR)), 1);
+    }
+
+    #[test]
+    fn other_errors_exit_with_1() {
+        assert_eq!(exit_code(&anyhow::anyhow!("cannot reach http://localhost:18083")), 1);
+        assert_eq!(exit_code(&api_error(StatusCode::FORBIDDEN).context("while listing sessions")), 4);
+    }
+}
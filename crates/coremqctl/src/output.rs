@@ -0,0 +1,73 @@
+use serde_json::Value;
+
+use crate::args::Output;
+
+/*
+  Prints `data` either as pretty JSON or as an aligned table with the
+  given columns. Column values are looked up by dotted path, e.g.
+  "connect_queue.depth".
+*/
+pub fn print(output: Output, data: &Value, columns: &[(&str, &str)]) {
+    match output {
+        Output::Json => print_json(data),
+        Output::Table => {
+            let rows: Vec<&Value> = match data {
+                Value::Array(items) => items.iter().collect(),
+                Value::Null => Vec::new(),
+                other => vec![other],
+            };
+            print_table(&rows, columns);
+        }
+    }
+}
+
+pub fn print_json(data: &Value) {
+    println!("{}", serde_json::to_string_pretty(data).unwrap_or_default());
+}
+
+pub fn print_table(rows: &[&Value], columns: &[(&str, &str)]) {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|(_, path)| cell(row, path)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = columns.iter().map(|(header, _)| header.len()).collect();
+    for row in &cells {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let headers: Vec<String> = columns.iter().map(|(header, _)| header.to_string()).collect();
+    print_row(&headers, &widths);
+    for row in &cells {
+        print_row(row, &widths);
+    }
+}
+
+fn print_row(values: &[String], widths: &[usize]) {
+    let line: Vec<String> = values
+        .iter()
+        .zip(widths)
+        .map(|(value, width)| format!("{:<width$}", value, width = width))
+        .collect();
+    println!("{}", line.join("  ").trim_end());
+}
+
+fn cell(row: &Value, path: &str) -> String {
+    let mut value = row;
+    for key in path.split('.') {
+        match value.get(key) {
+            Some(next) => value = next,
+            None => return "-".to_string(),
+        }
+    }
+
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.len().to_string(),
+        Value::Object(map) => map.len().to_string(),
+        other => other.to_string(),
+    }
+}
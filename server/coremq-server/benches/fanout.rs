@@ -0,0 +1,122 @@
+/*
+  Fan-out routing throughput across worker threads.
+
+  Every publisher task routes directly through `RoutingService`, the same way
+  connection tasks do, so throughput should scale with the number of cores
+  instead of being capped by the engine loop.
+
+  cargo bench -p coremq-server --bench fanout
+*/
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use chrono::Local;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use tokio::{runtime::Builder, sync::mpsc};
+
+use coremq_server::{
+    enums::MqttChannel,
+    models::session::SessionSubscription,
+    protocol::packets::{ConnectPacket, PublishPacket, PROTOCOL_LEVEL_3_1_1},
+    services::{RoutingService, SessionService, TopicService},
+};
+
+const SUBSCRIBERS: usize = 64;
+const PUBLISHERS: usize = 8;
+const MESSAGES_PER_PUBLISHER: usize = 2_000;
+
+fn setup(runtime: &tokio::runtime::Runtime) -> Arc<RoutingService> {
+    let sessions = Arc::new(SessionService::new());
+    let topics = Arc::new(TopicService::new());
+    let addr: SocketAddr = "127.0.0.1:1883".parse().unwrap();
+
+    for i in 0..SUBSCRIBERS {
+        let client_id = format!("sub-{}", i);
+        let (tx, mut rx) = mpsc::channel::<MqttChannel>(4096);
+        runtime.spawn(async move { while rx.recv().await.is_some() {} });
+
+        let connect = ConnectPacket {
+            protocol_level: PROTOCOL_LEVEL_3_1_1,
+            client_id: client_id.clone(),
+            keep_alive: 60,
+            clean_session: true,
+            username: None,
+            password: None,
+            will: None,
+        };
+        sessions.add_client(&connect, 1883, addr, i as u64 + 1, tx, Arc::default());
+
+        let filter = if i % 2 == 0 { "bench/+/data" } else { "bench/#" };
+        sessions.add_subscribtion(&client_id, SessionSubscription {
+            packet_id: 1,
+            topic: filter.to_string(),
+            qos: 0,
+            subscribed_at: Local::now(),
+        });
+        topics.subscribe(filter, &client_id, 0);
+    }
+
+    Arc::new(RoutingService::new(sessions, topics))
+}
+
+fn run_publishers(runtime: &tokio::runtime::Runtime, routing: &Arc<RoutingService>) -> Duration {
+    let start = Instant::now();
+    runtime.block_on(async {
+        let mut handles = Vec::with_capacity(PUBLISHERS);
+        for p in 0..PUBLISHERS {
+            let routing = routing.clone();
+            handles.push(tokio::spawn(async move {
+                let packet = PublishPacket {
+                    packet_id: None,
+                    topic: format!("bench/{}/data", p),
+                    payload: Bytes::from(vec![0u8; 256]),
+                    qos: 0,
+                    retain: false,
+                    dup: false,
+                };
+                for i in 0..MESSAGES_PER_PUBLISHER {
+                    routing.publish(&packet).await;
+                    if i % 64 == 0 {
+                        tokio::task::yield_now().await;
+                    }
+                }
+            }));
+        }
+        for h in handles {
+            h.await.unwrap();
+        }
+    });
+    start.elapsed()
+}
+
+fn fanout(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fanout");
+    group.throughput(Throughput::Elements((PUBLISHERS * MESSAGES_PER_PUBLISHER * SUBSCRIBERS) as u64));
+    group.sample_size(10);
+
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut workers = 1;
+    while workers <= cores {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(workers)
+            .enable_all()
+            .build()
+            .unwrap();
+        let routing = setup(&runtime);
+
+        group.bench_with_input(BenchmarkId::new("workers", workers), &workers, |b, _| {
+            b.iter_custom(|iters| (0..iters).map(|_| run_publishers(&runtime, &routing)).sum());
+        });
+
+        workers *= 2;
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, fanout);
+criterion_main!(benches);
@@ -0,0 +1,146 @@
+/*
+  Route resolution and delivery for a single high fan-out topic.
+
+  Compares resolving a topic against the subscription tree on every publish
+  (`uncached`) with reusing the cached route (`cached`), and measures a full
+  publish to every subscriber through the cached route. `deliver_1mb` fans a
+  1 MB payload out to 1,000 subscribers and fetches each subscriber's frame
+  the way the TCP transport does.
+
+  cargo bench -p coremq-server --bench routing
+*/
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use chrono::Local;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures_util::FutureExt;
+use tokio::sync::mpsc;
+
+use coremq_server::{
+    enums::MqttChannel,
+    models::session::SessionSubscription,
+    protocol::packets::{ConnectPacket, PublishPacket, PROTOCOL_LEVEL_3_1_1},
+    services::{RoutingService, SessionService, TopicService},
+};
+
+const TOPIC: &str = "broadcast/all/config";
+
+fn setup(subscribers: usize) -> (Arc<RoutingService>, Vec<mpsc::Receiver<MqttChannel>>) {
+    let sessions = Arc::new(SessionService::new());
+    let topics = Arc::new(TopicService::new());
+    let addr: SocketAddr = "127.0.0.1:1883".parse().unwrap();
+    let mut receivers = Vec::with_capacity(subscribers);
+
+    for i in 0..subscribers {
+        let client_id = format!("device-{}", i);
+        let (tx, rx) = mpsc::channel::<MqttChannel>(4);
+        receivers.push(rx);
+
+        let connect = ConnectPacket {
+            protocol_level: PROTOCOL_LEVEL_3_1_1,
+            client_id: client_id.clone(),
+            keep_alive: 60,
+            clean_session: true,
+            username: None,
+            password: None,
+            will: None,
+        };
+        sessions.add_client(&connect, 1883, addr, i as u64 + 1, tx, Arc::default());
+
+        let filter = match i % 3 {
+            0 => TOPIC,
+            1 => "broadcast/+/config",
+            _ => "broadcast/#",
+        };
+        sessions.add_subscribtion(&client_id, SessionSubscription {
+            packet_id: 1,
+            topic: filter.to_string(),
+            qos: 0,
+            subscribed_at: Local::now(),
+        });
+        topics.subscribe(filter, &client_id, 0);
+    }
+
+    (Arc::new(RoutingService::new(sessions, topics)), receivers)
+}
+
+fn routing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("routing");
+    group.sample_size(20);
+
+    for subscribers in [1_000, 50_000] {
+        let (router, mut receivers) = setup(subscribers);
+        group.throughput(Throughput::Elements(subscribers as u64));
+
+        group.bench_with_input(BenchmarkId::new("uncached", subscribers), &subscribers, |b, _| {
+            b.iter(|| {
+                router.invalidate([TOPIC]);
+                router.resolve(TOPIC).len()
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("cached", subscribers), &subscribers, |b, _| {
+            router.resolve(TOPIC);
+            b.iter(|| router.resolve(TOPIC).len());
+        });
+
+        let packet = PublishPacket {
+            packet_id: None,
+            topic: TOPIC.to_string(),
+            payload: Bytes::from_static(b"{\"interval\":30}"),
+            qos: 0,
+            retain: false,
+            dup: false,
+        };
+
+        group.bench_with_input(BenchmarkId::new("publish", subscribers), &subscribers, |b, _| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    /* The mailboxes are drained every iteration, so this never waits. */
+                    router.publish(&packet).now_or_never().expect("mailboxes have room");
+                    total += start.elapsed();
+
+                    for rx in receivers.iter_mut() {
+                        while rx.try_recv().is_ok() {}
+                    }
+                }
+                total
+            });
+        });
+    }
+
+    let (router, mut receivers) = setup(1_000);
+    let packet = PublishPacket {
+        packet_id: Some(1),
+        topic: TOPIC.to_string(),
+        payload: Bytes::from(vec![7u8; 1024 * 1024]),
+        qos: 1,
+        retain: false,
+        dup: false,
+    };
+    group.throughput(Throughput::Elements(1_000));
+    group.bench_function("deliver_1mb/1000", |b| {
+        b.iter(|| {
+            router.publish(&packet).now_or_never().expect("mailboxes have room");
+            let mut bytes = 0;
+            for rx in receivers.iter_mut() {
+                while let Ok(MqttChannel::Publish(message, qos)) = rx.try_recv() {
+                    bytes += message.frame(qos, false).map_or(0, |frame| frame.len());
+                }
+            }
+            bytes
+        });
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, routing);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+/*
+  Runs a broker inside this process, publishes and receives a message
+  without opening a socket, then shuts the broker down.
+
+  cargo run -p coremq-server --example embedded
+*/
+use coremq_server::{Broker, Config};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let dir = env!("CARGO_MANIFEST_DIR");
+    let config: Config = serde_yaml::from_str(&format!(
+        r#"
+middleware:
+  model_path: {dir}/config/model.conf
+  policy_path: {dir}/config/policy.csv
+  secret: embedded-example-secret
+
+mqtt:
+  listeners:
+    - name: "tcp"
+      protocol: "tcp"
+      host: "127.0.0.1"
+      port: 0
+"#
+    ))?;
+
+    let broker = Broker::builder(config)
+        .admin_api(false)
+        .storage_path(std::env::temp_dir().join("coremq-embedded.redb").to_string_lossy())
+        .start()
+        .await?;
+
+    for listener in broker.listeners() {
+        println!("{} listening on port {}", listener.name, listener.port);
+    }
+
+    let mut sub = broker.subscribe("sensors/+/temp", 1).await?;
+    let delivered = broker.publish("sensors/1/temp", "23.5", 1, false).await;
+    println!("delivered to {} subscriber(s)", delivered);
+
+    if let Some(msg) = sub.recv().await {
+        println!("{} -> {}", msg.topic, String::from_utf8_lossy(&msg.payload));
+    }
+
+    drop(sub);
+    broker.shutdown().await
+}
@@ -1,30 +1,26 @@
-use std::sync::{Arc, atomic::{AtomicU16, Ordering}};
+use std::sync::{Arc, atomic::AtomicU16};
 
-use axum::http::StatusCode;
-use casbin::Enforcer;
+use axum::{Json, http::StatusCode};
 use serde::Serialize;
 use tokio::sync::mpsc;
 
-use crate::{engine::AdminCommand, services::jwt::JwtService, storage::redb::Storage};
+use crate::{engine::AdminCommand, services::{auth::AuthService, jwt::JwtService, policy::PolicyService}, storage::redb::Storage, transport::ProtocolState, utils::packet_id};
 
 #[derive(Clone)]
 pub struct ApiState {
     pub jwt_service: Arc<JwtService>,
-    pub enforcer: Arc<Enforcer>,
+    pub policy: Arc<PolicyService>,
+    pub auth: Arc<AuthService>,
     pub storage: Arc<Storage>,
-    pub engine: mpsc::UnboundedSender<AdminCommand>,
+    pub engine: mpsc::Sender<AdminCommand>,
+    pub ingress: Arc<ProtocolState>,
     pub packet_id_counter: Arc<AtomicU16>,
 }
 
 impl ApiState {
     /// Returns the next packet ID (1–65535), wrapping around and skipping 0.
     pub fn next_packet_id(&self) -> u16 {
-        loop {
-            let id = self.packet_id_counter.fetch_add(1, Ordering::Relaxed);
-            if id != 0 {
-                return id;
-            }
-        }
+        packet_id::next(&self.packet_id_counter)
     }
 }
 
@@ -54,4 +50,19 @@ impl<T> ApiResponse<T> {
             data: None,
         }
     }
-}
\ No newline at end of file
+}
+
+/*
+  Status and envelope for a failed request.
+*/
+pub fn error<T>(status: StatusCode, message: impl Into<String>) -> (StatusCode, Json<ApiResponse<T>>) {
+    (status, Json(ApiResponse::error(status, message)))
+}
+
+/*
+  The engine task has stopped, so an admin command could not be sent or
+  got no reply.
+*/
+pub fn engine_unavailable<T>() -> (StatusCode, Json<ApiResponse<T>>) {
+    error(StatusCode::INTERNAL_SERVER_ERROR, "Engine unavailable")
+}
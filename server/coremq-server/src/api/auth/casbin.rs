@@ -1,14 +1,39 @@
 use axum::{
     Json,
     body::Body,
-    extract::{Request, State},
-    http::{self, StatusCode},
+    extract::{Query, Request, State},
+    http::{self, HeaderMap, StatusCode, Uri},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use chrono::Utc;
+use serde::Deserialize;
 
-use crate::api::api_state::{ApiResponse, ApiState};
-use casbin::CoreApi;
+use crate::{
+    api::api_state::{ApiResponse, ApiState},
+    enums::jwt::JwtType,
+    models::claims::Claims,
+    utils::api_key,
+};
+
+fn reject(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(status, message))).into_response()
+}
+
+type Denied = (StatusCode, String);
+
+fn denied(status: StatusCode, message: impl Into<String>) -> Denied {
+    (status, message.into())
+}
+
+/*
+  Credentials are either a JWT access token (`Authorization: Bearer ...`)
+  or an API key (`X-API-Key: cmq_...`, or the key as the Bearer token).
+  Both resolve to claims, checked by Casbin against the request under the
+  stored role and any role assigned to the user or key name.
+  The event streams also take either as `?access_token=`, since browsers
+  can't set headers on EventSource and WebSocket requests.
+*/
 pub async fn auth_middleware(
     State(state): State<ApiState>,
     mut req: Request<Body>,
@@ -25,59 +50,139 @@ pub async fn auth_middleware(
         return next.run(req).await;
     }
 
-    let token = match req
-        .headers()
+    let query_token = if path.starts_with("/api/v1/events") { access_token(req.uri()) } else { None };
+    let credentials = credentials(req.headers()).or_else(|| query_token.as_deref().map(classify));
+
+    let claims = match credentials {
+        Some(Credentials::ApiKey(key)) => authenticate_api_key(&state, key),
+        Some(Credentials::Jwt(token)) => authenticate_jwt(&state, token, &path),
+        None => Err(denied(StatusCode::UNAUTHORIZED, "Missing Authorization header")),
+    };
+    let claims = match claims {
+        Ok(claims) => claims,
+        Err((status, message)) => return reject(status, message),
+    };
+
+    req.extensions_mut().insert(claims.clone());
+
+    if !state.policy.allows(&claims.sub, &claims.role, &path, &method) {
+        return reject(StatusCode::FORBIDDEN, "Access denied");
+    }
+
+    next.run(req).await
+}
+
+enum Credentials<'a> {
+    Jwt(&'a str),
+    ApiKey(&'a str),
+}
+
+fn credentials(headers: &HeaderMap) -> Option<Credentials<'_>> {
+    if let Some(key) = headers.get("X-API-Key").and_then(|v| v.to_str().ok()) {
+        return Some(Credentials::ApiKey(key));
+    }
+
+    let token = headers
         .get("Authorization")
         .and_then(|v| v.to_str().ok())
-        .and_then(|v| v.strip_prefix("Bearer "))
-    {
-        Some(token) => token, 
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(ApiResponse::<()>::error(
-                    StatusCode::UNAUTHORIZED,
-                    "Missing Authorization header",
-                )),
-            )
-                .into_response();
-        }
-    };
+        .and_then(|v| v.strip_prefix("Bearer "))?;
 
-    let claims = match state.jwt_service.parse(token) {
-        Ok(claims) => {
-            claims
-        }
+    Some(classify(token))
+}
+
+fn classify(token: &str) -> Credentials<'_> {
+    if token.starts_with(api_key::PREFIX) {
+        Credentials::ApiKey(token)
+    } else {
+        Credentials::Jwt(token)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+fn access_token(uri: &Uri) -> Option<String> {
+    Query::<TokenQuery>::try_from_uri(uri).ok()?.0.access_token
+}
+
+fn authenticate_jwt(state: &ApiState, token: &str, path: &str) -> Result<Claims, Denied> {
+    /*
+      Only access tokens authenticate requests; refresh tokens are accepted
+      by the refresh endpoint alone.
+    */
+    let claims = match state.jwt_service.parse_as(token, JwtType::AccessToken) {
+        Ok(claims) => claims,
         Err(_) => {
             println!("Invalid token");
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(ApiResponse::<()>::error(
-                    StatusCode::UNAUTHORIZED,
-                    "Invalid token",
-                )),
-            )
-                .into_response();
+            return Err(denied(StatusCode::UNAUTHORIZED, "Invalid token"));
         }
     };
 
-    req.extensions_mut().insert(claims.clone());
+    match state.storage.revoked.is_revoked(&claims.jti) {
+        Ok(false) => {}
+        Ok(true) => return Err(denied(StatusCode::UNAUTHORIZED, "Token revoked")),
+        Err(e) => return Err(denied(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to check token: {}", e))),
+    }
+
+    /*
+      Tokens carry the role they were issued with. If the user has been
+      deleted, their role changed or their password changed since, the
+      token no longer applies.
+    */
+    let user = match state.storage.user.get(&claims.sub) {
+        Ok(Some(user)) if user.role == claims.role && (claims.iat as i64) >= user.password_changed_at => user,
+        Ok(_) => return Err(denied(StatusCode::UNAUTHORIZED, "Token no longer valid, please log in again")),
+        Err(e) => return Err(denied(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to check token: {}", e))),
+    };
 
-    let allowed = state
-        .enforcer
-        .enforce((&claims.role, &path, &method))
-        .unwrap_or(false);
-
-    if !allowed {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(ApiResponse::<()>::error(
-                StatusCode::FORBIDDEN,
-                "Access denied",
-            )),
-        )
-            .into_response();
+    /*
+      Accounts still on a default password may only change it or log out.
+    */
+    if user.must_change_password
+        && path != "/api/v1/account/password"
+        && path != "/api/v1/logout"
+    {
+        return Err(denied(StatusCode::FORBIDDEN, "Password change required"));
     }
 
-    next.run(req).await
+    Ok(claims)
+}
+
+/*
+  API keys act as their configured role. The claims name the key
+  (`api_key:<name>`) so handlers and logs can tell it apart from users.
+*/
+fn authenticate_api_key(state: &ApiState, key: &str) -> Result<Claims, Denied> {
+    let invalid = || denied(StatusCode::UNAUTHORIZED, "Invalid API key");
+
+    let (id, secret) = api_key::parse(key).ok_or_else(invalid)?;
+    let stored = match state.storage.api_keys.get(id) {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return Err(invalid()),
+        Err(e) => return Err(denied(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to check API key: {}", e))),
+    };
+
+    if !api_key::verify(secret, &stored.key_hash) {
+        return Err(invalid());
+    }
+
+    let now = Utc::now().timestamp();
+    if stored.is_expired(now) {
+        return Err(denied(StatusCode::UNAUTHORIZED, "API key expired"));
+    }
+
+    if let Err(e) = state.storage.api_keys.touch(&stored, now) {
+        eprintln!("Failed to record API key use: {}", e);
+    }
+
+    Ok(Claims {
+        sub: format!("api_key:{}", stored.name),
+        role: stored.role,
+        token_type: "api_key".to_string(),
+        exp: stored.expires_at.unwrap_or(i64::MAX) as usize,
+        iat: stored.created_at as usize,
+        jti: stored.id,
+    })
 }
@@ -0,0 +1,77 @@
+use axum::{Extension, Json, extract::{Path, State}, http::StatusCode};
+use chrono::Utc;
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState, error},
+    models::{api_key::{ApiKey, ApiKeyInfo, CreateApiKey, CreatedApiKey}, claims::Claims},
+    utils::api_key,
+};
+
+pub async fn get_api_keys(
+    State(state): State<ApiState>,
+) -> (StatusCode, Json<ApiResponse<Vec<ApiKeyInfo>>>) {
+    match state.storage.api_keys.get_all() {
+        Ok(mut keys) => {
+            keys.sort_by_key(|key| key.created_at);
+            (StatusCode::OK, Json(ApiResponse::success(keys.into_iter().map(ApiKeyInfo::from).collect(), "Fetched API keys successfully")))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch API keys: {}", e)),
+    }
+}
+
+/*
+  Creates a key acting as `role`. The key itself is only in this response;
+  storage keeps a hash.
+*/
+pub async fn create_api_key(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<Claims>,
+    Json(data): Json<CreateApiKey>,
+) -> (StatusCode, Json<ApiResponse<CreatedApiKey>>) {
+    let name = data.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return error(StatusCode::BAD_REQUEST, "name must be 1 to 64 characters");
+    }
+    if data.role.is_empty() || !data.role.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return error(StatusCode::BAD_REQUEST, "invalid role");
+    }
+    if data.expires_in_days == Some(0) {
+        return error(StatusCode::BAD_REQUEST, "expires_in_days must be at least 1");
+    }
+
+    let now = Utc::now().timestamp();
+    let (id, secret, key) = api_key::generate();
+    let stored = ApiKey {
+        id,
+        name: name.to_string(),
+        role: data.role,
+        key_hash: api_key::hash(&secret),
+        created_by: claims.sub,
+        created_at: now,
+        expires_at: data.expires_in_days.map(|days| now + i64::from(days) * 86400),
+        last_used_at: None,
+    };
+
+    match state.storage.api_keys.create(&stored) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(CreatedApiKey { key, api_key: stored.into() }, "API key created")),
+        ),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create API key: {}", e)),
+    }
+}
+
+/*
+  Revocation is immediate: the key is deleted and the next request using
+  it is rejected.
+*/
+pub async fn delete_api_key(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    match state.storage.api_keys.delete(&id) {
+        Ok(true) => (StatusCode::OK, Json(ApiResponse::success((), "API key revoked"))),
+        Ok(false) => error(StatusCode::NOT_FOUND, "API key not found"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to revoke API key: {}", e)),
+    }
+}
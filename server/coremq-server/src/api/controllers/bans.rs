@@ -0,0 +1,74 @@
+use axum::{Extension, Json, extract::{Query, State}, http::StatusCode};
+use chrono::Utc;
+use tokio::sync::oneshot;
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState, engine_unavailable, error},
+    engine::AdminCommand,
+    models::{ban::{Ban, BanInfo, BanQuery, CreateBan, CreatedBan}, claims::Claims},
+};
+
+pub async fn get_bans(
+    State(state): State<ApiState>,
+) -> (StatusCode, Json<ApiResponse<Vec<BanInfo>>>) {
+    let mut bans = state.ingress.bans.list();
+    bans.sort_by_key(|ban| ban.created_at);
+    (StatusCode::OK, Json(ApiResponse::success(bans.into_iter().map(BanInfo::from).collect(), "Fetched bans successfully")))
+}
+
+/*
+  Bans a client id, username, IP or CIDR network. Connected clients that
+  match are disconnected before the response is sent; the response lists
+  them. Banning an existing value again replaces its reason and expiry.
+*/
+pub async fn create_ban(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<Claims>,
+    Json(data): Json<CreateBan>,
+) -> (StatusCode, Json<ApiResponse<CreatedBan>>) {
+    let value = match data.kind.normalize(&data.value) {
+        Ok(value) => value,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+    if data.expires_in_secs == Some(0) {
+        return error(StatusCode::BAD_REQUEST, "expires_in_secs must be at least 1");
+    }
+
+    let now = Utc::now().timestamp();
+    let ban = Ban {
+        kind: data.kind,
+        value,
+        reason: data.reason.filter(|reason| !reason.trim().is_empty()),
+        created_by: claims.sub,
+        created_at: now,
+        expires_at: data.expires_in_secs.map(|secs| now.saturating_add(i64::try_from(secs).unwrap_or(i64::MAX))),
+    };
+
+    if let Err(e) = state.ingress.bans.add(ban.clone()) {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save ban: {}", e));
+    }
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state.engine.send(AdminCommand::EnforceBans(reply_tx)).await.is_err() {
+        return engine_unavailable();
+    }
+    let disconnected = reply_rx.await.unwrap_or_default();
+
+    (StatusCode::OK, Json(ApiResponse::success(CreatedBan { ban: ban.into(), disconnected }, "Ban created")))
+}
+
+pub async fn delete_ban(
+    State(state): State<ApiState>,
+    Query(query): Query<BanQuery>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    let value = match query.kind.normalize(&query.value) {
+        Ok(value) => value,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.ingress.bans.remove(query.kind, &value) {
+        Ok(true) => (StatusCode::OK, Json(ApiResponse::success((), "Ban removed"))),
+        Ok(false) => error(StatusCode::NOT_FOUND, format!("No ban on {} '{}'", query.kind, value)),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to remove ban: {}", e)),
+    }
+}
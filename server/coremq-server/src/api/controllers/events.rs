@@ -0,0 +1,92 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::{Query, State, WebSocketUpgrade, ws::{Message, WebSocket}},
+    http::StatusCode,
+    response::{IntoResponse, Response, sse::{self, KeepAlive, Sse}},
+};
+use futures_util::{Stream, stream};
+
+use crate::{
+    api::api_state::{ApiState, error},
+    models::event::{Event, EventFilter, EventQuery},
+    services::events::EventSubscription,
+};
+
+/*
+  Next event passing the filter, `None` once the broker shuts down.
+*/
+async fn next_event(events: &mut EventSubscription, filter: &EventFilter) -> Option<Event> {
+    loop {
+        let event = events.recv().await?;
+        if filter.matches(&event.event) {
+            return Some(event);
+        }
+    }
+}
+
+fn event_stream(events: EventSubscription, filter: EventFilter) -> impl Stream<Item = Result<sse::Event, Infallible>> {
+    stream::unfold((events, filter), |(mut events, filter)| async move {
+        let event = next_event(&mut events, &filter).await?;
+        let sse = sse::Event::default()
+            .event(event.event.kind())
+            .data(serde_json::to_string(&event).unwrap_or_default());
+        Some((Ok(sse), (events, filter)))
+    })
+}
+
+/*
+  Server-Sent Events: one JSON object per event, with the event type as
+  the SSE event name.
+*/
+pub async fn stream_events(State(state): State<ApiState>, Query(query): Query<EventQuery>) -> Response {
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(e) => return error::<()>(StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let events = state.ingress.events.subscribe();
+    Sse::new(event_stream(events, filter))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/*
+  The same stream over a WebSocket, one JSON text message per event.
+*/
+pub async fn stream_events_ws(
+    State(state): State<ApiState>,
+    Query(query): Query<EventQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let filter = match query.filter() {
+        Ok(filter) => filter,
+        Err(e) => return error::<()>(StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let events = state.ingress.events.subscribe();
+    ws.on_upgrade(move |socket| forward_events(socket, events, filter))
+}
+
+async fn forward_events(mut socket: WebSocket, mut events: EventSubscription, filter: EventFilter) {
+    loop {
+        tokio::select! {
+            event = next_event(&mut events, &filter) => {
+                let Some(event) = event else {
+                    let _ = socket.send(Message::Close(None)).await;
+                    return;
+                };
+                let text = serde_json::to_string(&event).unwrap_or_default();
+                if socket.send(Message::Text(text)).await.is_err() {
+                    return;
+                }
+            }
+
+            /* Nothing is expected from the client; it may only close. */
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+}
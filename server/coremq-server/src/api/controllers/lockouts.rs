@@ -0,0 +1,37 @@
+use axum::{Json, extract::{Path, State}, http::StatusCode};
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState, error},
+    models::lockout::LockoutInfo,
+    services::lockout::LockoutKind,
+};
+
+/*
+  Usernames and IPs with recent failed logins, locked or not, most recent
+  first.
+*/
+pub async fn get_lockouts(
+    State(state): State<ApiState>,
+) -> (StatusCode, Json<ApiResponse<Vec<LockoutInfo>>>) {
+    let entries = state.auth.guard().entries().into_iter().map(LockoutInfo::from).collect();
+    (StatusCode::OK, Json(ApiResponse::success(entries, "Fetched lockouts successfully")))
+}
+
+/*
+  Forgets the failures of one username or IP, lifting any lockout.
+*/
+pub async fn clear_lockout(
+    State(state): State<ApiState>,
+    Path((kind, value)): Path<(String, String)>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    let kind: LockoutKind = match kind.parse() {
+        Ok(kind) => kind,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    if state.auth.guard().clear(kind, &value) {
+        (StatusCode::OK, Json(ApiResponse::success((), "Lockout cleared")))
+    } else {
+        error(StatusCode::NOT_FOUND, format!("No failed logins recorded for {} '{}'", kind, value))
+    }
+}
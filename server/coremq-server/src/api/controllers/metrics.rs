@@ -0,0 +1,22 @@
+use axum::{extract::State, response::Json};
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState},
+    models::metrics::{EngineMetrics, QueueDepth},
+};
+
+/*
+  GET /api/v1/metrics
+  Returns the current depth of the engine ingress queues and the number
+  of publishes waiting for subscriber mailboxes.
+*/
+pub async fn get_metrics(State(state): State<ApiState>) -> Json<ApiResponse<EngineMetrics>> {
+    let metrics = EngineMetrics {
+        connect_queue: QueueDepth::of(&state.ingress.connect_tx),
+        pubsub_queue: QueueDepth::of(&state.ingress.pubsub_tx),
+        admin_queue: QueueDepth::of(&state.engine),
+        publishers_waiting: state.ingress.routing.publishers_waiting(),
+    };
+
+    Json(ApiResponse::success(metrics, "successfully fetched metrics"))
+}
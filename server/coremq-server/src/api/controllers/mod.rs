@@ -2,4 +2,12 @@ pub mod sessions;
 pub mod listeners;
 pub mod users;
 pub mod topics;
+pub mod publish;
+pub mod metrics;
 
+pub mod policies;
+pub mod api_keys;
+pub mod lockouts;
+pub mod bans;
+pub mod events;
+pub mod traces;
@@ -0,0 +1,127 @@
+use axum::{Json, extract::{Query, State}, http::StatusCode};
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState, error},
+    models::policy::{PolicyRule, RoleAssignment},
+    services::policy::{PolicyChange, PolicyError},
+};
+
+fn policy_error<T>(e: PolicyError) -> (StatusCode, Json<ApiResponse<T>>) {
+    let status = match e {
+        PolicyError::AlreadyExists | PolicyError::Lockout => StatusCode::CONFLICT,
+        PolicyError::NotFound => StatusCode::NOT_FOUND,
+        PolicyError::Casbin(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error(status, e.to_string())
+}
+
+/*
+  Objects are API paths; actions are HTTP methods (upper-cased) or `*`.
+*/
+fn validate_policy(mut rule: PolicyRule) -> Result<PolicyRule, String> {
+    rule.subject = rule.subject.trim().to_string();
+    rule.object = rule.object.trim().to_string();
+    rule.action = rule.action.trim().to_ascii_uppercase();
+
+    if rule.subject.is_empty() {
+        return Err("subject must not be empty".to_string());
+    }
+    if !rule.object.starts_with('/') {
+        return Err("object must be a path starting with '/'".to_string());
+    }
+    if rule.action != "*" && !matches!(rule.action.as_str(), "GET" | "POST" | "PUT" | "PATCH" | "DELETE") {
+        return Err(format!("unsupported action: {}", rule.action));
+    }
+    Ok(rule)
+}
+
+fn validate_role(mut assignment: RoleAssignment) -> Result<RoleAssignment, String> {
+    assignment.subject = assignment.subject.trim().to_string();
+    assignment.role = assignment.role.trim().to_string();
+
+    if assignment.subject.is_empty() || assignment.role.is_empty() {
+        return Err("subject and role must not be empty".to_string());
+    }
+    if assignment.subject == assignment.role {
+        return Err("a role cannot inherit from itself".to_string());
+    }
+    Ok(assignment)
+}
+
+pub async fn get_policies(
+    State(state): State<ApiState>,
+) -> (StatusCode, Json<ApiResponse<Vec<PolicyRule>>>) {
+    let policies = state.policy.policies().into_iter().filter_map(PolicyRule::from_rule).collect();
+    (StatusCode::OK, Json(ApiResponse::success(policies, "Fetched policies successfully")))
+}
+
+pub async fn add_policy(
+    State(state): State<ApiState>,
+    Json(rule): Json<PolicyRule>,
+) -> (StatusCode, Json<ApiResponse<PolicyRule>>) {
+    let rule = match validate_policy(rule) {
+        Ok(rule) => rule,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.policy.apply(PolicyChange::AddPolicy(rule.clone().into_rule())).await {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::success(rule, "policy added"))),
+        Err(e) => policy_error(e),
+    }
+}
+
+/*
+  The rule to remove is given in the query string:
+  `DELETE /api/v1/policies?subject=user&object=/api/v1/topics&action=GET`.
+*/
+pub async fn remove_policy(
+    State(state): State<ApiState>,
+    Query(rule): Query<PolicyRule>,
+) -> (StatusCode, Json<ApiResponse<PolicyRule>>) {
+    let rule = match validate_policy(rule) {
+        Ok(rule) => rule,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.policy.apply(PolicyChange::RemovePolicy(rule.clone().into_rule())).await {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::success(rule, "policy removed"))),
+        Err(e) => policy_error(e),
+    }
+}
+
+pub async fn get_role_assignments(
+    State(state): State<ApiState>,
+) -> (StatusCode, Json<ApiResponse<Vec<RoleAssignment>>>) {
+    let roles = state.policy.roles().into_iter().filter_map(RoleAssignment::from_rule).collect();
+    (StatusCode::OK, Json(ApiResponse::success(roles, "Fetched role assignments successfully")))
+}
+
+pub async fn add_role_assignment(
+    State(state): State<ApiState>,
+    Json(assignment): Json<RoleAssignment>,
+) -> (StatusCode, Json<ApiResponse<RoleAssignment>>) {
+    let assignment = match validate_role(assignment) {
+        Ok(assignment) => assignment,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.policy.apply(PolicyChange::AddRole(assignment.clone().into_rule())).await {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::success(assignment, "role assignment added"))),
+        Err(e) => policy_error(e),
+    }
+}
+
+pub async fn remove_role_assignment(
+    State(state): State<ApiState>,
+    Query(assignment): Query<RoleAssignment>,
+) -> (StatusCode, Json<ApiResponse<RoleAssignment>>) {
+    let assignment = match validate_role(assignment) {
+        Ok(assignment) => assignment,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.policy.apply(PolicyChange::RemoveRole(assignment.clone().into_rule())).await {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::success(assignment, "role assignment removed"))),
+        Err(e) => policy_error(e),
+    }
+}
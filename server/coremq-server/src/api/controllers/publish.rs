@@ -0,0 +1,103 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, extract::{Query, State}, http::StatusCode};
+use tokio::time::Instant;
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState, error},
+    models::publish::{PublishQuery, PublishRequest, PublishResult},
+    protocol::packets::PublishPacket,
+    services::routing::AckTracker,
+};
+
+/*
+  Most messages accepted by one batch publish.
+*/
+const MAX_BATCH_SIZE: usize = 1000;
+
+const DEFAULT_ACK_TIMEOUT_MS: u64 = 5_000;
+const MAX_ACK_TIMEOUT_MS: u64 = 60_000;
+
+/*
+  POST /api/v1/publish
+  Publishes a message to a topic through the shared router.
+*/
+pub async fn publish_message(
+    State(state): State<ApiState>,
+    Query(query): Query<PublishQuery>,
+    Json(body): Json<PublishRequest>,
+) -> (StatusCode, Json<ApiResponse<PublishResult>>) {
+    match publish_all(&state, vec![body], &query).await {
+        Ok(mut results) => (StatusCode::OK, Json(ApiResponse::success(results.remove(0), "message published successfully"))),
+        Err((_, e)) => error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+/*
+  POST /api/v1/publish/batch
+  Publishes every message in order. Nothing is published unless all of
+  them are valid.
+*/
+pub async fn publish_batch(
+    State(state): State<ApiState>,
+    Query(query): Query<PublishQuery>,
+    Json(body): Json<Vec<PublishRequest>>,
+) -> (StatusCode, Json<ApiResponse<Vec<PublishResult>>>) {
+    if body.is_empty() || body.len() > MAX_BATCH_SIZE {
+        return error(StatusCode::BAD_REQUEST, format!("a batch holds 1 to {} messages", MAX_BATCH_SIZE));
+    }
+
+    match publish_all(&state, body, &query).await {
+        Ok(results) => (StatusCode::OK, Json(ApiResponse::success(results, "messages published successfully"))),
+        Err((i, e)) => error(StatusCode::BAD_REQUEST, format!("message {}: {}", i, e)),
+    }
+}
+
+/*
+  Fails with the index of the first invalid message.
+*/
+async fn publish_all(
+    state: &ApiState,
+    requests: Vec<PublishRequest>,
+    query: &PublishQuery,
+) -> Result<Vec<PublishResult>, (usize, String)> {
+    let mut packets = Vec::with_capacity(requests.len());
+    for (i, request) in requests.into_iter().enumerate() {
+        let payload = request.validate().map_err(|e| (i, e))?;
+        packets.push(PublishPacket {
+            packet_id: (request.qos > 0).then(|| state.next_packet_id()),
+            topic: request.topic,
+            payload,
+            qos: request.qos,
+            retain: request.retain,
+            dup: false,
+        });
+    }
+
+    let timeout_ms = query.timeout_ms.unwrap_or(DEFAULT_ACK_TIMEOUT_MS).min(MAX_ACK_TIMEOUT_MS);
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+
+    let mut published = Vec::with_capacity(packets.len());
+    for packet in packets {
+        let ack = (query.wait_for_ack && packet.qos > 0).then(|| Arc::new(AckTracker::default()));
+        let outcome = state.ingress.routing.publish_tracked(&packet, ack.clone()).await;
+        published.push((packet.topic, outcome, ack));
+    }
+
+    let mut results = Vec::with_capacity(published.len());
+    for (topic, outcome, ack) in published {
+        let (acks_expected, acknowledged) = match (&ack, query.wait_for_ack) {
+            (Some(ack), _) => (Some(ack.expected()), Some(ack.wait(deadline).await)),
+            (None, true) => (Some(0), Some(0)),
+            (None, false) => (None, None),
+        };
+        results.push(PublishResult {
+            topic,
+            matched: outcome.matched,
+            delivered: outcome.delivered,
+            acks_expected,
+            acknowledged,
+        });
+    }
+    Ok(results)
+}
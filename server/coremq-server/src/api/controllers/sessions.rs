@@ -1,32 +1,46 @@
 use axum::{extract::{Path, Query, State}, response::Json};
 
 use crate::{
-  api::api_state::{ApiResponse, ApiState}, engine::AdminCommand, models::{pagination::Page, session::Session, session_query::SessionQuery}
+  api::api_state::{ApiResponse, ApiState, engine_unavailable, error},
+  engine::AdminCommand,
+  models::{
+    pagination::{MAX_PAGE_SIZE, Page},
+    session::Session,
+    session_detail::{ClientSubscription, SessionDetail, SubscriptionQuery},
+    session_query::SessionQuery,
+  },
+  protocol::packets::SubscribeFilter,
+  utils::topic::is_valid_filter,
 };
 
 use tokio::sync::oneshot;
 use axum::http::StatusCode;
 
+fn not_connected<T>(client_id: &str) -> (StatusCode, Json<ApiResponse<T>>) {
+    error(StatusCode::NOT_FOUND, format!("Client '{}' is not connected", client_id))
+}
+
 pub async fn get_sessions(
     State(state): State<ApiState>,
     Query(params): Query<SessionQuery>
-) -> Result<Json<ApiResponse< Page<Session>>>, StatusCode> {
+) -> (StatusCode, Json<ApiResponse<Page<Session>>>) {
     let page = params.page.unwrap_or(0);  // default page = 0
-    let size = params.size.unwrap_or(10); // default size = 10
-   // let search = params.search.unwrap_or_default(); // default empty search
+    let size = params.size.unwrap_or(10).clamp(1, MAX_PAGE_SIZE); // default size = 10
+    let filter = match params.filter() {
+        Ok(filter) => filter,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
 
     let (reply_tx, reply_rx) = oneshot::channel();
 
-    state
-        .engine
-        .send(AdminCommand::GetClients(reply_tx, page, size))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let sessions = reply_rx
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if state.engine.send(AdminCommand::GetClients(reply_tx, filter, page, size)).await.is_err() {
+        return engine_unavailable();
+    }
 
-    Ok(Json(ApiResponse::success(sessions, "successfully fetched data")))
+    match reply_rx.await {
+        Ok(sessions) => (StatusCode::OK, Json(ApiResponse::success(sessions, "successfully fetched data"))),
+        Err(_) => engine_unavailable(),
+    }
 }
 
 pub async fn disconnect_session(
@@ -38,6 +52,7 @@ pub async fn disconnect_session(
     state
         .engine
         .send(AdminCommand::DisconnectClient(client_id.clone(), reply_tx))
+        .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let found = reply_rx
@@ -51,3 +66,70 @@ pub async fn disconnect_session(
     }
 }
 
+
+pub async fn get_session(
+    State(state): State<ApiState>,
+    Path(client_id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<SessionDetail>>) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state.engine.send(AdminCommand::GetClient(client_id.clone(), reply_tx)).await.is_err() {
+        return engine_unavailable();
+    }
+
+    match reply_rx.await {
+        Ok(Some(session)) => (StatusCode::OK, Json(ApiResponse::success(session.into(), "successfully fetched data"))),
+        Ok(None) => not_connected(&client_id),
+        Err(_) => engine_unavailable(),
+    }
+}
+
+/*
+  Subscribes a connected client as if it had sent SUBSCRIBE itself, for
+  devices whose subscriptions can't be changed in their firmware. The
+  subscription lasts as long as the session.
+*/
+pub async fn add_subscription(
+    State(state): State<ApiState>,
+    Path(client_id): Path<String>,
+    Json(data): Json<ClientSubscription>,
+) -> (StatusCode, Json<ApiResponse<ClientSubscription>>) {
+    if !is_valid_filter(&data.topic) {
+        return error(StatusCode::BAD_REQUEST, format!("invalid topic filter '{}'", data.topic));
+    }
+    if data.qos > 2 {
+        return error(StatusCode::BAD_REQUEST, "qos must be 0, 1 or 2");
+    }
+
+    let filter = SubscribeFilter { topic: data.topic.clone(), qos: data.qos };
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state.engine.send(AdminCommand::SubscribeClient(client_id.clone(), filter, reply_tx)).await.is_err() {
+        return engine_unavailable();
+    }
+
+    match reply_rx.await {
+        Ok(Some(qos)) => (
+            StatusCode::OK,
+            Json(ApiResponse::success(ClientSubscription { topic: data.topic, qos }, "Subscription added")),
+        ),
+        Ok(None) => not_connected(&client_id),
+        Err(_) => engine_unavailable(),
+    }
+}
+
+pub async fn remove_subscription(
+    State(state): State<ApiState>,
+    Path(client_id): Path<String>,
+    Query(query): Query<SubscriptionQuery>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state.engine.send(AdminCommand::UnsubscribeClient(client_id.clone(), query.topic.clone(), reply_tx)).await.is_err() {
+        return engine_unavailable();
+    }
+
+    match reply_rx.await {
+        Ok(Some(true)) => (StatusCode::OK, Json(ApiResponse::success((), "Subscription removed"))),
+        Ok(Some(false)) => error(StatusCode::NOT_FOUND, format!("Client '{}' is not subscribed to '{}'", client_id, query.topic)),
+        Ok(None) => not_connected(&client_id),
+        Err(_) => engine_unavailable(),
+    }
+}
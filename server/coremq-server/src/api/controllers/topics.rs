@@ -1,70 +1,63 @@
-use axum::{extract::State, response::Json};
+use axum::{extract::{Query, State}, response::Json};
 use axum::http::StatusCode;
 use tokio::sync::oneshot;
 
 use crate::{
-    api::api_state::{ApiResponse, ApiState},
+    api::api_state::{ApiResponse, ApiState, engine_unavailable, error},
     engine::AdminCommand,
-    models::topic_info::{TopicInfo, PublishRequest},
-    protocol::packets::PublishPacket,
+    models::{
+        pagination::{MAX_PAGE_SIZE, Page},
+        topic_info::{SubscribersQuery, TopicInfo, TopicQuery, TopicSubscriber},
+    },
 };
 
 /*
   GET /api/v1/topics
-  Returns active topics with subscriber counts.
+  Returns one page of subscribed and published topics with subscriber
+  counts and publish counters.
 */
 pub async fn get_topics(
     State(state): State<ApiState>,
-) -> Result<Json<ApiResponse<Vec<TopicInfo>>>, StatusCode> {
-    let (reply_tx, reply_rx) = oneshot::channel();
-
-    state
-        .engine
-        .send(AdminCommand::GetTopics(reply_tx))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let topics = reply_rx
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Query(params): Query<TopicQuery>,
+) -> (StatusCode, Json<ApiResponse<Page<TopicInfo>>>) {
+    let page = params.page.unwrap_or(0);
+    let size = params.size.unwrap_or(10).clamp(1, MAX_PAGE_SIZE);
+    let filter = match params.filter() {
+        Ok(filter) => filter,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
 
-    Ok(Json(ApiResponse::success(topics, "successfully fetched topics")))
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state.engine.send(AdminCommand::GetTopics(reply_tx, filter, page, size)).await.is_err() {
+        return engine_unavailable();
+    }
+
+    match reply_rx.await {
+        Ok(topics) => (StatusCode::OK, Json(ApiResponse::success(topics, "successfully fetched topics"))),
+        Err(_) => engine_unavailable(),
+    }
 }
 
 /*
-  POST /api/v1/publish
-  Publishes a message to a topic via the engine.
+  GET /api/v1/topics/subscribers
+  Lists the clients subscribed with a filter, or reached by a topic.
 */
-pub async fn publish_message(
+pub async fn get_subscribers(
     State(state): State<ApiState>,
-    Json(body): Json<PublishRequest>,
-) -> Result<Json<ApiResponse<String>>, StatusCode> {
-    let packet_id = match body.qos {
-        1 | 2 => Some(state.next_packet_id()),
-        _ => None,
-    };
-
-    let packet = PublishPacket {
-        packet_id,
-        topic: body.topic.clone(),
-        payload: body.payload.into_bytes(),
-        qos: body.qos,
-        retain: body.retain,
-        dup: false,
+    Query(params): Query<SubscribersQuery>,
+) -> (StatusCode, Json<ApiResponse<Vec<TopicSubscriber>>>) {
+    let lookup = match params.lookup() {
+        Ok(lookup) => lookup,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
     };
 
     let (reply_tx, reply_rx) = oneshot::channel();
-
-    state
-        .engine
-        .send(AdminCommand::PublishMessage(packet, reply_tx))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    reply_rx
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(ApiResponse::success(
-        body.topic,
-        "message published successfully",
-    )))
+    if state.engine.send(AdminCommand::GetSubscribers(lookup, reply_tx)).await.is_err() {
+        return engine_unavailable();
+    }
+
+    match reply_rx.await {
+        Ok(subscribers) => (StatusCode::OK, Json(ApiResponse::success(subscribers, "successfully fetched subscribers"))),
+        Err(_) => engine_unavailable(),
+    }
 }
@@ -0,0 +1,77 @@
+use axum::{Extension, Json, extract::{Path, Query, State}, http::StatusCode};
+
+use crate::{
+    api::api_state::{ApiResponse, ApiState, error},
+    models::{claims::Claims, trace::{CreateTrace, TraceDetail, TraceInfo, TraceRecordQuery}},
+    services::trace::{MAX_TRACES, TraceError},
+};
+
+fn trace_error<T>(id: &str, e: TraceError) -> (StatusCode, Json<ApiResponse<T>>) {
+    match e {
+        TraceError::NotFound => error(StatusCode::NOT_FOUND, format!("Trace '{}' not found", id)),
+        TraceError::TooMany => error(StatusCode::CONFLICT, format!("At most {} traces are kept; delete one first", MAX_TRACES)),
+    }
+}
+
+pub async fn get_traces(
+    State(state): State<ApiState>,
+) -> (StatusCode, Json<ApiResponse<Vec<TraceInfo>>>) {
+    let mut traces = state.ingress.traces.list();
+    traces.sort_by_key(|trace| trace.started_at);
+    (StatusCode::OK, Json(ApiResponse::success(traces, "Fetched traces successfully")))
+}
+
+/*
+  Starts capturing the packets of a client id, topic filter or remote
+  address. The trace stops on its own after `duration_secs`.
+*/
+pub async fn create_trace(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<Claims>,
+    Json(data): Json<CreateTrace>,
+) -> (StatusCode, Json<ApiResponse<TraceInfo>>) {
+    let options = match data.options() {
+        Ok(options) => options,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.ingress.traces.start(options, &claims.sub) {
+        Ok(trace) => (StatusCode::OK, Json(ApiResponse::success(trace, "Trace started"))),
+        Err(e) => trace_error(&data.value, e),
+    }
+}
+
+/*
+  The trace and its buffered records. Polling with `after` set to the
+  last `seq` seen returns only the new ones.
+*/
+pub async fn get_trace(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+    Query(query): Query<TraceRecordQuery>,
+) -> (StatusCode, Json<ApiResponse<TraceDetail>>) {
+    match state.ingress.traces.get(&id, query.after.unwrap_or(0)) {
+        Ok(detail) => (StatusCode::OK, Json(ApiResponse::success(detail, "Fetched trace successfully"))),
+        Err(e) => trace_error(&id, e),
+    }
+}
+
+pub async fn stop_trace(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<TraceInfo>>) {
+    match state.ingress.traces.stop(&id) {
+        Ok(trace) => (StatusCode::OK, Json(ApiResponse::success(trace, "Trace stopped"))),
+        Err(e) => trace_error(&id, e),
+    }
+}
+
+pub async fn delete_trace(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    match state.ingress.traces.delete(&id) {
+        Ok(()) => (StatusCode::OK, Json(ApiResponse::success((), "Trace deleted"))),
+        Err(e) => trace_error(&id, e),
+    }
+}
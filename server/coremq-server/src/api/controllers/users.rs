@@ -1,86 +1,308 @@
-use axum::{Json, extract::State, http::StatusCode};
-use crate::{api::api_state::{ApiResponse, ApiState}, enums::role::RoleType, models::{login::{Login, Token}, user::User}, utils::{self, password::hash_password}};
+use std::net::SocketAddr;
 
+use chrono::Utc;
+use axum::{Extension, Json, extract::{ConnectInfo, Path, State}, http::StatusCode};
+use crate::{api::api_state::{ApiResponse, ApiState, error}, enums::{jwt::JwtType, role::RoleType}, models::{claims::Claims, login::{Login, RefreshRequest, Token}, user::{ChangePassword, CreateUser, RoleUpdate, UpdateUser, User, UserInfo}}, services::{auth::LoginError, jwt::REFRESH_TOKEN_TTL}, storage::redb::user::UserWrite, utils::{self, password::hash_password}};
 
 pub async fn create_user(
     State(state): State<ApiState>,
-    Json(mut user): Json<User>,
-) -> (StatusCode, Json<ApiResponse<User>>) {
+    Json(data): Json<CreateUser>,
+) -> (StatusCode, Json<ApiResponse<UserInfo>>) {
+    if data.username.is_empty() || data.username.contains('/') {
+        return error(StatusCode::BAD_REQUEST, "invalid username");
+    }
+    if data.password.is_empty() {
+        return error(StatusCode::BAD_REQUEST, "password must not be empty");
+    }
+    if let Err(e) = data.role.parse::<RoleType>() {
+        return error(StatusCode::BAD_REQUEST, e);
+    }
 
-    let hashed_password = match  hash_password(&user.password_hash) {
+    let hashed_password = match  hash_password(&data.password) {
         Ok(passwd) => passwd,
         Err(e) => {
-            return  (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())));
+            return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
         }
     };
 
-    user.password_hash = hashed_password;
+    let user = User {
+        username: data.username,
+        password_hash: hashed_password,
+        role: data.role,
+        must_change_password: false,
+        password_changed_at: 0,
+    };
 
-    match state.storage.user.create(&user) {
-        Ok(()) => (
-          
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse::success(user, "successfully created"))
-            
+    match state.storage.user.insert_new(&user) {
+        Ok(true) => (
+                StatusCode::OK,
+                Json(ApiResponse::success(user.into(), "successfully created"))
         ),
+        Ok(false) => error(StatusCode::CONFLICT, format!("user {} already exists", user.username)),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create user: {}", e)),
+    }
+}
+
+pub async fn get_user(
+    State(state): State<ApiState>,
+    Path(username): Path<String>,
+) -> (StatusCode, Json<ApiResponse<UserInfo>>) {
+    match state.storage.user.get(&username) {
+        Ok(Some(user)) => (StatusCode::OK, Json(ApiResponse::success(user.into(), "Fetched user successfully"))),
+        Ok(None) => error(StatusCode::NOT_FOUND, "user not found"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch user: {}", e)),
+    }
+}
+
+/*
+  Admin update of another user's role and/or password. Role changes follow
+  the same rules as `update_role`; a new password invalidates the user's
+  existing tokens.
+*/
+pub async fn update_user(
+    State(state): State<ApiState>,
+    Path(username): Path<String>,
+    Json(data): Json<UpdateUser>,
+) -> (StatusCode, Json<ApiResponse<UserInfo>>) {
+    let role = match data.role.as_deref().map(str::parse::<RoleType>) {
+        Some(Ok(role)) => Some(role),
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, e),
+        None => None,
+    };
+
+    let password_hash = match data.password.as_deref() {
+        Some("") => return error(StatusCode::BAD_REQUEST, "password must not be empty"),
+        Some(password) => match hash_password(password) {
+            Ok(hash) => Some(hash),
+            Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        },
+        None => None,
+    };
+
+    let updated = state.storage.user.update(&username, |user| {
+        if let Some(role) = role {
+            user.role = role.to_string();
+        }
+        if let Some(hash) = password_hash {
+            user.password_hash = hash;
+            user.password_changed_at = Utc::now().timestamp();
+        }
+    });
 
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse::error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to fetch users: {}", e),
-            ))
-        )
+    match updated {
+        Ok(UserWrite::Done(user)) => (StatusCode::OK, Json(ApiResponse::success(user.into(), "user updated"))),
+        Ok(UserWrite::NotFound) => error(StatusCode::NOT_FOUND, "user not found"),
+        Ok(UserWrite::LastAdmin) => error(StatusCode::CONFLICT, "cannot demote the last admin"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update user: {}", e)),
+    }
+}
+
+/*
+  Deleting a user also invalidates their tokens (see `auth_middleware`).
+  The last admin can't be deleted.
+*/
+pub async fn delete_user(
+    State(state): State<ApiState>,
+    Path(username): Path<String>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    match state.storage.user.delete(&username) {
+        Ok(UserWrite::Done(())) => (StatusCode::OK, Json(ApiResponse::success((), "user deleted"))),
+        Ok(UserWrite::NotFound) => error(StatusCode::NOT_FOUND, "user not found"),
+        Ok(UserWrite::LastAdmin) => error(StatusCode::CONFLICT, "cannot delete the last admin"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete user: {}", e)),
+    }
+}
+
+/*
+  Self-service password change for the logged-in user. This is the only
+  call (besides logout) allowed while a password change is required.
+  Tokens issued before the change stop working, the caller's included, so
+  the user has to log in again.
+*/
+pub async fn change_password(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<Claims>,
+    Json(data): Json<ChangePassword>,
+) -> (StatusCode, Json<ApiResponse<UserInfo>>) {
+    let user = match state.storage.user.get(&claims.sub) {
+        Ok(Some(user)) => user,
+        Ok(None) => return error(StatusCode::NOT_FOUND, "user not found"),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    if !utils::password::verify(&data.current_password, &user.password_hash) {
+        return error(StatusCode::BAD_REQUEST, "wrong password");
+    }
+    if data.new_password.is_empty() || data.new_password == data.current_password {
+        return error(StatusCode::BAD_REQUEST, "new password must be non-empty and differ from the current one");
+    }
+
+    let hash = match hash_password(&data.new_password) {
+        Ok(hash) => hash,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let updated = state.storage.user.update(&claims.sub, |user| {
+        user.password_hash = hash;
+        user.must_change_password = false;
+        user.password_changed_at = Utc::now().timestamp();
+    });
+
+    /*
+      `iat` has a resolution of one second, so the timestamp alone misses a
+      token issued in the same second as the change. Revoke the caller's
+      pair explicitly.
+    */
+    if let Ok(UserWrite::Done(_)) = updated
+        && let Err(e) = state.storage.revoked.revoke(&claims.jti, (claims.iat as i64 + REFRESH_TOKEN_TTL) as u64)
+    {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to revoke token: {}", e));
+    }
+
+    match updated {
+        Ok(UserWrite::Done(user)) => (StatusCode::OK, Json(ApiResponse::success(user.into(), "password changed"))),
+        Ok(_) => error(StatusCode::NOT_FOUND, "user not found"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to change password: {}", e)),
     }
 }
-// delete user 
 
 // login user 
 
+/*
+  Unknown users and wrong passwords get the same response, so the login
+  can't be used to find out which usernames exist.
+*/
 pub async fn login(
     State(state): State<ApiState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(data): Json<Login>,
 )-> (StatusCode, Json<ApiResponse<Token>>) {
-    let user = match state.storage.user.get(&data.username) {
-        Ok(Some(user)) => user,
-        _ => return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(StatusCode::BAD_REQUEST, "client not found")))
-        
+    let user = match state.auth.login(&data.username, &data.password, addr.ip()).await {
+        Ok(user) => user,
+        Err(LoginError::Invalid) => return error(StatusCode::UNAUTHORIZED, "Invalid username or password"),
+        Err(LoginError::Locked(retry_after)) => {
+            return error(StatusCode::TOO_MANY_REQUESTS, format!("Too many failed attempts, try again in {} seconds", retry_after));
+        }
+        Err(LoginError::Storage(e)) => return error(StatusCode::INTERNAL_SERVER_ERROR, e),
     };
 
-    if !utils::password::verify(&data.password, &user.password_hash) {
-        return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(StatusCode::BAD_REQUEST, "wrong password")));
-    }
+    let role: RoleType = match user.role.parse() {
+        Ok(role) => role,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    };
 
-    let token = match state.jwt_service.generate(user.username, RoleType::Admin) {
+    let mut token = match state.jwt_service.generate(user.username, role) {
         Ok(token) => token,
         Err(e) => {
-            return (StatusCode::BAD_REQUEST, Json(ApiResponse::error(StatusCode::BAD_REQUEST, e.to_string())));
+            return error(StatusCode::BAD_REQUEST, e.to_string());
         }
     };
+    token.password_change_required = user.must_change_password;
 
     (StatusCode::OK, Json(ApiResponse::success(token, "successfully created")))
+}
 
-    
+/*
+  Exchanges a refresh token for a new token pair. The old pair is revoked,
+  so each refresh token can be used once.
+*/
+pub async fn refresh(
+    State(state): State<ApiState>,
+    Json(data): Json<RefreshRequest>,
+) -> (StatusCode, Json<ApiResponse<Token>>) {
+    let unauthorized = || error(StatusCode::UNAUTHORIZED, "Invalid refresh token");
+
+    let claims = match state.jwt_service.parse_as(&data.refresh_token, JwtType::RefreshToken) {
+        Ok(claims) => claims,
+        Err(_) => return unauthorized(),
+    };
+
+    /*
+      Revoking is what claims the token: of several concurrent refreshes
+      with the same token only the one that revoked it gets a new pair.
+    */
+    match state.storage.revoked.revoke(&claims.jti, claims.exp as u64) {
+        Ok(true) => {}
+        Ok(false) => return unauthorized(),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+
+    /*
+      The user may have been deleted or changed their password since the
+      token was issued. The new pair carries the role stored now, not the
+      one in the old token.
+    */
+    let user = match state.storage.user.get(&claims.sub) {
+        Ok(Some(user)) if (claims.iat as i64) >= user.password_changed_at => user,
+        Ok(_) => return unauthorized(),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let role: RoleType = match user.role.parse() {
+        Ok(role) => role,
+        Err(_) => return unauthorized(),
+    };
+
+    match state.jwt_service.generate(claims.sub, role) {
+        Ok(mut token) => {
+            token.password_change_required = user.must_change_password;
+            (StatusCode::OK, Json(ApiResponse::success(token, "token refreshed")))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/*
+  Revokes the caller's token pair. The deny-list entry lives until the
+  refresh token of the pair would have expired.
+*/
+pub async fn logout(
+    State(state): State<ApiState>,
+    Extension(claims): Extension<Claims>,
+) -> (StatusCode, Json<ApiResponse<()>>) {
+    if claims.token_type != JwtType::AccessToken.as_str() {
+        return error(StatusCode::BAD_REQUEST, "only login sessions can log out; revoke API keys instead");
+    }
+
+    let expires_at = (claims.iat as i64 + REFRESH_TOKEN_TTL) as u64;
+
+    match state.storage.revoked.revoke(&claims.jti, expires_at) {
+        Ok(_) => (StatusCode::OK, Json(ApiResponse::success((), "logged out"))),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to revoke token: {}", e)),
+    }
 }
-// logout user 
 
-// delete user
+/*
+  Changes a user's role. Tokens issued with the old role stop working (see
+  `auth_middleware`), so the user has to log in again.
+*/
+pub async fn update_role(
+    State(state): State<ApiState>,
+    Path(username): Path<String>,
+    Json(data): Json<RoleUpdate>,
+) -> (StatusCode, Json<ApiResponse<UserInfo>>) {
+    let role: RoleType = match data.role.parse() {
+        Ok(role) => role,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e),
+    };
+
+    match state.storage.user.update(&username, |user| user.role = role.to_string()) {
+        Ok(UserWrite::Done(user)) => (StatusCode::OK, Json(ApiResponse::success(user.into(), "role updated"))),
+        Ok(UserWrite::NotFound) => error(StatusCode::NOT_FOUND, "user not found"),
+        Ok(UserWrite::LastAdmin) => error(StatusCode::CONFLICT, "cannot demote the last admin"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update role: {}", e)),
+    }
+}
 
 // get users
 pub async fn get_all_users(
     State(state): State<ApiState>,
-) -> (StatusCode, Json<ApiResponse<Vec<User>>>) {
+) -> (StatusCode, Json<ApiResponse<Vec<UserInfo>>>) {
     match state.storage.user.get_all() {
         Ok(users) => (
             StatusCode::OK,
-            Json(ApiResponse::success(users, "Fetched all users successfully")),
-        ),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiResponse::error(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to fetch users: {}", e),
-            )),
+            Json(ApiResponse::success(users.into_iter().map(UserInfo::from).collect(), "Fetched all users successfully")),
         ),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch users: {}", e)),
     }
 }
@@ -1,11 +1,17 @@
-use axum::{Router, http::StatusCode, middleware, response::Html, routing::{delete, get, post}};
+use axum::{Router, http::StatusCode, middleware, response::Html, routing::{delete, get, post, put}};
 use tower_http::cors::{Any, CorsLayer};
 
 
-use crate::api::{ api_state::ApiState, controllers::{sessions, listeners, users, topics}, auth};
+use crate::api::{ api_state::ApiState, controllers::{sessions, listeners, users, topics, publish, metrics, policies, api_keys, lockouts, bans, events, traces}, auth};
 
 pub struct  RouterHandler {}
 
+impl Default for RouterHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RouterHandler  {
     pub fn new() -> Self {
         RouterHandler {  }
@@ -16,9 +22,14 @@ impl RouterHandler  {
         .nest("/api/v1", self.get_session_routes())
         .nest("/api/v1", self.get_user_routes())
         .nest("/api/v1", self.get_topic_routes())
+        .nest("/api/v1", self.get_policy_routes())
+        .nest("/api/v1", self.get_trace_routes())
         .nest("/api/v1/public", self.auth_routes())
         .route("/api/v1/listeners", get(listeners::get_listeners))
         .route("/api/v1/listeners/:port", delete(listeners::stop_listener))
+        .route("/api/v1/metrics", get(metrics::get_metrics))
+        .route("/api/v1/events", get(events::stream_events))
+        .route("/api/v1/events/ws", get(events::stream_events_ws))
         .fallback(not_found)
         .layer(middleware::from_fn_with_state( state.clone(),  auth::casbin::auth_middleware))
         .layer(self.cors())
@@ -29,23 +40,49 @@ impl RouterHandler  {
     pub fn auth_routes(&self) -> Router<ApiState> {
         Router::new()
         .route("/login", post(users::login))
+        .route("/refresh", post(users::refresh))
     }
 
     pub fn get_session_routes(&self) -> Router<ApiState> {
         Router::new()
         .route("/sessions", get(sessions::get_sessions))
-        .route("/sessions/:client_id", delete(sessions::disconnect_session))
+        .route("/sessions/:client_id", get(sessions::get_session).delete(sessions::disconnect_session))
+        .route("/sessions/:client_id/subscriptions", post(sessions::add_subscription).delete(sessions::remove_subscription))
     }
 
     pub fn get_user_routes(&self) -> Router<ApiState> {
         Router::new()
         .route("/users", post(users::create_user).get(users::get_all_users) )
+        .route("/users/:username", get(users::get_user).put(users::update_user).delete(users::delete_user))
+        .route("/users/:username/role", put(users::update_role))
+        .route("/account/password", post(users::change_password))
+        .route("/logout", post(users::logout))
+    }
+
+    pub fn get_policy_routes(&self) -> Router<ApiState> {
+        Router::new()
+        .route("/policies", get(policies::get_policies).post(policies::add_policy).delete(policies::remove_policy))
+        .route("/api-keys", get(api_keys::get_api_keys).post(api_keys::create_api_key))
+        .route("/api-keys/:id", delete(api_keys::delete_api_key))
+        .route("/lockouts", get(lockouts::get_lockouts))
+        .route("/bans", get(bans::get_bans).post(bans::create_ban).delete(bans::delete_ban))
+        .route("/lockouts/:kind/:value", delete(lockouts::clear_lockout))
+        .route("/role-assignments", get(policies::get_role_assignments).post(policies::add_role_assignment).delete(policies::remove_role_assignment))
+    }
+
+    pub fn get_trace_routes(&self) -> Router<ApiState> {
+        Router::new()
+        .route("/traces", get(traces::get_traces).post(traces::create_trace))
+        .route("/traces/:id", get(traces::get_trace).delete(traces::delete_trace))
+        .route("/traces/:id/stop", post(traces::stop_trace))
     }
 
     pub fn get_topic_routes(&self) -> Router<ApiState> {
         Router::new()
         .route("/topics", get(topics::get_topics))
-        .route("/publish", post(topics::publish_message))
+        .route("/topics/subscribers", get(topics::get_subscribers))
+        .route("/publish", post(publish::publish_message))
+        .route("/publish/batch", post(publish::publish_batch))
     }
 
      fn cors(&self) -> CorsLayer {
@@ -0,0 +1,344 @@
+use std::{
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, atomic::{AtomicU16, AtomicU64, Ordering}},
+};
+
+use bytes::Bytes;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+
+use crate::{
+    api::{api_state::ApiState, router::RouterHandler},
+    engine::{AdminCommand, ConnectCommand, Engine, EngineChannels, LOCAL_CLIENT_PREFIX, PubSubCommand},
+    enums::MqttChannel,
+    models::{config::Config, event::DisconnectReason, listener::ListenerConfig},
+    pkg,
+    protocol::packets::{ConnectPacket, PublishPacket, SubscribeFilter, SubscribePacket, PROTOCOL_LEVEL_3_1_1},
+    services::{RoutingService, SessionService, TopicService, auth::AuthService, ban::BanService, events::EventService, jwt::JwtService, trace::TraceService},
+    storage::redb::Storage,
+    transport::ProtocolState,
+    utils::packet_id,
+};
+
+/*
+  Entry point for running CoreMQ inside another process.
+
+    let broker = Broker::builder(config).admin_api(false).start().await?;
+    let mut sub = broker.subscribe("sensors/#", 1).await?;
+    broker.publish("sensors/1/temp", "23.5", 1, false).await;
+    let msg = sub.recv().await;
+    broker.shutdown().await?;
+*/
+pub struct Broker;
+
+impl Broker {
+    pub fn builder(config: Config) -> BrokerBuilder {
+        BrokerBuilder { config }
+    }
+}
+
+pub struct BrokerBuilder {
+    config: Config,
+}
+
+impl BrokerBuilder {
+    /*
+      Enables or disables the admin REST API, overriding `http.enabled`.
+    */
+    pub fn admin_api(mut self, enabled: bool) -> Self {
+        self.config.http.enabled = enabled;
+        self
+    }
+
+    /*
+      Overrides the redb database file, `storage.path` in the config.
+    */
+    pub fn storage_path(mut self, path: impl Into<String>) -> Self {
+        self.config.storage.path = path.into();
+        self
+    }
+
+    /*
+      Opens storage, binds every listener and spawns the engine. Returns once
+      the broker is accepting connections.
+    */
+    pub async fn start(self) -> anyhow::Result<BrokerHandle> {
+        let config = self.config;
+
+        if let Some(dir) = Path::new(&config.storage.path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let db = pkg::db::new(&config.storage.path)?;
+
+        let client_service = Arc::new(SessionService::new());
+        let topic_service = Arc::new(TopicService::new());
+        let routing_service = Arc::new(RoutingService::new(client_service.clone(), topic_service.clone()));
+        let jwt_service = Arc::new(JwtService::new(&config.middleware));
+        let storage = Arc::new(Storage::new(Arc::new(db)));
+        let policy = Arc::new(pkg::enforcer::new(config.middleware.clone(), storage.policy.clone()).await?);
+        let events = Arc::new(EventService::new(config.engine.event_buffer_size));
+        let auth = Arc::new(AuthService::new(storage.clone(), config.lockout.clone(), events.clone()));
+        let bans = Arc::new(BanService::new(storage.bans.clone())?);
+
+        let (connect_tx, connect_rx) = mpsc::channel::<ConnectCommand>(config.engine.connect_queue_size);
+        let (pubsub_tx, pubsub_rx) = mpsc::channel::<PubSubCommand>(config.engine.pubsub_queue_size);
+        let (admin_tx, admin_rx) = mpsc::channel::<AdminCommand>(config.engine.admin_queue_size);
+
+        let channels = EngineChannels {
+            connect_rx,
+            pubsub_rx,
+            admin_rx,
+        };
+
+        let ingress = Arc::new(ProtocolState {
+            connect_tx,
+            pubsub_tx,
+            routing: routing_service,
+            bans: bans.clone(),
+            events: events.clone(),
+            traces: Arc::new(TraceService::new()),
+        });
+
+        let mut engine = Engine::new(client_service, topic_service, ingress.routing.clone(), bans, events, config.clone(), channels);
+        engine.start_listeners(ingress.clone()).await?;
+        let listeners = engine.get_listeners();
+
+        let engine_task = tokio::spawn(async move {
+            engine.run().await;
+        });
+
+        let packet_ids = Arc::new(AtomicU16::new(1));
+        let (admin_stop_tx, mut admin_stop_rx) = watch::channel(false);
+
+        let mut admin_addr = None;
+        let mut admin_task = None;
+        if config.http.enabled {
+            let state = ApiState {
+                jwt_service,
+                policy,
+                auth,
+                engine: admin_tx.clone(),
+                ingress: ingress.clone(),
+                storage: storage.clone(),
+                packet_id_counter: packet_ids.clone(),
+            };
+
+            let listener = TcpListener::bind((config.http.host.as_str(), config.http.port)).await?;
+            admin_addr = Some(listener.local_addr()?);
+
+            let router = RouterHandler::new().create_router(state);
+            admin_task = Some(tokio::spawn(async move {
+                let shutdown = async move {
+                    let _ = admin_stop_rx.changed().await;
+                };
+                if let Err(e) = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>()).with_graceful_shutdown(shutdown).await {
+                    eprintln!("Admin API error: {}", e);
+                }
+            }));
+        }
+
+        Ok(BrokerHandle {
+            admin_tx,
+            ingress,
+            storage,
+            listeners,
+            admin_addr,
+            admin_stop_tx,
+            admin_task,
+            engine_task,
+            packet_ids,
+            next_local_id: AtomicU64::new(1),
+        })
+    }
+}
+
+/*
+  A running broker. Dropping the handle leaves the broker running; call
+  `shutdown` to stop it gracefully.
+*/
+pub struct BrokerHandle {
+    admin_tx: mpsc::Sender<AdminCommand>,
+    ingress: Arc<ProtocolState>,
+    storage: Arc<Storage>,
+    listeners: Vec<ListenerConfig>,
+    admin_addr: Option<SocketAddr>,
+    admin_stop_tx: watch::Sender<bool>,
+    admin_task: Option<JoinHandle<()>>,
+    engine_task: JoinHandle<()>,
+    packet_ids: Arc<AtomicU16>,
+    next_local_id: AtomicU64,
+}
+
+impl BrokerHandle {
+    /*
+      MQTT listeners started with the broker, with their bound ports.
+    */
+    pub fn listeners(&self) -> &[ListenerConfig] {
+        &self.listeners
+    }
+
+    pub fn admin_addr(&self) -> Option<SocketAddr> {
+        self.admin_addr
+    }
+
+    /*
+      Sender for engine admin commands, the same channel the REST API uses.
+    */
+    pub fn admin(&self) -> mpsc::Sender<AdminCommand> {
+        self.admin_tx.clone()
+    }
+
+    pub fn storage(&self) -> Arc<Storage> {
+        self.storage.clone()
+    }
+
+    /*
+      Publishes from inside the process and returns the number of
+      subscribers the message was delivered to. Waits while a subscriber's
+      mailbox is full, like an MQTT publisher does.
+    */
+    pub async fn publish(&self, topic: &str, payload: impl Into<Bytes>, qos: u8, retain: bool) -> usize {
+        let packet_id = match qos {
+            1 | 2 => Some(packet_id::next(&self.packet_ids)),
+            _ => None,
+        };
+
+        self.ingress.routing.publish(&PublishPacket {
+            packet_id,
+            topic: topic.to_string(),
+            payload: payload.into(),
+            qos,
+            retain,
+            dup: false,
+        })
+        .await
+    }
+
+    /*
+      Registers an in-process session subscribed to `filter`. It shows up in
+      the sessions API like any other client and is removed when dropped.
+    */
+    pub async fn subscribe(&self, filter: &str, qos: u8) -> anyhow::Result<Subscription> {
+        let client_id = format!("{}{}", LOCAL_CLIENT_PREFIX, self.next_local_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = mpsc::channel::<MqttChannel>(2048);
+
+        let packet = ConnectPacket {
+            protocol_level: PROTOCOL_LEVEL_3_1_1,
+            client_id: client_id.clone(),
+            keep_alive: 0,
+            clean_session: true,
+            username: None,
+            password: None,
+            will: None,
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.ingress
+            .connect_tx
+            .send(ConnectCommand::Connect(packet, 0, SocketAddr::from(([127, 0, 0, 1], 0)), tx, Arc::default(), reply_tx))
+            .await?;
+        let connection_id = reply_rx.await?.map_err(|code| anyhow::anyhow!("in-process session refused: {:?}", code))?;
+
+        let subscription = Subscription {
+            client_id: client_id.clone(),
+            connection_id,
+            rx,
+            connect_tx: self.ingress.connect_tx.clone(),
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let sub = SubscribePacket {
+            packet_id: 0,
+            filters: vec![SubscribeFilter { topic: filter.to_string(), qos }],
+        };
+        self.ingress
+            .pubsub_tx
+            .send(PubSubCommand::Subscribe(sub, client_id, connection_id, reply_tx))
+            .await?;
+        reply_rx.await?;
+
+        Ok(subscription)
+    }
+
+    /*
+      Stops all listeners, disconnects every client, ends the event streams,
+      stops the admin API and waits for the engine to exit.
+    */
+    pub async fn shutdown(self) -> anyhow::Result<()> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        if self.admin_tx.send(AdminCommand::Shutdown(reply_tx)).await.is_ok() {
+            let _ = reply_rx.await;
+        }
+        self.ingress.events.close();
+
+        let _ = self.admin_stop_tx.send(true);
+        if let Some(task) = self.admin_task {
+            task.await?;
+        }
+
+        self.engine_task.await?;
+        Ok(())
+    }
+}
+
+/*
+  Messages delivered to an in-process subscriber. Its mailbox is bounded
+  like a network client's: once full, publishers to matching topics wait
+  until `recv` makes room. Keep draining a subscription, or drop it.
+*/
+pub struct Subscription {
+    client_id: String,
+    connection_id: u64,
+    rx: mpsc::Receiver<MqttChannel>,
+    connect_tx: mpsc::Sender<ConnectCommand>,
+}
+
+impl Subscription {
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    /*
+      Waits for the next message. Returns `None` once the session has been
+      disconnected, for example by an admin kick or broker shutdown.
+    */
+    pub async fn recv(&mut self) -> Option<PublishPacket> {
+        match self.rx.recv().await? {
+            MqttChannel::Publish(message, qos) => {
+                let mut packet = message.packet.clone();
+                packet.qos = packet.qos.min(qos);
+                if packet.qos == 0 {
+                    packet.packet_id = None;
+                } else if let Some(ack) = &message.ack {
+                    /* Nothing can be lost past this point. */
+                    ack.ack();
+                }
+                packet.retain = false;
+                packet.dup = false;
+                Some(packet)
+            }
+            MqttChannel::Disconnect => None,
+        }
+    }
+}
+
+impl Drop for Subscription {
+    /*
+      Waits for room in the engine queue from a separate task, so the
+      session is removed even when the queue is momentarily full. Outside
+      a runtime the broker is gone and there is nothing left to remove.
+    */
+    fn drop(&mut self) {
+        let command = ConnectCommand::Disconnect(self.client_id.clone(), self.connection_id, DisconnectReason::ClientDisconnect);
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let connect_tx = self.connect_tx.clone();
+            runtime.spawn(async move {
+                let _ = connect_tx.send(command).await;
+            });
+        }
+    }
+}
@@ -1,44 +1,99 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 
 use tokio::sync::{mpsc, oneshot};
 
 use crate::{
     enums::MqttChannel,
-    models::{ listener::ListenerConfig, pagination::Page, session::Session, topic_info::TopicInfo},
-    protocol::packets::{ConnectPacket, PublishPacket, SubscribePacket, UnsubscribePacket}
+    models::{ event::DisconnectReason, listener::ListenerConfig, pagination::Page, session::Session, session_query::SessionFilter, topic_info::{SubscriberLookup, TopicFilter, TopicInfo, TopicSubscriber}},
+    protocol::packets::{ConnectPacket, ConnectReturnCode, SubscribeFilter, SubscribePacket, UnsubscribePacket},
+    transport::ConnectionStats,
 };
 
 
 pub struct EngineChannels {
-    pub connect_rx: mpsc::UnboundedReceiver<ConnectCommand>,
-    pub pubsub_rx: mpsc::UnboundedReceiver<PubSubCommand>,
-    pub admin_rx: mpsc::UnboundedReceiver<AdminCommand>,
+    pub connect_rx: mpsc::Receiver<ConnectCommand>,
+    pub pubsub_rx: mpsc::Receiver<PubSubCommand>,
+    pub admin_rx: mpsc::Receiver<AdminCommand>,
 }
+/*
+  Client id prefix of in-process sessions (`Broker::subscribe`). Network
+  clients can't use it, so they can't take such a session over.
+*/
+pub const LOCAL_CLIENT_PREFIX: &str = "$local-";
+
+/*
+  Session lifecycle commands. The engine replies to `Connect` with the
+  connection id that owns the new session, or the CONNACK return code if
+  the client is refused; later commands from that connection carry the id
+  so stale connections are ignored after a takeover. `Disconnect` carries
+  why the connection ended.
+*/
 pub enum ConnectCommand {
-    Connect(ConnectPacket, u16, SocketAddr, mpsc::Sender<MqttChannel>),
-    Disconnect(String),
+    Connect(
+        ConnectPacket,
+        u16,
+        SocketAddr,
+        mpsc::Sender<MqttChannel>,
+        Arc<ConnectionStats>,
+        oneshot::Sender<Result<u64, ConnectReturnCode>>,
+    ),
+    Disconnect(String, u64, DisconnectReason),
 }
 
+/*
+  Subscription changes are acknowledged once applied, so SUBACK/UNSUBACK
+  are only sent after routing reflects them. `Subscribe` replies with one
+  SUBACK return code per filter. Publishes bypass the engine and go
+  through `RoutingService`.
+*/
 pub enum PubSubCommand {
-    Subscribe(SubscribePacket, String),
-    Unsubscribe(UnsubscribePacket, String),
-    Publish(PublishPacket),
+    Subscribe(SubscribePacket, String, u64, oneshot::Sender<Vec<u8>>),
+    Unsubscribe(UnsubscribePacket, String, u64, oneshot::Sender<()>),
 }
 
 pub enum AdminCommand {
-    GetClients(oneshot::Sender<Page<Session>>, usize, usize),
+    GetClients(oneshot::Sender<Page<Session>>, SessionFilter, usize, usize),
     GetListeners(oneshot::Sender<Vec<ListenerConfig>>),
     StopListener(u16),
     DisconnectClient(String, oneshot::Sender<bool>),
 
     /*
-      Returns active topics and subscriber counts.
+      One session by client id, `None` if it is not connected.
+    */
+    GetClient(String, oneshot::Sender<Option<Session>>),
+
+    /*
+      Subscribe or unsubscribe a connected client on its behalf, exactly as
+      its own SUBSCRIBE/UNSUBSCRIBE would. Both reply `None` if the client
+      is not connected; `Subscribe` replies with the granted QoS and
+      `Unsubscribe` with whether the client had that subscription.
+    */
+    SubscribeClient(String, SubscribeFilter, oneshot::Sender<Option<u8>>),
+    UnsubscribeClient(String, String, oneshot::Sender<Option<bool>>),
+
+    /*
+      Disconnects every client matching an active ban and replies with
+      their client ids.
+    */
+    EnforceBans(oneshot::Sender<Vec<String>>),
+
+    /*
+      Returns one page of the topics matching the filter, with subscriber
+      counts and publish counters.
       Reply is sent through the provided oneshot sender.
     */
-    GetTopics(oneshot::Sender<Vec<TopicInfo>>),
+    GetTopics(oneshot::Sender<Page<TopicInfo>>, TopicFilter, usize, usize),
+
+    /*
+      Clients subscribed with a filter, or reached by a topic, sorted by
+      client id. A client with several matching filters is listed once,
+      with its highest QoS.
+    */
+    GetSubscribers(SubscriberLookup, oneshot::Sender<Vec<TopicSubscriber>>),
 
     /*
-      Publishes a message and replies with success status.
+      Stops every listener, disconnects all clients and ends the engine loop.
+      Replies once the listeners have stopped.
     */
-    PublishMessage(PublishPacket, oneshot::Sender<bool>),
+    Shutdown(oneshot::Sender<()>),
 }
\ No newline at end of file
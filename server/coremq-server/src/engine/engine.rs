@@ -1,17 +1,29 @@
 use std::{collections::HashMap, sync::Arc};
+use chrono::Local;
 use tokio::{sync::watch, task::JoinHandle};
 
 
 use crate::{
-    engine::{AdminCommand, ConnectCommand, EngineChannels, PubSubCommand}, 
-    enums::MqttChannel, models::{config::Config, listener::ListenerConfig, pagination::Page, session::Session}, 
-    protocol::packets::PublishPacket, services::{SessionService, TopicService}
+    engine::{AdminCommand, ConnectCommand, EngineChannels, LOCAL_CLIENT_PREFIX, PubSubCommand}, 
+    enums::MqttChannel, models::{config::Config, event::{BrokerEvent, DisconnectReason}, listener::ListenerConfig, session::SessionSubscription, topic_info::{SubscriberLookup, TopicSubscriber}}, 
+    protocol::packets::{ConnectReturnCode, SubscribePacket, SUBACK_FAILURE},
+    services::{RoutingService, SessionService, TopicService, ban::BanService, events::EventService}
 };
 
+/*
+  Owns session lifecycle and subscription changes. Publishes are routed
+  concurrently by `RoutingService` against the shared session and topic
+  indexes, so this loop only handles the low-rate state changes, and
+  publishes each of them to `EventService`.
+*/
 pub struct Engine {
     client_service: Arc<SessionService>,
-    topic_service: TopicService,
+    topic_service: Arc<TopicService>,
+    routing: Arc<RoutingService>,
+    bans: Arc<BanService>,
+    pub events: Arc<EventService>,
     channels: EngineChannels,
+    next_connection_id: u64,
    pub listeners: HashMap<u16, (JoinHandle<()>, watch::Sender<bool>, ListenerConfig)>,
    pub config: Config,
 }
@@ -19,36 +31,84 @@ pub struct Engine {
 impl Engine {
     pub fn new(
         client_service: Arc<SessionService>,
+        topic_service: Arc<TopicService>,
+        routing: Arc<RoutingService>,
+        bans: Arc<BanService>,
+        events: Arc<EventService>,
         config: Config,
         channels: EngineChannels,
     ) -> Self {
         Self {
-            topic_service: TopicService::new(),
+            topic_service,
+            routing,
+            bans,
+            events,
             listeners: HashMap::new(),
             client_service,
             channels,
-            config: config,
+            next_connection_id: 1,
+            config,
 
         }
     }
 
-    pub fn drop_client(&mut self, client_id: &str) {
-        if let Some(_) = self.client_service.remove_client(client_id) {
+    /*
+      Removes the session and tells its connection to close. The connection
+      may already be gone, in which case the notification is simply dropped.
+    */
+    pub fn drop_client(&mut self, client_id: &str, reason: DisconnectReason) {
+        if let Some(session) = self.client_service.remove_client(client_id) {
             self.topic_service.remove_client(client_id);
+            self.routing.invalidate(session.subscriptions.keys().map(String::as_str));
+            self.events.publish(BrokerEvent::ClientDisconnected {
+                client_id: client_id.to_string(),
+                reason,
+            });
+            tokio::spawn(async move {
+                let _ = session.tx.send(MqttChannel::Disconnect).await;
+            });
         }
     }
 
-    fn publish(&self, p: PublishPacket) {
-        let subscribers = self.topic_service.match_subscribers(&p.topic);
-        for client_id in subscribers {
-            if let Some(session) = self.client_service.get_session(&client_id) {
-                let _ = session.tx.try_send(MqttChannel::Publish(p.clone()));
-            }
+    /*
+      Applies every filter of a SUBSCRIBE and returns the SUBACK return
+      codes. The granted QoS is the requested one, capped at 2.
+    */
+    fn subscribe(&self, packet: &SubscribePacket, client_id: &str) -> Vec<u8> {
+        let mut return_codes = Vec::with_capacity(packet.filters.len());
+
+        for filter in &packet.filters {
+            let qos = filter.qos.min(2);
+            self.client_service.add_subscribtion(client_id, SessionSubscription {
+                packet_id: packet.packet_id,
+                topic: filter.topic.clone(),
+                qos,
+                subscribed_at: Local::now(),
+            });
+            self.topic_service.subscribe(&filter.topic, client_id, qos);
+            self.events.publish(BrokerEvent::Subscribed {
+                client_id: client_id.to_string(),
+                topic: filter.topic.clone(),
+                qos,
+            });
+            return_codes.push(qos);
         }
+
+        self.routing.invalidate(packet.filters.iter().map(|f| f.topic.as_str()));
+        return_codes
     }
 
-    pub fn get_paginated(&self, page: usize, size: usize) -> Page<Session> {
-        self.client_service.get_paginated(page, size)
+    fn unsubscribe(&self, topics: &[String], client_id: &str) {
+        for topic in topics {
+            if self.client_service.remove_subscribtion(client_id, topic) {
+                self.events.publish(BrokerEvent::Unsubscribed {
+                    client_id: client_id.to_string(),
+                    topic: topic.clone(),
+                });
+            }
+            self.topic_service.unsubscribe(topic, client_id);
+        }
+        self.routing.invalidate(topics.iter().map(String::as_str));
     }
 
     pub fn get_listeners(&self) -> Vec<ListenerConfig> {
@@ -60,50 +120,97 @@ impl Engine {
             tokio::select! {
                 Some(cmd) = self.channels.connect_rx.recv() => {
                     match cmd {
-                        ConnectCommand::Connect(packet, port, remote_addr,   tx) => {
+                        ConnectCommand::Connect(packet, port, remote_addr, tx, stats, reply_tx) => {
+                            /*
+                              Checked here as well as on accept: a ban added while the
+                              CONNECT was queued must still apply. In-process sessions
+                              (port 0) are never banned.
+                            */
+                            if port != 0
+                                && let Some(ban) = self.bans.find(&packet.client_id, packet.username.as_deref(), remote_addr.ip())
+                            {
+                                println!("Refused client {} from {}: banned by {} {}", packet.client_id, remote_addr, ban.kind, ban.value);
+                                let _ = reply_tx.send(Err(ConnectReturnCode::NotAuthorized));
+                                continue;
+                            }
+
+                            if port != 0 && packet.client_id.starts_with(LOCAL_CLIENT_PREFIX) {
+                                println!("Refused client {} from {}: reserved client id", packet.client_id, remote_addr);
+                                let _ = reply_tx.send(Err(ConnectReturnCode::IdentifierRejected));
+                                continue;
+                            }
+
                             let old_session = self.client_service.remove_client(&packet.client_id);
                             if let Some(session) = old_session {
                                 self.topic_service.remove_client(&session.client_id);
-                                let _ = session.tx.send(MqttChannel::Disconnect).await;
+                                self.routing.invalidate(session.subscriptions.keys().map(String::as_str));
+                                self.events.publish(BrokerEvent::ClientDisconnected {
+                                    client_id: session.client_id.clone(),
+                                    reason: DisconnectReason::TakenOver,
+                                });
+                                /*
+                                  The old connection may itself be waiting on a full engine queue,
+                                  so never block the engine loop on its mailbox.
+                                */
+                                tokio::spawn(async move {
+                                    let _ = session.tx.send(MqttChannel::Disconnect).await;
+                                });
                             }
 
                             println!("Clinet connected: {:?}", packet);
 
-                            self.client_service.add_client(&packet, port, remote_addr, tx);
+                            let connection_id = self.next_connection_id;
+                            self.next_connection_id += 1;
+
+                            /* A new session has no subscriptions yet, so no cached route changes. */
+                            self.client_service.add_client(&packet, port, remote_addr, connection_id, tx, stats);
+                            self.events.publish(BrokerEvent::ClientConnected {
+                                client_id: packet.client_id.clone(),
+                                username: packet.username.clone(),
+                                remote_addr,
+                                port,
+                                protocol_version: packet.protocol_level,
+                            });
+                            let _ = reply_tx.send(Ok(connection_id));
                         }
-                        ConnectCommand::Disconnect(client_id) => {
-                            self.drop_client(&client_id);
+                        ConnectCommand::Disconnect(client_id, connection_id, reason) => {
+                            if self.client_service.is_current(&client_id, connection_id) {
+                                self.drop_client(&client_id, reason);
+                            }
                         }
                     }
                 }
 
                 Some(cmd) = self.channels.pubsub_rx.recv() => {
                     match cmd {
-                        PubSubCommand::Subscribe(packet, client_id) => {
-                            self.client_service.add_subscribtion(&client_id, &packet);
-                            self.topic_service.subscribe(&packet.topic, &client_id);
-                        }
-                        PubSubCommand::Unsubscribe(packet, client_id) => {
-                            self.client_service.remove_subscribtion(&client_id, &packet.topic);
-                            self.topic_service.unsubscribe(&packet.topic, &client_id);
+                        PubSubCommand::Subscribe(packet, client_id, connection_id, reply_tx) => {
+                            let return_codes = if self.client_service.is_current(&client_id, connection_id) {
+                                self.subscribe(&packet, &client_id)
+                            } else {
+                                vec![SUBACK_FAILURE; packet.filters.len()]
+                            };
+                            let _ = reply_tx.send(return_codes);
                         }
-                        PubSubCommand::Publish(packet) => {
-                            self.publish(packet);
+                        PubSubCommand::Unsubscribe(packet, client_id, connection_id, reply_tx) => {
+                            if self.client_service.is_current(&client_id, connection_id) {
+                                self.unsubscribe(&packet.topics, &client_id);
+                            }
+                            let _ = reply_tx.send(());
                         }
                     }
                 }
 
                 Some(cmd) = self.channels.admin_rx.recv() => {
                     match cmd {
-                        AdminCommand::GetClients(reply_tx, page, size) => {
-                            let clients = self.get_paginated(page, size);
+                        AdminCommand::GetClients(reply_tx, filter, page, size) => {
+                            let clients = self.client_service.query(&filter, page, size);
                             let _ = reply_tx.send(clients);
                         }
 
                         AdminCommand::StopListener(port) => {
                            let sessions = self.client_service.get_by_listener(port);
                            for s in sessions {
-                             self.drop_client(&s.client_id);
+                             self.drop_client(&s.client_id, DisconnectReason::ListenerStopped);
                            }
                            
                            self.stop_listener(port).await;
@@ -117,29 +224,96 @@ impl Engine {
                         AdminCommand::DisconnectClient(client_id, reply_tx) => {
                             let existed = self.client_service.get_session(&client_id).is_some();
                             if existed {
-                                self.drop_client(&client_id);
+                                self.drop_client(&client_id, DisconnectReason::Kicked);
                             }
                             let _ = reply_tx.send(existed);
                         }
 
+                        AdminCommand::GetClient(client_id, reply_tx) => {
+                            let _ = reply_tx.send(self.client_service.get_session(&client_id));
+                        }
+
+                        AdminCommand::SubscribeClient(client_id, filter, reply_tx) => {
+                            let granted = self.client_service.get_session(&client_id).map(|_| {
+                                let packet = SubscribePacket { packet_id: 0, filters: vec![filter] };
+                                self.subscribe(&packet, &client_id)[0]
+                            });
+                            let _ = reply_tx.send(granted);
+                        }
+
+                        AdminCommand::UnsubscribeClient(client_id, topic, reply_tx) => {
+                            let removed = self.client_service.get_session(&client_id).map(|session| {
+                                let subscribed = session.subscriptions.contains_key(&topic);
+                                if subscribed {
+                                    self.unsubscribe(&[topic], &client_id);
+                                }
+                                subscribed
+                            });
+                            let _ = reply_tx.send(removed);
+                        }
+
+                        AdminCommand::EnforceBans(reply_tx) => {
+                            let banned: Vec<String> = self
+                                .client_service
+                                .sessions()
+                                .into_iter()
+                                .filter(|s| s.connected_port != 0)
+                                .filter(|s| {
+                                    let username = (!s.username.is_empty()).then_some(s.username.as_str());
+                                    self.bans.find(&s.client_id, username, s.remote_addr.ip()).is_some()
+                                })
+                                .map(|s| s.client_id)
+                                .collect();
+                            for client_id in &banned {
+                                self.drop_client(client_id, DisconnectReason::Banned);
+                            }
+                            let _ = reply_tx.send(banned);
+                        }
+
                         /*
                           Collect active topics and reply with results.
                         */
-                        AdminCommand::GetTopics(reply_tx) => {
-                            let topics = self.topic_service.collect_topics();
+                        AdminCommand::GetTopics(reply_tx, filter, page, size) => {
+                            let subscribed = self.topic_service.collect_topics();
+                            let topics = self.routing.topic_stats().browse(subscribed, &filter, page, size);
                             let _ = reply_tx.send(topics);
                         }
 
-                        /*
-                          Publish a message and acknowledge completion.
-                        */
-                        AdminCommand::PublishMessage(packet, reply_tx) => {
-                            self.publish(packet);
-                            let _ = reply_tx.send(true);
+                        AdminCommand::GetSubscribers(lookup, reply_tx) => {
+                            let mut subscribers = match lookup {
+                                SubscriberLookup::Filter(filter) => self.topic_service.subscribers(&filter),
+                                SubscriberLookup::Topic(topic) => self.topic_service.match_subscribers(&topic),
+                            };
+                            subscribers.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+                            subscribers.dedup_by(|a, b| a.0 == b.0);
+                            let subscribers = subscribers
+                                .into_iter()
+                                .map(|(client_id, qos)| TopicSubscriber { client_id, qos })
+                                .collect();
+                            let _ = reply_tx.send(subscribers);
+                        }
+
+                        AdminCommand::Shutdown(reply_tx) => {
+                            self.shutdown().await;
+                            let _ = reply_tx.send(());
+                            return;
                         }
                     }
                 }
+
+                else => break,
             }
         }
     }
+
+    async fn shutdown(&mut self) {
+        let ports: Vec<u16> = self.listeners.keys().copied().collect();
+        for port in ports {
+            self.stop_listener(port).await;
+        }
+
+        for client_id in self.client_service.client_ids() {
+            self.drop_client(&client_id, DisconnectReason::Shutdown);
+        }
+    }
 }
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod engine;
 pub mod commands;
 pub mod workers;
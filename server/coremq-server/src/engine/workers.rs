@@ -7,6 +7,7 @@ use tower_http::cors::CorsLayer;
 use crate::{
     engine::Engine,
     enums::protocol::ProtocolType,
+    models::event::BrokerEvent,
     transport::{
         ProtocolState,
         tcp::tcp_connection,
@@ -15,15 +16,25 @@ use crate::{
 };
 
 impl Engine {
-    async fn tcp_worker(port: u16, state: Arc<ProtocolState>, mut stop_rx: watch::Receiver<bool>) {
-        let addr = format!("0.0.0.0:{}", port);
-        let listener = TcpListener::bind(addr).await.unwrap();
+    async fn tcp_worker(listener: TcpListener, port: u16, state: Arc<ProtocolState>, mut stop_rx: watch::Receiver<bool>) {
         println!("MQTT TCP listening on port {}", port);
 
         loop {
             tokio::select! {
                 res = listener.accept() => {
-                    let (socket, _) = res.unwrap();
+                    let socket = match res {
+                        Ok((socket, addr)) => {
+                            if state.bans.ip_banned(addr.ip()) {
+                                println!("Refused connection from banned address {}", addr);
+                                continue;
+                            }
+                            socket
+                        }
+                        Err(e) => {
+                            println!("TCP accept error on port {}: {}", port, e);
+                            continue;
+                        }
+                    };
                     let state_clone = state.clone();
                     tokio::spawn(async move {
                         if let Err(e) = tcp_connection(socket, state_clone, port).await {
@@ -39,26 +50,22 @@ impl Engine {
         }
     }
 
-    async fn ws_worker(port: u16, state: Arc<ProtocolState>, mut stop_rx: watch::Receiver<bool>) {
+    async fn ws_worker(listener: TcpListener, port: u16, state: Arc<ProtocolState>, mut stop_rx: watch::Receiver<bool>) {
         let ws_state = WsState {
             engine: state.clone(),
-            port: port,
+            port,
         };
 
-        let addr: SocketAddr = format!("0.0.0.0:{}", port).parse().unwrap();
         let app = Router::new()
             .route("/mqtt", get(ws_handler))
             .with_state(ws_state.clone())
             .layer(CorsLayer::permissive());
 
-        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-
         let server = axum::serve(
             listener,
             app.into_make_service_with_connect_info::<SocketAddr>(),
         );
 
-    //    let server = axum::serve(tokio::net::TcpListener::bind(addr).await.unwrap(), app);
         println!("MQTT WS listening on port {}", port);
 
         tokio::select! {
@@ -74,37 +81,58 @@ impl Engine {
         }
     }
 
-    pub async fn start_listeners(&mut self, state: Arc<ProtocolState>) {
-        for port_cfg in &self.config.mqtt.listeners {
-            if self.listeners.contains_key(&port_cfg.port) {
+    /*
+      Binds every configured listener before spawning its worker, so bind
+      errors are reported to the caller. Listeners configured with port 0
+      get an ephemeral port; the registered config carries the bound port.
+    */
+    pub async fn start_listeners(&mut self, state: Arc<ProtocolState>) -> anyhow::Result<()> {
+        for port_cfg in self.config.mqtt.listeners.clone() {
+            if port_cfg.port != 0 && self.listeners.contains_key(&port_cfg.port) {
+                continue;
+            }
+
+            if !matches!(port_cfg.protocol, ProtocolType::Tcp | ProtocolType::Ws) {
+                println!("Skipping {} listener '{}': not supported yet", port_cfg.protocol.as_str(), port_cfg.name);
                 continue;
             }
 
+            let listener = TcpListener::bind((port_cfg.host.as_str(), port_cfg.port)).await?;
+            let port_num = listener.local_addr()?.port();
+
             let (tx, rx) = watch::channel(false);
             let state_clone = state.clone();
-            let port_num = port_cfg.port;
 
             // spawn the worker
             let handle: JoinHandle<()> = match port_cfg.protocol {
-                ProtocolType::Tcp => tokio::spawn(async move {
-                    Engine::tcp_worker(port_num, state_clone, rx).await;
-                }),
                 ProtocolType::Ws => tokio::spawn(async move {
-                    Engine::ws_worker(port_num, state_clone, rx).await;
+                    Engine::ws_worker(listener, port_num, state_clone, rx).await;
+                }),
+                _ => tokio::spawn(async move {
+                    Engine::tcp_worker(listener, port_num, state_clone, rx).await;
                 }),
-                _ => continue,
             };
 
+            let mut bound_cfg = port_cfg;
+            bound_cfg.port = port_num;
+            self.events.publish(BrokerEvent::ListenerStarted {
+                name: bound_cfg.name.clone(),
+                port: port_num,
+                protocol: bound_cfg.protocol.clone(),
+            });
             self.listeners
-                .insert(port_num, (handle, tx, port_cfg.clone()));
+                .insert(port_num, (handle, tx, bound_cfg));
         }
+
+        Ok(())
     }
 
     pub async fn stop_listener(&mut self, port: u16) {
-        if let Some((handle, stop_tx, _)) = self.listeners.remove(&port) {
-            stop_tx.send(true).unwrap();
-            handle.await.unwrap();
+        if let Some((handle, stop_tx, config)) = self.listeners.remove(&port) {
+            let _ = stop_tx.send(true);
+            let _ = handle.await;
             println!("Stopped listener on port {}", port);
+            self.events.publish(BrokerEvent::ListenerStopped { name: config.name, port });
         }
     }
 }
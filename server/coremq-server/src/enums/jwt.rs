@@ -11,8 +11,10 @@ impl JwtType {
             JwtType::RefreshToken => "refresh_token"
         }
     } 
+}
 
-    pub fn to_string(&self) -> String {
-        self.as_str().to_owned()
+impl std::fmt::Display for JwtType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,16 @@
-use crate::protocol::packets::PublishPacket;
+use std::sync::Arc;
+
+use crate::protocol::encoder::OutgoingPublish;
 
-pub mod packet;
 pub mod jwt;
 pub mod role;
 pub mod protocol;
 
+/*
+  Messages delivered to a connection's mailbox. `Publish` carries the shared
+  outgoing message and the QoS granted to this subscriber.
+*/
 pub enum MqttChannel {
-    Publish(PublishPacket),
+    Publish(Arc<OutgoingPublish>, u8),
     Disconnect,
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum  RoleType {
     Admin,
     User
@@ -13,8 +14,22 @@ impl RoleType {
             RoleType::User => "user",
         }
     }
+}
 
-    pub fn to_string(&self) -> String {
-        self.as_str().to_owned()
+impl std::fmt::Display for RoleType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
-}
\ No newline at end of file
+}
+
+impl std::str::FromStr for RoleType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "admin" => Ok(RoleType::Admin),
+            "user" => Ok(RoleType::User),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
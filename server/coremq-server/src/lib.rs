@@ -0,0 +1,14 @@
+pub mod api;
+pub mod broker;
+pub mod engine;
+pub mod enums;
+pub mod models;
+pub mod pkg;
+pub mod protocol;
+pub mod services;
+pub mod storage;
+pub mod transport;
+pub mod utils;
+
+pub use broker::{Broker, BrokerBuilder, BrokerHandle, Subscription};
+pub use models::config::Config;
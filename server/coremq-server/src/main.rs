@@ -1,21 +1,4 @@
-mod api;
-mod engine;
-mod enums;
-mod models;
-mod pkg;
-mod protocol;
-mod services;
-mod storage;
-mod transport;
-mod utils;
-
-use axum::{Router, routing::get};
-use std::{net::SocketAddr, sync::{Arc, atomic::AtomicU16}};
-use tokio::{net::TcpListener, sync::mpsc};
-
-use crate::{
-    api::{api_state::ApiState, router::RouterHandler}, engine::{AdminCommand, ConnectCommand, Engine, EngineChannels, PubSubCommand}, services::{SessionService, jwt::{self, JwtService}}, storage::redb::Storage, transport::{ProtocolState, tcp::tcp_connection, ws::ws_handler}
-};
+use coremq_server::{Broker, utils};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -27,62 +10,14 @@ async fn main() -> anyhow::Result<()> {
         Err(e) => panic!("Failed to load config: {}", e)
     };
 
-    let enforcer = match pkg::enforcer::new(config.middleware.clone()).await {
-        Ok(enforcer) => enforcer,
-        Err(e) => { panic!("Failed to create enforcer: {}", e)}
-    };
-
-    let path = "data/coremq.redb";
-    let _ = std::fs::create_dir_all("data");
-    let db = match pkg::db::new(path) {
-        Ok(db) => db,
-        Err(e) => { panic!("Failed to create database: {}", e)}
-    };
-
-    let client_service = Arc::new(SessionService::new());
-    let jwt_service = Arc::new(JwtService::new(&config.middleware));
-    let enforcer = Arc::new(enforcer);
-    let db_arc = Arc::new(db);
-    let storage = Arc::new(Storage::new(db_arc));
-
-    let (connect_tx, connect_rx) = mpsc::unbounded_channel::<ConnectCommand>();
-    let (pubsub_tx, pubsub_rx) = mpsc::unbounded_channel::<PubSubCommand>();
-    let (admin_tx, admin_rx) = mpsc::unbounded_channel::<AdminCommand>();
-
-    let channels = EngineChannels {
-        connect_rx,
-        pubsub_rx,
-        admin_rx,
-    };
-
-    let engine_channels = Arc::new(ProtocolState {
-        connect_tx: connect_tx.clone(),
-        pubsub_tx: pubsub_tx.clone(),
-    });
-
-    let mut engine = Engine::new(client_service.clone(), config,  channels);
-    engine.start_listeners(engine_channels.clone()).await;
-
-    tokio::spawn(async move {
-        engine.run().await;
-    });
-
-    let state = ApiState {
-        jwt_service: jwt_service.clone(),
-        enforcer: enforcer.clone(),
-        engine: admin_tx.clone(),
-        storage: storage.clone(),
-        packet_id_counter: Arc::new(AtomicU16::new(1)),
-    };
+    let broker = Broker::builder(config).start().await?;
 
-    let router = RouterHandler::new();
-    let addr = format!("{}:{}", "0.0.0.0", 18083);
-    let listener = TcpListener::bind(addr.clone()).await?;
-    println!("Admin Panel running on {}", addr);
+    if let Some(addr) = broker.admin_addr() {
+        println!("Admin Panel running on {}", addr);
+    }
 
-    axum::serve(listener, router.create_router(state))
-        .await
-        .unwrap();
+    tokio::signal::ctrl_c().await?;
+    println!("Shutting down");
 
-    Ok(())
-}
\ No newline at end of file
+    broker.shutdown().await
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::format_time::{format_datetime, format_optional_datetime};
+
+
+/*
+  Stored API key. Times are unix seconds; `key_hash` never leaves storage.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub role: String,
+    pub key_hash: String,
+    pub created_by: String,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+}
+
+impl ApiKey {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub name: String,
+    pub role: String,
+    pub created_by: String,
+    #[serde(serialize_with = "format_datetime")]
+    pub created_at: DateTime<Local>,
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub expires_at: Option<DateTime<Local>>,
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub last_used_at: Option<DateTime<Local>>,
+    pub expired: bool,
+}
+
+fn local(secs: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(secs, 0).single()
+}
+
+impl From<ApiKey> for ApiKeyInfo {
+    fn from(key: ApiKey) -> Self {
+        let expired = key.is_expired(chrono::Utc::now().timestamp());
+        Self {
+            id: key.id,
+            name: key.name,
+            role: key.role,
+            created_by: key.created_by,
+            created_at: local(key.created_at).unwrap_or_else(Local::now),
+            expires_at: key.expires_at.and_then(local),
+            last_used_at: key.last_used_at.and_then(local),
+            expired,
+        }
+    }
+}
+
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CreateApiKey {
+    pub name: String,
+    /*
+      Casbin role the key acts as.
+    */
+    pub role: String,
+    /*
+      Lifetime in days; keys without one never expire.
+    */
+    pub expires_in_days: Option<u32>,
+}
+
+
+/*
+  Returned once, on creation: `key` can't be retrieved later.
+*/
+#[derive(Debug, Serialize, Clone)]
+pub struct CreatedApiKey {
+    pub key: String,
+    pub api_key: ApiKeyInfo,
+}
@@ -0,0 +1,139 @@
+use std::{fmt, net::IpAddr};
+
+use chrono::{DateTime, Local, TimeZone};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::format_time::{format_datetime, format_optional_datetime};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BanKind {
+    ClientId,
+    Username,
+    Ip,
+    Cidr,
+}
+
+impl fmt::Display for BanKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BanKind::ClientId => write!(f, "client_id"),
+            BanKind::Username => write!(f, "username"),
+            BanKind::Ip => write!(f, "ip"),
+            BanKind::Cidr => write!(f, "cidr"),
+        }
+    }
+}
+
+impl BanKind {
+    /*
+      Canonical form of a ban value, so that equivalent spellings of an
+      address or network are one ban: `::ffff:10.0.0.1` becomes `10.0.0.1`
+      and `10.1.2.3/8` becomes `10.0.0.0/8`.
+    */
+    pub fn normalize(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("value must not be empty".to_string());
+        }
+
+        match self {
+            BanKind::ClientId | BanKind::Username => Ok(value.to_string()),
+            BanKind::Ip => value
+                .parse::<IpAddr>()
+                .map(|ip| ip.to_canonical().to_string())
+                .map_err(|_| format!("invalid IP address '{}'", value)),
+            BanKind::Cidr => value
+                .parse::<IpNet>()
+                .map(|net| net.trunc().to_string())
+                .map_err(|_| format!("invalid CIDR network '{}'", value)),
+        }
+    }
+}
+
+
+/*
+  Stored ban. Times are unix seconds; bans without `expires_at` last until
+  deleted.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Ban {
+    pub kind: BanKind,
+    pub value: String,
+    pub reason: Option<String>,
+    pub created_by: String,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+impl Ban {
+    pub fn key(&self) -> String {
+        key(self.kind, &self.value)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+pub fn key(kind: BanKind, value: &str) -> String {
+    format!("{}:{}", kind, value)
+}
+
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BanInfo {
+    pub kind: BanKind,
+    pub value: String,
+    pub reason: Option<String>,
+    pub created_by: String,
+    #[serde(serialize_with = "format_datetime")]
+    pub created_at: DateTime<Local>,
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub expires_at: Option<DateTime<Local>>,
+    pub expired: bool,
+}
+
+impl From<Ban> for BanInfo {
+    fn from(ban: Ban) -> Self {
+        let expired = ban.is_expired(chrono::Utc::now().timestamp());
+        Self {
+            kind: ban.kind,
+            value: ban.value,
+            reason: ban.reason,
+            created_by: ban.created_by,
+            created_at: Local.timestamp_opt(ban.created_at, 0).single().unwrap_or_else(Local::now),
+            expires_at: ban.expires_at.and_then(|secs| Local.timestamp_opt(secs, 0).single()),
+            expired,
+        }
+    }
+}
+
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CreateBan {
+    pub kind: BanKind,
+    pub value: String,
+    pub reason: Option<String>,
+    /*
+      Lifetime in seconds; bans without one last until deleted.
+    */
+    pub expires_in_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct BanQuery {
+    pub kind: BanKind,
+    pub value: String,
+}
+
+/*
+  A new ban, with the clients it disconnected.
+*/
+#[derive(Debug, Serialize, Clone)]
+pub struct CreatedBan {
+    pub ban: BanInfo,
+    pub disconnected: Vec<String>,
+}
@@ -8,4 +8,8 @@ pub struct Claims {
     pub token_type: String,
     pub exp: usize,   
     pub iat: usize,   
-}
\ No newline at end of file
+    /*
+      Token pair id, used by the revocation deny-list.
+    */
+    pub jti: String,
+}
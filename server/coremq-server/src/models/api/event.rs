@@ -0,0 +1,193 @@
+use std::net::{IpAddr, SocketAddr};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    enums::protocol::ProtocolType,
+    utils::{format_time::format_datetime, topic::{is_valid_filter, matches_filter}},
+};
+
+/*
+  Why a client's session ended.
+*/
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    ClientDisconnect, // the client sent DISCONNECT
+    ConnectionClosed, // the socket closed or failed without a DISCONNECT
+    KeepAliveTimeout,
+    ProtocolError,
+    TakenOver, // another connection used the same client id
+    Kicked,    // disconnected through the admin API
+    Banned,
+    ListenerStopped,
+    Shutdown,
+}
+
+/*
+  Something that happened in the broker, as streamed by
+  `GET /api/v1/events`. Serialized with its type in a `type` field.
+*/
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrokerEvent {
+    ClientConnected {
+        client_id: String,
+        username: Option<String>,
+        remote_addr: SocketAddr,
+        port: u16,
+        protocol_version: u8,
+    },
+    ClientDisconnected {
+        client_id: String,
+        reason: DisconnectReason,
+    },
+    Subscribed {
+        client_id: String,
+        topic: String,
+        qos: u8,
+    },
+    Unsubscribed {
+        client_id: String,
+        topic: String,
+    },
+    ListenerStarted {
+        name: String,
+        port: u16,
+        protocol: ProtocolType,
+    },
+    ListenerStopped {
+        name: String,
+        port: u16,
+    },
+    AuthFailed {
+        username: String,
+        ip: IpAddr,
+        locked: bool,   // the username or address is locked out after this attempt
+    },
+
+    /*
+      Sent by a stream in place of the events it fell too far behind to
+      deliver. Never filtered out.
+    */
+    Lagged {
+        missed: u64,
+    },
+}
+
+pub const EVENT_TYPES: [&str; 8] = [
+    "client_connected",
+    "client_disconnected",
+    "subscribed",
+    "unsubscribed",
+    "listener_started",
+    "listener_stopped",
+    "auth_failed",
+    "lagged",
+];
+
+impl BrokerEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BrokerEvent::ClientConnected { .. } => "client_connected",
+            BrokerEvent::ClientDisconnected { .. } => "client_disconnected",
+            BrokerEvent::Subscribed { .. } => "subscribed",
+            BrokerEvent::Unsubscribed { .. } => "unsubscribed",
+            BrokerEvent::ListenerStarted { .. } => "listener_started",
+            BrokerEvent::ListenerStopped { .. } => "listener_stopped",
+            BrokerEvent::AuthFailed { .. } => "auth_failed",
+            BrokerEvent::Lagged { .. } => "lagged",
+        }
+    }
+
+    fn client_id(&self) -> Option<&str> {
+        match self {
+            BrokerEvent::ClientConnected { client_id, .. }
+            | BrokerEvent::ClientDisconnected { client_id, .. }
+            | BrokerEvent::Subscribed { client_id, .. }
+            | BrokerEvent::Unsubscribed { client_id, .. } => Some(client_id),
+            _ => None,
+        }
+    }
+
+    fn topic(&self) -> Option<&str> {
+        match self {
+            BrokerEvent::Subscribed { topic, .. } | BrokerEvent::Unsubscribed { topic, .. } => Some(topic),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    #[serde(flatten)]
+    pub event: BrokerEvent,
+
+    #[serde(serialize_with = "format_datetime")]
+    pub at: DateTime<Local>,
+}
+
+/*
+  Query string of the event streams. Every filter is optional and they
+  combine with AND.
+*/
+#[derive(Debug, Deserialize)]
+pub struct EventQuery {
+    pub types: Option<String>,     // comma-separated event types
+    pub client_id: Option<String>, // only events about this client
+    pub topic: Option<String>,     // only (un)subscriptions to filters under this MQTT filter
+}
+
+/*
+  Validated form of the query.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub types: Option<Vec<String>>,
+    pub client_id: Option<String>,
+    pub topic: Option<String>,
+}
+
+impl EventQuery {
+    pub fn filter(&self) -> Result<EventFilter, String> {
+        let types = match self.types.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            None => None,
+            Some(value) => {
+                let types: Vec<String> = value.split(',').map(|t| t.trim().to_string()).collect();
+                if let Some(unknown) = types.iter().find(|t| !EVENT_TYPES.contains(&t.as_str())) {
+                    return Err(format!("unknown event type '{}': expected one of {}", unknown, EVENT_TYPES.join(", ")));
+                }
+                Some(types)
+            }
+        };
+
+        let topic = self.topic.clone().filter(|t| !t.is_empty());
+        if let Some(topic) = &topic
+            && !is_valid_filter(topic)
+        {
+            return Err(format!("invalid topic filter '{}'", topic));
+        }
+
+        Ok(EventFilter {
+            types,
+            client_id: self.client_id.clone().filter(|c| !c.is_empty()),
+            topic,
+        })
+    }
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &BrokerEvent) -> bool {
+        if matches!(event, BrokerEvent::Lagged { .. }) {
+            return true;
+        }
+
+        self.types.as_ref().is_none_or(|types| types.iter().any(|t| t == event.kind()))
+            && self.client_id.as_deref().is_none_or(|id| event.client_id() == Some(id))
+            && self
+                .topic
+                .as_deref()
+                .is_none_or(|filter| event.topic().is_some_and(|topic| matches_filter(filter, topic)))
+    }
+}
@@ -0,0 +1,38 @@
+use chrono::{DateTime, Local, TimeZone};
+use serde::Serialize;
+
+use crate::{
+    services::lockout::{LockoutEntry, LockoutKind},
+    utils::format_time::{format_datetime, format_optional_datetime},
+};
+
+
+/*
+  Failed logins tracked for one username or IP. `locked` is false once
+  `locked_until` has passed.
+*/
+#[derive(Debug, Serialize, Clone)]
+pub struct LockoutInfo {
+    pub kind: LockoutKind,
+    pub value: String,
+    pub failures: u32,
+    #[serde(serialize_with = "format_datetime")]
+    pub last_failure_at: DateTime<Local>,
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub locked_until: Option<DateTime<Local>>,
+    pub locked: bool,
+}
+
+impl From<LockoutEntry> for LockoutInfo {
+    fn from(entry: LockoutEntry) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            kind: entry.kind,
+            value: entry.value,
+            failures: entry.failures,
+            last_failure_at: Local.timestamp_opt(entry.last_failure_at, 0).single().unwrap_or_else(Local::now),
+            locked_until: entry.locked_until.and_then(|secs| Local.timestamp_opt(secs, 0).single()),
+            locked: entry.locked_until.is_some_and(|until| until > now),
+        }
+    }
+}
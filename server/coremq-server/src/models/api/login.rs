@@ -6,6 +6,12 @@ use serde::{Deserialize, Serialize};
 pub struct Token {
     pub access_token:String,
     pub refresh_token:String,
+    /*
+      Set while the account still has to change its password; every other
+      call is refused until it does.
+    */
+    #[serde(default)]
+    pub password_change_required: bool,
 }
 
 
@@ -16,3 +22,7 @@ pub struct Login {
 }
 
 
+#[derive(Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
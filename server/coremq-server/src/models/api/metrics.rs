@@ -0,0 +1,32 @@
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+/*
+  Current occupancy of a bounded engine queue.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueDepth {
+    pub depth: usize,
+    pub capacity: usize,
+}
+
+impl QueueDepth {
+    pub fn of<T>(tx: &mpsc::Sender<T>) -> Self {
+        Self {
+            depth: tx.max_capacity() - tx.capacity(),
+            capacity: tx.max_capacity(),
+        }
+    }
+}
+
+/*
+  API response payload for engine ingress metrics. `publishers_waiting`
+  counts publishes currently held back by a full subscriber mailbox.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineMetrics {
+    pub connect_queue: QueueDepth,
+    pub pubsub_queue: QueueDepth,
+    pub admin_queue: QueueDepth,
+    pub publishers_waiting: usize,
+}
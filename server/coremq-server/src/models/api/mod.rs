@@ -3,4 +3,13 @@ pub mod claims;
 pub mod login;
 pub mod pagination;
 pub mod session_query;
-pub mod topic_info;
\ No newline at end of file
+pub mod topic_info;
+pub mod metrics;
+pub mod policy;
+pub mod api_key;
+pub mod lockout;
+pub mod ban;
+pub mod session_detail;
+pub mod publish;
+pub mod event;
+pub mod trace;
@@ -1,5 +1,10 @@
 use serde::Serialize;
 
+/*
+  Largest page the paginated APIs return; bigger `size` values are capped.
+*/
+pub const MAX_PAGE_SIZE: usize = 1000;
+
 #[derive(Debug, Serialize)]
 pub struct Page<T> {
     pub content: Vec<T>,
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+
+/*
+  A `p` rule: `subject` (a role) may call `action` on `object`. Objects
+  support `keyMatch2` patterns: `:name` matches one path segment and a
+  trailing `*` matches the rest. `*` as action matches any method.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PolicyRule {
+    pub subject: String,
+    pub object: String,
+    pub action: String,
+}
+
+impl PolicyRule {
+    pub fn from_rule(rule: Vec<String>) -> Option<Self> {
+        let [subject, object, action]: [String; 3] = rule.try_into().ok()?;
+        Some(Self { subject, object, action })
+    }
+
+    pub fn into_rule(self) -> Vec<String> {
+        vec![self.subject, self.object, self.action]
+    }
+}
+
+
+/*
+  A `g` rule: `subject` inherits every permission of `role`.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoleAssignment {
+    pub subject: String,
+    pub role: String,
+}
+
+impl RoleAssignment {
+    pub fn from_rule(rule: Vec<String>) -> Option<Self> {
+        let [subject, role]: [String; 2] = rule.try_into().ok()?;
+        Some(Self { subject, role })
+    }
+
+    pub fn into_rule(self) -> Vec<String> {
+        vec![self.subject, self.role]
+    }
+}
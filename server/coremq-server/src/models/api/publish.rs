@@ -0,0 +1,90 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::topic::is_valid_topic_name;
+
+/*
+  API request payload for publishing a message (deserialized from JSON).
+  `payload` is taken as text unless `encoding` says it is base64 or hex,
+  which is how binary payloads are sent.
+*/
+#[derive(Debug, Deserialize)]
+pub struct PublishRequest {
+    pub topic: String,
+    pub payload: String,
+    #[serde(default)]
+    pub encoding: PayloadEncoding,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadEncoding {
+    #[default]
+    Plain,
+    Base64,
+    Hex,
+}
+
+impl PublishRequest {
+    /*
+      Checks the topic and QoS and returns the decoded payload.
+    */
+    pub fn validate(&self) -> Result<Bytes, String> {
+        if !is_valid_topic_name(&self.topic) {
+            return Err(format!("invalid topic '{}': topic names can't be empty or contain wildcards", self.topic));
+        }
+        if self.qos > 2 {
+            return Err("qos must be 0, 1 or 2".to_string());
+        }
+
+        match self.encoding {
+            PayloadEncoding::Plain => Ok(Bytes::from(self.payload.clone())),
+            PayloadEncoding::Base64 => STANDARD
+                .decode(self.payload.trim())
+                .map(Bytes::from)
+                .map_err(|e| format!("invalid base64 payload: {}", e)),
+            PayloadEncoding::Hex => decode_hex(self.payload.trim())
+                .map(Bytes::from)
+                .ok_or_else(|| "invalid hex payload".to_string()),
+        }
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| value.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+/*
+  Query string of `POST /api/v1/publish` and `/api/v1/publish/batch`.
+*/
+#[derive(Debug, Deserialize)]
+pub struct PublishQuery {
+    /* Respond once subscribers acknowledged the QoS 1/2 deliveries. */
+    #[serde(default)]
+    pub wait_for_ack: bool,
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublishResult {
+    pub topic: String,
+    pub matched: usize,   // subscribers whose filters match the topic
+    pub delivered: usize, // of those, how many accepted the message
+    /*
+      Only set with `wait_for_ack`: QoS 1/2 deliveries, and how many of
+      them were acknowledged before the response.
+    */
+    pub acks_expected: Option<usize>,
+    pub acknowledged: Option<usize>,
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{models::session::Session, utils::format_time::format_optional_datetime};
+
+/*
+  Response of `GET /api/v1/sessions/:client_id`: the session plus the live
+  counters of its connection.
+*/
+#[derive(Debug, Serialize)]
+pub struct SessionDetail {
+    #[serde(flatten)]
+    pub session: Session,
+    pub inflight_incoming: usize, // QoS 2 publishes awaiting PUBREL
+    pub inflight_outgoing: usize, // deliveries awaiting PUBACK or PUBCOMP
+    pub queued: usize,            // messages waiting in the connection's mailbox
+    pub queue_capacity: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub last_activity_at: Option<DateTime<Local>>,
+}
+
+impl From<Session> for SessionDetail {
+    fn from(session: Session) -> Self {
+        let (inflight_incoming, inflight_outgoing) = session.stats.inflight();
+        let queue_capacity = session.tx.max_capacity();
+        Self {
+            inflight_incoming,
+            inflight_outgoing,
+            queued: queue_capacity - session.tx.capacity(),
+            queue_capacity,
+            bytes_in: session.stats.bytes_in(),
+            bytes_out: session.stats.bytes_out(),
+            last_activity_at: session.stats.last_activity(),
+            session,
+        }
+    }
+}
+
+/*
+  Body of `POST /api/v1/sessions/:client_id/subscriptions`, and its
+  response with the granted QoS.
+*/
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClientSubscription {
+    pub topic: String,
+    #[serde(default)]
+    pub qos: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionQuery {
+    pub topic: String,
+}
@@ -1,8 +1,139 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Local, TimeZone};
+use ipnet::IpNet;
 use serde::Deserialize;
 
+use crate::models::session::Session;
+
+/*
+  Query string of `GET /api/v1/sessions`. Every filter is optional and
+  they combine with AND.
+*/
 #[derive(Debug, Deserialize)]
 pub struct SessionQuery {
     pub page: Option<usize>,    // page number
     pub size: Option<usize>,    // page size
-    pub search: Option<String>, // optional search term
-}
\ No newline at end of file
+    pub search: Option<String>, // substring of the client id or username, case-insensitive
+    pub ip: Option<String>,     // remote address, or a CIDR network
+    pub port: Option<u16>,      // listener port
+    pub protocol_version: Option<u8>,
+    pub connected_since: Option<String>, // RFC 3339 or unix seconds
+    pub sort: Option<SessionSort>,
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    #[default]
+    ClientId,
+    Username,
+    ConnectedAt,
+    RemoteAddr,
+    Port,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/*
+  Validated form of the query's filters and ordering.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct SessionFilter {
+    pub search: Option<String>,
+    pub ip: Option<IpNet>,
+    pub port: Option<u16>,
+    pub protocol_version: Option<u8>,
+    pub connected_since: Option<DateTime<Local>>,
+    pub sort: SessionSort,
+    pub order: SortOrder,
+}
+
+impl SessionQuery {
+    pub fn filter(&self) -> Result<SessionFilter, String> {
+        let ip = match self.ip.as_deref().map(str::trim).filter(|ip| !ip.is_empty()) {
+            None => None,
+            Some(value) => Some(
+                value
+                    .parse::<IpNet>()
+                    .map(|net| net.trunc())
+                    .or_else(|_| value.parse::<IpAddr>().map(|ip| IpNet::from(ip.to_canonical())))
+                    .map_err(|_| format!("invalid ip '{}': expected an address or CIDR network", value))?,
+            ),
+        };
+
+        let connected_since = match self.connected_since.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            None => None,
+            Some(value) => Some(parse_time(value).ok_or_else(|| {
+                format!("invalid connected_since '{}': expected RFC 3339 or unix seconds", value)
+            })?),
+        };
+
+        Ok(SessionFilter {
+            search: self
+                .search
+                .as_deref()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_lowercase),
+            ip,
+            port: self.port,
+            protocol_version: self.protocol_version,
+            connected_since,
+            sort: self.sort.unwrap_or_default(),
+            order: self.order.unwrap_or_default(),
+        })
+    }
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Local>> {
+    if let Ok(secs) = value.parse::<i64>() {
+        return Local.timestamp_opt(secs, 0).single();
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Local))
+}
+
+/*
+  Value a session is ordered by. One query only ever compares keys of the
+  same variant.
+*/
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SessionSortKey {
+    Text(String),
+    Time(DateTime<Local>),
+    Addr(IpAddr, u16),
+    Port(u16),
+}
+
+impl SessionFilter {
+    pub fn matches(&self, session: &Session) -> bool {
+        if let Some(search) = &self.search
+            && !session.client_id.to_lowercase().contains(search)
+            && !session.username.to_lowercase().contains(search)
+        {
+            return false;
+        }
+
+        self.ip.is_none_or(|net| net.contains(&session.remote_addr.ip().to_canonical()))
+            && self.port.is_none_or(|port| session.connected_port == port)
+            && self.protocol_version.is_none_or(|version| session.protocol_version == version)
+            && self.connected_since.is_none_or(|since| session.connected_at >= since)
+    }
+
+    pub fn sort_key(&self, session: &Session) -> SessionSortKey {
+        match self.sort {
+            SessionSort::ClientId => SessionSortKey::Text(session.client_id.clone()),
+            SessionSort::Username => SessionSortKey::Text(session.username.clone()),
+            SessionSort::ConnectedAt => SessionSortKey::Time(session.connected_at),
+            SessionSort::RemoteAddr => SessionSortKey::Addr(session.remote_addr.ip(), session.remote_addr.port()),
+            SessionSort::Port => SessionSortKey::Port(session.connected_port),
+        }
+    }
+}
@@ -1,21 +1,137 @@
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    models::session_query::SortOrder,
+    utils::{format_time::format_optional_datetime, topic::{is_valid_filter, is_valid_topic_name, matches_filter}},
+};
+
 /*
-  API response payload for topics (serialized to JSON).
+  API response payload for topics (serialized to JSON). A topic is listed
+  if clients subscribe to it as a filter, or if messages were published to
+  it; the counters cover publishes since the broker started.
 */
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct TopicInfo {
     pub topic: String,
-    pub subscriber_count: usize,
+    pub subscriber_count: usize, // clients subscribed with exactly this filter
+    pub messages: u64,
+    pub bytes: u64, // payload bytes
+
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub last_publish_at: Option<DateTime<Local>>,
+    pub retained: bool, // the last publish had the retain flag set
 }
 
 /*
-  API request payload for publishing a message (deserialized from JSON).
+  Query string of `GET /api/v1/topics`.
 */
 #[derive(Debug, Deserialize)]
-pub struct PublishRequest {
-    pub topic: String,
-    pub payload: String,
+pub struct TopicQuery {
+    pub page: Option<usize>,
+    pub size: Option<usize>,
+    pub prefix: Option<String>,
+    pub filter: Option<String>, // MQTT topic filter, e.g. `sensors/+/temp`
+    pub sort: Option<TopicSort>,
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TopicSort {
+    #[default]
+    Topic,
+    Subscribers,
+    Messages,
+    Bytes,
+    LastPublishAt,
+}
+
+/*
+  Validated form of the query's filters and ordering.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct TopicFilter {
+    pub prefix: Option<String>,
+    pub filter: Option<String>,
+    pub sort: TopicSort,
+    pub order: SortOrder,
+}
+
+impl TopicQuery {
+    pub fn filter(&self) -> Result<TopicFilter, String> {
+        let filter = self.filter.clone().filter(|f| !f.is_empty());
+        if let Some(filter) = &filter
+            && !is_valid_filter(filter)
+        {
+            return Err(format!("invalid topic filter '{}'", filter));
+        }
+
+        Ok(TopicFilter {
+            prefix: self.prefix.clone().filter(|p| !p.is_empty()),
+            filter,
+            sort: self.sort.unwrap_or_default(),
+            order: self.order.unwrap_or_default(),
+        })
+    }
+}
+
+impl TopicFilter {
+    pub fn matches(&self, topic: &TopicInfo) -> bool {
+        self.prefix.as_ref().is_none_or(|prefix| topic.topic.starts_with(prefix.as_str()))
+            && self.filter.as_ref().is_none_or(|filter| matches_filter(filter, &topic.topic))
+    }
+
+    /*
+      Orders two topics by the requested key, ties broken by name.
+    */
+    pub fn compare(&self, a: &TopicInfo, b: &TopicInfo) -> std::cmp::Ordering {
+        let order = match self.sort {
+            TopicSort::Topic => a.topic.cmp(&b.topic),
+            TopicSort::Subscribers => a.subscriber_count.cmp(&b.subscriber_count),
+            TopicSort::Messages => a.messages.cmp(&b.messages),
+            TopicSort::Bytes => a.bytes.cmp(&b.bytes),
+            TopicSort::LastPublishAt => a.last_publish_at.cmp(&b.last_publish_at),
+        };
+        let order = match self.order {
+            SortOrder::Asc => order,
+            SortOrder::Desc => order.reverse(),
+        };
+        order.then_with(|| a.topic.cmp(&b.topic))
+    }
+}
+
+/*
+  Query string of `GET /api/v1/topics/subscribers`: exactly one of
+  `filter` (clients subscribed with that exact filter) or `topic` (clients
+  a publish to that topic would reach).
+*/
+#[derive(Debug, Deserialize)]
+pub struct SubscribersQuery {
+    pub filter: Option<String>,
+    pub topic: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SubscriberLookup {
+    Filter(String),
+    Topic(String),
+}
+
+impl SubscribersQuery {
+    pub fn lookup(self) -> Result<SubscriberLookup, String> {
+        match (self.filter, self.topic) {
+            (Some(filter), None) if is_valid_filter(&filter) => Ok(SubscriberLookup::Filter(filter)),
+            (Some(filter), None) => Err(format!("invalid topic filter '{}'", filter)),
+            (None, Some(topic)) if is_valid_topic_name(&topic) => Ok(SubscriberLookup::Topic(topic)),
+            (None, Some(topic)) => Err(format!("invalid topic name '{}'", topic)),
+            _ => Err("pass exactly one of 'filter' or 'topic'".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicSubscriber {
+    pub client_id: String,
     pub qos: u8,
-    pub retain: bool,
 }
@@ -0,0 +1,315 @@
+use std::net::{IpAddr, SocketAddr};
+
+use chrono::{DateTime, Local};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::publish::PayloadEncoding,
+    protocol::packets::Packet,
+    utils::{
+        format_time::{format_datetime, format_datetime_millis, format_optional_datetime},
+        topic::{is_valid_filter, matches_filter},
+    },
+};
+
+pub const DEFAULT_DURATION_SECS: u64 = 60;
+pub const MAX_DURATION_SECS: u64 = 3600;
+pub const DEFAULT_CAPACITY: usize = 1000;
+pub const MAX_CAPACITY: usize = 100_000;
+pub const DEFAULT_PAYLOAD_BYTES: usize = 64;
+pub const MAX_PAYLOAD_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceKind {
+    ClientId,
+    Topic, // an MQTT topic filter
+    Ip,    // an address or CIDR network
+}
+
+/*
+  What a trace captures: every packet of a client id or remote address,
+  or the PUBLISH, SUBSCRIBE and UNSUBSCRIBE packets whose topic falls
+  under a filter.
+*/
+#[derive(Debug, Clone)]
+pub enum TraceTarget {
+    ClientId(String),
+    Topic(String),
+    Ip(IpNet),
+}
+
+impl TraceKind {
+    pub fn target(&self, value: &str) -> Result<TraceTarget, String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("value must not be empty".to_string());
+        }
+
+        match self {
+            TraceKind::ClientId => Ok(TraceTarget::ClientId(value.to_string())),
+            TraceKind::Topic if is_valid_filter(value) => Ok(TraceTarget::Topic(value.to_string())),
+            TraceKind::Topic => Err(format!("invalid topic filter '{}'", value)),
+            TraceKind::Ip => value
+                .parse::<IpNet>()
+                .map(|net| net.trunc())
+                .or_else(|_| value.parse::<IpAddr>().map(|ip| IpNet::from(ip.to_canonical())))
+                .map(TraceTarget::Ip)
+                .map_err(|_| format!("invalid ip '{}': expected an address or CIDR network", value)),
+        }
+    }
+}
+
+impl TraceTarget {
+    pub fn kind(&self) -> TraceKind {
+        match self {
+            TraceTarget::ClientId(_) => TraceKind::ClientId,
+            TraceTarget::Topic(_) => TraceKind::Topic,
+            TraceTarget::Ip(_) => TraceKind::Ip,
+        }
+    }
+
+    pub fn value(&self) -> String {
+        match self {
+            TraceTarget::ClientId(value) | TraceTarget::Topic(value) => value.clone(),
+            TraceTarget::Ip(net) if net.prefix_len() == net.max_prefix_len() => net.addr().to_string(),
+            TraceTarget::Ip(net) => net.to_string(),
+        }
+    }
+
+    pub fn matches(&self, client_id: Option<&str>, ip: IpAddr, packet: &Packet) -> bool {
+        match self {
+            TraceTarget::ClientId(id) => client_id == Some(id.as_str()),
+            TraceTarget::Ip(net) => net.contains(&ip.to_canonical()),
+            TraceTarget::Topic(filter) => match packet {
+                Packet::Publish(p) => matches_filter(filter, &p.topic),
+                Packet::Subscribe(p) => p.filters.iter().any(|f| matches_filter(filter, &f.topic)),
+                Packet::Unsubscribe(p) => p.topics.iter().any(|topic| matches_filter(filter, topic)),
+                _ => false,
+            },
+        }
+    }
+}
+
+/*
+  Body of `POST /api/v1/traces`.
+*/
+#[derive(Debug, Deserialize)]
+pub struct CreateTrace {
+    pub kind: TraceKind,
+    pub value: String,
+    pub duration_secs: Option<u64>, // stops on its own after this long
+    pub capacity: Option<usize>,    // records kept; the oldest are dropped past it
+    pub payload_bytes: Option<usize>, // payload bytes kept per PUBLISH, 0 for none
+}
+
+/*
+  Validated form of `CreateTrace`.
+*/
+#[derive(Debug, Clone)]
+pub struct TraceOptions {
+    pub target: TraceTarget,
+    pub duration_secs: u64,
+    pub capacity: usize,
+    pub payload_bytes: usize,
+}
+
+impl CreateTrace {
+    pub fn options(&self) -> Result<TraceOptions, String> {
+        let duration_secs = self.duration_secs.unwrap_or(DEFAULT_DURATION_SECS);
+        if !(1..=MAX_DURATION_SECS).contains(&duration_secs) {
+            return Err(format!("duration_secs must be between 1 and {}", MAX_DURATION_SECS));
+        }
+        let capacity = self.capacity.unwrap_or(DEFAULT_CAPACITY);
+        if !(1..=MAX_CAPACITY).contains(&capacity) {
+            return Err(format!("capacity must be between 1 and {}", MAX_CAPACITY));
+        }
+        let payload_bytes = self.payload_bytes.unwrap_or(DEFAULT_PAYLOAD_BYTES);
+        if payload_bytes > MAX_PAYLOAD_BYTES {
+            return Err(format!("payload_bytes must be at most {}", MAX_PAYLOAD_BYTES));
+        }
+
+        Ok(TraceOptions {
+            target: self.kind.target(&self.value)?,
+            duration_secs,
+            capacity,
+            payload_bytes,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TraceRecordQuery {
+    pub after: Option<u64>, // only records with a greater `seq`
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceInfo {
+    pub id: String,
+    pub kind: TraceKind,
+    pub value: String,
+    pub created_by: String,
+
+    #[serde(serialize_with = "format_datetime")]
+    pub started_at: DateTime<Local>,
+    #[serde(serialize_with = "format_datetime")]
+    pub ends_at: DateTime<Local>,
+    #[serde(serialize_with = "format_optional_datetime")]
+    pub stopped_at: Option<DateTime<Local>>,
+    pub active: bool,
+
+    pub capacity: usize,
+    pub payload_bytes: usize,
+    pub captured: u64, // records captured since the start
+    pub dropped: u64,  // of those, pushed out of the buffer
+}
+
+/*
+  A trace with its buffered records, oldest first.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceDetail {
+    pub trace: TraceInfo,
+    pub records: Vec<TraceRecord>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceDirection {
+    In,  // received from the client
+    Out, // sent to the client
+}
+
+/*
+  One captured packet. `topic`, `qos`, `retain` and the payload fields are
+  set for PUBLISH only; `detail` summarizes the other packets.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceRecord {
+    pub seq: u64,
+
+    #[serde(serialize_with = "format_datetime_millis")]
+    pub at: DateTime<Local>,
+    pub direction: TraceDirection,
+    pub client_id: Option<String>,
+    pub remote_addr: SocketAddr,
+    pub packet_type: &'static str,
+    pub packet_id: Option<u16>,
+    pub topic: Option<String>,
+    pub qos: Option<u8>,
+    pub retain: Option<bool>,
+    pub payload_size: Option<usize>,
+    pub payload_preview: Option<String>,
+    pub payload_encoding: Option<PayloadEncoding>, // `plain` for UTF-8 text, `hex` otherwise
+    pub detail: Option<String>,
+}
+
+impl TraceRecord {
+    pub fn new(
+        direction: TraceDirection,
+        client_id: Option<&str>,
+        remote_addr: SocketAddr,
+        packet: &Packet,
+        payload_bytes: usize,
+    ) -> Self {
+        let mut record = TraceRecord {
+            seq: 0,
+            at: Local::now(),
+            direction,
+            client_id: client_id.map(str::to_string),
+            remote_addr,
+            packet_type: packet_type(packet),
+            packet_id: None,
+            topic: None,
+            qos: None,
+            retain: None,
+            payload_size: None,
+            payload_preview: None,
+            payload_encoding: None,
+            detail: None,
+        };
+
+        match packet {
+            Packet::Connect(p) => {
+                record.client_id = Some(p.client_id.clone());
+                record.detail = Some(format!(
+                    "protocol_level={} keep_alive={} clean_session={} username={}",
+                    p.protocol_level,
+                    p.keep_alive,
+                    p.clean_session,
+                    p.username.as_deref().unwrap_or("-"),
+                ));
+            }
+            Packet::ConnAck(p) => record.detail = Some(format!("return_code={:?}", p.return_code)),
+            Packet::Publish(p) => {
+                record.packet_id = p.packet_id;
+                record.topic = Some(p.topic.clone());
+                record.qos = Some(p.qos);
+                record.retain = Some(p.retain);
+                record.payload_size = Some(p.payload.len());
+                if payload_bytes > 0 {
+                    let (preview, encoding) = preview(&p.payload, payload_bytes);
+                    record.payload_preview = Some(preview);
+                    record.payload_encoding = Some(encoding);
+                }
+            }
+            Packet::PubAck(id) | Packet::PubRec(id) | Packet::PubRel(id) | Packet::PubComp(id) | Packet::UnsubAck(id) => {
+                record.packet_id = Some(*id);
+            }
+            Packet::Subscribe(p) => {
+                record.packet_id = Some(p.packet_id);
+                let filters: Vec<String> = p.filters.iter().map(|f| format!("{} qos={}", f.topic, f.qos)).collect();
+                record.detail = Some(filters.join(", "));
+            }
+            Packet::SubAck(p) => {
+                record.packet_id = Some(p.packet_id);
+                record.detail = Some(format!("return_codes={:?}", p.return_codes));
+            }
+            Packet::Unsubscribe(p) => {
+                record.packet_id = Some(p.packet_id);
+                record.detail = Some(p.topics.join(", "));
+            }
+            Packet::PingReq | Packet::PingResp | Packet::Disconnect => {}
+        }
+
+        record
+    }
+}
+
+fn packet_type(packet: &Packet) -> &'static str {
+    match packet {
+        Packet::Connect(_) => "CONNECT",
+        Packet::ConnAck(_) => "CONNACK",
+        Packet::Publish(_) => "PUBLISH",
+        Packet::PubAck(_) => "PUBACK",
+        Packet::PubRec(_) => "PUBREC",
+        Packet::PubRel(_) => "PUBREL",
+        Packet::PubComp(_) => "PUBCOMP",
+        Packet::Subscribe(_) => "SUBSCRIBE",
+        Packet::SubAck(_) => "SUBACK",
+        Packet::Unsubscribe(_) => "UNSUBSCRIBE",
+        Packet::UnsubAck(_) => "UNSUBACK",
+        Packet::PingReq => "PINGREQ",
+        Packet::PingResp => "PINGRESP",
+        Packet::Disconnect => "DISCONNECT",
+    }
+}
+
+/*
+  The first `limit` bytes of a payload: as text if they are UTF-8 (a
+  character cut off by the limit is left out), hex otherwise.
+*/
+fn preview(payload: &[u8], limit: usize) -> (String, PayloadEncoding) {
+    let head = &payload[..payload.len().min(limit)];
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => Some(text),
+        Err(e) if e.error_len().is_none() && head.len() < payload.len() => std::str::from_utf8(&head[..e.valid_up_to()]).ok(),
+        Err(_) => None,
+    };
+
+    match text {
+        Some(text) => (text.to_string(), PayloadEncoding::Plain),
+        None => (head.iter().map(|b| format!("{:02x}", b)).collect(), PayloadEncoding::Hex),
+    }
+}
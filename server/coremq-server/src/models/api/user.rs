@@ -1,9 +1,72 @@
 use serde::{Deserialize, Serialize};
 
 
+/*
+  Stored user record. Contains the password hash, so it is never sent in
+  API responses; use `UserInfo` for that.
+*/
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct User {
     pub username: String,
     pub password_hash: String,
     pub role: String,
-}
\ No newline at end of file
+    /*
+      Set for the default admin account until its password is changed.
+    */
+    pub must_change_password: bool,
+    /*
+      Unix seconds of the last password change. Tokens issued before it
+      are rejected.
+    */
+    pub password_changed_at: i64,
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserInfo {
+    pub username: String,
+    pub role: String,
+    pub must_change_password: bool,
+}
+
+impl From<User> for UserInfo {
+    fn from(user: User) -> Self {
+        Self {
+            username: user.username,
+            role: user.role,
+            must_change_password: user.must_change_password,
+        }
+    }
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateUser {
+    pub username: String,
+    /*
+      Plain-text password. Older clients sent it as `password_hash`.
+    */
+    #[serde(alias = "password_hash")]
+    pub password: String,
+    pub role: String,
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpdateUser {
+    pub role: Option<String>,
+    pub password: Option<String>,
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoleUpdate {
+    pub role: String,
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangePassword {
+    pub current_password: String,
+    pub new_password: String,
+}
@@ -4,10 +4,77 @@ use serde::{Deserialize, Serialize};
 
 use crate::models::listener::ListenerConfig;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub middleware: Middleware,
     pub mqtt: MqttConfig,
+
+    #[serde(default)]
+    pub engine: EngineConfig,
+
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    #[serde(default)]
+    pub lockout: LockoutConfig,
+}
+
+/*
+  Admin REST API listener.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HttpConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    #[serde(default = "default_http_host")]
+    pub host: String,
+
+    #[serde(default = "default_http_port")]
+    pub port: u16,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            host: default_http_host(),
+            port: default_http_port(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StorageConfig {
+    #[serde(default = "default_storage_path")]
+    pub path: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: default_storage_path(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_http_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_http_port() -> u16 {
+    18083
+}
+
+fn default_storage_path() -> String {
+    "data/coremq.redb".to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,3 +89,96 @@ pub struct MqttConfig {
     pub listeners: Vec<ListenerConfig>,
 }
 
+/*
+  Capacities of the bounded engine ingress queues.
+  When a queue is full the sending connection stops reading from its socket
+  until the engine catches up.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EngineConfig {
+    #[serde(default = "default_connect_queue_size")]
+    pub connect_queue_size: usize,
+
+    #[serde(default = "default_pubsub_queue_size")]
+    pub pubsub_queue_size: usize,
+
+    #[serde(default = "default_admin_queue_size")]
+    pub admin_queue_size: usize,
+
+    /* Events an event stream may fall behind before it skips the oldest. */
+    #[serde(default = "default_event_buffer_size")]
+    pub event_buffer_size: usize,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            connect_queue_size: default_connect_queue_size(),
+            pubsub_queue_size: default_pubsub_queue_size(),
+            admin_queue_size: default_admin_queue_size(),
+            event_buffer_size: default_event_buffer_size(),
+        }
+    }
+}
+
+fn default_connect_queue_size() -> usize {
+    1024
+}
+
+fn default_pubsub_queue_size() -> usize {
+    8192
+}
+
+fn default_admin_queue_size() -> usize {
+    256
+}
+
+fn default_event_buffer_size() -> usize {
+    1024
+}
+
+/*
+  Failed-login limits for the admin API. A limit of 0 disables that
+  lockout.
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LockoutConfig {
+    #[serde(default = "default_max_failures")]
+    pub max_failures: u32,
+
+    #[serde(default = "default_max_failures_per_ip")]
+    pub max_failures_per_ip: u32,
+
+    #[serde(default = "default_lockout_base_secs")]
+    pub base_secs: u64,
+
+    #[serde(default = "default_lockout_max_secs")]
+    pub max_secs: u64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: default_max_failures(),
+            max_failures_per_ip: default_max_failures_per_ip(),
+            base_secs: default_lockout_base_secs(),
+            max_secs: default_lockout_max_secs(),
+        }
+    }
+}
+
+fn default_max_failures() -> u32 {
+    5
+}
+
+fn default_max_failures_per_ip() -> u32 {
+    20
+}
+
+fn default_lockout_base_secs() -> u64 {
+    30
+}
+
+fn default_lockout_max_secs() -> u64 {
+    900
+}
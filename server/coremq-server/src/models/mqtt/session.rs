@@ -1,57 +1,113 @@
-use std::{collections::HashMap, net::SocketAddr};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 use chrono::{DateTime, Local};
 use serde::Serialize;
 use tokio::sync::{ mpsc};
 
 use crate::{
-    enums::MqttChannel, 
-    utils::format_time::format_datetime,
-    protocol::packets::{ SubscribePacket}};
+    enums::MqttChannel,
+    protocol::packets::{ConnectPacket, Will},
+    transport::ConnectionStats,
+    utils::format_time::format_datetime};
 
 
+/*
+  One granted filter of a session, as shown by the admin API.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSubscription {
+    pub packet_id: u16,
+    pub topic: String,
+    pub qos: u8,
+
+    #[serde(serialize_with = "format_datetime")]
+    pub subscribed_at: DateTime<Local>,
+}
+
+/*
+  Last will registered at CONNECT. The payload itself is not exposed.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionWill {
+    pub topic: String,
+    pub qos: u8,
+    pub retain: bool,
+    pub payload_size: usize,
+}
+
+impl From<&Will> for SessionWill {
+    fn from(will: &Will) -> Self {
+        Self {
+            topic: will.topic.clone(),
+            qos: will.qos,
+            retain: will.retain,
+            payload_size: will.message.len(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct Session {
     pub client_id: String,
     pub username: String,
     pub clean_session: bool,
+    /* Protocol level from CONNECT: 3 for MQTT 3.1, 4 for 3.1.1. */
+    pub protocol_version: u8,
+    pub keep_alive: u16,
+    pub will: Option<SessionWill>,
     pub remote_addr: SocketAddr,
     pub connected_port: u16,
 
     #[serde(serialize_with = "format_datetime")]
     pub connected_at: DateTime<Local>,
-    pub subscriptions: HashMap<String, SubscribePacket>,
+    pub subscriptions: HashMap<String, SessionSubscription>,
+
+    /*
+      Identifies the network connection that owns this session, so a
+      connection that was taken over cannot tear down its successor.
+    */
+    #[serde(skip)]
+    pub connection_id: u64,
 
      #[serde(skip)]
     pub tx: mpsc::Sender<MqttChannel>,
+
+    /* Shared with the connection's transport task. */
+    #[serde(skip)]
+    pub stats: Arc<ConnectionStats>,
 }
 
 impl Session {
     pub fn new(
-        client_id: String,
-        username: String,
-        clean_session: bool,
+        packet: &ConnectPacket,
         connected_port: u16,
         remote_addr: SocketAddr,
+        connection_id: u64,
         tx: mpsc::Sender<MqttChannel>,
+        stats: Arc<ConnectionStats>,
     ) -> Self {
         Self {
-            client_id,
-            username,
-            clean_session,
+            client_id: packet.client_id.clone(),
+            username: packet.username.clone().unwrap_or_default(),
+            clean_session: packet.clean_session,
+            protocol_version: packet.protocol_level,
+            keep_alive: packet.keep_alive,
+            will: packet.will.as_ref().map(SessionWill::from),
             connected_port,
             connected_at: Local::now(),
             subscriptions: HashMap::new(),
             remote_addr,
+            connection_id,
             tx,
+            stats,
         }
     }
 
-    pub fn add_subscription(&mut self, sub: SubscribePacket) {
+    pub fn add_subscription(&mut self, sub: SessionSubscription) {
         self.subscriptions.insert(sub.topic.clone(), sub);
     }
 
-    pub fn remove_subscription(&mut self, topic: &str) {
-        self.subscriptions.remove(topic);
+    pub fn remove_subscription(&mut self, topic: &str) -> bool {
+        self.subscriptions.remove(topic).is_some()
     }
 }
 
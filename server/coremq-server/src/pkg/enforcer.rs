@@ -1,12 +1,28 @@
-use casbin::{CoreApi, DefaultModel, Enforcer, Error, FileAdapter};
+use casbin::{CoreApi, DefaultModel, Enforcer, FileAdapter, MgmtApi};
 
-use crate::models::config::Middleware;
+use crate::{models::config::Middleware, services::policy::PolicyService, storage::redb::policy::PolicyAdapter};
 
-pub async  fn new(cfg: Middleware) -> Result<Enforcer, Error> {
-    let model = DefaultModel::from_file(cfg.model_path).await?;
-    let adapter = FileAdapter::new(cfg.policy_path);
-    let mut enforcer = Enforcer::new(model, adapter).await?;
+/*
+  Builds the policy service on top of redb. The first start imports the
+  rules from `policy_path`; later edits to that file are ignored and
+  policies are managed through the admin API instead. Rules shipped since
+  are added by the adapter's migrations.
+*/
+pub async fn new(cfg: Middleware, adapter: PolicyAdapter) -> anyhow::Result<PolicyService> {
+    if !adapter.is_seeded()? {
+        let model = DefaultModel::from_file(&cfg.model_path).await?;
+        let file = Enforcer::new(model, FileAdapter::new(cfg.policy_path.clone())).await?;
 
-    enforcer.load_policy().await?;
-    Ok(enforcer)
-}
\ No newline at end of file
+        let mut rules: Vec<(String, Vec<String>)> = Vec::new();
+        rules.extend(file.get_policy().into_iter().map(|rule| ("p".to_string(), rule)));
+        rules.extend(file.get_grouping_policy().into_iter().map(|rule| ("g".to_string(), rule)));
+        adapter.seed(&rules)?;
+        println!("Imported {} policy rules from {}", rules.len(), cfg.policy_path);
+    }
+    for name in adapter.migrate()? {
+        println!("Applied policy migration {}", name);
+    }
+
+    let model = tokio::fs::read_to_string(&cfg.model_path).await?;
+    Ok(PolicyService::new(model, adapter).await?)
+}
@@ -1,138 +1,99 @@
-use crate::protocol::packets::PublishPacket;
-
-
-use axum::extract::ws::{Message, WebSocket};
-use futures_util::{SinkExt, stream::SplitSink};
-use tokio::{io::AsyncWriteExt, net::TcpStream};
-
-
-
-pub enum Encoder {
-    ConnAck { session_present: bool },
-    SubAck { packet_id: u16 },
-    UnsubAck { packet_id: u16 },
-    PubAck {  packet_id: u16},
-    PingResp,
-    Disconnect,
-    None
+use std::sync::{Arc, OnceLock};
+
+use bytes::{Bytes, BytesMut};
+use coremq_codec::{encode_publish, header::Header};
+
+use crate::{protocol::packets::PublishPacket, services::routing::AckTracker};
+
+/*
+  A publish being fanned out to subscribers. The outgoing frame is encoded
+  lazily, at most once per QoS/retain variant, and shared by every
+  subscriber that receives that variant.
+*/
+#[derive(Debug)]
+pub struct OutgoingPublish {
+    pub packet: PublishPacket,
+    /* Set when the publisher waits for subscribers to acknowledge. */
+    pub ack: Option<Arc<AckTracker>>,
+    frames: [OnceLock<Option<Bytes>>; 6],
 }
 
+impl OutgoingPublish {
+    pub fn new(packet: PublishPacket) -> Self {
+        Self::with_ack(packet, None)
+    }
 
-impl Encoder {
-    pub fn to_bytes(&self) -> Vec<u8> {
-        match self {
-            Encoder::ConnAck { session_present } => vec![
-                0x20,
-                0x02,
-                if *session_present { 0x01 } else { 0x00 },
-                0x00,
-            ],
-
-            Encoder::SubAck { packet_id } => vec![
-                0x90,
-                0x03,
-                (packet_id >> 8) as u8,
-                (*packet_id & 0xFF) as u8,
-                0x00,
-            ],
-
-            Encoder::UnsubAck { packet_id } => vec![
-                0xB0,
-                0x02,
-                (packet_id >> 8) as u8,
-                (*packet_id & 0xFF) as u8,
-            ],
-
-            Encoder::PubAck { packet_id } =>  vec![
-                0x40,
-                0x02,
-                (packet_id >> 8) as u8,
-                (*packet_id & 0xFF) as u8,
-            ],
-            Encoder::PingResp => vec![0xD0, 0x00],
-
-            Encoder::Disconnect => vec![0xE0, 0x00],
-            Encoder::None => vec![]
+    pub fn with_ack(packet: PublishPacket, ack: Option<Arc<AckTracker>>) -> Self {
+        Self {
+            packet,
+            ack,
+            frames: Default::default(),
         }
     }
 
-
-    pub async fn send_tcp(self, socket: &mut TcpStream) -> anyhow::Result<()> {
-        let bytes = self.to_bytes();
-        socket.write_all(&bytes).await?;
-        Ok(())
+    /*
+      Returns the encoded frame for a subscriber granted `qos`. The delivered
+      QoS never exceeds the QoS the message was published with. `None` means
+      the message cannot be put on the wire.
+    */
+    pub fn frame(&self, qos: u8, retain: bool) -> Option<Bytes> {
+        let qos = qos.min(self.packet.qos).min(2);
+        let slot = (qos as usize) * 2 + retain as usize;
+        self.frames[slot]
+            .get_or_init(|| {
+                let variant = PublishPacket {
+                    qos,
+                    retain,
+                    dup: false,
+                    ..self.packet.clone()
+                };
+                encode_publish(&variant).ok()
+            })
+            .clone()
     }
 
-   pub async fn send_ws(
-    self,
-    sender: &mut SplitSink<WebSocket, Message>,
-) -> anyhow::Result<()> {
-    let bytes = self.to_bytes();
-    sender.send(Message::Binary(bytes)).await?;
-    Ok(())
+    /*
+      Returns the frame for one subscriber with `packet_id` written in.
+      Packet ids belong to the receiving session, so the publisher's id
+      cannot be forwarded as is. Only the fixed header, topic and id are
+      copied to patch the id; the rest of the frame stays shared. QoS 0
+      frames have no id and are returned whole in `head`.
+    */
+    pub fn frame_with_id(&self, qos: u8, retain: bool, packet_id: Option<u16>) -> Option<Frame> {
+        let frame = self.frame(qos, retain)?;
+        let Some(packet_id) = packet_id.filter(|_| qos.min(self.packet.qos) > 0) else {
+            return Some(Frame { head: frame, body: Bytes::new() });
+        };
+
+        let (_, length_size) = Header::read_remaining_length(&frame[1..]).ok()??;
+        let topic_start = 1 + length_size + 2;
+        let topic_len = u16::from_be_bytes([frame[topic_start - 2], frame[topic_start - 1]]) as usize;
+        let id_end = topic_start + topic_len + 2;
+
+        let mut head = BytesMut::from(&frame[..id_end]);
+        head[id_end - 2..].copy_from_slice(&packet_id.to_be_bytes());
+        Some(Frame {
+            head: head.freeze(),
+            body: frame.slice(id_end..),
+        })
+    }
 }
 
+/*
+  One subscriber's PUBLISH frame: `head` followed by `body` on the wire.
+*/
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub head: Bytes,
+    pub body: Bytes,
 }
 
-
-
-pub fn encode_publish(msg: &PublishPacket) -> Vec<u8> {
-    let mut out = Vec::new();
-
-    let mut first_byte = 0b0011_0000;
-
-    if msg.dup {
-        first_byte |= 0b0000_1000;
+impl Frame {
+    pub fn len(&self) -> usize {
+        self.head.len() + self.body.len()
     }
 
-    let qos = msg.qos & 0x03;
-    first_byte |= qos << 1;
-
-    if msg.retain {
-        first_byte |= 0b0000_0001;
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
-
-    out.push(first_byte);
-
-    let mut remaining_len =
-        2 + msg.topic.len() + msg.payload.len();
-
-    if qos > 0 {
-        remaining_len += 2;
-    }
-
-    out.extend(encode_remaining_length(remaining_len));
-
-    out.extend_from_slice(&(msg.topic.len() as u16).to_be_bytes());
-    out.extend_from_slice(msg.topic.as_bytes());
-
-    if qos > 0 {
-        let packet_id = msg.packet_id.expect("QoS > 0 requires packet_id");
-        out.extend_from_slice(&packet_id.to_be_bytes());
-    }
-
-    out.extend_from_slice(&msg.payload);
-
-    out
-}
-
-fn encode_remaining_length(mut len: usize) -> Vec<u8> {
-    let mut encoded = Vec::new();
-
-    loop {
-        let mut byte = (len % 128) as u8;
-        len /= 128;
-
-        if len > 0 {
-            byte |= 0x80;
-        }
-
-        encoded.push(byte);
-
-        if len == 0 {
-            break;
-        }
-    }
-
-    encoded
 }
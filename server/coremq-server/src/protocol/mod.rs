@@ -1,4 +1,7 @@
-pub mod header;
+/*
+  The wire format lives in the `coremq-codec` crate so clients and tools
+  share it; only broker-specific delivery helpers are defined here.
+*/
+pub use coremq_codec::{header, packets, CodecError, DecodeError, EncodeError, MqttPacketType};
+
 pub mod encoder;
-pub mod packets;
-pub mod decoder;
\ No newline at end of file
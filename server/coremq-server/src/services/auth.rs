@@ -0,0 +1,95 @@
+use std::{net::IpAddr, sync::{Arc, LazyLock}};
+
+use chrono::Utc;
+
+use crate::{
+    models::{config::LockoutConfig, event::BrokerEvent, user::User},
+    services::{events::EventService, lockout::LoginGuard},
+    storage::redb::Storage,
+    utils,
+};
+
+/*
+  Verified against when the username doesn't exist, so an unknown user
+  takes as long to reject as a wrong password.
+*/
+static DUMMY_HASH: LazyLock<String> =
+    LazyLock::new(|| utils::password::hash_password("coremq-unknown-user").unwrap_or_default());
+
+#[derive(Debug)]
+pub enum LoginError {
+    /* Unknown user or wrong password; callers must not tell them apart. */
+    Invalid,
+    /* Too many failures; seconds until the next attempt is allowed. */
+    Locked(i64),
+    Storage(String),
+}
+
+/*
+  Password checks for the admin API login, subject to the lockouts. Every
+  refused attempt is published as an `auth_failed` event.
+*/
+pub struct AuthService {
+    storage: Arc<Storage>,
+    guard: LoginGuard,
+    events: Arc<EventService>,
+}
+
+impl AuthService {
+    pub fn new(storage: Arc<Storage>, config: LockoutConfig, events: Arc<EventService>) -> Self {
+        Self {
+            storage,
+            guard: LoginGuard::new(config),
+            events,
+        }
+    }
+
+    pub fn guard(&self) -> &LoginGuard {
+        &self.guard
+    }
+
+    pub async fn login(&self, username: &str, password: &str, ip: IpAddr) -> Result<User, LoginError> {
+        /* A dual-stack listener reports IPv4 peers as `::ffff:a.b.c.d`. */
+        let ip = ip.to_canonical();
+        if let Some(retry_after) = self.guard.locked(username, ip, Utc::now().timestamp()) {
+            println!("Rejected login for '{}' from {}: locked for {}s", username, ip, retry_after);
+            self.failed(username, ip, true);
+            return Err(LoginError::Locked(retry_after));
+        }
+
+        let user = self.storage.user.get(username).map_err(|e| LoginError::Storage(e.to_string()))?;
+
+        /* Argon2 takes tens of milliseconds; keep it off the async workers. */
+        let hash = user.as_ref().map_or_else(|| DUMMY_HASH.clone(), |u| u.password_hash.clone());
+        let password = password.to_string();
+        let valid = tokio::task::spawn_blocking(move || utils::password::verify(&password, &hash))
+            .await
+            .unwrap_or(false);
+
+        match user {
+            Some(user) if valid => {
+                self.guard.record_success(username);
+                Ok(user)
+            }
+            _ => {
+                let locked = self.guard.record_failure(username, ip, Utc::now().timestamp());
+                println!(
+                    "Failed login for '{}' from {}{}",
+                    username,
+                    ip,
+                    if locked { "; locked out" } else { "" }
+                );
+                self.failed(username, ip, locked);
+                Err(LoginError::Invalid)
+            }
+        }
+    }
+
+    fn failed(&self, username: &str, ip: IpAddr, locked: bool) {
+        self.events.publish(BrokerEvent::AuthFailed {
+            username: username.to_string(),
+            ip,
+            locked,
+        });
+    }
+}
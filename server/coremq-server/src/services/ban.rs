@@ -0,0 +1,118 @@
+use std::{net::IpAddr, sync::RwLock};
+
+use chrono::Utc;
+use ipnet::IpNet;
+
+use crate::{
+    models::ban::{self, Ban, BanKind},
+    storage::redb::ban::BanRepo,
+};
+
+struct Rule {
+    ban: Ban,
+    net: Option<IpNet>,
+}
+
+impl Rule {
+    fn new(ban: Ban) -> Self {
+        let net = match ban.kind {
+            BanKind::Cidr => ban.value.parse().ok(),
+            _ => None,
+        };
+        Self { ban, net }
+    }
+
+    fn matches_ip(&self, ip: IpAddr) -> bool {
+        match self.ban.kind {
+            BanKind::Ip => self.ban.value == ip.to_string(),
+            BanKind::Cidr => self.net.is_some_and(|net| net.contains(&ip)),
+            _ => false,
+        }
+    }
+
+    fn matches(&self, client_id: &str, username: Option<&str>, ip: IpAddr) -> bool {
+        match self.ban.kind {
+            BanKind::ClientId => self.ban.value == client_id,
+            BanKind::Username => username == Some(self.ban.value.as_str()),
+            BanKind::Ip | BanKind::Cidr => self.matches_ip(ip),
+        }
+    }
+}
+
+/*
+  Persisted ban list with an in-memory copy, so that accepting a
+  connection doesn't need a database read. Expired bans stop matching
+  right away and are deleted from storage on the next change.
+*/
+pub struct BanService {
+    repo: BanRepo,
+    rules: RwLock<Vec<Rule>>,
+}
+
+impl BanService {
+    pub fn new(repo: BanRepo) -> anyhow::Result<Self> {
+        repo.prune(Utc::now().timestamp())?;
+        let rules = repo.get_all()?.into_iter().map(Rule::new).collect();
+        Ok(Self {
+            repo,
+            rules: RwLock::new(rules),
+        })
+    }
+
+    pub fn list(&self) -> Vec<Ban> {
+        self.rules.read().unwrap().iter().map(|rule| rule.ban.clone()).collect()
+    }
+
+    /*
+      Adds the ban, replacing an existing ban of the same value.
+    */
+    pub fn add(&self, ban: Ban) -> anyhow::Result<()> {
+        let now = Utc::now().timestamp();
+        let mut rules = self.rules.write().unwrap();
+        self.repo.prune(now)?;
+        self.repo.put(&ban)?;
+
+        let key = ban.key();
+        rules.retain(|rule| rule.ban.key() != key && !rule.ban.is_expired(now));
+        rules.push(Rule::new(ban));
+        Ok(())
+    }
+
+    /*
+      `value` must be normalized. Returns false if there was no such ban.
+    */
+    pub fn remove(&self, kind: BanKind, value: &str) -> anyhow::Result<bool> {
+        let key = ban::key(kind, value);
+        let mut rules = self.rules.write().unwrap();
+        let removed = self.repo.delete(&key)?;
+        rules.retain(|rule| rule.ban.key() != key);
+        Ok(removed)
+    }
+
+    /*
+      Checked when a connection is accepted, before any packet is read.
+    */
+    pub fn ip_banned(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        let now = Utc::now().timestamp();
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .any(|rule| !rule.ban.is_expired(now) && rule.matches_ip(ip))
+    }
+
+    /*
+      The first active ban matching a CONNECT.
+    */
+    pub fn find(&self, client_id: &str, username: Option<&str>, ip: IpAddr) -> Option<Ban> {
+        let ip = ip.to_canonical();
+        let now = Utc::now().timestamp();
+        self.rules
+            .read()
+            .unwrap()
+            .iter()
+            .find(|rule| !rule.ban.is_expired(now) && rule.matches(client_id, username, ip))
+            .map(|rule| rule.ban.clone())
+    }
+}
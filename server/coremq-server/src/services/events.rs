@@ -0,0 +1,71 @@
+use chrono::Local;
+use tokio::sync::{broadcast::{self, error::RecvError}, watch};
+
+use crate::models::event::{BrokerEvent, Event};
+
+/*
+  Fans broker events out to the event streams. Publishing never blocks:
+  with no stream open the event is dropped, and a stream that falls more
+  than `capacity` events behind skips the oldest ones.
+*/
+pub struct EventService {
+    tx: broadcast::Sender<Event>,
+    closed: watch::Sender<bool>,
+}
+
+impl EventService {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity.max(1));
+        let (closed, _) = watch::channel(false);
+        Self { tx, closed }
+    }
+
+    pub fn publish(&self, event: BrokerEvent) {
+        let _ = self.tx.send(Event {
+            event,
+            at: Local::now(),
+        });
+    }
+
+    pub fn subscribe(&self) -> EventSubscription {
+        EventSubscription {
+            rx: self.tx.subscribe(),
+            closed: self.closed.subscribe(),
+        }
+    }
+
+    /*
+      Ends every open stream, so the admin API can shut down without
+      waiting on them.
+    */
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+}
+
+pub struct EventSubscription {
+    rx: broadcast::Receiver<Event>,
+    closed: watch::Receiver<bool>,
+}
+
+impl EventSubscription {
+    /*
+      Waits for the next event. Events missed by falling behind are
+      reported as one `Lagged` event. Returns `None` once the service is
+      closed and the events published before that have been received.
+    */
+    pub async fn recv(&mut self) -> Option<Event> {
+        tokio::select! {
+            biased;
+            received = self.rx.recv() => match received {
+                Ok(event) => Some(event),
+                Err(RecvError::Lagged(missed)) => Some(Event {
+                    event: BrokerEvent::Lagged { missed },
+                    at: Local::now(),
+                }),
+                Err(RecvError::Closed) => None,
+            },
+            _ = self.closed.wait_for(|closed| *closed) => None,
+        }
+    }
+}
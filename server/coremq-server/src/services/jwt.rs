@@ -1,9 +1,10 @@
 use chrono::{Duration, Utc};
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::Error};
-
-use crate::{enums::{jwt::JwtType, role::RoleType}, models::{claims::{self, Claims}, config, login::Token, user}};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::{Error, ErrorKind}};
 
+use crate::{enums::{jwt::JwtType, role::RoleType}, models::{claims::Claims, config, login::Token}, utils::random};
 
+pub const ACCESS_TOKEN_TTL: i64 = 86400;
+pub const REFRESH_TOKEN_TTL: i64 = 604800;
 
 #[derive(Clone)]
 pub struct JwtService {
@@ -25,11 +26,16 @@ impl JwtService {
         }
     }
 
+    /*
+      Both tokens of a pair share one `jti`, so revoking it (logout, refresh
+      rotation) invalidates the access and the refresh token together.
+    */
     pub fn generate(&self, username: String, role:RoleType) -> Result<Token, Error> {
-        let access_token = self.generate_token(username.clone(), JwtType::AccessToken, 86400, &role)?;
-        let refresh_token = self.generate_token(username.clone(), JwtType::RefreshToken, 604800, &role)?;
+        let jti = random::hex(16);
+        let access_token = self.generate_token(username.clone(), JwtType::AccessToken, ACCESS_TOKEN_TTL, &role, &jti)?;
+        let refresh_token = self.generate_token(username.clone(), JwtType::RefreshToken, REFRESH_TOKEN_TTL, &role, &jti)?;
         Ok(Token{
-            access_token, refresh_token
+            access_token, refresh_token, password_change_required: false
         })
     }
 
@@ -38,17 +44,29 @@ impl JwtService {
         Ok(data.claims)
     }
 
+    /*
+      Like `parse`, but rejects tokens of any other type.
+    */
+    pub fn parse_as(&self, token: &str, token_type: JwtType) -> Result<Claims, Error> {
+        let claims = self.parse(token)?;
+        if claims.token_type != token_type.as_str() {
+            return Err(ErrorKind::InvalidToken.into());
+        }
+        Ok(claims)
+    }
 
-    fn generate_token(&self, username:String, token_type:JwtType, ttl:i64, role:&RoleType) -> Result<String, Error> {
+
+    fn generate_token(&self, username:String, token_type:JwtType, ttl:i64, role:&RoleType, jti: &str) -> Result<String, Error> {
         let now = Utc::now();
         let claims = Claims{
             sub: username,
             iat:now.timestamp() as usize,
             role: role.to_string(),
             exp: (now + Duration::seconds(ttl)).timestamp() as usize,
-            token_type: token_type.to_string()
+            token_type: token_type.to_string(),
+            jti: jti.to_string(),
         };
 
         encode(&Header::default(), &claims, &self.encoding)
     }
-}
\ No newline at end of file
+}
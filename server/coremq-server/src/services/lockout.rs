@@ -0,0 +1,150 @@
+use std::{collections::HashMap, fmt, net::IpAddr, str::FromStr, sync::Mutex};
+
+use serde::Serialize;
+
+use crate::models::config::LockoutConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockoutKind {
+    Username,
+    Ip,
+}
+
+impl fmt::Display for LockoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockoutKind::Username => write!(f, "username"),
+            LockoutKind::Ip => write!(f, "ip"),
+        }
+    }
+}
+
+impl FromStr for LockoutKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "username" => Ok(LockoutKind::Username),
+            "ip" => Ok(LockoutKind::Ip),
+            _ => Err(format!("invalid lockout kind '{}', expected username or ip", s)),
+        }
+    }
+}
+
+struct Attempts {
+    failures: u32,
+    last_failure_at: i64,
+    locked_until: Option<i64>,
+}
+
+/*
+  Failed attempts for one username or IP. Times are unix seconds.
+*/
+#[derive(Debug, Clone, Serialize)]
+pub struct LockoutEntry {
+    pub kind: LockoutKind,
+    pub value: String,
+    pub failures: u32,
+    pub last_failure_at: i64,
+    pub locked_until: Option<i64>,
+}
+
+/*
+  Counts failed admin API logins per username and per client IP. Once a
+  key reaches its limit it is locked, for `base_secs` doubling with each
+  further failure up to `max_secs`. A key is forgotten `max_secs` after
+  its last failure. Kept in memory, so a restart clears every lockout.
+*/
+pub struct LoginGuard {
+    config: LockoutConfig,
+    attempts: Mutex<HashMap<(LockoutKind, String), Attempts>>,
+}
+
+impl LoginGuard {
+    pub fn new(config: LockoutConfig) -> Self {
+        Self {
+            config,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /*
+      Seconds until the attempt may be made, if the username or the IP is
+      locked.
+    */
+    pub fn locked(&self, username: &str, ip: IpAddr, now: i64) -> Option<i64> {
+        let attempts = self.attempts.lock().unwrap();
+        [(LockoutKind::Username, username.to_string()), (LockoutKind::Ip, ip.to_string())]
+            .iter()
+            .filter_map(|key| attempts.get(key)?.locked_until)
+            .filter(|until| *until > now)
+            .max()
+            .map(|until| until - now)
+    }
+
+    /*
+      Records a failure against both keys and returns true if either is
+      now locked.
+    */
+    pub fn record_failure(&self, username: &str, ip: IpAddr, now: i64) -> bool {
+        let mut attempts = self.attempts.lock().unwrap();
+        let forget_after = self.config.max_secs as i64;
+        attempts.retain(|_, a| a.last_failure_at + forget_after > now || a.locked_until.is_some_and(|until| until > now));
+
+        let limits = [
+            ((LockoutKind::Username, username.to_string()), self.config.max_failures),
+            ((LockoutKind::Ip, ip.to_string()), self.config.max_failures_per_ip),
+        ];
+
+        let mut locked = false;
+        for (key, limit) in limits {
+            let entry = attempts.entry(key).or_insert(Attempts {
+                failures: 0,
+                last_failure_at: now,
+                locked_until: None,
+            });
+            entry.failures += 1;
+            entry.last_failure_at = now;
+
+            if limit > 0 && entry.failures >= limit {
+                entry.locked_until = Some(now + self.lock_secs(entry.failures - limit));
+                locked = true;
+            }
+        }
+        locked
+    }
+
+    /*
+      A successful login clears the username's failures. The IP keeps its
+      count, so one valid account can't be used to reset it.
+    */
+    pub fn record_success(&self, username: &str) {
+        self.attempts.lock().unwrap().remove(&(LockoutKind::Username, username.to_string()));
+    }
+
+    pub fn entries(&self) -> Vec<LockoutEntry> {
+        let attempts = self.attempts.lock().unwrap();
+        let mut entries: Vec<LockoutEntry> = attempts
+            .iter()
+            .map(|((kind, value), a)| LockoutEntry {
+                kind: *kind,
+                value: value.clone(),
+                failures: a.failures,
+                last_failure_at: a.last_failure_at,
+                locked_until: a.locked_until,
+            })
+            .collect();
+        entries.sort_by(|a, b| b.last_failure_at.cmp(&a.last_failure_at).then_with(|| a.value.cmp(&b.value)));
+        entries
+    }
+
+    pub fn clear(&self, kind: LockoutKind, value: &str) -> bool {
+        self.attempts.lock().unwrap().remove(&(kind, value.to_string())).is_some()
+    }
+
+    fn lock_secs(&self, extra_failures: u32) -> i64 {
+        let secs = self.config.base_secs.saturating_mul(1u64 << extra_failures.min(32));
+        secs.min(self.config.max_secs) as i64
+    }
+}
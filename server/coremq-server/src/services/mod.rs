@@ -1,6 +1,15 @@
 pub mod  session;
 pub mod  topic;
 pub mod jwt;
+pub mod routing;
+pub mod policy;
+pub mod lockout;
+pub mod auth;
+pub mod ban;
+pub mod topic_stats;
+pub mod events;
+pub mod trace;
 
 pub use session::*;
-pub use topic::*;
\ No newline at end of file
+pub use topic::*;
+pub use routing::*;
@@ -0,0 +1,144 @@
+use std::sync::{Arc, RwLock};
+
+use casbin::{CoreApi, DefaultModel, Enforcer, MgmtApi, RbacApi};
+use tokio::sync::Mutex;
+
+use crate::storage::redb::policy::PolicyAdapter;
+
+pub enum PolicyChange {
+    AddPolicy(Vec<String>),
+    RemovePolicy(Vec<String>),
+    AddRole(Vec<String>),
+    RemoveRole(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum PolicyError {
+    AlreadyExists,
+    NotFound,
+    /*
+      The change would leave the admin role unable to manage policies.
+    */
+    Lockout,
+    Casbin(casbin::Error),
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyError::AlreadyExists => f.write_str("rule already exists"),
+            PolicyError::NotFound => f.write_str("rule not found"),
+            PolicyError::Lockout => f.write_str("change would lock the admin role out of policy management"),
+            PolicyError::Casbin(e) => write!(f, "casbin error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl From<casbin::Error> for PolicyError {
+    fn from(e: casbin::Error) -> Self {
+        PolicyError::Casbin(e)
+    }
+}
+
+/*
+  Owns the live enforcer. Requests take a snapshot with `enforcer()`; a
+  change builds a new enforcer from storage, applies and persists the rule,
+  then swaps it in. Requests already holding the old snapshot finish with
+  it, so nothing is dropped or sees a half-applied change.
+*/
+pub struct PolicyService {
+    model: String,
+    adapter: PolicyAdapter,
+    current: RwLock<Arc<Enforcer>>,
+    /*
+      Serializes changes so that each one starts from the latest rules.
+    */
+    writer: Mutex<()>,
+}
+
+impl PolicyService {
+    pub async fn new(model: String, adapter: PolicyAdapter) -> Result<Self, casbin::Error> {
+        let enforcer = Self::build(&model, adapter.clone()).await?;
+        Ok(Self {
+            model,
+            adapter,
+            current: RwLock::new(Arc::new(enforcer)),
+            writer: Mutex::new(()),
+        })
+    }
+
+    pub fn enforcer(&self) -> Arc<Enforcer> {
+        self.current.read().unwrap().clone()
+    }
+
+    /*
+      Whether a request may go ahead. The caller acts as its stored `role`
+      and as every role assigned to `subject` itself (`g, alice, admin`).
+      The subject is never matched against rules directly, so a username
+      that happens to equal a role name does not gain that role.
+    */
+    pub fn allows(&self, subject: &str, role: &str, object: &str, action: &str) -> bool {
+        let enforcer = self.enforcer();
+        std::iter::once(role.to_string())
+            .chain(enforcer.get_roles_for_user(subject, None))
+            .any(|role| enforcer.enforce((&role, object, action)).unwrap_or(false))
+    }
+
+    pub fn policies(&self) -> Vec<Vec<String>> {
+        self.enforcer().get_policy()
+    }
+
+    pub fn roles(&self) -> Vec<Vec<String>> {
+        self.enforcer().get_grouping_policy()
+    }
+
+    pub async fn apply(&self, change: PolicyChange) -> Result<(), PolicyError> {
+        let _writer = self.writer.lock().await;
+
+        /*
+          Apply in memory first; storage is only touched once the result is
+          known to be valid.
+        */
+        let mut next = Self::build(&self.model, self.adapter.clone()).await?;
+        next.enable_auto_save(false);
+
+        let changed = match &change {
+            PolicyChange::AddPolicy(rule) => next.add_policy(rule.clone()).await?,
+            PolicyChange::RemovePolicy(rule) => next.remove_policy(rule.clone()).await?,
+            PolicyChange::AddRole(rule) => next.add_grouping_policy(rule.clone()).await?,
+            PolicyChange::RemoveRole(rule) => next.remove_grouping_policy(rule.clone()).await?,
+        };
+
+        if !changed {
+            return Err(match change {
+                PolicyChange::AddPolicy(_) | PolicyChange::AddRole(_) => PolicyError::AlreadyExists,
+                PolicyChange::RemovePolicy(_) | PolicyChange::RemoveRole(_) => PolicyError::NotFound,
+            });
+        }
+
+        if !next.enforce(("admin", "/api/v1/policies", "POST"))? {
+            return Err(PolicyError::Lockout);
+        }
+
+        let mut adapter = self.adapter.clone();
+        let persisted = match change {
+            PolicyChange::AddPolicy(rule) => casbin::Adapter::add_policy(&mut adapter, "p", "p", rule).await?,
+            PolicyChange::RemovePolicy(rule) => casbin::Adapter::remove_policy(&mut adapter, "p", "p", rule).await?,
+            PolicyChange::AddRole(rule) => casbin::Adapter::add_policy(&mut adapter, "g", "g", rule).await?,
+            PolicyChange::RemoveRole(rule) => casbin::Adapter::remove_policy(&mut adapter, "g", "g", rule).await?,
+        };
+        if !persisted {
+            return Err(PolicyError::NotFound);
+        }
+
+        *self.current.write().unwrap() = Arc::new(next);
+        Ok(())
+    }
+
+    async fn build(model: &str, adapter: PolicyAdapter) -> Result<Enforcer, casbin::Error> {
+        let model = DefaultModel::from_str(model).await?;
+        Enforcer::new(model, adapter).await
+    }
+}
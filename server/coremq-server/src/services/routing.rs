@@ -0,0 +1,277 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use dashmap::DashMap;
+use tokio::{sync::{Notify, mpsc::{self, error::TrySendError}}, time::{self, Instant}};
+
+use crate::{
+    enums::MqttChannel,
+    protocol::{encoder::OutgoingPublish, packets::PublishPacket},
+    services::{SessionService, TopicService, topic_stats::TopicStatsService},
+    utils::topic::matches_filter,
+};
+
+/*
+  Upper bound on cached topic routes. Topic names are client controlled,
+  so the cache is cleared rather than allowed to grow without limit.
+*/
+const MAX_CACHED_ROUTES: usize = 10_000;
+
+/*
+  Everything needed to deliver to one subscriber, without cloning its
+  whole session. `qos` is the QoS granted by the matching subscription.
+*/
+#[derive(Debug, Clone)]
+pub struct DeliveryHandle {
+    pub client_id: Arc<str>,
+    pub qos: u8,
+    pub tx: mpsc::Sender<MqttChannel>,
+}
+
+/*
+  Counts subscriber acknowledgements of one publish, for REST publishes
+  that wait for them. Routing records how many QoS 1/2 deliveries were
+  queued; connections report each PUBACK or PUBCOMP, and in-process
+  subscribers acknowledge on receipt.
+*/
+#[derive(Debug, Default)]
+pub struct AckTracker {
+    expected: AtomicUsize,
+    acked: AtomicUsize,
+    notify: Notify,
+}
+
+impl AckTracker {
+    pub fn ack(&self) {
+        self.acked.fetch_add(1, Ordering::AcqRel);
+        self.notify.notify_waiters();
+    }
+
+    pub fn expected(&self) -> usize {
+        self.expected.load(Ordering::Acquire)
+    }
+
+    /*
+      Waits until every expected acknowledgement has arrived or `deadline`
+      passes, and returns how many arrived.
+    */
+    pub async fn wait(&self, deadline: Instant) -> usize {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let acked = self.acked.load(Ordering::Acquire);
+            if acked >= self.expected() || time::timeout_at(deadline, notified).await.is_err() {
+                return self.acked.load(Ordering::Acquire);
+            }
+        }
+    }
+}
+
+/*
+  Result of routing one publish: subscribers matched, and how many of
+  them were still connected to accept the message.
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct PublishOutcome {
+    pub matched: usize,
+    pub delivered: usize,
+}
+
+/*
+  Routes publishes to subscribers without going through the engine loop.
+  Connection tasks call it directly, so routing runs on every core while
+  session and subscription changes stay serialized in the engine.
+
+  Resolved routes are cached per topic name. After a subscription change
+  the engine invalidates the filters involved, which drops only the cached
+  topics those filters match. Every invalidation also bumps a generation,
+  so a route computed concurrently with a change is never kept.
+*/
+pub struct RoutingService {
+    sessions: Arc<SessionService>,
+    topics: Arc<TopicService>,
+    routes: DashMap<String, Arc<[DeliveryHandle]>>,
+    generation: AtomicU64,
+    stats: TopicStatsService,
+    waiting: AtomicUsize, // deliveries waiting on a full mailbox
+}
+
+impl RoutingService {
+    pub fn new(sessions: Arc<SessionService>, topics: Arc<TopicService>) -> Self {
+        Self {
+            sessions,
+            topics,
+            routes: DashMap::new(),
+            generation: AtomicU64::new(0),
+            stats: TopicStatsService::default(),
+            waiting: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn topic_stats(&self) -> &TopicStatsService {
+        &self.stats
+    }
+
+    /*
+      Drops the cached routes of every topic matched by one of `filters`.
+      Must be called after the change has been applied to the session and
+      topic indexes.
+    */
+    pub fn invalidate<'a>(&self, filters: impl IntoIterator<Item = &'a str>) {
+        let filters: Vec<&str> = filters.into_iter().collect();
+        if filters.is_empty() {
+            return;
+        }
+
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.routes.retain(|topic, _| !filters.iter().any(|filter| matches_filter(filter, topic)));
+    }
+
+    /*
+      Returns the delivery handles for every subscriber matching `topic`.
+    */
+    pub fn resolve(&self, topic: &str) -> Arc<[DeliveryHandle]> {
+        if let Some(route) = self.routes.get(topic) {
+            return route.clone();
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let handles: Arc<[DeliveryHandle]> = self
+            .topics
+            .match_subscribers(topic)
+            .iter()
+            .filter_map(|(client_id, qos)| self.sessions.get_handle(client_id, *qos))
+            .collect();
+
+        if self.routes.len() >= MAX_CACHED_ROUTES {
+            self.routes.clear();
+        }
+        self.routes.insert(topic.to_string(), handles.clone());
+
+        /*
+          A change applied while the route was computed may have been
+          invalidated before it was inserted, so it must not stay cached.
+        */
+        if self.generation.load(Ordering::SeqCst) != generation {
+            self.routes.remove_if(topic, |_, cached| Arc::ptr_eq(cached, &handles));
+        }
+
+        handles
+    }
+
+    pub fn publishers_waiting(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    /*
+      Delivers the packet to every matching subscriber and returns how many
+      mailboxes accepted it. A full mailbox is waited on, so a publisher
+      can't outrun a subscriber; only a subscriber that has gone away is
+      skipped.
+
+      Subscribers share one `OutgoingPublish`; the payload is never copied
+      per subscriber and each QoS variant of the frame is encoded once.
+    */
+    pub async fn publish(&self, p: &PublishPacket) -> usize {
+        self.route(p, None).send().await.delivered
+    }
+
+    /*
+      Like `publish`, and when `ack` is given, sets the number of QoS 1/2
+      deliveries it should expect acknowledgements for.
+    */
+    pub async fn publish_tracked(&self, p: &PublishPacket, ack: Option<Arc<AckTracker>>) -> PublishOutcome {
+        self.route(p, ack).send().await
+    }
+
+    /*
+      Resolves the subscribers of a publish without delivering it yet.
+      Connections use this to keep serving their own mailbox while they
+      wait for room in the subscribers'.
+    */
+    pub fn route(&self, p: &PublishPacket, ack: Option<Arc<AckTracker>>) -> Delivery<'_> {
+        self.stats.record(p);
+        let handles = self.resolve(&p.topic);
+        Delivery {
+            waiting: &self.waiting,
+            message: Arc::new(OutgoingPublish::with_ack(p.clone(), ack.clone())),
+            handles,
+            next: 0,
+            delivered: 0,
+            expected: 0,
+            ack,
+        }
+    }
+}
+
+/*
+  A publish on its way to the subscribers it was routed to, in order.
+*/
+pub struct Delivery<'a> {
+    waiting: &'a AtomicUsize,
+    message: Arc<OutgoingPublish>,
+    handles: Arc<[DeliveryHandle]>,
+    next: usize,
+    delivered: usize,
+    expected: usize,
+    ack: Option<Arc<AckTracker>>,
+}
+
+impl Delivery<'_> {
+    /*
+      Delivers to the remaining subscribers, waiting while a mailbox is
+      full. Cancel safe: if the future is dropped, calling `send` again
+      continues with the subscriber it was waiting on.
+    */
+    pub async fn send(&mut self) -> PublishOutcome {
+        while let Some(handle) = self.handles.get(self.next) {
+            let accepted = match handle.tx.try_send(MqttChannel::Publish(self.message.clone(), handle.qos)) {
+                Ok(()) => true,
+                Err(TrySendError::Closed(_)) => false,
+                Err(TrySendError::Full(msg)) => {
+                    let _waiting = Waiting::start(self.waiting);
+                    handle.tx.send(msg).await.is_ok()
+                }
+            };
+
+            if accepted {
+                self.delivered += 1;
+                if handle.qos.min(self.message.packet.qos) > 0 {
+                    self.expected += 1;
+                }
+            }
+            self.next += 1;
+        }
+
+        if let Some(ack) = self.ack.take() {
+            ack.expected.store(self.expected, Ordering::Release);
+            ack.notify.notify_waiters();
+        }
+        PublishOutcome {
+            matched: self.handles.len(),
+            delivered: self.delivered,
+        }
+    }
+}
+
+/*
+  Counts a delivery waiting on a full mailbox for as long as it is alive.
+*/
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn start(count: &'a AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::Relaxed);
+        Self(count)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -1,18 +1,26 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, sync::Arc};
 
 use dashmap::DashMap;
 use tokio::sync::mpsc;
 
 use crate::{
     enums::MqttChannel,
-    models::{pagination::Page, session::Session},
-    protocol::packets::{ConnectPacket, SubscribePacket},
+    models::{pagination::Page, session::{Session, SessionSubscription}, session_query::{SessionFilter, SessionSortKey, SortOrder}},
+    protocol::packets::ConnectPacket,
+    services::DeliveryHandle,
+    transport::ConnectionStats,
 };
 
 pub struct SessionService {
     sessions: DashMap<String, Session>,
 }
 
+impl Default for SessionService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SessionService {
     pub fn new() -> Self {
         Self {
@@ -25,16 +33,11 @@ impl SessionService {
         packet: &ConnectPacket,
         connected_port: u16,
         remote_addr: SocketAddr,
+        connection_id: u64,
         tx: mpsc::Sender<MqttChannel>,
+        stats: Arc<ConnectionStats>,
     ) {
-        let session = Session::new(
-            packet.client_id.clone(),
-            packet.username.clone().unwrap_or_default(),
-            packet.clean_session,
-            connected_port,
-            remote_addr,
-            tx,
-        );
+        let session = Session::new(packet, connected_port, remote_addr, connection_id, tx, stats);
 
         self.sessions.insert(packet.client_id.clone(), session);
     }
@@ -43,20 +46,52 @@ impl SessionService {
         self.sessions.remove(client_id).map(|(_, v)| v)
     }
 
+    /*
+      Returns true when the session for `client_id` is still owned by the
+      given connection.
+    */
+    pub fn is_current(&self, client_id: &str, connection_id: u64) -> bool {
+        self.sessions
+            .get(client_id)
+            .is_some_and(|s| s.connection_id == connection_id)
+    }
+
+    /*
+      Returns only what routing needs to reach the client.
+    */
+    pub fn get_handle(&self, client_id: &str, qos: u8) -> Option<DeliveryHandle> {
+        self.sessions.get(client_id).map(|s| DeliveryHandle {
+            client_id: Arc::from(s.client_id.as_str()),
+            qos,
+            tx: s.tx.clone(),
+        })
+    }
+
     pub fn get_session(&self, key: &str) -> Option<Session> {
         self.sessions.get(key).map(|r| r.value().clone())
     }
 
-    pub fn add_subscribtion(&self, client_id: &str, sub: &SubscribePacket) {
+    pub fn add_subscribtion(&self, client_id: &str, sub: SessionSubscription) {
         if let Some(mut session) = self.sessions.get_mut(client_id) {
-            session.add_subscription(sub.clone());
+            session.add_subscription(sub);
         }
     }
 
-    pub fn remove_subscribtion(&self, client_id: &str, topic: &str) {
-        if let Some(mut session) = self.sessions.get_mut(client_id) {
-            session.remove_subscription(topic);
-        }
+    /*
+      Returns false if the client had no such subscription.
+    */
+    pub fn remove_subscribtion(&self, client_id: &str, topic: &str) -> bool {
+        self.sessions
+            .get_mut(client_id)
+            .is_some_and(|mut session| session.remove_subscription(topic))
+    }
+
+    pub fn client_ids(&self) -> Vec<String> {
+        self.sessions.iter().map(|entry| entry.key().clone()).collect()
+    }
+
+    pub fn sessions(&self) -> Vec<Session> {
+        self.sessions.iter().map(|entry| entry.value().clone()).collect()
     }
 
     pub fn get_by_listener(&self, port: u16) -> Vec<Session> {
@@ -67,23 +102,36 @@ impl SessionService {
             .collect()
     }
 
-    pub fn get_paginated(&self, page: usize, size: usize) -> Page<Session> {
-        let total_elements = self.sessions.len();
+    /*
+      One page of the sessions matching `filter`. Ties in the sort key are
+      broken by client id, so the order is the same on every call and pages
+      don't overlap while the session set is unchanged. Only the sessions on
+      the requested page are cloned.
+    */
+    pub fn query(&self, filter: &SessionFilter, page: usize, size: usize) -> Page<Session> {
+        let mut matches: Vec<(SessionSortKey, String)> = self
+            .sessions
+            .iter()
+            .filter(|entry| filter.matches(entry.value()))
+            .map(|entry| (filter.sort_key(entry.value()), entry.key().clone()))
+            .collect();
 
-        let total_pages = if total_elements == 0 {
-            0
-        } else {
-            (total_elements + size - 1) / size
-        };
+        matches.sort_unstable_by(|a, b| {
+            let order = match filter.order {
+                SortOrder::Asc => a.0.cmp(&b.0),
+                SortOrder::Desc => b.0.cmp(&a.0),
+            };
+            order.then_with(|| a.1.cmp(&b.1))
+        });
 
-        let start = page * size;
+        let total_elements = matches.len();
+        let total_pages = total_elements.div_ceil(size);
 
-        let content: Vec<Session> = self
-            .sessions
+        let content: Vec<Session> = matches
             .iter()
-            .skip(start)
+            .skip(page.saturating_mul(size))
             .take(size)
-            .map(|r| r.value().clone())
+            .filter_map(|(_, client_id)| self.get_session(client_id))
             .collect();
 
         Page {
@@ -1,4 +1,4 @@
-use dashmap::{DashMap, DashSet};
+use dashmap::DashMap;
 use std::sync::Arc;
 
 use crate::models::topic_info::TopicInfo;
@@ -6,7 +6,10 @@ use crate::models::topic_info::TopicInfo;
 #[derive(Debug, Default)]
 pub struct TopicNode {
     children: DashMap<String, Arc<TopicNode>>,
-    subscribers: DashSet<String>,
+    /*
+      Subscribed client ids with the QoS granted for this filter.
+    */
+    subscribers: DashMap<String, u8>,
 }
 
 #[derive(Debug, Default)]
@@ -21,26 +24,23 @@ impl TopicService {
         }
     }
 
-    pub fn subscribe(&self, topic: &str, client_id: &str) {
+    pub fn subscribe(&self, topic: &str, client_id: &str, qos: u8) {
         let mut current = Arc::clone(&self.root);
 
         for level in topic.split('/') {
-            current = {
-                /*
-                  Ensure the child node exists for this topic level.
-                */
-                let node = current.children.entry(level.to_string())
-                    .or_insert_with(|| Arc::new(TopicNode::default()))
-                    .clone();
-                
-                node
-            };
+            /*
+              Ensure the child node exists for this topic level.
+            */
+            let node = current.children.entry(level.to_string())
+                .or_insert_with(|| Arc::new(TopicNode::default()))
+                .clone();
+            current = node;
         }
 
         /*
           Add the client as a subscriber for this topic node.
         */
-        current.subscribers.insert(client_id.to_string());
+        current.subscribers.insert(client_id.to_string(), qos);
     }
 
     pub fn unsubscribe(&self, topic: &str, client_id: &str) {
@@ -48,13 +48,28 @@ impl TopicService {
         self.remove_recursive(&self.root, &levels, client_id);
     }
 
-    pub fn match_subscribers(&self, topic: &str) -> Vec<String> {
+    pub fn match_subscribers(&self, topic: &str) -> Vec<(String, u8)> {
         let levels: Vec<&str> = topic.split('/').collect();
         let mut result = Vec::new();
         self.match_recursive(&self.root, &levels, &mut result);
         result
     }
 
+    /*
+      Clients subscribed with exactly `filter`, as opposed to every client
+      a publish would reach.
+    */
+    pub fn subscribers(&self, filter: &str) -> Vec<(String, u8)> {
+        let mut current = Arc::clone(&self.root);
+        for level in filter.split('/') {
+            let Some(child) = current.children.get(level).map(|c| c.clone()) else {
+                return Vec::new();
+            };
+            current = child;
+        }
+        current.subscribers.iter().map(|r| (r.key().clone(), *r.value())).collect()
+    }
+
     pub fn remove_client(&self, client_id: &str) {
         self.remove_client_recursive(&self.root, client_id);
     }
@@ -62,7 +77,10 @@ impl TopicService {
     fn remove_recursive(&self, node: &Arc<TopicNode>, levels: &[&str], client_id: &str) -> bool {
         if levels.is_empty() {
             node.subscribers.remove(client_id);
-        } else if let Some(child) = node.children.get(levels[0]) {
+        } else if let Some(child) = node.children.get(levels[0]).map(|c| c.clone()) {
+            /*
+              The map guard must be released before the child is removed.
+            */
             let should_delete = self.remove_recursive(&child, &levels[1..], client_id);
             if should_delete {
                 node.children.remove(levels[0]);
@@ -72,9 +90,15 @@ impl TopicService {
         node.children.is_empty() && node.subscribers.is_empty()
     }
 
-    fn match_recursive(&self, node: &Arc<TopicNode>, levels: &[&str], result: &mut Vec<String>) {
+    fn match_recursive(&self, node: &Arc<TopicNode>, levels: &[&str], result: &mut Vec<(String, u8)>) {
         if levels.is_empty() {
-            result.extend(node.subscribers.iter().map(|r| r.clone()));
+            result.extend(node.subscribers.iter().map(|r| (r.key().clone(), *r.value())));
+            /*
+              "a/#" also matches the parent level "a".
+            */
+            if let Some(child) = node.children.get("#") {
+                result.extend(child.subscribers.iter().map(|r| (r.key().clone(), *r.value())));
+            }
             return;
         }
 
@@ -89,7 +113,7 @@ impl TopicService {
         }
 
         if let Some(child) = node.children.get("#") {
-            result.extend(child.subscribers.iter().map(|r| r.clone()));
+            result.extend(child.subscribers.iter().map(|r| (r.key().clone(), *r.value())));
         }
     }
 
@@ -111,6 +135,7 @@ impl TopicService {
             result.push(TopicInfo {
                 topic: path.clone(),
                 subscriber_count: count,
+                ..Default::default()
             });
         }
 
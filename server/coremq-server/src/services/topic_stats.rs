@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local, Utc};
+use dashmap::DashMap;
+
+use crate::{
+    models::{pagination::Page, topic_info::{TopicFilter, TopicInfo}},
+    protocol::packets::PublishPacket,
+};
+
+/*
+  Upper bound on topics with counters. Topic names are client controlled;
+  when the limit is reached, the least recently published tenth is
+  forgotten to make room.
+*/
+const MAX_TRACKED_TOPICS: usize = 10_000;
+
+struct Counters {
+    messages: u64,
+    bytes: u64,
+    last_publish_at: DateTime<Utc>,
+    retained: bool,
+}
+
+/*
+  Publish counters per topic name, updated by `RoutingService` on every
+  publish, including those with no subscribers.
+*/
+#[derive(Default)]
+pub struct TopicStatsService {
+    topics: DashMap<String, Counters>,
+}
+
+impl TopicStatsService {
+    pub fn record(&self, packet: &PublishPacket) {
+        let now = Utc::now();
+        if let Some(mut counters) = self.topics.get_mut(&packet.topic) {
+            counters.messages += 1;
+            counters.bytes += packet.payload.len() as u64;
+            counters.last_publish_at = now;
+            counters.retained = packet.retain;
+            return;
+        }
+
+        if self.topics.len() >= MAX_TRACKED_TOPICS {
+            self.evict();
+        }
+        self.topics.insert(packet.topic.clone(), Counters {
+            messages: 1,
+            bytes: packet.payload.len() as u64,
+            last_publish_at: now,
+            retained: packet.retain,
+        });
+    }
+
+    fn evict(&self) {
+        let mut idle: Vec<(DateTime<Utc>, String)> = self
+            .topics
+            .iter()
+            .map(|entry| (entry.last_publish_at, entry.key().clone()))
+            .collect();
+        idle.sort_unstable();
+        for (_, topic) in idle.into_iter().take(MAX_TRACKED_TOPICS / 10) {
+            self.topics.remove(&topic);
+        }
+    }
+
+    /*
+      Merges the counters with the subscribed filters from `TopicService`
+      and returns one page of the topics matching `filter`.
+    */
+    pub fn browse(&self, subscribed: Vec<TopicInfo>, filter: &TopicFilter, page: usize, size: usize) -> Page<TopicInfo> {
+        let mut topics: HashMap<String, TopicInfo> =
+            subscribed.into_iter().map(|topic| (topic.topic.clone(), topic)).collect();
+
+        for entry in self.topics.iter() {
+            let topic = topics.entry(entry.key().clone()).or_insert_with(|| TopicInfo {
+                topic: entry.key().clone(),
+                ..Default::default()
+            });
+            topic.messages = entry.messages;
+            topic.bytes = entry.bytes;
+            topic.last_publish_at = Some(entry.last_publish_at.with_timezone(&Local));
+            topic.retained = entry.retained;
+        }
+
+        let mut matches: Vec<TopicInfo> = topics.into_values().filter(|topic| filter.matches(topic)).collect();
+        matches.sort_unstable_by(|a, b| filter.compare(a, b));
+
+        let total_elements = matches.len();
+        let content = matches.into_iter().skip(page.saturating_mul(size)).take(size).collect();
+
+        Page {
+            content,
+            page,
+            size,
+            total_elements,
+            total_pages: total_elements.div_ceil(size),
+        }
+    }
+}
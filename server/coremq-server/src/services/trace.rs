@@ -0,0 +1,231 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard, RwLock, atomic::{AtomicUsize, Ordering}},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::{
+    models::trace::{TraceDetail, TraceDirection, TraceInfo, TraceOptions, TraceRecord, TraceTarget},
+    protocol::packets::Packet,
+    utils::random,
+};
+
+/*
+  Traces kept at once, running or stopped. Stopped traces stay readable
+  until deleted.
+*/
+pub const MAX_TRACES: usize = 32;
+
+#[derive(Debug)]
+pub enum TraceError {
+    TooMany,
+    NotFound,
+}
+
+struct Trace {
+    id: String,
+    target: TraceTarget,
+    created_by: String,
+    started_at: DateTime<Local>,
+    ends_at: DateTime<Local>,
+    capacity: usize,
+    payload_bytes: usize,
+    buffer: Mutex<TraceBuffer>,
+}
+
+#[derive(Default)]
+struct TraceBuffer {
+    records: VecDeque<TraceRecord>,
+    captured: u64,
+    stopped_at: Option<DateTime<Local>>,
+}
+
+impl Trace {
+    /*
+      Marks the trace stopped at `ends_at` once that has passed. Returns
+      whether it stopped just now.
+    */
+    fn expire(&self, buffer: &mut TraceBuffer, now: DateTime<Local>) -> bool {
+        if buffer.stopped_at.is_none() && now >= self.ends_at {
+            buffer.stopped_at = Some(self.ends_at);
+            return true;
+        }
+        false
+    }
+
+    fn push(&self, buffer: &mut TraceBuffer, mut record: TraceRecord) {
+        if buffer.stopped_at.is_some() {
+            return;
+        }
+
+        buffer.captured += 1;
+        record.seq = buffer.captured;
+        if buffer.records.len() == self.capacity {
+            buffer.records.pop_front();
+        }
+        buffer.records.push_back(record);
+    }
+
+    fn info(&self, buffer: &TraceBuffer) -> TraceInfo {
+        TraceInfo {
+            id: self.id.clone(),
+            kind: self.target.kind(),
+            value: self.target.value(),
+            created_by: self.created_by.clone(),
+            started_at: self.started_at,
+            ends_at: self.ends_at,
+            stopped_at: buffer.stopped_at,
+            active: buffer.stopped_at.is_none(),
+            capacity: self.capacity,
+            payload_bytes: self.payload_bytes,
+            captured: buffer.captured,
+            dropped: buffer.captured - buffer.records.len() as u64,
+        }
+    }
+}
+
+/*
+  Packet traces started through the admin API. Connections report every
+  packet they receive and send; while no trace is running that costs one
+  atomic load.
+*/
+#[derive(Default)]
+pub struct TraceService {
+    traces: RwLock<Vec<Arc<Trace>>>,
+    running: AtomicUsize,
+}
+
+impl TraceService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed) > 0
+    }
+
+    /*
+      Starts a trace and schedules its stop after the requested duration.
+    */
+    pub fn start(self: &Arc<Self>, options: TraceOptions, created_by: &str) -> Result<TraceInfo, TraceError> {
+        let now = Local::now();
+        let duration = Duration::from_secs(options.duration_secs);
+        let trace = Arc::new(Trace {
+            id: random::hex(8),
+            target: options.target,
+            created_by: created_by.to_string(),
+            started_at: now,
+            ends_at: now + duration,
+            capacity: options.capacity,
+            payload_bytes: options.payload_bytes,
+            buffer: Mutex::new(TraceBuffer::default()),
+        });
+
+        {
+            let mut traces = self.traces.write().unwrap();
+            if traces.len() >= MAX_TRACES {
+                return Err(TraceError::TooMany);
+            }
+            traces.push(trace.clone());
+            self.running.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let traces = self.clone();
+        let expiring = trace.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            drop(traces.lock(&expiring));
+        });
+
+        let buffer = self.lock(&trace);
+        Ok(trace.info(&buffer))
+    }
+
+    /*
+      Stops a trace, keeping its records. Stopping a stopped trace changes
+      nothing.
+    */
+    pub fn stop(&self, id: &str) -> Result<TraceInfo, TraceError> {
+        let trace = self.find(id)?;
+        let mut buffer = self.lock(&trace);
+        if buffer.stopped_at.is_none() {
+            buffer.stopped_at = Some(Local::now().min(trace.ends_at));
+            self.running.fetch_sub(1, Ordering::Relaxed);
+        }
+        Ok(trace.info(&buffer))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), TraceError> {
+        self.stop(id)?;
+        self.traces.write().unwrap().retain(|trace| trace.id != id);
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<TraceInfo> {
+        self.traces
+            .read()
+            .unwrap()
+            .iter()
+            .map(|trace| trace.info(&self.lock(trace)))
+            .collect()
+    }
+
+    /*
+      The trace with its buffered records numbered above `after`.
+    */
+    pub fn get(&self, id: &str, after: u64) -> Result<TraceDetail, TraceError> {
+        let trace = self.find(id)?;
+        let buffer = self.lock(&trace);
+        Ok(TraceDetail {
+            trace: trace.info(&buffer),
+            records: buffer.records.iter().filter(|record| record.seq > after).cloned().collect(),
+        })
+    }
+
+    /*
+      Adds a packet to every running trace it matches. `client_id` is `None`
+      before the connection has sent CONNECT.
+    */
+    pub fn record(&self, direction: TraceDirection, client_id: Option<&str>, remote_addr: SocketAddr, packet: &Packet) {
+        if !self.is_running() {
+            return;
+        }
+
+        let client_id = match packet {
+            Packet::Connect(p) => Some(p.client_id.as_str()),
+            _ => client_id,
+        };
+
+        for trace in self.traces.read().unwrap().iter() {
+            if trace.target.matches(client_id, remote_addr.ip(), packet) {
+                let record = TraceRecord::new(direction, client_id, remote_addr, packet, trace.payload_bytes);
+                trace.push(&mut self.lock(trace), record);
+            }
+        }
+    }
+
+    /*
+      Locks a trace's buffer after applying its expiry. Every access goes
+      through here, so `stopped_at`, `active` and `running` always agree.
+    */
+    fn lock<'a>(&self, trace: &'a Trace) -> MutexGuard<'a, TraceBuffer> {
+        let mut buffer = trace.buffer.lock().unwrap();
+        if trace.expire(&mut buffer, Local::now()) {
+            self.running.fetch_sub(1, Ordering::Relaxed);
+        }
+        buffer
+    }
+
+    fn find(&self, id: &str) -> Result<Arc<Trace>, TraceError> {
+        self.traces
+            .read()
+            .unwrap()
+            .iter()
+            .find(|trace| trace.id == id)
+            .cloned()
+            .ok_or(TraceError::NotFound)
+    }
+}
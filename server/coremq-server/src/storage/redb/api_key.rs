@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use redb::{Database, ReadableTable, TableDefinition};
+
+use crate::models::api_key::ApiKey;
+
+pub const API_KEYS: TableDefinition<&str, &[u8]> = TableDefinition::new("api_keys");
+
+/*
+  `last_used_at` is written at most this often per key, so that busy
+  automation doesn't turn every request into a write transaction.
+*/
+const TOUCH_INTERVAL_SECS: i64 = 60;
+
+#[derive(Clone)]
+pub struct ApiKeyRepo {
+    db: Arc<Database>,
+}
+
+impl ApiKeyRepo {
+    pub fn new(db: Arc<Database>) -> Self {
+        let write_txn = db
+            .begin_write()
+            .expect("Failed to begin write txn for table init");
+        let _ = write_txn
+            .open_table(API_KEYS)
+            .expect("Failed to create/open API_KEYS table");
+        write_txn.commit().expect("Failed to commit table init");
+        Self { db }
+    }
+
+    pub fn create(&self, key: &ApiKey) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(API_KEYS)?;
+            let bytes = bincode::serialize(key)?;
+            table.insert(key.id.as_str(), bytes.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<ApiKey>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(API_KEYS)?;
+        match table.get(id)? {
+            Some(value) => Ok(Some(bincode::deserialize(value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_all(&self) -> Result<Vec<ApiKey>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(API_KEYS)?;
+
+        let mut keys = Vec::new();
+        for entry in table.iter()? {
+            let (_id, value) = entry?;
+            keys.push(bincode::deserialize(value.value())?);
+        }
+        Ok(keys)
+    }
+
+    /*
+      Returns false if the key doesn't exist.
+    */
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(API_KEYS)?;
+            table.remove(id)?.is_some()
+        };
+        write_txn.commit()?;
+        Ok(removed)
+    }
+
+    /*
+      Records a use of the key at `now`, unless one was recorded recently.
+    */
+    pub fn touch(&self, key: &ApiKey, now: i64) -> Result<()> {
+        if key.last_used_at.is_some_and(|last| now - last < TOUCH_INTERVAL_SECS) {
+            return Ok(());
+        }
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(API_KEYS)?;
+            let stored = match table.get(key.id.as_str())? {
+                Some(value) => Some(bincode::deserialize::<ApiKey>(value.value())?),
+                None => None,
+            };
+            if let Some(mut stored) = stored {
+                stored.last_used_at = Some(now);
+                let bytes = bincode::serialize(&stored)?;
+                table.insert(key.id.as_str(), bytes.as_slice())?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use redb::{Database, ReadableTable, TableDefinition};
+
+use crate::models::ban::Ban;
+
+/*
+  Keyed by `<kind>:<value>`, so banning the same value again replaces the
+  earlier ban.
+*/
+pub const BANS: TableDefinition<&str, &[u8]> = TableDefinition::new("bans");
+
+#[derive(Clone)]
+pub struct BanRepo {
+    db: Arc<Database>,
+}
+
+impl BanRepo {
+    pub fn new(db: Arc<Database>) -> Self {
+        let write_txn = db
+            .begin_write()
+            .expect("Failed to begin write txn for table init");
+        let _ = write_txn
+            .open_table(BANS)
+            .expect("Failed to create/open BANS table");
+        write_txn.commit().expect("Failed to commit table init");
+        Self { db }
+    }
+
+    pub fn put(&self, ban: &Ban) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(BANS)?;
+            let bytes = bincode::serialize(ban)?;
+            table.insert(ban.key().as_str(), bytes.as_slice())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    pub fn get_all(&self) -> Result<Vec<Ban>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(BANS)?;
+
+        let mut bans = Vec::new();
+        for entry in table.iter()? {
+            let (_key, value) = entry?;
+            bans.push(bincode::deserialize(value.value())?);
+        }
+        Ok(bans)
+    }
+
+    /*
+      Returns false if there was no such ban.
+    */
+    pub fn delete(&self, key: &str) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(BANS)?;
+            table.remove(key)?.is_some()
+        };
+        write_txn.commit()?;
+        Ok(removed)
+    }
+
+    /*
+      Deletes bans that expired before `now`.
+    */
+    pub fn prune(&self, now: i64) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(BANS)?;
+            table.retain(|_, value| {
+                bincode::deserialize::<Ban>(value).map_or(true, |ban| !ban.is_expired(now))
+            })?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
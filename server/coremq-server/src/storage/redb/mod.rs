@@ -2,18 +2,32 @@ use std::sync::Arc;
 
 use redb::Database;
 
-use crate::storage::redb::user::UserRepo;
+use crate::storage::redb::{api_key::ApiKeyRepo, ban::BanRepo, policy::PolicyAdapter, revoked::RevokedTokenRepo, user::UserRepo};
 
+pub mod api_key;
+pub mod ban;
+pub mod policy;
+pub mod revoked;
 pub mod user;
 
 #[derive(Clone)]
 pub struct Storage {
-    pub user: UserRepo
+    pub user: UserRepo,
+    pub revoked: RevokedTokenRepo,
+    pub policy: PolicyAdapter,
+    pub api_keys: ApiKeyRepo,
+    pub bans: BanRepo,
 }
 
 
 impl Storage {
     pub fn new(db: Arc<Database>) -> Self {
-        Self { user: UserRepo::new(db) }
+        Self {
+            user: UserRepo::new(db.clone()),
+            revoked: RevokedTokenRepo::new(db.clone()),
+            policy: PolicyAdapter::new(db.clone()),
+            api_keys: ApiKeyRepo::new(db.clone()),
+            bans: BanRepo::new(db),
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use casbin::{Adapter, Filter, Model, error::AdapterError};
+use redb::{Database, ReadableTable, TableDefinition};
+
+/*
+  Casbin rules, keyed by the bincode-encoded line `[ptype, field, ...]`
+  (e.g. `["p", "user", "/api/v1/sessions", "GET"]`). The section is the
+  first letter of the ptype.
+*/
+pub const CASBIN_RULES: TableDefinition<&[u8], ()> = TableDefinition::new("casbin_rules");
+pub const CASBIN_META: TableDefinition<&str, u64> = TableDefinition::new("casbin_meta");
+
+const SEEDED: &str = "seeded";
+
+/*
+  Rules added after the policy file was first imported. The file is only
+  read on the first start, so existing installs get these through a
+  migration instead: each runs once, after seeding, and is recorded in
+  CASBIN_META under `migration:<name>`.
+*/
+struct Migration {
+    name: &'static str,
+    rules: &'static [&'static [&'static str]],
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    name: "user_events",
+    rules: &[
+        &["p", "user", "/api/v1/events", "GET"],
+        &["p", "user", "/api/v1/events/ws", "GET"],
+    ],
+}];
+
+fn adapter_error(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> casbin::Error {
+    AdapterError(e.into()).into()
+}
+
+fn encode(ptype: &str, rule: &[String]) -> casbin::Result<Vec<u8>> {
+    let mut line = Vec::with_capacity(rule.len() + 1);
+    line.push(ptype.to_string());
+    line.extend_from_slice(rule);
+    bincode::serialize(&line).map_err(adapter_error)
+}
+
+/*
+  Casbin adapter backed by redb, so policy changes made through the admin
+  API survive restarts. On first start the table is seeded from the policy
+  file; after that the file is no longer read.
+*/
+#[derive(Clone)]
+pub struct PolicyAdapter {
+    db: Arc<Database>,
+}
+
+impl PolicyAdapter {
+    pub fn new(db: Arc<Database>) -> Self {
+        let write_txn = db
+            .begin_write()
+            .expect("Failed to begin write txn for table init");
+        let _ = write_txn
+            .open_table(CASBIN_RULES)
+            .expect("Failed to create/open CASBIN_RULES table");
+        let _ = write_txn
+            .open_table(CASBIN_META)
+            .expect("Failed to create/open CASBIN_META table");
+        write_txn.commit().expect("Failed to commit table init");
+        Self { db }
+    }
+
+    pub fn is_seeded(&self) -> Result<bool> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(CASBIN_META)?;
+        Ok(table.get(SEEDED)?.is_some())
+    }
+
+    /*
+      Stores the initial rules as `(ptype, fields)` and marks the table as
+      seeded, in one transaction.
+    */
+    pub fn seed(&self, rules: &[(String, Vec<String>)]) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(CASBIN_RULES)?;
+            for (ptype, rule) in rules {
+                table.insert(encode(ptype, rule)?.as_slice(), ())?;
+            }
+            let mut meta = write_txn.open_table(CASBIN_META)?;
+            meta.insert(SEEDED, chrono::Utc::now().timestamp() as u64)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /*
+      Applies the migrations not yet recorded, each in its own transaction,
+      and returns their names. Rules that already exist are kept as they are.
+    */
+    pub fn migrate(&self) -> Result<Vec<&'static str>> {
+        let mut applied = Vec::new();
+        for migration in MIGRATIONS {
+            let key = format!("migration:{}", migration.name);
+            let write_txn = self.db.begin_write()?;
+            if write_txn.open_table(CASBIN_META)?.get(key.as_str())?.is_some() {
+                write_txn.abort()?;
+                continue;
+            }
+            {
+                let mut meta = write_txn.open_table(CASBIN_META)?;
+                let mut table = write_txn.open_table(CASBIN_RULES)?;
+                for rule in migration.rules {
+                    let line: Vec<String> = rule.iter().map(|field| field.to_string()).collect();
+                    table.insert(bincode::serialize(&line)?.as_slice(), ())?;
+                }
+                meta.insert(key.as_str(), chrono::Utc::now().timestamp() as u64)?;
+            }
+            write_txn.commit()?;
+            applied.push(migration.name);
+        }
+        Ok(applied)
+    }
+
+    fn lines(&self) -> casbin::Result<Vec<Vec<String>>> {
+        let read_txn = self.db.begin_read().map_err(adapter_error)?;
+        let table = read_txn.open_table(CASBIN_RULES).map_err(adapter_error)?;
+
+        let mut lines = Vec::new();
+        for entry in table.iter().map_err(adapter_error)? {
+            let (key, _) = entry.map_err(adapter_error)?;
+            let line: Vec<String> = bincode::deserialize(key.value()).map_err(adapter_error)?;
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        Ok(lines)
+    }
+
+    /*
+      Inserts all rules, or none if any of them already exists.
+    */
+    fn insert(&self, ptype: &str, rules: &[Vec<String>]) -> casbin::Result<bool> {
+        let write_txn = self.db.begin_write().map_err(adapter_error)?;
+        let complete = {
+            let mut table = write_txn.open_table(CASBIN_RULES).map_err(adapter_error)?;
+            let mut complete = true;
+            for rule in rules {
+                let key = encode(ptype, rule)?;
+                if table.insert(key.as_slice(), ()).map_err(adapter_error)?.is_some() {
+                    complete = false;
+                    break;
+                }
+            }
+            complete
+        };
+
+        if complete {
+            write_txn.commit().map_err(adapter_error)?;
+        } else {
+            write_txn.abort().map_err(adapter_error)?;
+        }
+        Ok(complete)
+    }
+
+    /*
+      Removes all rules, or none if any of them is missing.
+    */
+    fn remove(&self, ptype: &str, rules: &[Vec<String>]) -> casbin::Result<bool> {
+        let write_txn = self.db.begin_write().map_err(adapter_error)?;
+        let complete = {
+            let mut table = write_txn.open_table(CASBIN_RULES).map_err(adapter_error)?;
+            let mut complete = true;
+            for rule in rules {
+                let key = encode(ptype, rule)?;
+                if table.remove(key.as_slice()).map_err(adapter_error)?.is_none() {
+                    complete = false;
+                    break;
+                }
+            }
+            complete
+        };
+
+        if complete {
+            write_txn.commit().map_err(adapter_error)?;
+        } else {
+            write_txn.abort().map_err(adapter_error)?;
+        }
+        Ok(complete)
+    }
+
+    fn replace_all(&self, lines: &[Vec<String>]) -> casbin::Result<()> {
+        let write_txn = self.db.begin_write().map_err(adapter_error)?;
+        {
+            let mut table = write_txn.open_table(CASBIN_RULES).map_err(adapter_error)?;
+            table.retain(|_, _| false).map_err(adapter_error)?;
+            for line in lines {
+                let key = bincode::serialize(line).map_err(adapter_error)?;
+                table.insert(key.as_slice(), ()).map_err(adapter_error)?;
+            }
+        }
+        write_txn.commit().map_err(adapter_error)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Adapter for PolicyAdapter {
+    async fn load_policy(&mut self, m: &mut dyn Model) -> casbin::Result<()> {
+        for line in self.lines()? {
+            let ptype = &line[0];
+            let sec = &ptype[..1];
+            m.add_policy(sec, ptype, line[1..].to_vec());
+        }
+        Ok(())
+    }
+
+    /*
+      The broker never loads a filtered policy; the filter is ignored.
+    */
+    async fn load_filtered_policy<'a>(&mut self, m: &mut dyn Model, _f: Filter<'a>) -> casbin::Result<()> {
+        self.load_policy(m).await
+    }
+
+    async fn save_policy(&mut self, m: &mut dyn Model) -> casbin::Result<()> {
+        let mut lines = Vec::new();
+        for sec in ["p", "g"] {
+            if let Some(assertions) = m.get_model().get(sec) {
+                for (ptype, assertion) in assertions {
+                    for rule in assertion.get_policy() {
+                        let mut line = vec![ptype.clone()];
+                        line.extend(rule.iter().cloned());
+                        lines.push(line);
+                    }
+                }
+            }
+        }
+        self.replace_all(&lines)
+    }
+
+    async fn clear_policy(&mut self) -> casbin::Result<()> {
+        self.replace_all(&[])
+    }
+
+    fn is_filtered(&self) -> bool {
+        false
+    }
+
+    async fn add_policy(&mut self, _sec: &str, ptype: &str, rule: Vec<String>) -> casbin::Result<bool> {
+        self.insert(ptype, &[rule])
+    }
+
+    async fn add_policies(&mut self, _sec: &str, ptype: &str, rules: Vec<Vec<String>>) -> casbin::Result<bool> {
+        self.insert(ptype, &rules)
+    }
+
+    async fn remove_policy(&mut self, _sec: &str, ptype: &str, rule: Vec<String>) -> casbin::Result<bool> {
+        self.remove(ptype, &[rule])
+    }
+
+    async fn remove_policies(&mut self, _sec: &str, ptype: &str, rules: Vec<Vec<String>>) -> casbin::Result<bool> {
+        self.remove(ptype, &rules)
+    }
+
+    async fn remove_filtered_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> casbin::Result<bool> {
+        let matching: Vec<Vec<String>> = self
+            .lines()?
+            .into_iter()
+            .filter(|line| line[0] == ptype)
+            .map(|line| line[1..].to_vec())
+            .filter(|rule| {
+                field_values
+                    .iter()
+                    .enumerate()
+                    .all(|(i, value)| value.is_empty() || rule.get(field_index + i) == Some(value))
+            })
+            .collect();
+
+        if matching.is_empty() {
+            return Ok(false);
+        }
+        self.remove(ptype, &matching)
+    }
+}
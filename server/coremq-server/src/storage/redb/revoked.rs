@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::Utc;
+use redb::{Database, ReadableTable, TableDefinition};
+
+/*
+  Deny-list of revoked token ids (`jti`) with the unix time after which the
+  tokens would have expired anyway. Expired entries are pruned on startup
+  and on every revocation, so the table only holds tokens that could still
+  be presented.
+*/
+pub const REVOKED_TOKENS: TableDefinition<&str, u64> = TableDefinition::new("revoked_tokens");
+
+#[derive(Clone)]
+pub struct RevokedTokenRepo {
+    db: Arc<Database>,
+}
+
+impl RevokedTokenRepo {
+    pub fn new(db: Arc<Database>) -> Self {
+        let write_txn = db
+            .begin_write()
+            .expect("Failed to begin write txn for table init");
+        let _ = write_txn
+            .open_table(REVOKED_TOKENS)
+            .expect("Failed to create/open REVOKED_TOKENS table");
+        write_txn.commit().expect("Failed to commit table init");
+        let repo = Self { db };
+        repo.prune().expect("Failed to prune revoked tokens");
+        repo
+    }
+
+    /*
+      Adds `jti` to the deny-list. Returns false when it was already there
+      or has expired, so of several concurrent callers exactly one gets
+      true: the check and the insert share one write transaction.
+    */
+    pub fn revoke(&self, jti: &str, expires_at: u64) -> Result<bool> {
+        let now = Utc::now().timestamp() as u64;
+        let write_txn = self.db.begin_write()?;
+        let inserted = {
+            let mut table = write_txn.open_table(REVOKED_TOKENS)?;
+            table.retain(|_, exp| exp > now)?;
+            if expires_at > now && table.get(jti)?.is_none() {
+                table.insert(jti, expires_at)?;
+                true
+            } else {
+                false
+            }
+        };
+        write_txn.commit()?;
+        Ok(inserted)
+    }
+
+    pub fn is_revoked(&self, jti: &str) -> Result<bool> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(REVOKED_TOKENS)?;
+        Ok(table.get(jti)?.is_some())
+    }
+
+    pub fn prune(&self) -> Result<()> {
+        let now = Utc::now().timestamp() as u64;
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(REVOKED_TOKENS)?;
+            table.retain(|_, exp| exp > now)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
@@ -2,11 +2,84 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use redb::{Database, ReadableTable, TableDefinition};
+use serde::Deserialize;
 
 use crate::{enums::role::RoleType, models::user::User, utils};
 
 pub const USERS: TableDefinition<&str, &[u8]> = TableDefinition::new("users");
-static DEFAULT_PASSWORD: &str = "public";
+pub static DEFAULT_PASSWORD: &str = "public";
+
+/*
+  Record layout written before `must_change_password` existed.
+*/
+#[derive(Deserialize)]
+struct LegacyUser {
+    username: String,
+    password_hash: String,
+    role: String,
+}
+
+/*
+  Record layout written before `password_changed_at` existed.
+*/
+#[derive(Deserialize)]
+struct UnstampedUser {
+    username: String,
+    password_hash: String,
+    role: String,
+    must_change_password: bool,
+}
+
+/*
+  Older layouts are prefixes of newer ones, so try the longest first.
+*/
+fn decode(bytes: &[u8]) -> Result<User> {
+    if let Ok(user) = bincode::deserialize::<User>(bytes) {
+        return Ok(user);
+    }
+
+    if let Ok(user) = bincode::deserialize::<UnstampedUser>(bytes) {
+        return Ok(User {
+            username: user.username,
+            password_hash: user.password_hash,
+            role: user.role,
+            must_change_password: user.must_change_password,
+            password_changed_at: 0,
+        });
+    }
+
+    let legacy: LegacyUser = bincode::deserialize(bytes)?;
+    Ok(User {
+        username: legacy.username,
+        password_hash: legacy.password_hash,
+        role: legacy.role,
+        must_change_password: false,
+        password_changed_at: 0,
+    })
+}
+
+/*
+  Outcome of a write that could leave the broker without an admin. The
+  admin count is checked in the same transaction as the write, so two
+  concurrent demotions can't both pass it.
+*/
+#[derive(Debug)]
+pub enum UserWrite<T> {
+    Done(T),
+    NotFound,
+    LastAdmin,
+}
+
+fn count_admins(table: &impl ReadableTable<&'static str, &'static [u8]>) -> Result<usize> {
+    let mut count = 0;
+    for entry in table.iter()? {
+        let (_key, value) = entry?;
+        if decode(value.value())?.role == RoleType::Admin.as_str() {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
 
 #[derive(Clone)]
 pub struct UserRepo {
@@ -38,25 +111,97 @@ impl UserRepo {
         Ok(())
     }
 
+    /*
+      Like `create`, but leaves an existing user untouched. Returns false if
+      the username is taken.
+    */
+    pub fn insert_new(&self, user: &User) -> Result<bool> {
+        let write_txn = self.db.begin_write()?;
+        let inserted = {
+            let mut table = write_txn.open_table(USERS)?;
+            if table.get(user.username.as_str())?.is_some() {
+                false
+            } else {
+                let bytes = bincode::serialize(user)?;
+                table.insert(user.username.as_str(), bytes.as_slice())?;
+                true
+            }
+        };
+        write_txn.commit()?;
+        Ok(inserted)
+    }
+
     pub fn get(&self, username: &str) -> Result<Option<User>> {
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(USERS)?;
         if let Some(value) = table.get(username)? {
-            let user: User = bincode::deserialize(value.value())?;
-            Ok(Some(user))
+            Ok(Some(decode(value.value())?))
         } else {
             Ok(None)
         }
     }
 
-    pub fn delete(&self, username: &str) -> Result<()> {
+    /*
+      Applies `change` to a stored user in one transaction. Fails with
+      `LastAdmin`, leaving the user untouched, if the change would take the
+      admin role from the only admin.
+    */
+    pub fn update(&self, username: &str, change: impl FnOnce(&mut User)) -> Result<UserWrite<User>> {
         let write_txn = self.db.begin_write()?;
-        {
+        let outcome = {
             let mut table = write_txn.open_table(USERS)?;
-            table.remove(username)?;
-        }
+            let user = match table.get(username)? {
+                Some(value) => Some(decode(value.value())?),
+                None => None,
+            };
+            match user {
+                Some(mut user) => {
+                    let was_admin = user.role == RoleType::Admin.as_str();
+                    change(&mut user);
+                    if was_admin && user.role != RoleType::Admin.as_str() && count_admins(&table)? <= 1 {
+                        UserWrite::LastAdmin
+                    } else {
+                        let bytes = bincode::serialize(&user)?;
+                        table.insert(username, bytes.as_slice())?;
+                        UserWrite::Done(user)
+                    }
+                }
+                None => UserWrite::NotFound,
+            }
+        };
         write_txn.commit()?;
-        Ok(())
+        Ok(outcome)
+    }
+
+    /*
+      Changes a user's role. Returns false if the user doesn't exist.
+    */
+    pub fn set_role(&self, username: &str, role: &RoleType) -> Result<bool> {
+        Ok(matches!(self.update(username, |user| user.role = role.to_string())?, UserWrite::Done(_)))
+    }
+
+    /*
+      Removes a user. The only admin can't be removed.
+    */
+    pub fn delete(&self, username: &str) -> Result<UserWrite<()>> {
+        let write_txn = self.db.begin_write()?;
+        let outcome = {
+            let mut table = write_txn.open_table(USERS)?;
+            let user = match table.get(username)? {
+                Some(value) => Some(decode(value.value())?),
+                None => None,
+            };
+            match user {
+                Some(user) if user.role == RoleType::Admin.as_str() && count_admins(&table)? <= 1 => UserWrite::LastAdmin,
+                Some(_) => {
+                    table.remove(username)?;
+                    UserWrite::Done(())
+                }
+                None => UserWrite::NotFound,
+            }
+        };
+        write_txn.commit()?;
+        Ok(outcome)
     }
 
     pub fn get_all(&self) -> Result<Vec<User>> {
@@ -67,15 +212,35 @@ impl UserRepo {
 
         for entry in table.iter()? {
             let (_key, value) = entry?;
-            let user: User = bincode::deserialize(value.value())?;
-            users.push(user);
+            users.push(decode(value.value())?);
         }
 
         Ok(users)
     }
 
+    pub fn count_admins(&self) -> Result<usize> {
+        let read_txn = self.db.begin_read()?;
+        count_admins(&read_txn.open_table(USERS)?)
+    }
+
     fn ensure_admin(&self) -> Result<()> {
-        if self.get("admin")?.is_some() {
+        if let Some(admin) = self.get("admin")? {
+            /*
+              Older releases created the default admin with the `user` role.
+              Promote it, unless another account already holds the admin role.
+            */
+            if admin.role != RoleType::Admin.as_str() && self.count_admins()? == 0 {
+                self.set_role("admin", &RoleType::Admin)?;
+                println!("Default admin user promoted to the admin role");
+            }
+
+            /*
+              Accounts from older releases may still use the default password.
+            */
+            if !admin.must_change_password && utils::password::verify(DEFAULT_PASSWORD, &admin.password_hash) {
+                self.update("admin", |user| user.must_change_password = true)?;
+                println!("Default admin user still uses the default password; a change is required on next login");
+            }
             return Ok(());
         }
 
@@ -84,7 +249,9 @@ impl UserRepo {
         let admin = User {
             username: "admin".to_string(),
             password_hash: hashed,
-            role: RoleType::User.to_string(),
+            role: RoleType::Admin.to_string(),
+            must_change_password: true,
+            password_changed_at: 0,
         };
 
         self.create(&admin)?;
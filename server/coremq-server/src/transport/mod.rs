@@ -1,11 +1,161 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering}},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, TimeZone, Utc};
 use tokio::sync::mpsc;
 
-use crate::engine::{ConnectCommand, PubSubCommand};
+use crate::{
+    engine::{ConnectCommand, PubSubCommand},
+    models::trace::TraceDirection,
+    protocol::packets::Packet,
+    services::{RoutingService, ban::BanService, events::EventService, routing::AckTracker, trace::TraceService},
+};
 
 pub mod ws;
 pub mod tcp;
 
 pub struct ProtocolState {
-    pub connect_tx: mpsc::UnboundedSender<ConnectCommand>,
-    pub pubsub_tx: mpsc::UnboundedSender<PubSubCommand>,
-}
\ No newline at end of file
+    pub connect_tx: mpsc::Sender<ConnectCommand>,
+    pub pubsub_tx: mpsc::Sender<PubSubCommand>,
+    pub routing: Arc<RoutingService>,
+    pub bans: Arc<BanService>,
+    pub events: Arc<EventService>,
+    pub traces: Arc<TraceService>,
+}
+
+/*
+  Reports one connection's packets to the running traces. `client_id` is
+  set from the CONNECT, before it is accepted, so a refused CONNACK is
+  traced as well.
+*/
+pub struct TraceTap {
+    traces: Arc<TraceService>,
+    remote_addr: SocketAddr,
+    pub client_id: Option<String>,
+}
+
+impl TraceTap {
+    pub fn new(traces: Arc<TraceService>, remote_addr: SocketAddr) -> Self {
+        Self {
+            traces,
+            remote_addr,
+            client_id: None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.traces.is_running()
+    }
+
+    pub fn record(&self, direction: TraceDirection, packet: &Packet) {
+        self.traces.record(direction, self.client_id.as_deref(), self.remote_addr, packet);
+    }
+}
+
+/*
+  A client is dropped after 1.5 times its keep-alive without traffic.
+  Zero disables the check.
+*/
+pub fn keep_alive_timeout(keep_alive: u16) -> Duration {
+    if keep_alive == 0 {
+        Duration::MAX
+    } else {
+        Duration::from_secs((keep_alive as u64) * 3 / 2)
+    }
+}
+
+/*
+  Packet ids for QoS 1/2 messages the broker sends to one connection, and
+  the deliveries still awaiting PUBACK or PUBCOMP. Zero is not a valid id,
+  and ids still in flight are skipped on wrap-around.
+*/
+#[derive(Default)]
+pub struct PacketIds {
+    last: u16,
+    awaiting: HashMap<u16, Option<Arc<AckTracker>>>,
+}
+
+impl PacketIds {
+    /*
+      The next free id, `None` when all 65535 are awaiting an ack.
+    */
+    pub fn allocate(&mut self) -> Option<u16> {
+        for _ in 0..u16::MAX {
+            self.last = self.last.checked_add(1).unwrap_or(1);
+            if !self.awaiting.contains_key(&self.last) {
+                return Some(self.last);
+            }
+        }
+        None
+    }
+
+    pub fn insert(&mut self, packet_id: u16, ack: Option<Arc<AckTracker>>) {
+        self.awaiting.insert(packet_id, ack);
+    }
+
+    /*
+      Completes a delivery on PUBACK or PUBCOMP.
+    */
+    pub fn complete(&mut self, packet_id: u16) {
+        if let Some(Some(ack)) = self.awaiting.remove(&packet_id) {
+            ack.ack();
+        }
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.awaiting.len()
+    }
+}
+
+/*
+  Counters of one network connection. The transport task updates them and
+  the admin API reads them through the session, so both sides only touch
+  atomics.
+*/
+#[derive(Debug, Default)]
+pub struct ConnectionStats {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    last_activity: AtomicI64,  // unix millis of the last read, 0 before any
+    inflight_in: AtomicUsize,  // QoS 2 publishes received and awaiting PUBREL
+    inflight_out: AtomicUsize, // QoS 1/2 deliveries awaiting PUBACK or PUBCOMP
+}
+
+impl ConnectionStats {
+    pub fn received(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.last_activity.store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_inflight(&self, incoming: usize, outgoing: usize) {
+        self.inflight_in.store(incoming, Ordering::Relaxed);
+        self.inflight_out.store(outgoing, Ordering::Relaxed);
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    pub fn inflight(&self) -> (usize, usize) {
+        (self.inflight_in.load(Ordering::Relaxed), self.inflight_out.load(Ordering::Relaxed))
+    }
+
+    pub fn last_activity(&self) -> Option<DateTime<Local>> {
+        match self.last_activity.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Local.timestamp_millis_opt(millis).single(),
+        }
+    }
+}
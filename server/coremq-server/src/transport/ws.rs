@@ -1,27 +1,29 @@
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{collections::HashSet, net::SocketAddr, sync::Arc, time::Duration};
 
 use axum::{
     extract::{
         ConnectInfo, State, WebSocketUpgrade, ws::{Message, WebSocket}
     },
+    http::StatusCode,
     response::IntoResponse,
 };
 use bytes::BytesMut;
 use futures_util::{SinkExt, StreamExt, stream::SplitSink};
 use tokio::{
-    sync::mpsc,
+    sync::{mpsc, oneshot},
     time::{self, Instant},
 };
 
 use crate::{
     engine::{ConnectCommand, PubSubCommand},
     enums::MqttChannel,
+    models::{event::DisconnectReason, trace::TraceDirection},
     protocol::{
-        decoder::Decoder,
-        encoder::{Encoder, encode_publish},
-        packets::PublishPacket,
+        DecodeError,
+        encoder::OutgoingPublish,
+        packets::{ConnAckPacket, ConnectReturnCode, Packet, PublishPacket, SubAckPacket, SUBACK_FAILURE},
     },
-    transport::ProtocolState,
+    transport::{ConnectionStats, PacketIds, ProtocolState, TraceTap, keep_alive_timeout},
 };
 
 
@@ -37,8 +39,13 @@ pub async fn ws_handler(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> impl IntoResponse {
      println!("Client request arrived from {:?}", addr);
+    if state.engine.bans.ip_banned(addr.ip()) {
+        println!("Refused connection from banned address {}", addr);
+        return StatusCode::FORBIDDEN.into_response();
+    }
     ws.protocols(["mqtt"])
         .on_upgrade(move |socket| handle_socket(socket, state, addr))
+        .into_response()
 }
 
 async fn handle_socket(socket: WebSocket, state: WsState,   remote_addr: SocketAddr,) {
@@ -49,22 +56,25 @@ async fn handle_socket(socket: WebSocket, state: WsState,   remote_addr: SocketA
 
     let mut buffer = BytesMut::with_capacity(1024);
     let mut client_id = None;
+    let mut connection_id: u64 = 0;
     let mut timeout_duration = Duration::from_secs(60);
     let mut last_activity = Instant::now();
-    let mut disconnect_requested = false;
+    let mut disconnect_reason: Option<DisconnectReason> = None;
+    let mut awaiting_release: HashSet<u16> = HashSet::new();
+    let mut packet_ids = PacketIds::default();
+    let stats = Arc::new(ConnectionStats::default());
+    let mut tap = TraceTap::new(state.engine.traces.clone(), remote_addr);
 
     let mut ticker = time::interval(Duration::from_secs(5));
     ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
 
 
-    loop {
+    'connection: loop {
         tokio::select! {
 
                 _ = ticker.tick() => {
-                        if last_activity.elapsed() >= timeout_duration {
-                            if let Some(_) = client_id {
-                                request_disconnect(&tx, &mut disconnect_requested).await;
-                            }
+                        if last_activity.elapsed() >= timeout_duration && client_id.is_some() {
+                            request_disconnect(&tx, &mut disconnect_reason, DisconnectReason::KeepAliveTimeout).await;
                         }
                     }
 
@@ -74,69 +84,166 @@ async fn handle_socket(socket: WebSocket, state: WsState,   remote_addr: SocketA
                         match msg {
                             Message::Binary(data) => {
                                 buffer.extend_from_slice(&data);
-                                while let Some(packet) = Decoder::parse_packet(&mut buffer) {
-
+                                stats.received(data.len());
+                                loop {
+                                    let packet = match Packet::decode(&mut buffer) {
+                                        Ok(Some(packet)) => packet,
+                                        Ok(None) => break,
+                                        Err(DecodeError::UnsupportedProtocol { .. }) => {
+                                            let _ = send_ws(&mut sender, &Packet::ConnAck(ConnAckPacket {
+                                                session_present: false,
+                                                return_code: ConnectReturnCode::UnacceptableProtocolVersion,
+                                            }), &stats, &tap).await;
+                                            break 'connection;
+                                        }
+                                        Err(e) => {
+                                            println!("Closing connection from {}: {}", remote_addr, e);
+                                            disconnect_reason.get_or_insert(DisconnectReason::ProtocolError);
+                                            break 'connection;
+                                        }
+                                    };
 
-                                     let action: Encoder =   match &packet {
-                                        Decoder::Connect(p) => {
-                                            client_id = Some(p.client_id.clone());
-                                            timeout_duration = Duration::from_secs((p.keep_alive as u64) * 3 / 2);
-                                            if let Err(e) =  state.engine.connect_tx.send(ConnectCommand::Connect(p.clone(), state.port, remote_addr,  tx.clone() )) {
+                                    tap.record(TraceDirection::In, &packet);
+
+                                    /*
+                                      CONNECT must be the first packet and may only be sent once.
+                                    */
+                                    if client_id.is_none() != matches!(packet, Packet::Connect(_)) {
+                                        println!("Closing connection from {}: protocol violation", remote_addr);
+                                        disconnect_reason.get_or_insert(DisconnectReason::ProtocolError);
+                                        break 'connection;
+                                    }
+
+                                    let reply = match packet {
+                                        Packet::Connect(p) => {
+                                            tap.client_id = Some(p.client_id.clone());
+                                            let id = p.client_id.clone();
+                                            timeout_duration = keep_alive_timeout(p.keep_alive);
+                                            let (reply_tx, reply_rx) = oneshot::channel();
+                                            if let Err(e) = state.engine.connect_tx.send(ConnectCommand::Connect(p, state.port, remote_addr, tx.clone(), stats.clone(), reply_tx)).await {
                                                 println!("Error connecting:  {}", e);
                                             }
-                                            Encoder::ConnAck {session_present: false, }
-
+                                            connection_id = match reply_rx.await {
+                                                Ok(Ok(connection_id)) => connection_id,
+                                                Ok(Err(return_code)) => {
+                                                    let _ = send_ws(&mut sender, &Packet::ConnAck(ConnAckPacket {
+                                                        session_present: false,
+                                                        return_code,
+                                                    }), &stats, &tap).await;
+                                                    break 'connection;
+                                                }
+                                                Err(_) => 0,
+                                            };
+                                            client_id = Some(id);
+                                            Some(Packet::ConnAck(ConnAckPacket {
+                                                session_present: false,
+                                                return_code: ConnectReturnCode::Accepted,
+                                            }))
                                         }
 
-                                        Decoder::Disconnect => {
-                                            request_disconnect(&tx, &mut disconnect_requested).await;
-                                            Encoder::Disconnect
+                                        Packet::Disconnect => {
+                                            request_disconnect(&tx, &mut disconnect_reason, DisconnectReason::ClientDisconnect).await;
+                                            None
                                         }
 
-                                        Decoder::PingReq => {
+                                        Packet::PingReq => {
                                              last_activity = Instant::now();
-                                             Encoder::PingResp
+                                             Some(Packet::PingResp)
                                         }
 
-                                        Decoder::Publish(p) => {
+                                        Packet::Publish(p) => {
                                             last_activity = Instant::now();
-                                           if  let  Err(e) = state.engine.pubsub_tx.send(PubSubCommand::Publish(p.clone())) {
-                                              println!("Error publishing: {}", e);
-                                           }
-                                            match p.packet_id {
-                                                Some(packet_id) => Encoder::PubAck { packet_id },
-                                                None => Encoder::None,
-                                             }
+                                            /*
+                                              A QoS 2 publish is routed on first receipt only; a DUP
+                                              resend before PUBREL must not be delivered twice.
+                                            */
+                                            let first_receipt = match (p.qos, p.packet_id) {
+                                                (2, Some(packet_id)) => awaiting_release.insert(packet_id),
+                                                _ => true,
+                                            };
+
+                                            if first_receipt {
+                                                /*
+                                                  Nothing more is read until every subscriber's mailbox
+                                                  has taken the message, while this connection's own
+                                                  mailbox keeps being written out (see tcp.rs).
+                                                */
+                                                let mut delivery = state.engine.routing.route(&p, None);
+                                                loop {
+                                                    tokio::select! {
+                                                        biased;
+                                                        _ = delivery.send() => break,
+                                                        msg = rx.recv() => match msg {
+                                                            Some(MqttChannel::Publish(message, qos)) => {
+                                                                if let Err(e) = publish_ws(&mut sender, &message, qos, &mut packet_ids, &stats, &tap).await {
+                                                                    println!("Publish WS error: {:?}", e);
+                                                                    break 'connection;
+                                                                }
+                                                            }
+                                                            Some(MqttChannel::Disconnect) | None => break 'connection,
+                                                        },
+                                                    }
+                                                }
+                                            }
+
+                                            match (p.qos, p.packet_id) {
+                                                (2, Some(packet_id)) => Some(Packet::PubRec(packet_id)),
+                                                (_, packet_id) => packet_id.map(Packet::PubAck),
+                                            }
+                                        }
+
+                                        Packet::PubRel(packet_id) => {
+                                            awaiting_release.remove(&packet_id);
+                                            Some(Packet::PubComp(packet_id))
+                                        }
+
+                                        Packet::PubRec(packet_id) => Some(Packet::PubRel(packet_id)),
 
+                                        Packet::PubAck(packet_id) | Packet::PubComp(packet_id) => {
+                                            packet_ids.complete(packet_id);
+                                            None
                                         }
 
-                                        Decoder::Subscribe(p) => {
+                                        Packet::Subscribe(p) => {
+                                            let packet_id = p.packet_id;
+                                            let mut return_codes = vec![SUBACK_FAILURE; p.filters.len()];
                                             if let Some(ref id) = client_id {
-                                                 let _ = state.engine.pubsub_tx.send(PubSubCommand::Subscribe(p.clone(), id.clone()));
+                                                 let (reply_tx, reply_rx) = oneshot::channel();
+                                                 if state.engine.pubsub_tx.send(PubSubCommand::Subscribe(p, id.clone(), connection_id, reply_tx)).await.is_ok()
+                                                     && let Ok(codes) = reply_rx.await
+                                                 {
+                                                     return_codes = codes;
+                                                 }
                                             }
 
-                                            Encoder::SubAck { packet_id: p.packet_id }
+                                            Some(Packet::SubAck(SubAckPacket { packet_id, return_codes }))
                                         }
 
-                                        Decoder::Unsubscribe(p) =>{
+                                        Packet::Unsubscribe(p) => {
+                                            let packet_id = p.packet_id;
                                             if let Some(ref id) = client_id {
-                                                let _ = state.engine.pubsub_tx.send(PubSubCommand::Unsubscribe(p.clone(), id.clone()));
+                                                let (reply_tx, reply_rx) = oneshot::channel();
+                                                if state.engine.pubsub_tx.send(PubSubCommand::Unsubscribe(p, id.clone(), connection_id, reply_tx)).await.is_ok() {
+                                                    let _ = reply_rx.await;
+                                                }
                                             }
 
-                                            Encoder::UnsubAck { packet_id: p.packet_id }
+                                            Some(Packet::UnsubAck(packet_id))
                                         }
 
+                                        _ => None,
                                     };
 
-
-                                    let _ = action.send_ws(&mut sender).await;
-
+                                    if let Some(reply) = reply {
+                                        let _ = send_ws(&mut sender, &reply, &stats, &tap).await;
+                                    }
+                                    stats.set_inflight(awaiting_release.len(), packet_ids.in_flight());
                                 }
                             }
 
                             Message::Close(_) => {
 
-                                 request_disconnect(&tx, &mut disconnect_requested).await;
+                                 request_disconnect(&tx, &mut disconnect_reason, DisconnectReason::ConnectionClosed).await;
                                 break;
                             }
 
@@ -153,16 +260,19 @@ async fn handle_socket(socket: WebSocket, state: WsState,   remote_addr: SocketA
 
             channel_msg = rx.recv() => {
                 match channel_msg {
-                    Some(MqttChannel::Publish(packet)) => {
-                        if let Err(e) = publish_ws(&mut sender, packet).await {
-                            println!("Publish WS error: {:?}", e);
-                            break;
+                    Some(MqttChannel::Publish(message, qos)) => {
+                        match publish_ws(&mut sender, &message, qos, &mut packet_ids, &stats, &tap).await {
+                            Ok(()) => stats.set_inflight(awaiting_release.len(), packet_ids.in_flight()),
+                            Err(e) => {
+                                println!("Publish WS error: {:?}", e);
+                                break;
+                            }
                         }
                     }
 
                     Some(MqttChannel::Disconnect) => {
                           if let Some(ref id) = client_id {
-                                    let _ =  state.engine.connect_tx.send(ConnectCommand::Disconnect(id.clone())).unwrap();
+                                    let _ = state.engine.connect_tx.send(ConnectCommand::Disconnect(id.clone(), connection_id, disconnect_reason.unwrap_or(DisconnectReason::ConnectionClosed))).await;
                         }
                         break;
                     }
@@ -179,27 +289,87 @@ async fn handle_socket(socket: WebSocket, state: WsState,   remote_addr: SocketA
 
     if let Some(id) = client_id {
         let _ = state
-        .engine
+            .engine
             .connect_tx
-            .send(ConnectCommand::Disconnect(id.clone()))
-            .unwrap();
+            .send(ConnectCommand::Disconnect(id.clone(), connection_id, disconnect_reason.unwrap_or(DisconnectReason::ConnectionClosed)))
+            .await;
     }
 
     println!("WebSocket connection closed");
 }
 
-async fn request_disconnect(tx: &mpsc::Sender<MqttChannel>, flag: &mut bool) {
-    if !*flag {
+/*
+  Closes the connection through its own mailbox, remembering the first
+  reason given so the engine can report it.
+*/
+async fn request_disconnect(tx: &mpsc::Sender<MqttChannel>, requested: &mut Option<DisconnectReason>, reason: DisconnectReason) {
+    if requested.is_none() {
         let _ = tx.send(MqttChannel::Disconnect).await;
-        *flag = true;
+        *requested = Some(reason);
     }
 }
 
+async fn send_ws(
+    sender: &mut SplitSink<WebSocket, Message>,
+    packet: &Packet,
+    stats: &ConnectionStats,
+    tap: &TraceTap,
+) -> anyhow::Result<()> {
+    let bytes = packet.to_bytes()?;
+    sender.send(Message::Binary(bytes.to_vec())).await?;
+    stats.sent(bytes.len());
+    tap.record(TraceDirection::Out, packet);
+    Ok(())
+}
+
+/*
+  Writes one delivery. QoS 1/2 deliveries get a packet id of this
+  connection and are tracked until the client acknowledges them.
+*/
 async fn publish_ws(
     sender: &mut SplitSink<WebSocket, Message>,
-    packet: PublishPacket,
+    message: &OutgoingPublish,
+    qos: u8,
+    packet_ids: &mut PacketIds,
+    stats: &ConnectionStats,
+    tap: &TraceTap,
 ) -> anyhow::Result<()> {
-    let bytes = encode_publish(&packet);
+    let qos = qos.min(message.packet.qos);
+    let packet_id = match qos {
+        0 => None,
+        _ => match packet_ids.allocate() {
+            Some(packet_id) => Some(packet_id),
+            None => {
+                println!("Dropping WebSocket delivery: no free packet id");
+                return Ok(());
+            }
+        },
+    };
+    let Some(frame) = message.frame_with_id(qos, false, packet_id) else {
+        return Ok(());
+    };
+
+    /*
+      axum's WebSocket message owns its buffer, so the shared frame is
+      copied here for every subscriber.
+    */
+    let mut bytes = Vec::with_capacity(frame.len());
+    bytes.extend_from_slice(&frame.head);
+    bytes.extend_from_slice(&frame.body);
     sender.send(Message::Binary(bytes)).await?;
+    stats.sent(frame.len());
+    if let Some(packet_id) = packet_id {
+        packet_ids.insert(packet_id, message.ack.clone());
+    }
+
+    if tap.is_running() {
+        tap.record(TraceDirection::Out, &Packet::Publish(PublishPacket {
+            qos,
+            packet_id,
+            dup: false,
+            retain: false,
+            ..message.packet.clone()
+        }));
+    }
     Ok(())
 }
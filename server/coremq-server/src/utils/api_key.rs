@@ -0,0 +1,33 @@
+use blake2::{Blake2b512, Digest};
+
+use crate::utils::random;
+
+pub const PREFIX: &str = "cmq_";
+
+/*
+  Keys look like `cmq_<id>_<secret>`. The id is stored in clear and used
+  for lookup; only a hash of the secret is stored. Secrets are random, so
+  a fast hash is enough (unlike passwords).
+*/
+pub fn generate() -> (String, String, String) {
+    let id = random::hex(8);
+    let secret = random::hex(24);
+    let key = format!("{}{}_{}", PREFIX, id, secret);
+    (id, secret, key)
+}
+
+pub fn parse(key: &str) -> Option<(&str, &str)> {
+    let (id, secret) = key.strip_prefix(PREFIX)?.split_once('_')?;
+    if id.is_empty() || secret.is_empty() {
+        return None;
+    }
+    Some((id, secret))
+}
+
+pub fn hash(secret: &str) -> String {
+    Blake2b512::digest(secret.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn verify(secret: &str, hashed: &str) -> bool {
+    hash(secret) == hashed
+}
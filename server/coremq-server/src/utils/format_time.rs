@@ -11,3 +11,31 @@ where
     let formatted = datetime.format("%Y-%m-%d %H:%M:%S").to_string();
     serializer.serialize_str(&formatted)
 }
+
+pub fn format_optional_datetime<S>(
+    datetime: &Option<DateTime<Local>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match datetime {
+        Some(datetime) => format_datetime(datetime, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/*
+  Like `format_datetime`, with milliseconds, for records that are often
+  less than a second apart.
+*/
+pub fn format_datetime_millis<S>(
+    datetime: &DateTime<Local>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let formatted = datetime.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+    serializer.serialize_str(&formatted)
+}
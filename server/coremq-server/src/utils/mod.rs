@@ -1,3 +1,7 @@
 pub mod format_time;
 pub mod config;
-pub mod password;
\ No newline at end of file
+pub mod password;
+pub mod packet_id;
+pub mod random;
+pub mod api_key;
+pub mod topic;
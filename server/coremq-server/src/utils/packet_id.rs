@@ -0,0 +1,11 @@
+use std::sync::atomic::{AtomicU16, Ordering};
+
+/// Returns the next packet ID (1–65535), wrapping around and skipping 0.
+pub fn next(counter: &AtomicU16) -> u16 {
+    loop {
+        let id = counter.fetch_add(1, Ordering::Relaxed);
+        if id != 0 {
+            return id;
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use rand_core::{OsRng, RngCore};
+
+/*
+  `len` random bytes from the OS generator, hex-encoded.
+*/
+pub fn hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
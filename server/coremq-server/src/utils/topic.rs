@@ -0,0 +1,41 @@
+/*
+  Checks a topic filter the way a SUBSCRIBE would need it: non-empty, no
+  NUL characters, and wildcards only as whole levels, with `#` last.
+*/
+pub fn is_valid_filter(filter: &str) -> bool {
+    if filter.is_empty() || filter.len() > u16::MAX as usize || filter.contains('\0') {
+        return false;
+    }
+
+    let levels: Vec<&str> = filter.split('/').collect();
+    levels.iter().enumerate().all(|(i, level)| match *level {
+        "#" => i == levels.len() - 1,
+        "+" => true,
+        level => !level.contains(['#', '+']),
+    })
+}
+
+/*
+  Checks a topic name as a PUBLISH must carry it: non-empty, no NUL and no
+  wildcards.
+*/
+pub fn is_valid_topic_name(topic: &str) -> bool {
+    !topic.is_empty() && topic.len() <= u16::MAX as usize && !topic.contains(['\0', '#', '+'])
+}
+
+/*
+  Whether `topic` falls under `filter`, with the same rules as routing:
+  "a/#" also matches "a".
+*/
+pub fn matches_filter(filter: &str, topic: &str) -> bool {
+    let mut levels = topic.split('/');
+    for pattern in filter.split('/') {
+        match (pattern, levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (pattern, Some(level)) if pattern == level => {}
+            _ => return false,
+        }
+    }
+    levels.next().is_none()
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use std::time::Duration;
+
+use reqwest::Method;
+use common::{MqttClient, TestBroker};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_id_takeover_closes_the_old_connection() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let port = broker.tcp_port();
+
+    let mut old = MqttClient::connect(port, "device-1").await;
+    old.subscribe(&[("cmd/device-1", 0)]).await;
+
+    let mut new = MqttClient::connect(port, "device-1").await;
+    assert!(old.closed_within(Duration::from_secs(2)).await, "old connection still open");
+
+    /* The old connection closing must not remove the new session. */
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(broker.session_ids(&token).await, vec!["device-1".to_string()]);
+
+    /* Clean sessions don't inherit subscriptions. */
+    let mut publisher = MqttClient::connect(port, "publisher").await;
+    publisher.publish("cmd/device-1", "reboot", 0).await;
+    new.expect_no_publish().await;
+
+    new.subscribe(&[("cmd/device-1", 0)]).await;
+    publisher.publish("cmd/device-1", "reboot", 0).await;
+    assert_eq!(&new.expect_publish().await.payload[..], b"reboot");
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_kick_disconnects_the_client() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let port = broker.tcp_port();
+
+    let mut victim = MqttClient::connect(port, "victim").await;
+    victim.subscribe(&[("t", 0)]).await;
+    let _bystander = MqttClient::connect(port, "bystander").await;
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/sessions/victim", &token, None).await;
+    assert_eq!(status, 200);
+    assert!(victim.closed_within(Duration::from_secs(2)).await, "kicked client still connected");
+
+    assert_eq!(broker.session_ids(&token).await, vec!["bystander".to_string()]);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/sessions/victim", &token, None).await;
+    assert_eq!(status, 404);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_api_requires_a_token() {
+    let broker = TestBroker::start().await;
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", "not-a-token", None).await;
+    assert_eq!(status, 401);
+
+    let response = reqwest::get(broker.api_url("/api/v1/sessions")).await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stopping_a_listener_disconnects_its_clients() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let stopped_port = broker.port("tcp-2");
+
+    let mut on_stopped = MqttClient::connect(stopped_port, "on-stopped").await;
+    let mut on_running = MqttClient::connect(broker.tcp_port(), "on-running").await;
+
+    let path = format!("/api/v1/listeners/{}", stopped_port);
+    let (status, _) = broker.api(Method::DELETE, &path, &token, None).await;
+    assert_eq!(status, 200);
+
+    assert!(on_stopped.closed_within(Duration::from_secs(2)).await, "client on stopped listener still connected");
+    assert!(tokio::net::TcpStream::connect(("127.0.0.1", stopped_port)).await.is_err());
+
+    let (status, listeners) = broker.api(Method::GET, "/api/v1/listeners", &token, None).await;
+    assert_eq!(status, 200);
+    let ports: Vec<u64> = listeners.as_array().unwrap().iter().filter_map(|l| l["port"].as_u64()).collect();
+    assert!(!ports.contains(&(stopped_port as u64)));
+    assert!(ports.contains(&(broker.tcp_port() as u64)));
+
+    /* Clients on other listeners are untouched. */
+    on_running.send(coremq_codec::packets::Packet::PingReq).await;
+    assert_eq!(on_running.recv().await, coremq_codec::packets::Packet::PingResp);
+    assert_eq!(broker.session_ids(&token).await, vec!["on-running".to_string()]);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rest_publish_reaches_mqtt_subscribers() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut subscriber = MqttClient::connect(broker.tcp_port(), "subscriber").await;
+    subscriber.subscribe(&[("alerts/#", 1)]).await;
+
+    let body = serde_json::json!({ "topic": "alerts/fire", "payload": "evacuate", "qos": 1, "retain": false });
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &token, Some(body)).await;
+    assert_eq!(status, 200);
+
+    let received = subscriber.expect_publish().await;
+    assert_eq!(received.topic, "alerts/fire");
+    assert_eq!(&received.payload[..], b"evacuate");
+
+    broker.shutdown().await;
+}
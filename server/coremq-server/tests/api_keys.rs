@@ -0,0 +1,123 @@
+mod common;
+
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::TestBroker;
+
+async fn create_key(broker: &TestBroker, admin: &str, name: &str, role: &str) -> Value {
+    let body = json!({ "name": name, "role": role });
+    let (status, body) = broker.api(Method::POST, "/api/v1/api-keys", admin, Some(body)).await;
+    assert_eq!(status, 200, "{}", body);
+    body["data"].clone()
+}
+
+fn publish() -> Value {
+    json!({ "topic": "ci/status", "payload": "ok", "qos": 0, "retain": false })
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_keys_authenticate_with_their_role() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let created = create_key(&broker, &admin, "ci", "user").await;
+    let key = created["key"].as_str().unwrap();
+    assert!(key.starts_with("cmq_"));
+    assert_eq!(created["api_key"]["role"], "user");
+    assert_eq!(created["api_key"]["created_by"], "admin");
+
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/sessions", key, None).await;
+    assert_eq!(status, 200);
+    /* Bearer works too, so existing clients only need a new token value. */
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", key, None).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = broker.api_with_key(Method::POST, "/api/v1/publish", key, Some(publish())).await;
+    assert_eq!(status, 403);
+    let (status, _) = broker.api_with_key(Method::POST, "/api/v1/logout", key, None).await;
+    assert_eq!(status, 400);
+
+    let admin_key = create_key(&broker, &admin, "deploy", "admin").await;
+    let admin_key = admin_key["key"].as_str().unwrap();
+    let (status, _) = broker.api_with_key(Method::POST, "/api/v1/publish", admin_key, Some(publish())).await;
+    assert_eq!(status, 200);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_keys_are_listed_without_secrets_and_record_use() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let created = create_key(&broker, &admin, "ci", "user").await;
+    let key = created["key"].as_str().unwrap();
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/sessions", key, None).await;
+    assert_eq!(status, 200);
+
+    let (status, list) = broker.api(Method::GET, "/api/v1/api-keys", &admin, None).await;
+    assert_eq!(status, 200);
+    let keys = list["data"].as_array().unwrap();
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0]["name"], "ci");
+    assert_eq!(keys[0]["expired"], false);
+    assert!(keys[0].get("key_hash").is_none());
+    assert!(keys[0].get("key").is_none());
+    assert!(!keys[0]["last_used_at"].is_null());
+
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/api-keys", key, None).await;
+    assert_eq!(status, 403);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_and_revoked_keys_are_rejected() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let created = create_key(&broker, &admin, "ci", "user").await;
+    let key = created["key"].as_str().unwrap().to_string();
+    let id = created["api_key"]["id"].as_str().unwrap().to_string();
+
+    /* Right id, wrong secret. */
+    let forged = format!("cmq_{}_{}", id, "0".repeat(48));
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/sessions", &forged, None).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/sessions", "not-a-key", None).await;
+    assert_eq!(status, 401);
+
+    for body in [
+        json!({ "name": "", "role": "user" }),
+        json!({ "name": "x", "role": "" }),
+        json!({ "name": "x", "role": "user", "expires_in_days": 0 }),
+    ] {
+        let (status, _) = broker.api(Method::POST, "/api/v1/api-keys", &admin, Some(body)).await;
+        assert_eq!(status, 400);
+    }
+
+    let path = format!("/api/v1/api-keys/{}", id);
+    let (status, _) = broker.api(Method::DELETE, &path, &admin, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = broker.api(Method::DELETE, &path, &admin, None).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/sessions", &key, None).await;
+    assert_eq!(status, 401);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_keys_survive_a_restart() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let created = create_key(&broker, &admin, "ci", "user").await;
+    let key = created["key"].as_str().unwrap().to_string();
+
+    let broker = broker.restart().await;
+    let (status, _) = broker.api_with_key(Method::GET, "/api/v1/sessions", &key, None).await;
+    assert_eq!(status, 200);
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use futures_util::future::join_all;
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{ADMIN_PASSWORD, TestBroker};
+
+fn pair(tokens: &Value) -> (String, String) {
+    (
+        tokens["access_token"].as_str().expect("access token").to_string(),
+        tokens["refresh_token"].as_str().expect("refresh token").to_string(),
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn refresh_token_is_exchanged_for_a_new_pair() {
+    let broker = TestBroker::start().await;
+    let (access, refresh) = pair(&broker.login("admin", ADMIN_PASSWORD).await);
+
+    let (status, body) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": refresh })).await;
+    assert_eq!(status, 200);
+    let (new_access, new_refresh) = pair(&body["data"]);
+    assert_ne!(new_refresh, refresh);
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &new_access, None).await;
+    assert_eq!(status, 200);
+
+    /* Refreshing rotates the pair: the old tokens stop working. */
+    let (status, _) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": refresh })).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &access, None).await;
+    assert_eq!(status, 401);
+
+    broker.shutdown().await;
+}
+
+/*
+  Only one of several concurrent refreshes with the same token succeeds.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_refreshes_yield_one_new_pair() {
+    let broker = TestBroker::start().await;
+    let (_, refresh) = pair(&broker.login("admin", ADMIN_PASSWORD).await);
+
+    let body = json!({ "refresh_token": refresh });
+    let results = join_all((0..16).map(|_| broker.post_public("/api/v1/public/refresh", body.clone()))).await;
+    let mut statuses: Vec<u16> = results.iter().map(|(status, _)| *status).collect();
+    statuses.sort();
+    assert_eq!(statuses, [vec![200], vec![401; 15]].concat());
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn token_types_are_not_interchangeable() {
+    let broker = TestBroker::start().await;
+    let (access, refresh) = pair(&broker.login("admin", ADMIN_PASSWORD).await);
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &refresh, None).await;
+    assert_eq!(status, 401);
+
+    let (status, _) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": access })).await;
+    assert_eq!(status, 401);
+
+    let (status, _) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": "garbage" })).await;
+    assert_eq!(status, 401);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn logout_revokes_both_tokens_across_restarts() {
+    let broker = TestBroker::start().await;
+    let (access, refresh) = pair(&broker.login("admin", ADMIN_PASSWORD).await);
+    let other = broker.token().await;
+
+    let (status, _) = broker.api(Method::POST, "/api/v1/logout", &access, None).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &access, None).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": refresh })).await;
+    assert_eq!(status, 401);
+
+    /* Other logins of the same user are unaffected. */
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &other, None).await;
+    assert_eq!(status, 200);
+
+    let broker = broker.restart().await;
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &access, None).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &other, None).await;
+    assert_eq!(status, 200);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn user_role_tokens_are_read_only() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    assert_eq!(broker.create_user(&admin, "operator", "secret", "user").await, 200);
+
+    let (access, _) = pair(&broker.login("operator", "secret").await);
+
+    for path in ["/api/v1/sessions", "/api/v1/listeners", "/api/v1/topics", "/api/v1/metrics"] {
+        let (status, _) = broker.api(Method::GET, path, &access, None).await;
+        assert_eq!(status, 200, "GET {}", path);
+    }
+
+    let publish = json!({ "topic": "t", "payload": "x", "qos": 0, "retain": false });
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &access, Some(publish)).await;
+    assert_eq!(status, 403);
+    let (status, _) = broker.api(Method::GET, "/api/v1/users", &access, None).await;
+    assert_eq!(status, 403);
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/listeners/1883", &access, None).await;
+    assert_eq!(status, 403);
+    assert_eq!(broker.create_user(&access, "sneaky", "secret", "admin").await, 403);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unknown_roles_are_rejected() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    assert_eq!(broker.create_user(&admin, "root", "secret", "superuser").await, 400);
+
+    let (status, _) = broker.api(Method::PUT, "/api/v1/users/admin/role", &admin, Some(json!({ "role": "root" }))).await;
+    assert_eq!(status, 400);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn role_change_invalidates_existing_tokens() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    assert_eq!(broker.create_user(&admin, "operator", "secret", "user").await, 200);
+    let (old, refresh) = pair(&broker.login("operator", "secret").await);
+
+    let (status, body) =
+        broker.api(Method::PUT, "/api/v1/users/operator/role", &admin, Some(json!({ "role": "admin" }))).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["role"], "admin");
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &old, None).await;
+    assert_eq!(status, 401);
+
+    /* A refresh picks up the stored role. */
+    let (status, body) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": refresh })).await;
+    assert_eq!(status, 200);
+    let (promoted, _) = pair(&body["data"]);
+    let (status, _) = broker.api(Method::GET, "/api/v1/users", &promoted, None).await;
+    assert_eq!(status, 200);
+
+    let (status, _) =
+        broker.api(Method::PUT, "/api/v1/users/nobody/role", &admin, Some(json!({ "role": "user" }))).await;
+    assert_eq!(status, 404);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn last_admin_cannot_be_demoted() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let (status, _) = broker.api(Method::PUT, "/api/v1/users/admin/role", &admin, Some(json!({ "role": "user" }))).await;
+    assert_eq!(status, 409);
+
+    assert_eq!(broker.create_user(&admin, "second", "secret", "admin").await, 200);
+    let (status, _) = broker.api(Method::PUT, "/api/v1/users/admin/role", &admin, Some(json!({ "role": "user" }))).await;
+    assert_eq!(status, 200);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_demotions_keep_one_admin() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    assert_eq!(broker.create_user(&admin, "second", "secret", "admin").await, 200);
+    let second = pair(&broker.login("second", "secret").await).0;
+
+    /* Each admin demotes the other; only one of them may win. */
+    let body = Some(json!({ "role": "user" }));
+    let (first, other) = tokio::join!(
+        broker.api(Method::PUT, "/api/v1/users/second/role", &admin, body.clone()),
+        broker.api(Method::PUT, "/api/v1/users/admin/role", &second, body.clone()),
+    );
+    assert_eq!([first.0, other.0].iter().filter(|status| **status == 200).count(), 1);
+
+    let token = if first.0 == 200 { admin } else { second };
+    let (_, users) = broker.api(Method::GET, "/api/v1/users", &token, None).await;
+    let admins = users["data"].as_array().unwrap().iter().filter(|u| u["role"] == "admin").count();
+    assert_eq!(admins, 1);
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use std::time::Duration;
+
+use coremq_codec::packets::{ConnectReturnCode, Packet, PROTOCOL_LEVEL_3_1_1};
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{MqttClient, TestBroker, connect_packet, connect_packet_as};
+
+async fn ban(broker: &TestBroker, token: &str, body: Value) -> Value {
+    let (status, body) = broker.api(Method::POST, "/api/v1/bans", token, Some(body)).await;
+    assert_eq!(status, 200, "{}", body);
+    body["data"].clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn banning_a_client_id_disconnects_it_and_refuses_reconnects() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let mut banned = MqttClient::connect(broker.tcp_port(), "rogue").await;
+    let mut other = MqttClient::connect(broker.tcp_port(), "good").await;
+
+    let created = ban(&broker, &admin, json!({ "kind": "client_id", "value": "rogue", "reason": "flooding" })).await;
+    assert_eq!(created["disconnected"], json!(["rogue"]));
+    assert_eq!(created["ban"]["created_by"], "admin");
+    assert!(banned.closed_within(Duration::from_secs(2)).await);
+
+    assert_eq!(MqttClient::connect_code(broker.tcp_port(), connect_packet("rogue", 60)).await, ConnectReturnCode::NotAuthorized);
+    other.send(Packet::PingReq).await;
+    assert_eq!(other.recv().await, Packet::PingResp);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/bans?kind=client_id&value=rogue", &admin, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(MqttClient::connect_code(broker.tcp_port(), connect_packet("rogue", 60)).await, ConnectReturnCode::Accepted);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/bans?kind=client_id&value=rogue", &admin, None).await;
+    assert_eq!(status, 404);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn banned_addresses_are_refused_on_accept() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let mut connected = MqttClient::connect(broker.tcp_port(), "sensor-1").await;
+    let created = ban(&broker, &admin, json!({ "kind": "cidr", "value": "127.1.2.3/8", "expires_in_secs": 2 })).await;
+    assert_eq!(created["ban"]["value"], "127.0.0.0/8");
+    assert_eq!(created["disconnected"], json!(["sensor-1"]));
+    assert!(connected.closed_within(Duration::from_secs(2)).await);
+
+    /* Closed before CONNECT is even read. */
+    let mut refused = MqttClient::open(broker.tcp_port()).await;
+    assert!(refused.closed_within(Duration::from_secs(2)).await);
+
+    /* The ban expires on its own. */
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    MqttClient::connect(broker.tcp_port(), "sensor-1").await;
+
+    let (status, bans) = broker.api(Method::GET, "/api/v1/bans", &admin, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(bans["data"][0]["expired"], true);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn username_bans_persist_across_restarts() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    ban(&broker, &admin, json!({ "kind": "username", "value": "mallory" })).await;
+    ban(&broker, &admin, json!({ "kind": "ip", "value": "::ffff:10.9.9.9" })).await;
+
+    let broker = broker.restart().await;
+    let admin = broker.token().await;
+
+    assert_eq!(MqttClient::connect_code(broker.tcp_port(), connect_packet_as("m-1", 60, Some("mallory"), None, PROTOCOL_LEVEL_3_1_1)).await, ConnectReturnCode::NotAuthorized);
+    assert_eq!(MqttClient::connect_code(broker.tcp_port(), connect_packet_as("a-1", 60, Some("alice"), None, PROTOCOL_LEVEL_3_1_1)).await, ConnectReturnCode::Accepted);
+
+    let (status, bans) = broker.api(Method::GET, "/api/v1/bans", &admin, None).await;
+    assert_eq!(status, 200);
+    let mut values: Vec<&str> = bans["data"].as_array().unwrap().iter().map(|b| b["value"].as_str().unwrap()).collect();
+    values.sort();
+    assert_eq!(values, ["10.9.9.9", "mallory"]);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_bans_are_rejected() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    for body in [
+        json!({ "kind": "ip", "value": "10.0.0" }),
+        json!({ "kind": "cidr", "value": "10.0.0.0/33" }),
+        json!({ "kind": "client_id", "value": " " }),
+        json!({ "kind": "username", "value": "x", "expires_in_secs": 0 }),
+    ] {
+        let (status, _) = broker.api(Method::POST, "/api/v1/bans", &admin, Some(body.clone())).await;
+        assert_eq!(status, 400, "{}", body);
+    }
+
+    let (status, _) = broker.api(Method::POST, "/api/v1/bans", &admin, Some(json!({ "kind": "host", "value": "x" }))).await;
+    assert_eq!(status, 422);
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,450 @@
+/*
+  Shared harness for the integration tests.
+
+  `TestBroker` boots a broker inside the test process on ephemeral ports,
+  with its own redb file and a generated config, so tests can run in
+  parallel. `MqttClient` is a raw client on top of the codec: tests send
+  and expect exact packets instead of going through a client library.
+*/
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use coremq_codec::{
+    MqttCodec,
+    packets::{
+        ConnAckPacket, ConnectPacket, ConnectReturnCode, Packet, PublishPacket, SubscribeFilter, SubscribePacket,
+        UnsubscribePacket, PROTOCOL_LEVEL_3_1_1,
+    },
+};
+use coremq_server::{Broker, BrokerHandle, Config};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/*
+  Password the harness gives the default admin, which must change the
+  default `public` before using the API.
+*/
+pub const ADMIN_PASSWORD: &str = "integration-test-password";
+
+pub struct TestBroker {
+    handle: Option<BrokerHandle>,
+    http: reqwest::Client,
+    _dir: TempDir,
+}
+
+impl TestBroker {
+    /*
+      Two TCP listeners ("tcp", "tcp-2") and one WebSocket listener ("ws"),
+      all on port 0, plus the admin API on an ephemeral port.
+    */
+    pub async fn start() -> Self {
+        Self::start_with(|_| {}).await
+    }
+
+    /*
+      Like `start`, with the config adjusted before the broker starts.
+    */
+    pub async fn start_with(configure: impl FnOnce(&mut Config)) -> Self {
+        let broker = Self::start_in(tempfile::tempdir().expect("temp dir"), configure).await;
+
+        let tokens = broker.login("admin", "public").await;
+        let token = tokens["access_token"].as_str().expect("access token");
+        let body = serde_json::json!({ "current_password": "public", "new_password": ADMIN_PASSWORD });
+        let (status, _) = broker.api(reqwest::Method::POST, "/api/v1/account/password", token, Some(body)).await;
+        assert_eq!(status, 200, "admin password change");
+
+        broker
+    }
+
+    /*
+      Like `start`, but the admin account keeps its default password.
+    */
+    pub async fn start_with_default_admin() -> Self {
+        Self::start_in(tempfile::tempdir().expect("temp dir"), |_| {}).await
+    }
+
+    /*
+      Shuts the broker down and starts a new one on the same redb file.
+    */
+    pub async fn restart(mut self) -> Self {
+        if let Some(handle) = self.handle.take() {
+            handle.shutdown().await.expect("clean shutdown");
+        }
+        let dir = std::mem::replace(&mut self._dir, tempfile::tempdir().expect("temp dir"));
+        Self::start_in(dir, |_| {}).await
+    }
+
+    async fn start_in(dir: TempDir, configure: impl FnOnce(&mut Config)) -> Self {
+        let manifest = env!("CARGO_MANIFEST_DIR");
+
+        let mut config: Config = serde_yaml::from_str(&format!(
+            r#"
+http:
+  enabled: true
+  host: "127.0.0.1"
+  port: 0
+
+storage:
+  path: "{db}"
+
+middleware:
+  model_path: {manifest}/config/model.conf
+  policy_path: {manifest}/config/policy.csv
+  secret: integration-test-secret
+
+mqtt:
+  listeners:
+    - name: "tcp"
+      protocol: "tcp"
+      host: "127.0.0.1"
+      port: 0
+    - name: "tcp-2"
+      protocol: "tcp"
+      host: "127.0.0.1"
+      port: 0
+    - name: "ws"
+      protocol: "ws"
+      host: "127.0.0.1"
+      port: 0
+"#,
+            db = dir.path().join("coremq.redb").display(),
+        ))
+        .expect("test config");
+        configure(&mut config);
+
+        let handle = Broker::builder(config).start().await.expect("broker starts");
+
+        Self {
+            handle: Some(handle),
+            http: reqwest::Client::new(),
+            _dir: dir,
+        }
+    }
+
+    pub fn handle(&self) -> &BrokerHandle {
+        self.handle.as_ref().expect("broker running")
+    }
+
+    /*
+      Bound port of the listener with the given name.
+    */
+    pub fn port(&self, name: &str) -> u16 {
+        self.handle()
+            .listeners()
+            .iter()
+            .find(|l| l.name == name)
+            .unwrap_or_else(|| panic!("no listener named {}", name))
+            .port
+    }
+
+    pub fn tcp_port(&self) -> u16 {
+        self.port("tcp")
+    }
+
+    pub fn api_url(&self, path: &str) -> String {
+        let addr = self.handle().admin_addr().expect("admin API enabled");
+        format!("http://{}{}", addr, path)
+    }
+
+    /*
+      Logs in as the admin and returns the access token.
+    */
+    pub async fn token(&self) -> String {
+        self.login("admin", ADMIN_PASSWORD).await["access_token"].as_str().expect("access token").to_string()
+    }
+
+    /*
+      Logs in and returns the token pair (`access_token`, `refresh_token`).
+    */
+    pub async fn login(&self, username: &str, password: &str) -> Value {
+        let response = self
+            .http
+            .post(self.api_url("/api/v1/public/login"))
+            .json(&serde_json::json!({ "username": username, "password": password }))
+            .send()
+            .await
+            .expect("login request");
+        assert_eq!(response.status(), 200, "login as {}", username);
+
+        let mut body: Value = response.json().await.expect("login body");
+        body["data"].take()
+    }
+
+    /*
+      Creates a user with the given role; returns the status of the request.
+    */
+    pub async fn create_user(&self, token: &str, username: &str, password: &str, role: &str) -> u16 {
+        let body = serde_json::json!({ "username": username, "password": password, "role": role });
+        self.api(reqwest::Method::POST, "/api/v1/users", token, Some(body)).await.0
+    }
+
+    /*
+      Unauthenticated POST; returns the status and JSON body.
+    */
+    pub async fn post_public(&self, path: &str, body: Value) -> (u16, Value) {
+        let response = self.http.post(self.api_url(path)).json(&body).send().await.expect("public request");
+        let status = response.status().as_u16();
+        let body = response.json().await.unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    /*
+      Sends an authenticated request and returns the status and JSON body
+      (`Value::Null` if the body is not JSON).
+    */
+    pub async fn api(&self, method: reqwest::Method, path: &str, token: &str, body: Option<Value>) -> (u16, Value) {
+        let mut request = self.http.request(method, self.api_url(path)).bearer_auth(token);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send().await.expect("api request");
+        let status = response.status().as_u16();
+        let body = response.json().await.unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    /*
+      Like `api`, but authenticates with an `X-API-Key` header.
+    */
+    pub async fn api_with_key(&self, method: reqwest::Method, path: &str, key: &str, body: Option<Value>) -> (u16, Value) {
+        let mut request = self.http.request(method, self.api_url(path)).header("X-API-Key", key);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send().await.expect("api request");
+        let status = response.status().as_u16();
+        let body = response.json().await.unwrap_or(Value::Null);
+        (status, body)
+    }
+
+    /*
+      Client ids of the connected sessions, as reported by the admin API.
+    */
+    pub async fn session_ids(&self, token: &str) -> Vec<String> {
+        let (status, body) = self.api(reqwest::Method::GET, "/api/v1/sessions?page=0&size=1000", token, None).await;
+        assert_eq!(status, 200);
+        body["data"]["content"]
+            .as_array()
+            .expect("session page")
+            .iter()
+            .map(|s| s["client_id"].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+
+    pub async fn shutdown(mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.shutdown().await.expect("clean shutdown");
+        }
+    }
+}
+
+pub fn connect_packet(client_id: &str, keep_alive: u16) -> Packet {
+    connect_packet_as(client_id, keep_alive, None, None, PROTOCOL_LEVEL_3_1_1)
+}
+
+/*
+  Like `connect_packet`, with credentials and a protocol level.
+*/
+pub fn connect_packet_as(
+    client_id: &str,
+    keep_alive: u16,
+    username: Option<&str>,
+    password: Option<&str>,
+    protocol_level: u8,
+) -> Packet {
+    Packet::Connect(ConnectPacket {
+        protocol_level,
+        client_id: client_id.to_string(),
+        keep_alive,
+        clean_session: true,
+        username: username.map(str::to_string),
+        password: password.map(|p| Bytes::copy_from_slice(p.as_bytes())),
+        will: None,
+    })
+}
+
+pub struct MqttClient {
+    framed: Framed<TcpStream, MqttCodec>,
+    next_packet_id: u16,
+}
+
+impl MqttClient {
+    /*
+      Opens a TCP connection without sending anything.
+    */
+    pub async fn open(port: u16) -> Self {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.expect("tcp connect");
+        Self {
+            framed: Framed::new(stream, MqttCodec::new()),
+            next_packet_id: 1,
+        }
+    }
+
+    /*
+      Opens a connection and completes CONNECT/CONNACK with a 60s keep-alive.
+    */
+    pub async fn connect(port: u16, client_id: &str) -> Self {
+        Self::connect_with(port, connect_packet(client_id, 60)).await
+    }
+
+    /*
+      Like `connect`, with a CONNECT built by the test.
+    */
+    pub async fn connect_with(port: u16, connect: Packet) -> Self {
+        let mut client = Self::open(port).await;
+        let ack = client.handshake(connect).await;
+        assert_eq!(ack.return_code, ConnectReturnCode::Accepted, "CONNACK");
+        client
+    }
+
+    /*
+      Sends `connect` on a new connection and returns the CONNACK code,
+      for CONNECTs that may be refused.
+    */
+    pub async fn connect_code(port: u16, connect: Packet) -> ConnectReturnCode {
+        Self::open(port).await.handshake(connect).await.return_code
+    }
+
+    pub async fn handshake(&mut self, connect: Packet) -> ConnAckPacket {
+        self.send(connect).await;
+        match self.recv().await {
+            Packet::ConnAck(ack) => ack,
+            other => panic!("expected CONNACK, got {:?}", other),
+        }
+    }
+
+    pub async fn send(&mut self, packet: Packet) {
+        self.framed.send(packet).await.expect("send packet");
+    }
+
+    /*
+      Next packet from the broker; fails the test on timeout or close.
+    */
+    pub async fn recv(&mut self) -> Packet {
+        match tokio::time::timeout(TIMEOUT, self.framed.next()).await {
+            Ok(Some(Ok(packet))) => packet,
+            Ok(Some(Err(e))) => panic!("decode error: {}", e),
+            Ok(None) => panic!("connection closed by the broker"),
+            Err(_) => panic!("no packet within {:?}", TIMEOUT),
+        }
+    }
+
+    /*
+      Next packet within `wait`, or `None` if nothing arrives. Fails the test
+      if the connection closes.
+    */
+    pub async fn try_recv(&mut self, wait: Duration) -> Option<Packet> {
+        match tokio::time::timeout(wait, self.framed.next()).await {
+            Ok(Some(Ok(packet))) => Some(packet),
+            Ok(Some(Err(e))) => panic!("decode error: {}", e),
+            Ok(None) => panic!("connection closed by the broker"),
+            Err(_) => None,
+        }
+    }
+
+    /*
+      Waits up to `wait` for the broker to close the connection, skipping
+      any packets still in flight. Returns false if it stays open.
+    */
+    pub async fn closed_within(&mut self, wait: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + wait;
+        loop {
+            match tokio::time::timeout_at(deadline, self.framed.next()).await {
+                Ok(None) | Ok(Some(Err(_))) => return true,
+                Ok(Some(Ok(_))) => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    fn packet_id(&mut self) -> u16 {
+        let id = self.next_packet_id;
+        self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        id
+    }
+
+    /*
+      Subscribes to the filters and returns the SUBACK return codes.
+    */
+    pub async fn subscribe(&mut self, filters: &[(&str, u8)]) -> Vec<u8> {
+        let packet_id = self.packet_id();
+        self.send(Packet::Subscribe(SubscribePacket {
+            packet_id,
+            filters: filters
+                .iter()
+                .map(|(topic, qos)| SubscribeFilter { topic: topic.to_string(), qos: *qos })
+                .collect(),
+        }))
+        .await;
+
+        match self.recv().await {
+            Packet::SubAck(ack) => {
+                assert_eq!(ack.packet_id, packet_id);
+                ack.return_codes
+            }
+            other => panic!("expected SUBACK, got {:?}", other),
+        }
+    }
+
+    pub async fn unsubscribe(&mut self, topics: &[&str]) {
+        let packet_id = self.packet_id();
+        self.send(Packet::Unsubscribe(UnsubscribePacket {
+            packet_id,
+            topics: topics.iter().map(|t| t.to_string()).collect(),
+        }))
+        .await;
+
+        match self.recv().await {
+            Packet::UnsubAck(id) => assert_eq!(id, packet_id),
+            other => panic!("expected UNSUBACK, got {:?}", other),
+        }
+    }
+
+    /*
+      Publishes and, for QoS 1, waits for the PUBACK.
+    */
+    pub async fn publish(&mut self, topic: &str, payload: &str, qos: u8) {
+        let packet_id = (qos > 0).then(|| self.packet_id());
+        self.send(Packet::Publish(PublishPacket {
+            packet_id,
+            topic: topic.to_string(),
+            payload: Bytes::from(payload.to_string()),
+            qos,
+            retain: false,
+            dup: false,
+        }))
+        .await;
+
+        if qos == 1 {
+            match self.recv().await {
+                Packet::PubAck(id) => assert_eq!(Some(id), packet_id),
+                other => panic!("expected PUBACK, got {:?}", other),
+            }
+        }
+    }
+
+    pub async fn expect_publish(&mut self) -> PublishPacket {
+        match self.recv().await {
+            Packet::Publish(publish) => publish,
+            other => panic!("expected PUBLISH, got {:?}", other),
+        }
+    }
+
+    /*
+      Asserts that no PUBLISH arrives within a short grace period.
+    */
+    pub async fn expect_no_publish(&mut self) {
+        if let Some(packet) = self.try_recv(Duration::from_millis(300)).await {
+            panic!("unexpected packet {:?}", packet);
+        }
+    }
+}
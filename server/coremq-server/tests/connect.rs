@@ -0,0 +1,147 @@
+mod common;
+
+use std::time::Duration;
+
+use coremq_codec::packets::{ConnectReturnCode, Packet, PublishPacket};
+use common::{MqttClient, TestBroker, connect_packet, connect_packet_as};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn connect_is_acknowledged() {
+    let broker = TestBroker::start().await;
+
+    let mut client = MqttClient::open(broker.tcp_port()).await;
+    let ack = client.handshake(connect_packet("connect-ok", 60)).await;
+
+    assert_eq!(ack.return_code, ConnectReturnCode::Accepted);
+    assert!(!ack.session_present);
+
+    client.send(Packet::PingReq).await;
+    assert_eq!(client.recv().await, Packet::PingResp);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_is_listed_until_disconnect() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut client = MqttClient::connect(broker.tcp_port(), "listed").await;
+    assert_eq!(broker.session_ids(&token).await, vec!["listed".to_string()]);
+
+    client.send(Packet::Disconnect).await;
+    assert!(client.closed_within(Duration::from_secs(2)).await);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(broker.session_ids(&token).await.is_empty());
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsupported_protocol_level_is_refused() {
+    let broker = TestBroker::start().await;
+
+    let mut client = MqttClient::open(broker.tcp_port()).await;
+    let ack = client.handshake(connect_packet_as("mqtt5", 60, None, None, 5)).await;
+
+    assert_eq!(ack.return_code, ConnectReturnCode::UnacceptableProtocolVersion);
+    assert!(client.closed_within(Duration::from_secs(2)).await);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_packet_closes_the_connection() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let broker = TestBroker::start().await;
+
+    let mut stream = tokio::net::TcpStream::connect(("127.0.0.1", broker.tcp_port())).await.unwrap();
+    /* Reserved packet type 0 with a remaining length of zero. */
+    stream.write_all(&[0x00, 0x00]).await.unwrap();
+
+    let mut buf = [0u8; 16];
+    let read = tokio::time::timeout(common::TIMEOUT, stream.read(&mut buf)).await.expect("closed in time");
+    assert!(matches!(read, Ok(0) | Err(_)));
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn publish_before_connect_is_not_routed() {
+    let broker = TestBroker::start().await;
+    let mut subscriber = MqttClient::connect(broker.tcp_port(), "watcher").await;
+    subscriber.subscribe(&[("early", 0)]).await;
+
+    let mut rogue = MqttClient::open(broker.tcp_port()).await;
+    rogue
+        .send(Packet::Publish(PublishPacket {
+            packet_id: None,
+            topic: "early".to_string(),
+            payload: "nope".into(),
+            qos: 0,
+            retain: false,
+            dup: false,
+        }))
+        .await;
+
+    subscriber.expect_no_publish().await;
+
+    broker.shutdown().await;
+}
+
+/*
+  The broker checks idle connections every few seconds and drops those
+  silent for 1.5x their keep-alive.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn keep_alive_expiry_disconnects_idle_clients() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut client = MqttClient::open(broker.tcp_port()).await;
+    let ack = client.handshake(connect_packet("sleepy", 1)).await;
+    assert_eq!(ack.return_code, ConnectReturnCode::Accepted);
+
+    assert!(client.closed_within(Duration::from_secs(10)).await, "idle client was not dropped");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!broker.session_ids(&token).await.contains(&"sleepy".to_string()));
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pings_keep_the_connection_alive() {
+    let broker = TestBroker::start().await;
+
+    let mut client = MqttClient::open(broker.tcp_port()).await;
+    client.handshake(connect_packet("chatty", 1)).await;
+
+    for _ in 0..14 {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        client.send(Packet::PingReq).await;
+        assert_eq!(client.recv().await, Packet::PingResp);
+    }
+
+    broker.shutdown().await;
+}
+
+/*
+  Keep-alive 0 turns the idle check off rather than expiring at once.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn keep_alive_zero_never_expires() {
+    let broker = TestBroker::start().await;
+
+    let mut client = MqttClient::open(broker.tcp_port()).await;
+    client.handshake(connect_packet("patient", 0)).await;
+
+    /* Long enough for the idle check to run twice. */
+    assert!(!client.closed_within(Duration::from_secs(6)).await, "client with keep-alive 0 was dropped");
+    client.send(Packet::PingReq).await;
+    assert_eq!(client.recv().await, Packet::PingResp);
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use coremq_codec::packets::Packet;
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{MqttClient, TIMEOUT, TestBroker};
+
+/*
+  Reads `GET /api/v1/events` the way a browser EventSource would.
+*/
+struct EventStream {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventStream {
+    async fn open(broker: &TestBroker, query: &str, token: &str) -> Self {
+        let response = reqwest::Client::new()
+            .get(broker.api_url(&format!("/api/v1/events?{}", query)))
+            .bearer_auth(token)
+            .send()
+            .await
+            .expect("events request");
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        Self { response, buffer: String::new() }
+    }
+
+    /*
+      The next event's JSON, `None` once the stream has ended.
+    */
+    async fn next(&mut self) -> Option<Value> {
+        loop {
+            while let Some(end) = self.buffer.find("\n\n") {
+                let frame: String = self.buffer.drain(..end + 2).collect();
+                let data = frame.lines().find_map(|line| line.strip_prefix("data: "));
+                if let Some(data) = data {
+                    return Some(serde_json::from_str(data).expect("event JSON"));
+                }
+            }
+
+            let chunk = tokio::time::timeout(TIMEOUT, self.response.chunk())
+                .await
+                .expect("timed out waiting for an event")
+                .expect("event stream");
+            self.buffer.push_str(std::str::from_utf8(&chunk?).expect("UTF-8 event stream"));
+        }
+    }
+
+    async fn expect(&mut self, kind: &str) -> Value {
+        let event = self.next().await.expect("stream ended");
+        assert_eq!(event["type"], kind, "{}", event);
+        assert!(event["at"].is_string());
+        event
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_lifecycle_is_streamed() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let mut events = EventStream::open(&broker, "", &token).await;
+
+    let mut device = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    let connected = events.expect("client_connected").await;
+    assert_eq!(connected["client_id"], "device-1");
+    assert_eq!(connected["port"], broker.tcp_port());
+    assert_eq!(connected["protocol_version"], 4);
+
+    device.subscribe(&[("sensors/+/temp", 1)]).await;
+    assert_eq!(events.expect("subscribed").await["qos"], 1);
+    device.unsubscribe(&["sensors/+/temp"]).await;
+    assert_eq!(events.expect("unsubscribed").await["topic"], "sensors/+/temp");
+
+    device.send(Packet::Disconnect).await;
+    assert_eq!(events.expect("client_disconnected").await["reason"], "client_disconnect");
+
+    /* A second connection with the same id takes the session over. */
+    let _first = MqttClient::connect(broker.tcp_port(), "device-2").await;
+    events.expect("client_connected").await;
+    let second = MqttClient::connect(broker.tcp_port(), "device-2").await;
+    assert_eq!(events.expect("client_disconnected").await["reason"], "taken_over");
+    events.expect("client_connected").await;
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/sessions/device-2", &token, None).await;
+    assert_eq!(status, 200);
+    let kicked = events.expect("client_disconnected").await;
+    assert_eq!(kicked["client_id"], "device-2");
+    assert_eq!(kicked["reason"], "kicked");
+    drop(second);
+
+    let gone = MqttClient::connect(broker.tcp_port(), "device-3").await;
+    events.expect("client_connected").await;
+    drop(gone);
+    assert_eq!(events.expect("client_disconnected").await["reason"], "connection_closed");
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events_can_be_filtered() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let mut failures = EventStream::open(&broker, "types=auth_failed", &token).await;
+    let mut sensors = EventStream::open(&broker, "client_id=device-1&topic=sensors/%23", &token).await;
+
+    let mut other = MqttClient::connect(broker.tcp_port(), "device-2").await;
+    other.subscribe(&[("sensors/2/temp", 0)]).await;
+    let mut device = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    device.subscribe(&[("alerts/#", 0), ("sensors/1/temp", 1)]).await;
+
+    let subscribed = sensors.expect("subscribed").await;
+    assert_eq!(subscribed["client_id"], "device-1");
+    assert_eq!(subscribed["topic"], "sensors/1/temp");
+
+    let (status, _) = broker.post_public("/api/v1/public/login", json!({ "username": "admin", "password": "guess" })).await;
+    assert_eq!(status, 401);
+    let failed = failures.expect("auth_failed").await;
+    assert_eq!(failed["username"], "admin");
+    assert_eq!(failed["ip"], "127.0.0.1");
+    assert_eq!(failed["locked"], false);
+
+    for query in ["types=connected", "topic=sensors/%23/temp"] {
+        let (status, _) = broker.api(Method::GET, &format!("/api/v1/events?{}", query), &token, None).await;
+        assert_eq!(status, 400, "{}", query);
+    }
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn browsers_pass_the_token_in_the_query_string() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let http = reqwest::Client::new();
+
+    let response = http.get(broker.api_url("/api/v1/events")).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let url = broker.api_url(&format!("/api/v1/events?types=client_connected&access_token={}", token));
+    let response = http.get(url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    /* Only the event streams read it. */
+    let response = http.get(broker.api_url(&format!("/api/v1/sessions?access_token={}", token))).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn listener_changes_are_streamed_until_shutdown() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let mut events = EventStream::open(&broker, "", &token).await;
+
+    let port = broker.port("tcp-2");
+    let _client = MqttClient::connect(port, "device-1").await;
+    events.expect("client_connected").await;
+
+    let (status, _) = broker.api(Method::DELETE, &format!("/api/v1/listeners/{}", port), &token, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(events.expect("client_disconnected").await["reason"], "listener_stopped");
+    let stopped = events.expect("listener_stopped").await;
+    assert_eq!(stopped, json!({ "type": "listener_stopped", "name": "tcp-2", "port": port, "at": stopped["at"] }));
+
+    /* Shutdown stops the remaining listeners and ends the stream. */
+    broker.shutdown().await;
+    let mut names = Vec::new();
+    while let Some(event) = events.next().await {
+        assert_eq!(event["type"], "listener_stopped");
+        names.push(event["name"].as_str().unwrap().to_string());
+    }
+    names.sort();
+    assert_eq!(names, ["tcp", "ws"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn read_only_users_can_follow_events() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    assert_eq!(broker.create_user(&admin, "viewer", "secret", "user").await, 200);
+    let viewer = broker.login("viewer", "secret").await["access_token"].as_str().unwrap().to_string();
+
+    let mut events = EventStream::open(&broker, "", &viewer).await;
+    let _client = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    events.expect("client_connected").await;
+
+    let response = reqwest::Client::new()
+        .get(broker.api_url("/api/v1/events/ws"))
+        .bearer_auth(&viewer)
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .header("Sec-WebSocket-Version", "13")
+        .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+        .send()
+        .await
+        .expect("websocket upgrade");
+    assert_eq!(response.status(), 101);
+
+    broker.shutdown().await;
+}
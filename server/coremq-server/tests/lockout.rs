@@ -0,0 +1,122 @@
+mod common;
+
+use std::time::Duration;
+
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{ADMIN_PASSWORD, TestBroker};
+
+async fn attempt(broker: &TestBroker, username: &str, password: &str) -> (u16, Value) {
+    broker.post_public("/api/v1/public/login", json!({ "username": username, "password": password })).await
+}
+
+fn find<'a>(lockouts: &'a Value, kind: &str, value: &str) -> Option<&'a Value> {
+    lockouts["data"].as_array().unwrap().iter().find(|l| l["kind"] == kind && l["value"] == value)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unknown_users_and_wrong_passwords_look_the_same() {
+    let broker = TestBroker::start().await;
+
+    let (status, unknown) = attempt(&broker, "nobody", "secret").await;
+    assert_eq!(status, 401);
+    let (status, wrong) = attempt(&broker, "admin", "not-the-password").await;
+    assert_eq!(status, 401);
+    assert_eq!(unknown, wrong);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_failures_lock_the_username_until_cleared() {
+    let broker = TestBroker::start_with(|config| {
+        config.lockout.max_failures = 3;
+        config.lockout.base_secs = 1;
+    })
+    .await;
+    let admin = broker.token().await;
+    assert_eq!(broker.create_user(&admin, "operator", "secret", "user").await, 200);
+
+    for _ in 0..3 {
+        assert_eq!(attempt(&broker, "operator", "guess").await.0, 401);
+    }
+    /* Locked: even the right password is refused. */
+    let (status, body) = attempt(&broker, "operator", "secret").await;
+    assert_eq!(status, 429, "{}", body);
+
+    let (status, lockouts) = broker.api(Method::GET, "/api/v1/lockouts", &admin, None).await;
+    assert_eq!(status, 200);
+    let entry = find(&lockouts, "username", "operator").expect("username tracked");
+    assert_eq!(entry["failures"], 3);
+    assert_eq!(entry["locked"], true);
+    assert_eq!(find(&lockouts, "ip", "127.0.0.1").expect("ip tracked")["failures"], 3);
+
+    /* The lock expires on its own... */
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert_eq!(attempt(&broker, "operator", "secret").await.0, 200);
+
+    /* ...and a success resets the count, so it takes the full limit again. */
+    for _ in 0..3 {
+        assert_eq!(attempt(&broker, "operator", "guess").await.0, 401);
+    }
+    assert_eq!(attempt(&broker, "operator", "secret").await.0, 429);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/lockouts/username/operator", &admin, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(attempt(&broker, "operator", "secret").await.0, 200);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/lockouts/username/operator", &admin, None).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/lockouts/host/operator", &admin, None).await;
+    assert_eq!(status, 400);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failures_across_usernames_lock_the_ip() {
+    let broker = TestBroker::start_with(|config| {
+        config.lockout.max_failures_per_ip = 4;
+    })
+    .await;
+    let admin = broker.token().await;
+
+    for username in ["a", "b", "c", "d"] {
+        assert_eq!(attempt(&broker, username, "guess").await.0, 401);
+    }
+    assert_eq!(attempt(&broker, "admin", ADMIN_PASSWORD).await.0, 429);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/lockouts/ip/127.0.0.1", &admin, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(attempt(&broker, "admin", ADMIN_PASSWORD).await.0, 200);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ipv4_mapped_peers_are_locked_by_their_ipv4_address() {
+    let broker = TestBroker::start_with(|config| {
+        config.http.host = "::".to_string();
+        config.lockout.max_failures_per_ip = 2;
+    })
+    .await;
+    let admin = broker.token().await;
+
+    /* Reach the dual-stack listener over IPv4 so the peer is `::ffff:127.0.0.1`. */
+    let port = broker.handle().admin_addr().expect("admin API enabled").port();
+    let url = format!("http://127.0.0.1:{}/api/v1/public/login", port);
+    let client = reqwest::Client::new();
+    for username in ["a", "b"] {
+        let body = json!({ "username": username, "password": "guess" });
+        let response = client.post(&url).json(&body).send().await.expect("login request");
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let (_, lockouts) = broker.api(Method::GET, "/api/v1/lockouts", &admin, None).await;
+    assert_eq!(find(&lockouts, "ip", "127.0.0.1").expect("ip tracked")["locked"], true);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/lockouts/ip/127.0.0.1", &admin, None).await;
+    assert_eq!(status, 200);
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::TestBroker;
+
+fn contains(list: &Value, expected: &Value) -> bool {
+    list["data"].as_array().unwrap().contains(expected)
+}
+
+async fn operator(broker: &TestBroker, admin: &str) -> String {
+    assert_eq!(broker.create_user(admin, "operator", "secret", "user").await, 200);
+    broker.login("operator", "secret").await["access_token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn policies_are_seeded_from_the_policy_file() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let (status, policies) = broker.api(Method::GET, "/api/v1/policies", &admin, None).await;
+    assert_eq!(status, 200);
+    assert!(contains(&policies, &json!({ "subject": "admin", "object": "/api/v1/*", "action": "*" })));
+    assert!(contains(&policies, &json!({ "subject": "user", "object": "/api/v1/sessions", "action": "GET" })));
+
+    let (status, roles) = broker.api(Method::GET, "/api/v1/role-assignments", &admin, None).await;
+    assert_eq!(status, 200);
+    assert!(contains(&roles, &json!({ "subject": "alice", "role": "admin" })));
+
+    let operator = operator(&broker, &admin).await;
+    let (status, _) = broker.api(Method::GET, "/api/v1/policies", &operator, None).await;
+    assert_eq!(status, 403);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn policy_changes_apply_immediately_and_persist() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    let operator = operator(&broker, &admin).await;
+
+    let publish = json!({ "topic": "t", "payload": "x", "qos": 0, "retain": false });
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &operator, Some(publish.clone())).await;
+    assert_eq!(status, 403);
+
+    let rule = json!({ "subject": "user", "object": "/api/v1/publish", "action": "post" });
+    let (status, body) = broker.api(Method::POST, "/api/v1/policies", &admin, Some(rule.clone())).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["action"], "POST");
+    let (status, _) = broker.api(Method::POST, "/api/v1/policies", &admin, Some(rule)).await;
+    assert_eq!(status, 409);
+
+    /* No re-login needed: the next request sees the new enforcer. */
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &operator, Some(publish.clone())).await;
+    assert_eq!(status, 200);
+
+    let broker = broker.restart().await;
+    let operator = broker.login("operator", "secret").await["access_token"].as_str().unwrap().to_string();
+    let admin = broker.token().await;
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &operator, Some(publish.clone())).await;
+    assert_eq!(status, 200);
+
+    let query = "/api/v1/policies?subject=user&object=%2Fapi%2Fv1%2Fpublish&action=POST";
+    let (status, _) = broker.api(Method::DELETE, query, &admin, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = broker.api(Method::DELETE, query, &admin, None).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &operator, Some(publish)).await;
+    assert_eq!(status, 403);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn migrated_rules_are_added_once() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    let rule = json!({ "subject": "user", "object": "/api/v1/events/ws", "action": "GET" });
+
+    let (_, policies) = broker.api(Method::GET, "/api/v1/policies", &admin, None).await;
+    assert!(contains(&policies, &json!({ "subject": "user", "object": "/api/v1/events", "action": "GET" })));
+    assert!(contains(&policies, &rule));
+
+    let query = "/api/v1/policies?subject=user&object=%2Fapi%2Fv1%2Fevents%2Fws&action=GET";
+    assert_eq!(broker.api(Method::DELETE, query, &admin, None).await.0, 200);
+
+    let broker = broker.restart().await;
+    let admin = broker.token().await;
+    let (_, policies) = broker.api(Method::GET, "/api/v1/policies", &admin, None).await;
+    assert!(!contains(&policies, &rule));
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn role_assignments_grant_inherited_permissions() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+    let operator = operator(&broker, &admin).await;
+
+    let rule = json!({ "subject": "auditor", "object": "/api/v1/users", "action": "GET" });
+    assert_eq!(broker.api(Method::POST, "/api/v1/policies", &admin, Some(rule)).await.0, 200);
+    let (status, _) = broker.api(Method::GET, "/api/v1/users", &operator, None).await;
+    assert_eq!(status, 403);
+
+    let assignment = json!({ "subject": "user", "role": "auditor" });
+    assert_eq!(broker.api(Method::POST, "/api/v1/role-assignments", &admin, Some(assignment)).await.0, 200);
+    let (status, _) = broker.api(Method::GET, "/api/v1/users", &operator, None).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/role-assignments?subject=user&role=auditor", &admin, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = broker.api(Method::GET, "/api/v1/users", &operator, None).await;
+    assert_eq!(status, 403);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn role_assignments_apply_to_users_by_name() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    assert_eq!(broker.create_user(&admin, "alice", "secret", "user").await, 200);
+    let alice = broker.login("alice", "secret").await["access_token"].as_str().unwrap().to_string();
+    let (status, _) = broker.api(Method::GET, "/api/v1/policies", &alice, None).await;
+    assert_eq!(status, 200);
+
+    let operator = operator(&broker, &admin).await;
+    let rule = json!({ "subject": "auditor", "object": "/api/v1/bans", "action": "GET" });
+    assert_eq!(broker.api(Method::POST, "/api/v1/policies", &admin, Some(rule)).await.0, 200);
+    let assignment = json!({ "subject": "operator", "role": "auditor" });
+    assert_eq!(broker.api(Method::POST, "/api/v1/role-assignments", &admin, Some(assignment)).await.0, 200);
+    let (status, _) = broker.api(Method::GET, "/api/v1/bans", &operator, None).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/role-assignments?subject=operator&role=auditor", &admin, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = broker.api(Method::GET, "/api/v1/bans", &operator, None).await;
+    assert_eq!(status, 403);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn changes_that_lock_out_admins_are_refused() {
+    let broker = TestBroker::start().await;
+    let admin = broker.token().await;
+
+    let query = "/api/v1/policies?subject=admin&object=%2Fapi%2Fv1%2F*&action=*";
+    let (status, _) = broker.api(Method::DELETE, query, &admin, None).await;
+    assert_eq!(status, 409);
+    let (status, _) = broker.api(Method::GET, "/api/v1/policies", &admin, None).await;
+    assert_eq!(status, 200);
+
+    for rule in [
+        json!({ "subject": "", "object": "/api/v1/x", "action": "GET" }),
+        json!({ "subject": "user", "object": "api/v1/x", "action": "GET" }),
+        json!({ "subject": "user", "object": "/api/v1/x", "action": "FETCH" }),
+    ] {
+        let (status, _) = broker.api(Method::POST, "/api/v1/policies", &admin, Some(rule.clone())).await;
+        assert_eq!(status, 400, "{}", rule);
+    }
+
+    broker.shutdown().await;
+}
+
+/*
+  Requests racing with policy changes are answered from whichever enforcer
+  they started with; none fail.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn policy_reloads_do_not_disturb_in_flight_requests() {
+    let broker = std::sync::Arc::new(TestBroker::start().await);
+    let admin = broker.token().await;
+
+    let readers: Vec<_> = (0..8)
+        .map(|_| {
+            let broker = broker.clone();
+            let admin = admin.clone();
+            tokio::spawn(async move {
+                for _ in 0..25 {
+                    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &admin, None).await;
+                    assert_eq!(status, 200);
+                }
+            })
+        })
+        .collect();
+
+    for i in 0..20 {
+        let rule = json!({ "subject": "user", "object": format!("/api/v1/extra/{}", i), "action": "GET" });
+        assert_eq!(broker.api(Method::POST, "/api/v1/policies", &admin, Some(rule)).await.0, 200);
+    }
+
+    for reader in readers {
+        reader.await.unwrap();
+    }
+
+    let (_, policies) = broker.api(Method::GET, "/api/v1/policies", &admin, None).await;
+    assert!(contains(&policies, &json!({ "subject": "user", "object": "/api/v1/extra/19", "action": "GET" })));
+
+    std::sync::Arc::into_inner(broker).unwrap().shutdown().await;
+}
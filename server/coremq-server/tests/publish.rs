@@ -0,0 +1,128 @@
+mod common;
+
+use coremq_codec::packets::Packet;
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{MqttClient, TestBroker};
+
+async fn publish(broker: &TestBroker, token: &str, query: &str, body: Value) -> Value {
+    let (status, body) = broker.api(Method::POST, &format!("/api/v1/publish{}", query), token, Some(body)).await;
+    assert_eq!(status, 200, "{}", body);
+    body["data"].clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn binary_payloads_can_be_published() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut subscriber = MqttClient::connect(broker.tcp_port(), "subscriber").await;
+    subscriber.subscribe(&[("bin/#", 0)]).await;
+
+    let result = publish(&broker, &token, "", json!({ "topic": "bin/a", "payload": "AAH/", "encoding": "base64" })).await;
+    assert_eq!(result, json!({ "topic": "bin/a", "matched": 1, "delivered": 1, "acks_expected": null, "acknowledged": null }));
+    assert_eq!(&subscriber.expect_publish().await.payload[..], [0x00, 0x01, 0xff]);
+
+    publish(&broker, &token, "", json!({ "topic": "bin/b", "payload": "DEADbeef", "encoding": "hex" })).await;
+    assert_eq!(&subscriber.expect_publish().await.payload[..], [0xde, 0xad, 0xbe, 0xef]);
+
+    publish(&broker, &token, "", json!({ "topic": "bin/c", "payload": "plain text" })).await;
+    assert_eq!(&subscriber.expect_publish().await.payload[..], b"plain text");
+
+    let result = publish(&broker, &token, "", json!({ "topic": "nobody/listens", "payload": "x" })).await;
+    assert_eq!(result["matched"], 0);
+
+    for body in [
+        json!({ "topic": "bin/a", "payload": "not base64!", "encoding": "base64" }),
+        json!({ "topic": "bin/a", "payload": "abc", "encoding": "hex" }),
+        json!({ "topic": "bin/a", "payload": "zz", "encoding": "hex" }),
+        json!({ "topic": "bin/+", "payload": "x" }),
+        json!({ "topic": "", "payload": "x" }),
+        json!({ "topic": "bin/a", "payload": "x", "qos": 3 }),
+    ] {
+        let (status, _) = broker.api(Method::POST, "/api/v1/publish", &token, Some(body.clone())).await;
+        assert_eq!(status, 400, "{}", body);
+    }
+    subscriber.expect_no_publish().await;
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn publish_can_wait_for_subscriber_acks() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut qos1 = MqttClient::connect(broker.tcp_port(), "qos1").await;
+    qos1.subscribe(&[("cmd/#", 1)]).await;
+    let mut qos2 = MqttClient::connect(broker.tcp_port(), "qos2").await;
+    qos2.subscribe(&[("cmd/#", 2)]).await;
+    let mut qos0 = MqttClient::connect(broker.tcp_port(), "qos0").await;
+    qos0.subscribe(&[("cmd/#", 0)]).await;
+
+    let body = json!({ "topic": "cmd/reboot", "payload": "now", "qos": 2 });
+    let (result, _, _, _) = tokio::join!(
+        publish(&broker, &token, "?wait_for_ack=true", body.clone()),
+        async {
+            let packet_id = qos1.expect_publish().await.packet_id.unwrap();
+            qos1.send(Packet::PubAck(packet_id)).await;
+        },
+        async {
+            let packet_id = qos2.expect_publish().await.packet_id.unwrap();
+            qos2.send(Packet::PubRec(packet_id)).await;
+            assert_eq!(qos2.recv().await, Packet::PubRel(packet_id));
+            qos2.send(Packet::PubComp(packet_id)).await;
+        },
+        qos0.expect_publish(),
+    );
+    assert_eq!(result["matched"], 3);
+    assert_eq!(result["acks_expected"], 2);
+    assert_eq!(result["acknowledged"], 2);
+
+    /* Unacknowledged deliveries are reported once the timeout passes. */
+    let result = publish(&broker, &token, "?wait_for_ack=true&timeout_ms=300", body).await;
+    assert_eq!(result["acks_expected"], 2);
+    assert_eq!(result["acknowledged"], 0);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn batches_are_validated_before_publishing() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut subscriber = MqttClient::connect(broker.tcp_port(), "subscriber").await;
+    subscriber.subscribe(&[("batch/#", 1)]).await;
+
+    let (status, body) = broker
+        .api(Method::POST, "/api/v1/publish/batch", &token, Some(json!([
+            { "topic": "batch/1", "payload": "one" },
+            { "topic": "batch/2", "payload": "%%", "encoding": "base64" },
+        ])))
+        .await;
+    assert_eq!(status, 400);
+    assert!(body["message"].as_str().unwrap().starts_with("message 1:"), "{}", body);
+    subscriber.expect_no_publish().await;
+
+    let (status, body) = broker
+        .api(Method::POST, "/api/v1/publish/batch", &token, Some(json!([
+            { "topic": "batch/1", "payload": "one", "qos": 1 },
+            { "topic": "batch/2", "payload": "dHdv", "encoding": "base64" },
+            { "topic": "elsewhere", "payload": "three" },
+        ])))
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    let matched: Vec<&Value> = body["data"].as_array().unwrap().iter().map(|r| &r["matched"]).collect();
+    assert_eq!(matched, [1, 1, 0]);
+
+    let first = subscriber.expect_publish().await;
+    assert_eq!((first.topic.as_str(), &first.payload[..]), ("batch/1", &b"one"[..]));
+    let second = subscriber.expect_publish().await;
+    assert_eq!((second.topic.as_str(), &second.payload[..]), ("batch/2", &b"two"[..]));
+
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish/batch", &token, Some(json!([]))).await;
+    assert_eq!(status, 400);
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use coremq_client::{Client, ClientOptions};
+use coremq_codec::packets::{Packet, PublishPacket};
+use reqwest::Method;
+use common::{MqttClient, TIMEOUT, TestBroker};
+
+fn qos2(packet_id: u16, payload: &'static str, dup: bool) -> Packet {
+    Packet::Publish(PublishPacket {
+        packet_id: Some(packet_id),
+        topic: "cmd/reboot".to_string(),
+        payload: Bytes::from_static(payload.as_bytes()),
+        qos: 2,
+        retain: false,
+        dup,
+    })
+}
+
+/*
+  A QoS 2 publish is routed on first receipt only; retransmissions are
+  acknowledged again until PUBREL releases the id.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn qos_2_publishes_are_routed_once_per_packet_id() {
+    let broker = TestBroker::start().await;
+    let port = broker.tcp_port();
+
+    let mut subscriber = MqttClient::connect(port, "subscriber").await;
+    subscriber.subscribe(&[("cmd/#", 2)]).await;
+    let mut publisher = MqttClient::connect(port, "publisher").await;
+
+    publisher.send(qos2(7, "first", false)).await;
+    assert_eq!(publisher.recv().await, Packet::PubRec(7));
+    publisher.send(qos2(7, "first", true)).await;
+    assert_eq!(publisher.recv().await, Packet::PubRec(7));
+
+    let delivered = subscriber.expect_publish().await;
+    assert_eq!((delivered.qos, delivered.payload.as_ref()), (2, b"first".as_ref()));
+    subscriber.expect_no_publish().await;
+
+    /* The subscriber side of the exchange: PUBREC is answered with PUBREL. */
+    let id = delivered.packet_id.unwrap();
+    subscriber.send(Packet::PubRec(id)).await;
+    assert_eq!(subscriber.recv().await, Packet::PubRel(id));
+    subscriber.send(Packet::PubComp(id)).await;
+
+    publisher.send(Packet::PubRel(7)).await;
+    assert_eq!(publisher.recv().await, Packet::PubComp(7));
+
+    /* Once released, the id carries a new message. */
+    publisher.send(qos2(7, "second", false)).await;
+    assert_eq!(publisher.recv().await, Packet::PubRec(7));
+    assert_eq!(subscriber.expect_publish().await.payload, "second".as_bytes());
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn qos_2_completes_over_websocket() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let options = |client_id: &str| ClientOptions::new(client_id, "127.0.0.1", broker.port("ws")).websocket("/mqtt");
+
+    let (subscriber, mut messages) = Client::connect(options("ws-subscriber")).await.unwrap();
+    assert_eq!(subscriber.subscribe("cmd/#", 2).await.unwrap(), 2);
+    let (publisher, _) = Client::connect(options("ws-publisher")).await.unwrap();
+
+    /* Resolves on PUBCOMP, after PUBREC and PUBREL went both ways. */
+    tokio::time::timeout(TIMEOUT, publisher.publish("cmd/reboot", "now", 2, false)).await.unwrap().unwrap();
+    let message = tokio::time::timeout(TIMEOUT, messages.next_message()).await.unwrap().unwrap();
+    assert_eq!((message.qos, message.payload.as_ref()), (2, b"now".as_ref()));
+
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    for client_id in ["ws-publisher", "ws-subscriber"] {
+        loop {
+            let (status, body) = broker.api(Method::GET, &format!("/api/v1/sessions/{}", client_id), &token, None).await;
+            assert_eq!(status, 200);
+            if body["data"]["inflight_incoming"] == 0 && body["data"]["inflight_outgoing"] == 0 {
+                break;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "{} still has packets in flight: {}", client_id, body);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    broker.shutdown().await;
+}
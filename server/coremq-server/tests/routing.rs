@@ -0,0 +1,364 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use coremq_server::{
+    enums::MqttChannel,
+    protocol::packets::{ConnectPacket, PROTOCOL_LEVEL_3_1_1},
+    services::{RoutingService, SessionService, TopicService},
+    transport::PacketIds,
+    utils::topic::{is_valid_filter, matches_filter},
+};
+use reqwest::Method;
+use tokio::sync::mpsc;
+use common::{MqttClient, TIMEOUT, TestBroker};
+
+fn matches(topics: &TopicService, topic: &str) -> Vec<String> {
+    let mut ids: Vec<String> = topics.match_subscribers(topic).into_iter().map(|(id, _)| id).collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn topic_tree_matches_wildcards() {
+    let topics = TopicService::new();
+    topics.subscribe("sensors/1/temp", "exact", 0);
+    topics.subscribe("sensors/+/temp", "plus", 1);
+    topics.subscribe("sensors/#", "hash", 2);
+    topics.subscribe("#", "all", 0);
+    topics.subscribe("+/+", "two-levels", 0);
+
+    assert_eq!(matches(&topics, "sensors/1/temp"), ["all", "exact", "hash", "plus"]);
+    assert_eq!(matches(&topics, "sensors/2/temp"), ["all", "hash", "plus"]);
+    assert_eq!(matches(&topics, "sensors/1/humidity"), ["all", "hash"]);
+    assert_eq!(matches(&topics, "sensors/1/temp/raw"), ["all", "hash"]);
+    assert_eq!(matches(&topics, "sensors/1"), ["all", "hash", "two-levels"]);
+    assert_eq!(matches(&topics, "alerts"), ["all"]);
+}
+
+#[test]
+fn multi_level_wildcard_matches_parent_level() {
+    let topics = TopicService::new();
+    topics.subscribe("sensors/#", "hash", 0);
+
+    assert_eq!(matches(&topics, "sensors"), ["hash"]);
+    assert!(matches(&topics, "sensorsx").is_empty());
+}
+
+#[test]
+fn filter_helpers_agree_with_the_topic_tree() {
+    let topics = TopicService::new();
+    let filters = ["sensors/1/temp", "sensors/+/temp", "sensors/#", "#", "+/+", "+", "sensors/+"];
+    for filter in filters {
+        assert!(is_valid_filter(filter), "{}", filter);
+        topics.subscribe(filter, filter, 0);
+    }
+
+    for topic in ["sensors/1/temp", "sensors", "sensors/", "/", "alerts", "sensors/1/temp/raw", ""] {
+        let expected = matches(&topics, topic);
+        let mut actual: Vec<String> = filters.iter().filter(|f| matches_filter(f, topic)).map(|f| f.to_string()).collect();
+        actual.sort();
+        assert_eq!(actual, expected, "{}", topic);
+    }
+
+    for filter in ["", "sensors/#/temp", "sensors/x#", "sensors/+x", "a\0b"] {
+        assert!(!is_valid_filter(filter), "{:?}", filter);
+    }
+}
+
+#[test]
+fn single_level_wildcard_matches_empty_levels() {
+    let topics = TopicService::new();
+    topics.subscribe("a/+/c", "plus", 0);
+
+    assert_eq!(matches(&topics, "a//c"), ["plus"]);
+    assert!(matches(&topics, "a/b/b/c").is_empty());
+}
+
+#[test]
+fn granted_qos_is_reported_per_subscriber() {
+    let topics = TopicService::new();
+    topics.subscribe("a/b", "low", 0);
+    topics.subscribe("a/+", "high", 2);
+
+    let mut granted = topics.match_subscribers("a/b");
+    granted.sort();
+    assert_eq!(granted, [("high".to_string(), 2), ("low".to_string(), 0)]);
+}
+
+#[test]
+fn unsubscribe_and_remove_client_prune_the_tree() {
+    let topics = TopicService::new();
+    topics.subscribe("a/b/c", "one", 0);
+    topics.subscribe("a/#", "one", 0);
+    topics.subscribe("a/b/c", "two", 0);
+
+    topics.unsubscribe("a/b/c", "two");
+    assert_eq!(matches(&topics, "a/b/c"), ["one", "one"]);
+
+    topics.remove_client("one");
+    assert!(matches(&topics, "a/b/c").is_empty());
+    assert!(topics.collect_topics().is_empty());
+}
+
+/*
+  Unsubscribing the last subscriber of a branch removes its nodes while
+  the parent map is being walked, which used to deadlock on the map's
+  shard lock. Runs on its own thread so a regression fails instead of
+  hanging the suite.
+*/
+#[test]
+fn unsubscribing_the_last_subscriber_prunes_without_deadlock() {
+    let (done_tx, done_rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let topics = TopicService::new();
+        topics.subscribe("a/b/c", "one", 0);
+        topics.subscribe("a/d", "two", 0);
+        topics.unsubscribe("a/b/c", "one");
+        topics.unsubscribe("a/d", "two");
+        let _ = done_tx.send(topics.collect_topics().is_empty());
+    });
+
+    assert_eq!(done_rx.recv_timeout(Duration::from_secs(5)), Ok(true));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wildcard_subscribers_receive_matching_publishes() {
+    let broker = TestBroker::start().await;
+    let port = broker.tcp_port();
+
+    let mut plus = MqttClient::connect(port, "plus").await;
+    assert_eq!(plus.subscribe(&[("home/+/temp", 1)]).await, [1]);
+
+    let mut hash = MqttClient::connect(port, "hash").await;
+    assert_eq!(hash.subscribe(&[("home/#", 0)]).await, [0]);
+
+    let mut publisher = MqttClient::connect(port, "publisher").await;
+    publisher.publish("home/kitchen/temp", "21", 1).await;
+
+    let received = plus.expect_publish().await;
+    assert_eq!(received.topic, "home/kitchen/temp");
+    assert_eq!(&received.payload[..], b"21");
+    assert_eq!(received.qos, 1);
+
+    /* Delivered QoS is capped by the subscription. */
+    let received = hash.expect_publish().await;
+    assert_eq!(received.qos, 0);
+    assert_eq!(received.packet_id, None);
+
+    publisher.publish("home/kitchen/humidity", "40", 0).await;
+    assert_eq!(hash.expect_publish().await.topic, "home/kitchen/humidity");
+    plus.expect_no_publish().await;
+
+    publisher.publish("office/desk/temp", "19", 0).await;
+    plus.expect_no_publish().await;
+    hash.expect_no_publish().await;
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsubscribe_stops_delivery() {
+    let broker = TestBroker::start().await;
+    let port = broker.tcp_port();
+
+    let mut subscriber = MqttClient::connect(port, "subscriber").await;
+    subscriber.subscribe(&[("news/#", 0), ("weather", 0)]).await;
+
+    let mut publisher = MqttClient::connect(port, "publisher").await;
+    publisher.publish("news/today", "a", 0).await;
+    assert_eq!(subscriber.expect_publish().await.topic, "news/today");
+
+    subscriber.unsubscribe(&["news/#"]).await;
+    publisher.publish("news/today", "b", 0).await;
+    publisher.publish("weather", "sunny", 0).await;
+    assert_eq!(subscriber.expect_publish().await.topic, "weather");
+    subscriber.expect_no_publish().await;
+
+    broker.shutdown().await;
+}
+
+/*
+  Packet ids belong to the receiving session: two publishers using the same
+  id must not collide at a shared subscriber.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn forwarded_publishes_get_subscriber_packet_ids() {
+    let broker = TestBroker::start().await;
+    let port = broker.tcp_port();
+
+    let mut subscriber = MqttClient::connect(port, "subscriber").await;
+    subscriber.subscribe(&[("in/+", 1)]).await;
+
+    let mut first = MqttClient::connect(port, "first").await;
+    let mut second = MqttClient::connect(port, "second").await;
+    first.publish("in/1", "a", 1).await;
+    second.publish("in/2", "b", 1).await;
+
+    let a = subscriber.expect_publish().await;
+    let b = subscriber.expect_publish().await;
+    assert!(a.packet_id.is_some() && b.packet_id.is_some());
+    assert_ne!(a.packet_id, b.packet_id);
+
+    broker.shutdown().await;
+}
+
+/*
+  QoS 0 deliveries take no packet id, and the id written into each
+  subscriber's copy of a shared frame leaves the rest of it intact.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn only_qos_1_and_2_deliveries_take_packet_ids() {
+    let broker = TestBroker::start().await;
+    let port = broker.tcp_port();
+
+    let mut first = MqttClient::connect(port, "first").await;
+    let mut second = MqttClient::connect(port, "second").await;
+    first.subscribe(&[("in/#", 1)]).await;
+    second.subscribe(&[("in/#", 2)]).await;
+
+    let payload = "x".repeat(20_000);
+    let mut publisher = MqttClient::connect(port, "publisher").await;
+    publisher.publish("in/0", "zero", 0).await;
+    publisher.publish("in/1", &payload, 1).await;
+    publisher.publish("in/2", "two", 1).await;
+
+    for subscriber in [&mut first, &mut second] {
+        let zero = subscriber.expect_publish().await;
+        assert_eq!((zero.qos, zero.packet_id), (0, None));
+
+        let large = subscriber.expect_publish().await;
+        assert_eq!((large.topic.as_str(), large.packet_id), ("in/1", Some(1)));
+        assert_eq!(large.payload, payload.as_bytes());
+
+        let small = subscriber.expect_publish().await;
+        assert_eq!((small.topic.as_str(), small.packet_id), ("in/2", Some(2)));
+        assert_eq!(small.payload, "two".as_bytes());
+    }
+
+    broker.shutdown().await;
+}
+
+/*
+  Invalidating a filter recomputes only the cached routes it matches.
+*/
+#[test]
+fn invalidation_drops_only_matching_routes() {
+    let sessions = Arc::new(SessionService::new());
+    let topics = Arc::new(TopicService::new());
+    let routing = RoutingService::new(sessions.clone(), topics.clone());
+    let mut mailboxes = Vec::new();
+    for (i, client_id) in ["first", "second"].into_iter().enumerate() {
+        let (tx, rx) = mpsc::channel::<MqttChannel>(1);
+        mailboxes.push(rx);
+        let connect = ConnectPacket {
+            protocol_level: PROTOCOL_LEVEL_3_1_1,
+            client_id: client_id.to_string(),
+            keep_alive: 60,
+            clean_session: true,
+            username: None,
+            password: None,
+            will: None,
+        };
+        sessions.add_client(&connect, 1883, "127.0.0.1:1883".parse().unwrap(), i as u64 + 1, tx, Arc::default());
+    }
+
+    topics.subscribe("a/#", "first", 0);
+    assert_eq!(routing.resolve("a/1").len(), 1);
+    assert_eq!(routing.resolve("b/1").len(), 0);
+
+    /* Applied to the index, but only "a/+" is invalidated. */
+    topics.subscribe("a/+", "second", 0);
+    topics.subscribe("b/+", "second", 0);
+    routing.invalidate(["a/+"]);
+    assert_eq!(routing.resolve("a/1").len(), 2);
+    assert_eq!(routing.resolve("b/1").len(), 0);
+
+    routing.invalidate(["b/+"]);
+    assert_eq!(routing.resolve("b/1").len(), 1);
+}
+
+/*
+  A subscriber that stops reading fills its socket buffers and then its
+  mailbox. From there the publisher waits instead of losing messages, and
+  every message arrives once the subscriber catches up.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn slow_subscribers_hold_back_publishers_without_losing_messages() {
+    const MESSAGES: usize = 4_000;
+
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let port = broker.tcp_port();
+
+    let mut subscriber = MqttClient::connect(port, "slow").await;
+    subscriber.subscribe(&[("flood/#", 0)]).await;
+
+    let mut publisher = MqttClient::connect(port, "fast").await;
+    let publishing = tokio::spawn(async move {
+        let filler = "x".repeat(4096);
+        for i in 0..MESSAGES {
+            publisher.publish("flood/data", &format!("{:05}{}", i, filler), 1).await;
+        }
+    });
+
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    loop {
+        let (status, body) = broker.api(Method::GET, "/api/v1/metrics", &token, None).await;
+        assert_eq!(status, 200);
+        if body["data"]["publishers_waiting"] == 1 {
+            break;
+        }
+        assert!(tokio::time::Instant::now() < deadline, "the publisher never had to wait");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(!publishing.is_finished());
+
+    for i in 0..MESSAGES {
+        let publish = subscriber.expect_publish().await;
+        assert_eq!(&publish.payload[..5], format!("{:05}", i).as_bytes());
+    }
+    publishing.await.unwrap();
+    subscriber.expect_no_publish().await;
+
+    let (_, body) = broker.api(Method::GET, "/api/v1/metrics", &token, None).await;
+    assert_eq!(body["data"]["publishers_waiting"], 0);
+
+    broker.shutdown().await;
+}
+
+#[test]
+fn packet_ids_skip_deliveries_awaiting_an_ack() {
+    let mut ids = PacketIds::default();
+    let first = ids.allocate().unwrap();
+    assert_eq!(first, 1);
+    ids.insert(first, None);
+
+    /* After wrapping around, the unacknowledged id is skipped. */
+    let mut last = first;
+    for _ in 0..u16::MAX - 1 {
+        last = ids.allocate().unwrap();
+    }
+    assert_eq!(last, u16::MAX);
+    assert_eq!(ids.allocate(), Some(2));
+    assert_eq!(ids.in_flight(), 1);
+
+    ids.complete(first);
+    assert_eq!(ids.in_flight(), 0);
+    for _ in 0..u16::MAX - 2 {
+        ids.allocate();
+    }
+    assert_eq!(ids.allocate(), Some(1));
+}
+
+#[test]
+fn packet_ids_run_out_when_every_id_is_in_flight() {
+    let mut ids = PacketIds::default();
+    while let Some(id) = ids.allocate() {
+        ids.insert(id, None);
+    }
+    assert_eq!(ids.in_flight(), u16::MAX as usize);
+
+    ids.complete(300);
+    assert_eq!(ids.allocate(), Some(300));
+}
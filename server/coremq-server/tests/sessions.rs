@@ -0,0 +1,252 @@
+mod common;
+
+use bytes::Bytes;
+use coremq_codec::packets::{ConnectPacket, ConnectReturnCode, Packet, PublishPacket, Will, PROTOCOL_LEVEL_3_1, PROTOCOL_LEVEL_3_1_1};
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{MqttClient, TIMEOUT, TestBroker, connect_packet_as};
+
+async fn query(broker: &TestBroker, token: &str, params: &str) -> Value {
+    let (status, body) = broker.api(Method::GET, &format!("/api/v1/sessions?{}", params), token, None).await;
+    assert_eq!(status, 200, "{}: {}", params, body);
+    body["data"].clone()
+}
+
+fn ids(page: &Value) -> Vec<String> {
+    page["content"].as_array().unwrap().iter().map(|s| s["client_id"].as_str().unwrap().to_string()).collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sessions_can_be_filtered() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut clients = Vec::new();
+    for n in 1..=3 {
+        clients.push(MqttClient::connect(broker.tcp_port(), &format!("sensor-{}", n)).await);
+    }
+    clients.push(MqttClient::connect_with(broker.port("tcp-2"), connect_packet_as("gateway", 60, Some("Ops-Team"), None, PROTOCOL_LEVEL_3_1_1)).await);
+    clients.push(MqttClient::connect_with(broker.tcp_port(), connect_packet_as("legacy", 60, None, None, PROTOCOL_LEVEL_3_1)).await);
+
+    assert_eq!(ids(&query(&broker, &token, "search=SENSOR&size=100").await), ["sensor-1", "sensor-2", "sensor-3"]);
+    assert_eq!(ids(&query(&broker, &token, "search=ops").await), ["gateway"]);
+    assert_eq!(ids(&query(&broker, &token, &format!("port={}", broker.port("tcp-2"))).await), ["gateway"]);
+    assert_eq!(ids(&query(&broker, &token, "protocol_version=3").await), ["legacy"]);
+    assert_eq!(query(&broker, &token, "ip=127.0.0.1").await["total_elements"], 5);
+    assert_eq!(query(&broker, &token, "ip=127.0.0.0/8&search=sensor").await["total_elements"], 3);
+    assert_eq!(query(&broker, &token, "ip=10.0.0.0/8").await["total_elements"], 0);
+    assert_eq!(query(&broker, &token, "connected_since=2000-01-01T00:00:00Z").await["total_elements"], 5);
+    assert_eq!(query(&broker, &token, "connected_since=4102444800").await["total_elements"], 0);
+
+    let page = query(&broker, &token, "protocol_version=4").await;
+    assert_eq!(page["total_elements"], 4);
+    assert_eq!(page["content"][0]["protocol_version"], 4);
+
+    for params in ["ip=not-an-ip", "connected_since=yesterday", "sort=color", "order=up"] {
+        let (status, _) = broker.api(Method::GET, &format!("/api/v1/sessions?{}", params), &token, None).await;
+        assert_eq!(status, 400, "{}", params);
+    }
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pagination_is_stable_and_sortable() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut clients = Vec::new();
+    for n in [4, 1, 7, 3, 6, 2, 5] {
+        clients.push(MqttClient::connect(broker.tcp_port(), &format!("c{}", n)).await);
+    }
+
+    let mut seen = Vec::new();
+    for page in 0..3 {
+        let data = query(&broker, &token, &format!("page={}&size=3&sort=client_id&order=desc", page)).await;
+        assert_eq!(data["total_pages"], 3);
+        assert_eq!(data["total_elements"], 7);
+        seen.extend(ids(&data));
+    }
+    assert_eq!(seen, ["c7", "c6", "c5", "c4", "c3", "c2", "c1"]);
+
+    /* Same query, same order. */
+    let first = ids(&query(&broker, &token, "size=7&sort=port").await);
+    assert_eq!(first, ids(&query(&broker, &token, "size=7&sort=port").await));
+    assert_eq!(first, ["c1", "c2", "c3", "c4", "c5", "c6", "c7"]);
+
+    /* Connection order, newest first. */
+    let newest = ids(&query(&broker, &token, "size=7&sort=connected_at&order=desc").await);
+    assert_eq!(newest, ["c5", "c2", "c6", "c3", "c7", "c1", "c4"]);
+
+    /* Out-of-range pages are empty, and size is capped. */
+    assert!(ids(&query(&broker, &token, "page=5&size=3").await).is_empty());
+    assert_eq!(query(&broker, &token, "size=0").await["size"], 1);
+    assert_eq!(query(&broker, &token, "size=100000").await["size"], 1000);
+
+    broker.shutdown().await;
+}
+
+async fn detail(broker: &TestBroker, token: &str, client_id: &str) -> Value {
+    let (status, body) = broker.api(Method::GET, &format!("/api/v1/sessions/{}", client_id), token, None).await;
+    assert_eq!(status, 200, "{}", body);
+    body["data"].clone()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn session_detail_reports_connection_state() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut device = MqttClient::open(broker.tcp_port()).await;
+    let ack = device
+        .handshake(Packet::Connect(ConnectPacket {
+            protocol_level: PROTOCOL_LEVEL_3_1_1,
+            client_id: "device-1".to_string(),
+            keep_alive: 45,
+            clean_session: true,
+            username: None,
+            password: None,
+            will: Some(Will {
+                topic: "devices/1/status".to_string(),
+                message: Bytes::from_static(b"offline"),
+                qos: 1,
+                retain: true,
+            }),
+        }))
+        .await;
+    assert_eq!(ack.return_code, ConnectReturnCode::Accepted);
+    device.subscribe(&[("devices/1/cmd", 1)]).await;
+
+    /* A delivery the device never acknowledges, and a QoS 2 publish it never releases. */
+    let mut publisher = MqttClient::connect(broker.tcp_port(), "publisher").await;
+    publisher.publish("devices/1/cmd", "reboot", 1).await;
+    device.expect_publish().await;
+    device
+        .send(Packet::Publish(PublishPacket {
+            packet_id: Some(7),
+            topic: "devices/1/telemetry".to_string(),
+            payload: Bytes::from_static(b"42"),
+            qos: 2,
+            retain: false,
+            dup: false,
+        }))
+        .await;
+    assert_eq!(device.recv().await, Packet::PubRec(7));
+
+    let session = detail(&broker, &token, "device-1").await;
+    assert_eq!(session["client_id"], "device-1");
+    assert_eq!(session["keep_alive"], 45);
+    assert_eq!(session["will"], json!({ "topic": "devices/1/status", "qos": 1, "retain": true, "payload_size": 7 }));
+    assert_eq!(session["subscriptions"]["devices/1/cmd"]["qos"], 1);
+    assert_eq!(session["inflight_incoming"], 1);
+    assert_eq!(session["inflight_outgoing"], 1);
+    assert_eq!(session["queued"], 0);
+    assert_eq!(session["queue_capacity"], 2048);
+    assert!(session["bytes_in"].as_u64().unwrap() > 0);
+    assert!(session["bytes_out"].as_u64().unwrap() > 0);
+    assert!(session["last_activity_at"].is_string());
+
+    /* Acknowledging and releasing clears both. */
+    let sent = detail(&broker, &token, "device-1").await["bytes_out"].as_u64().unwrap();
+    device.send(Packet::PubAck(1)).await;
+    device.send(Packet::PubRel(7)).await;
+    assert_eq!(device.recv().await, Packet::PubComp(7));
+    let session = detail(&broker, &token, "device-1").await;
+    assert_eq!(session["inflight_incoming"], 0);
+    assert_eq!(session["inflight_outgoing"], 0);
+    assert!(session["bytes_out"].as_u64().unwrap() > sent);
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions/nobody", &token, None).await;
+    assert_eq!(status, 404);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscriptions_can_be_provisioned_for_a_client() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut device = MqttClient::connect(broker.tcp_port(), "device-2").await;
+    let mut publisher = MqttClient::connect(broker.tcp_port(), "publisher").await;
+
+    let (status, body) = broker
+        .api(Method::POST, "/api/v1/sessions/device-2/subscriptions", &token, Some(json!({ "topic": "devices/+/cmd", "qos": 1 })))
+        .await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"], json!({ "topic": "devices/+/cmd", "qos": 1 }));
+    assert_eq!(detail(&broker, &token, "device-2").await["subscriptions"]["devices/+/cmd"]["qos"], 1);
+
+    publisher.publish("devices/2/cmd", "reboot", 1).await;
+    let delivered = device.expect_publish().await;
+    assert_eq!(delivered.topic, "devices/2/cmd");
+    assert_eq!(delivered.qos, 1);
+
+    let path = "/api/v1/sessions/device-2/subscriptions?topic=devices%2F%2B%2Fcmd";
+    let (status, _) = broker.api(Method::DELETE, path, &token, None).await;
+    assert_eq!(status, 200);
+    publisher.publish("devices/2/cmd", "reboot", 1).await;
+    device.expect_no_publish().await;
+
+    let (status, _) = broker.api(Method::DELETE, path, &token, None).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/sessions/nobody/subscriptions?topic=a", &token, None).await;
+    assert_eq!(status, 404);
+
+    for (client_id, body, expected) in [
+        ("device-2", json!({ "topic": "devices/#/cmd" }), 400),
+        ("device-2", json!({ "topic": "devices/x+" }), 400),
+        ("device-2", json!({ "topic": "" }), 400),
+        ("device-2", json!({ "topic": "devices/2/cmd", "qos": 3 }), 400),
+        ("nobody", json!({ "topic": "devices/2/cmd" }), 404),
+    ] {
+        let (status, _) = broker
+            .api(Method::POST, &format!("/api/v1/sessions/{}/subscriptions", client_id), &token, Some(body.clone()))
+            .await;
+        assert_eq!(status, expected, "{}", body);
+    }
+
+    broker.shutdown().await;
+}
+
+/*
+  Dropping many in-process subscriptions at once overflows a small engine
+  queue; every session must still be removed.
+*/
+#[tokio::test(flavor = "multi_thread")]
+async fn dropped_subscriptions_are_removed_even_when_the_queue_is_full() {
+    let broker = TestBroker::start_with(|config| config.engine.connect_queue_size = 1).await;
+    let token = broker.token().await;
+
+    let mut subscriptions = Vec::new();
+    for _ in 0..50 {
+        subscriptions.push(broker.handle().subscribe("sensors/#", 0).await.unwrap());
+    }
+    assert_eq!(broker.session_ids(&token).await.len(), 50);
+    drop(subscriptions);
+
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    while !broker.session_ids(&token).await.is_empty() {
+        assert!(tokio::time::Instant::now() < deadline, "sessions leaked");
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn network_clients_cannot_take_over_in_process_sessions() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let subscription = broker.handle().subscribe("sensors/#", 0).await.unwrap();
+    let connect = connect_packet_as(subscription.client_id(), 60, None, None, PROTOCOL_LEVEL_3_1_1);
+    assert_eq!(MqttClient::connect_code(broker.tcp_port(), connect).await, ConnectReturnCode::IdentifierRejected);
+
+    let connect = connect_packet_as("$local-unused", 60, None, None, PROTOCOL_LEVEL_3_1_1);
+    assert_eq!(MqttClient::connect_code(broker.tcp_port(), connect).await, ConnectReturnCode::IdentifierRejected);
+
+    assert_eq!(broker.session_ids(&token).await, vec![subscription.client_id().to_string()]);
+
+    broker.shutdown().await;
+}
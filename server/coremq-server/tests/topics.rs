@@ -0,0 +1,112 @@
+mod common;
+
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{MqttClient, TestBroker};
+
+async fn query(broker: &TestBroker, token: &str, path: &str) -> Value {
+    let (status, body) = broker.api(Method::GET, path, token, None).await;
+    assert_eq!(status, 200, "{}: {}", path, body);
+    body["data"].clone()
+}
+
+fn names(page: &Value) -> Vec<String> {
+    page["content"].as_array().unwrap().iter().map(|t| t["topic"].as_str().unwrap().to_string()).collect()
+}
+
+fn client_ids(subscribers: &Value) -> Vec<(String, u64)> {
+    subscribers
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| (s["client_id"].as_str().unwrap().to_string(), s["qos"].as_u64().unwrap()))
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn topics_are_paged_filtered_and_counted() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut subscriber = MqttClient::connect(broker.tcp_port(), "dashboard").await;
+    subscriber.subscribe(&[("sensors/+/temp", 0), ("sensors/1/temp", 1)]).await;
+
+    let mut publisher = MqttClient::connect(broker.tcp_port(), "sensor-1").await;
+    publisher.publish("sensors/1/temp", "21", 1).await;
+    publisher.publish("sensors/1/temp", "22.5", 1).await;
+    publisher.publish("alerts/fire", "evacuate", 1).await;
+    let body = json!({ "topic": "sensors/2/temp", "payload": "19", "qos": 0, "retain": true });
+    let (status, _) = broker.api(Method::POST, "/api/v1/publish", &token, Some(body)).await;
+    assert_eq!(status, 200);
+
+    let all = query(&broker, &token, "/api/v1/topics?size=100").await;
+    assert_eq!(names(&all), ["alerts/fire", "sensors/+/temp", "sensors/1/temp", "sensors/2/temp"]);
+
+    let topic = &all["content"][2];
+    assert_eq!(topic["subscriber_count"], 1);
+    assert_eq!(topic["messages"], 2);
+    assert_eq!(topic["bytes"], 6);
+    assert_eq!(topic["retained"], false);
+    assert!(topic["last_publish_at"].is_string());
+
+    /* Subscribed but never published to. */
+    assert_eq!(all["content"][1]["messages"], 0);
+    assert!(all["content"][1]["last_publish_at"].is_null());
+    /* Published to without an exact subscription. */
+    assert_eq!(all["content"][3]["subscriber_count"], 0);
+    assert_eq!(all["content"][3]["retained"], true);
+
+    assert_eq!(names(&query(&broker, &token, "/api/v1/topics?prefix=alerts/").await), ["alerts/fire"]);
+    assert_eq!(
+        names(&query(&broker, &token, "/api/v1/topics?filter=sensors%2F%2B%2Ftemp").await),
+        ["sensors/+/temp", "sensors/1/temp", "sensors/2/temp"],
+    );
+    assert_eq!(names(&query(&broker, &token, "/api/v1/topics?filter=%23&sort=messages&order=desc&size=1").await), ["sensors/1/temp"]);
+
+    let page = query(&broker, &token, "/api/v1/topics?page=1&size=3").await;
+    assert_eq!(names(&page), ["sensors/2/temp"]);
+    assert_eq!(page["total_pages"], 2);
+    assert_eq!(page["total_elements"], 4);
+
+    for path in ["/api/v1/topics?filter=sensors%2F%23%2Ftemp", "/api/v1/topics?sort=size"] {
+        let (status, _) = broker.api(Method::GET, path, &token, None).await;
+        assert_eq!(status, 400, "{}", path);
+    }
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_of_a_filter_or_a_topic() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let mut a = MqttClient::connect(broker.tcp_port(), "a").await;
+    a.subscribe(&[("sensors/+/temp", 1)]).await;
+    let mut b = MqttClient::connect(broker.tcp_port(), "b").await;
+    b.subscribe(&[("sensors/#", 0)]).await;
+    let mut c = MqttClient::connect(broker.tcp_port(), "c").await;
+    c.subscribe(&[("sensors/+/temp", 2), ("sensors/1/temp", 0)]).await;
+
+    let exact = query(&broker, &token, "/api/v1/topics/subscribers?filter=sensors%2F%2B%2Ftemp").await;
+    assert_eq!(client_ids(&exact), [("a".to_string(), 1), ("c".to_string(), 2)]);
+
+    /* Every client a publish would reach, once each with its highest QoS. */
+    let reached = query(&broker, &token, "/api/v1/topics/subscribers?topic=sensors%2F1%2Ftemp").await;
+    assert_eq!(client_ids(&reached), [("a".to_string(), 1), ("b".to_string(), 0), ("c".to_string(), 2)]);
+
+    let none = query(&broker, &token, "/api/v1/topics/subscribers?filter=alerts%2F%23").await;
+    assert_eq!(none, json!([]));
+
+    for path in [
+        "/api/v1/topics/subscribers",
+        "/api/v1/topics/subscribers?filter=a&topic=a",
+        "/api/v1/topics/subscribers?topic=sensors%2F%2B",
+        "/api/v1/topics/subscribers?filter=sensors%2F%23%2Fx",
+    ] {
+        let (status, _) = broker.api(Method::GET, path, &token, None).await;
+        assert_eq!(status, 400, "{}", path);
+    }
+
+    broker.shutdown().await;
+}
@@ -0,0 +1,218 @@
+mod common;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use coremq_codec::packets::{Packet, PublishPacket};
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::{MqttClient, TIMEOUT, TestBroker};
+
+async fn start_trace(broker: &TestBroker, token: &str, body: Value) -> String {
+    let (status, body) = broker.api(Method::POST, "/api/v1/traces", token, Some(body)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["active"], true);
+    body["data"]["id"].as_str().unwrap().to_string()
+}
+
+/*
+  Polls the trace until `predicate` holds for it, since incoming packets
+  may be recorded after the test has moved on.
+*/
+async fn trace_until(broker: &TestBroker, token: &str, path: &str, predicate: impl Fn(&Value) -> bool) -> Value {
+    let deadline = tokio::time::Instant::now() + TIMEOUT;
+    loop {
+        let (status, body) = broker.api(Method::GET, path, token, None).await;
+        assert_eq!(status, 200, "{}", body);
+        if predicate(&body["data"]) {
+            return body["data"].clone();
+        }
+        assert!(tokio::time::Instant::now() < deadline, "trace never matched: {}", body["data"]);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+fn summary(records: &Value) -> Vec<(String, String)> {
+    records
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["direction"].as_str().unwrap().to_string(), r["packet_type"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn client_trace_captures_both_directions() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let id = start_trace(&broker, &token, json!({ "kind": "client_id", "value": "device-1" })).await;
+
+    let mut sensor = MqttClient::connect(broker.tcp_port(), "sensor").await;
+    let mut device = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    device.subscribe(&[("sensors/#", 1)]).await;
+    sensor.publish("sensors/1/temp", "21.5", 1).await;
+    let delivered = device.expect_publish().await;
+    device.send(Packet::PubAck(delivered.packet_id.unwrap())).await;
+
+    let path = format!("/api/v1/traces/{}", id);
+    let trace = trace_until(&broker, &token, &path, |t| t["records"].as_array().unwrap().len() == 6).await;
+    let expected = [
+        ("in", "CONNECT"),
+        ("out", "CONNACK"),
+        ("in", "SUBSCRIBE"),
+        ("out", "SUBACK"),
+        ("out", "PUBLISH"),
+        ("in", "PUBACK"),
+    ];
+    let expected: Vec<(String, String)> = expected.iter().map(|(d, t)| (d.to_string(), t.to_string())).collect();
+    assert_eq!(summary(&trace["records"]), expected);
+
+    let records = trace["records"].as_array().unwrap();
+    assert!(records.iter().all(|r| r["client_id"] == "device-1"));
+    assert_eq!(records.iter().map(|r| r["seq"].as_u64().unwrap()).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+    assert_eq!(records[2]["detail"], "sensors/# qos=1");
+
+    let publish = &records[4];
+    assert_eq!(publish["topic"], "sensors/1/temp");
+    assert_eq!(publish["qos"], 1);
+    assert_eq!(publish["packet_id"], delivered.packet_id.unwrap());
+    assert_eq!(publish["payload_size"], 4);
+    assert_eq!(publish["payload_preview"], "21.5");
+    assert_eq!(publish["payload_encoding"], "plain");
+    assert_eq!(records[5]["packet_id"], delivered.packet_id.unwrap());
+
+    assert_eq!(trace["trace"]["created_by"], "admin");
+    assert_eq!(trace["trace"]["captured"], 6);
+    assert_eq!(trace["trace"]["dropped"], 0);
+
+    /* Polling with `after` returns only the newer records. */
+    let (status, body) = broker.api(Method::GET, &format!("{}?after=4", path), &token, None).await;
+    assert_eq!(status, 200);
+    assert_eq!(summary(&body["data"]["records"]), &expected[4..]);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn topic_trace_keeps_a_bounded_buffer_of_previews() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let id = start_trace(
+        &broker,
+        &token,
+        json!({ "kind": "topic", "value": "sensors/#", "capacity": 2, "payload_bytes": 4 }),
+    )
+    .await;
+
+    let mut client = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    client.publish("alerts/1", "ignored", 0).await;
+    client.publish("sensors/0", "pushed out", 0).await;
+    client.publish("sensors/1", "hello world", 0).await;
+    client.send(Packet::Publish(PublishPacket {
+        packet_id: None,
+        topic: "sensors/2".to_string(),
+        payload: Bytes::from_static(&[0xff, 0x00, 0x10]),
+        qos: 0,
+        retain: true,
+        dup: false,
+    }))
+    .await;
+
+    let path = format!("/api/v1/traces/{}", id);
+    let trace = trace_until(&broker, &token, &path, |t| t["trace"]["captured"] == 3).await;
+    assert_eq!(trace["trace"]["dropped"], 1);
+
+    let records = trace["records"].as_array().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["seq"], 2);
+    assert_eq!(records[0]["topic"], "sensors/1");
+    assert_eq!(records[0]["payload_size"], 11);
+    assert_eq!(records[0]["payload_preview"], "hell");
+    assert_eq!(records[0]["payload_encoding"], "plain");
+    assert_eq!(records[1]["topic"], "sensors/2");
+    assert_eq!(records[1]["retain"], true);
+    assert_eq!(records[1]["payload_preview"], "ff0010");
+    assert_eq!(records[1]["payload_encoding"], "hex");
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ip_trace_captures_every_client_from_the_network() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+    let id = start_trace(&broker, &token, json!({ "kind": "ip", "value": "127.0.0.0/8", "payload_bytes": 0 })).await;
+
+    let mut client = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    client.send(Packet::PingReq).await;
+    assert_eq!(client.recv().await, Packet::PingResp);
+    client.publish("sensors/1", "21.5", 0).await;
+
+    let path = format!("/api/v1/traces/{}", id);
+    let trace = trace_until(&broker, &token, &path, |t| t["trace"]["captured"] == 5).await;
+    assert_eq!(trace["trace"]["value"], "127.0.0.0/8");
+
+    let records = trace["records"].as_array().unwrap();
+    assert!(records.iter().all(|r| r["remote_addr"].as_str().unwrap().starts_with("127.0.0.1:")));
+    assert_eq!(records[2]["packet_type"], "PINGREQ");
+    assert_eq!(records[3]["packet_type"], "PINGRESP");
+    assert_eq!(records[4]["payload_size"], 4);
+    assert!(records[4]["payload_preview"].is_null());
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn traces_stop_on_their_own_and_can_be_managed() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let short = start_trace(&broker, &token, json!({ "kind": "client_id", "value": "device-1", "duration_secs": 1 })).await;
+    let path = format!("/api/v1/traces/{}", short);
+    let trace = trace_until(&broker, &token, &path, |t| t["trace"]["active"] == false).await;
+    assert_eq!(trace["trace"]["stopped_at"], trace["trace"]["ends_at"]);
+
+    /* A stopped trace captures nothing more. */
+    let _client = MqttClient::connect(broker.tcp_port(), "device-1").await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let (_, body) = broker.api(Method::GET, &path, &token, None).await;
+    assert_eq!(body["data"]["trace"]["captured"], 0);
+
+    let long = start_trace(&broker, &token, json!({ "kind": "topic", "value": "sensors/+/temp" })).await;
+    for _ in 0..2 {
+        let (status, body) = broker.api(Method::POST, &format!("/api/v1/traces/{}/stop", long), &token, None).await;
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["active"], false);
+    }
+
+    let (_, body) = broker.api(Method::GET, "/api/v1/traces", &token, None).await;
+    let ids: Vec<&str> = body["data"].as_array().unwrap().iter().map(|t| t["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, [short.as_str(), long.as_str()]);
+
+    let (status, _) = broker.api(Method::DELETE, &path, &token, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = broker.api(Method::GET, &path, &token, None).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api(Method::DELETE, &path, &token, None).await;
+    assert_eq!(status, 404);
+
+    for body in [
+        json!({ "kind": "topic", "value": "sensors/#/temp" }),
+        json!({ "kind": "ip", "value": "not-an-ip" }),
+        json!({ "kind": "client_id", "value": " " }),
+        json!({ "kind": "client_id", "value": "device-1", "duration_secs": 0 }),
+        json!({ "kind": "client_id", "value": "device-1", "capacity": 0 }),
+        json!({ "kind": "client_id", "value": "device-1", "payload_bytes": 4096 }),
+    ] {
+        let (status, _) = broker.api(Method::POST, "/api/v1/traces", &token, Some(body.clone())).await;
+        assert_eq!(status, 400, "{}", body);
+    }
+
+    /* Read-only users cannot trace. */
+    assert_eq!(broker.create_user(&token, "operator", "secret", "user").await, 200);
+    let operator = broker.login("operator", "secret").await["access_token"].as_str().unwrap().to_string();
+    let (status, _) = broker.api(Method::GET, "/api/v1/traces", &operator, None).await;
+    assert_eq!(status, 403);
+
+    broker.shutdown().await;
+}
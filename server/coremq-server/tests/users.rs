@@ -0,0 +1,165 @@
+mod common;
+
+use std::time::Duration;
+
+use reqwest::Method;
+use serde_json::{Value, json};
+use common::TestBroker;
+
+fn assert_no_hashes(value: &Value) {
+    match value {
+        Value::Object(map) => {
+            assert!(!map.contains_key("password_hash"), "password hash in response: {}", value);
+            map.values().for_each(assert_no_hashes);
+        }
+        Value::Array(items) => items.iter().for_each(assert_no_hashes),
+        _ => {}
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn default_admin_must_change_password() {
+    let broker = TestBroker::start_with_default_admin().await;
+
+    let tokens = broker.login("admin", "public").await;
+    assert_eq!(tokens["password_change_required"], true);
+    let token = tokens["access_token"].as_str().unwrap();
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", token, None).await;
+    assert_eq!(status, 403);
+
+    let change = |current: &str, new: &str| json!({ "current_password": current, "new_password": new });
+    let (status, _) = broker.api(Method::POST, "/api/v1/account/password", token, Some(change("wrong", "new-secret"))).await;
+    assert_eq!(status, 400);
+    let (status, _) = broker.api(Method::POST, "/api/v1/account/password", token, Some(change("public", "public"))).await;
+    assert_eq!(status, 400);
+    let (status, body) = broker.api(Method::POST, "/api/v1/account/password", token, Some(change("public", "new-secret"))).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["must_change_password"], false);
+
+    /* The change revokes the caller's own tokens too. */
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", token, None).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": tokens["refresh_token"] })).await;
+    assert_eq!(status, 401);
+
+    let (status, _) = broker.post_public("/api/v1/public/login", json!({ "username": "admin", "password": "public" })).await;
+    assert_eq!(status, 401);
+    assert_eq!(broker.login("admin", "new-secret").await["password_change_required"], false);
+
+    /* The flag survives restarts only while the password is unchanged. */
+    let broker = broker.restart().await;
+    assert_eq!(broker.login("admin", "new-secret").await["password_change_required"], false);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn user_crud_never_returns_password_hashes() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let create = json!({ "username": "alice", "password": "secret", "role": "user" });
+    let (status, body) = broker.api(Method::POST, "/api/v1/users", &token, Some(create.clone())).await;
+    assert_eq!(status, 200);
+    assert_no_hashes(&body);
+    assert_eq!(body["data"]["username"], "alice");
+
+    let (status, _) = broker.api(Method::POST, "/api/v1/users", &token, Some(create)).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = broker.api(Method::GET, "/api/v1/users", &token, None).await;
+    assert_eq!(status, 200);
+    assert_no_hashes(&body);
+    assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+    let (status, body) = broker.api(Method::GET, "/api/v1/users/alice", &token, None).await;
+    assert_eq!(status, 200);
+    assert_no_hashes(&body);
+    assert_eq!(body["data"]["role"], "user");
+
+    let update = json!({ "password": "changed", "role": "admin" });
+    let (status, body) = broker.api(Method::PUT, "/api/v1/users/alice", &token, Some(update)).await;
+    assert_eq!(status, 200);
+    assert_no_hashes(&body);
+    assert_eq!(body["data"]["role"], "admin");
+    broker.login("alice", "changed").await;
+
+    let (status, body) = broker.api(Method::PUT, "/api/v1/users/alice/role", &token, Some(json!({ "role": "user" }))).await;
+    assert_eq!(status, 200);
+    assert_no_hashes(&body);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_users_are_rejected() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    for body in [
+        json!({ "username": "", "password": "secret", "role": "user" }),
+        json!({ "username": "a/b", "password": "secret", "role": "user" }),
+        json!({ "username": "bob", "password": "", "role": "user" }),
+    ] {
+        let (status, _) = broker.api(Method::POST, "/api/v1/users", &token, Some(body.clone())).await;
+        assert_eq!(status, 400, "{}", body);
+    }
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/users/nobody", &token, None).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api(Method::PUT, "/api/v1/users/nobody", &token, Some(json!({ "password": "x" }))).await;
+    assert_eq!(status, 404);
+    let (status, _) = broker.api(Method::PUT, "/api/v1/users/admin", &token, Some(json!({ "password": "" }))).await;
+    assert_eq!(status, 400);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deleted_users_lose_access() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    let create = json!({ "username": "bob", "password": "secret", "role": "user" });
+    assert_eq!(broker.api(Method::POST, "/api/v1/users", &token, Some(create)).await.0, 200);
+    let bob = broker.login("bob", "secret").await["access_token"].as_str().unwrap().to_string();
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/users/bob", &token, None).await;
+    assert_eq!(status, 200);
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &bob, None).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/users/bob", &token, None).await;
+    assert_eq!(status, 404);
+
+    let (status, _) = broker.api(Method::DELETE, "/api/v1/users/admin", &token, None).await;
+    assert_eq!(status, 409);
+
+    broker.shutdown().await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn password_resets_invalidate_existing_tokens() {
+    let broker = TestBroker::start().await;
+    let token = broker.token().await;
+
+    assert_eq!(broker.create_user(&token, "carol", "secret", "user").await, 200);
+    let tokens = broker.login("carol", "secret").await;
+    let access = tokens["access_token"].as_str().unwrap().to_string();
+
+    /* `iat` counts whole seconds; make sure the reset is strictly later. */
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let (status, _) = broker.api(Method::PUT, "/api/v1/users/carol", &token, Some(json!({ "password": "reset" }))).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &access, None).await;
+    assert_eq!(status, 401);
+    let (status, _) = broker.post_public("/api/v1/public/refresh", json!({ "refresh_token": tokens["refresh_token"] })).await;
+    assert_eq!(status, 401);
+
+    let access = broker.login("carol", "reset").await["access_token"].as_str().unwrap().to_string();
+    let (status, _) = broker.api(Method::GET, "/api/v1/sessions", &access, None).await;
+    assert_eq!(status, 200);
+
+    broker.shutdown().await;
+}